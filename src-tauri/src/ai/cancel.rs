@@ -0,0 +1,110 @@
+//! Cancellation of in-flight AI generations
+//!
+//! Each `think` request registers under an id chosen by the frontend, so a
+//! later `cancel_think` call can stop it before the provider finishes.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use tokio::sync::Notify;
+
+/// In-flight generations that can be cancelled, keyed by request id
+#[derive(Default)]
+pub struct Cancellations {
+    pending: RwLock<HashMap<String, Arc<Notify>>>,
+}
+
+impl Cancellations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drive `generation` to completion unless `cancel(request_id)` is called
+    /// first, in which case it is dropped and `None` returned
+    pub async fn run<F: Future>(&self, request_id: &str, generation: F) -> Option<F::Output> {
+        let cancelled = Arc::new(Notify::new());
+        self.pending
+            .write()
+            .insert(request_id.to_string(), cancelled.clone());
+        let _registration = Registration {
+            owner: self,
+            request_id,
+        };
+
+        tokio::select! {
+            output = generation => Some(output),
+            _ = cancelled.notified() => None,
+        }
+    }
+
+    /// Stop a running generation. Returns false if none has this id.
+    pub fn cancel(&self, request_id: &str) -> bool {
+        match self.pending.read().get(request_id) {
+            Some(cancelled) => {
+                // Stores a permit, so a cancel racing the start still counts
+                cancelled.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Unregisters a generation however `run` ends, including when it is dropped
+struct Registration<'a> {
+    owner: &'a Cancellations,
+    request_id: &'a str,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.owner.pending.write().remove(self.request_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::ollama::OllamaProvider;
+    use crate::ai::AiProvider;
+    use crate::test_support::spawn_streaming_server;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_stops_generation_midway() {
+        let parts = vec![
+            "{\"response\":\"Hel\",\"done\":false}\n".to_string(),
+            "{\"response\":\"lo\",\"done\":true}\n".to_string(),
+        ];
+        let (gate, gate_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (url, _request) = spawn_streaming_server(parts, gate_rx).await;
+
+        let cancellations = Arc::new(Cancellations::new());
+        let running = cancellations.clone();
+        let (tokens, mut token_rx) = tokio::sync::mpsc::unbounded_channel();
+        let generation = tokio::spawn(async move {
+            let provider = OllamaProvider::with_url(&url, "llama3.2");
+            running
+                .run("req-1", provider.generate_stream("hi", &[], &[], &tokens))
+                .await
+        });
+
+        // The first token arrives, then the rest never would
+        gate.send(()).unwrap();
+        let first = tokio::time::timeout(Duration::from_secs(5), token_rx.recv()).await;
+        assert_eq!(first.unwrap().as_deref(), Some("Hel"));
+
+        assert!(cancellations.cancel("req-1"));
+        let result = tokio::time::timeout(Duration::from_secs(2), generation)
+            .await
+            .expect("cancelled generation should return promptly")
+            .unwrap();
+        assert!(result.is_none());
+
+        // Finished generations are no longer registered
+        assert!(!cancellations.cancel("req-1"));
+        assert!(!cancellations.cancel("unknown"));
+    }
+}
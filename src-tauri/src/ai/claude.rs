@@ -2,32 +2,124 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::ai::conversation::ChatTurn;
 use crate::ai::{format_memory_context, AiResponse};
 use crate::brain::cognitive::RecallResult;
 
+/// Model used unless settings choose another
+pub const DEFAULT_MODEL: &str = "claude-sonnet-4-5-20250929";
+
+/// Response length limit unless settings choose another
+pub const DEFAULT_MAX_TOKENS: u32 = 1024;
+
+const API_URL: &str = "https://api.anthropic.com/v1/messages";
+
+/// Instructions used when settings don't provide a system prompt
+const DEFAULT_SYSTEM_PROMPT: &str =
+    "You are SuperBrain, an intelligent cognitive assistant running as a macOS app. \
+     You have access to the user's memories and knowledge base. \
+     Use the following memory context to inform your response. \
+     Be concise and helpful.";
+
 /// Claude provider configuration
 pub struct ClaudeProvider {
     api_key: String,
     model: String,
+    max_tokens: u32,
+    system_prompt: Option<String>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    api_url: String,
     client: reqwest::Client,
 }
 
 impl ClaudeProvider {
     pub fn new(api_key: &str) -> Self {
-        Self {
-            api_key: api_key.to_string(),
-            model: "claude-sonnet-4-5-20250929".to_string(),
-            client: reqwest::Client::new(),
-        }
+        Self::with_model(api_key, DEFAULT_MODEL)
     }
 
     pub fn with_model(api_key: &str, model: &str) -> Self {
         Self {
             api_key: api_key.to_string(),
             model: model.to_string(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+            system_prompt: None,
+            temperature: None,
+            top_p: None,
+            api_url: API_URL.to_string(),
             client: reqwest::Client::new(),
         }
     }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens.max(1);
+        self
+    }
+
+    /// Replace the default instructions; blank prompts keep the default
+    pub fn with_system_prompt(mut self, system_prompt: Option<&str>) -> Self {
+        self.system_prompt = system_prompt
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(str::to_string);
+        self
+    }
+
+    /// Sampling settings; `None` keeps the model's default. Both are clamped
+    /// to 0–1.
+    pub fn with_sampling(mut self, temperature: Option<f32>, top_p: Option<f32>) -> Self {
+        self.temperature = temperature.map(|t| t.clamp(0.0, 1.0));
+        self.top_p = top_p.map(|p| p.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Send requests to another Messages API endpoint
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = api_url.to_string();
+        self
+    }
+
+    /// Check the API key with the smallest possible request, a one-token
+    /// reply. The error says why the key was rejected.
+    pub async fn validate_key(&self) -> Result<(), String> {
+        self.send_messages(&MessagesRequest {
+            model: self.model.clone(),
+            max_tokens: 1,
+            system: None,
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: "ping".to_string(),
+            }],
+            temperature: None,
+            top_p: None,
+        })
+        .await
+        .map(|_| ())
+    }
+
+    async fn send_messages(&self, request: &MessagesRequest) -> Result<MessagesResponse, String> {
+        let resp = self
+            .client
+            .post(&self.api_url)
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", "2023-06-01")
+            .header("content-type", "application/json")
+            .json(request)
+            .timeout(std::time::Duration::from_secs(60))
+            .send()
+            .await
+            .map_err(|e| format!("Claude API request failed: {}", e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("Claude API error ({}): {}", status, body));
+        }
+
+        resp.json()
+            .await
+            .map_err(|e| format!("Failed to parse Claude response: {}", e))
+    }
 }
 
 #[derive(Serialize)]
@@ -35,7 +127,12 @@ struct MessagesRequest {
     model: String,
     max_tokens: u32,
     messages: Vec<Message>,
-    system: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
 }
 
 #[derive(Serialize)]
@@ -57,6 +154,8 @@ struct ContentBlock {
 
 #[derive(Deserialize)]
 struct Usage {
+    #[serde(default)]
+    input_tokens: u32,
     output_tokens: u32,
 }
 
@@ -66,49 +165,40 @@ impl super::AiProvider for ClaudeProvider {
         &self,
         prompt: &str,
         context_memories: &[RecallResult],
+        history: &[ChatTurn],
     ) -> Result<AiResponse, String> {
         let memory_context = format_memory_context(context_memories);
 
-        let system_prompt = format!(
-            "You are SuperBrain, an intelligent cognitive assistant running as a macOS app. \
-             You have access to the user's memories and knowledge base. \
-             Use the following memory context to inform your response. \
-             Be concise and helpful.\n\
-             {memory_context}"
-        );
+        let instructions = self.system_prompt.as_deref().unwrap_or(DEFAULT_SYSTEM_PROMPT);
+        let system_prompt = format!("{instructions}\n{memory_context}");
 
-        let url = "https://api.anthropic.com/v1/messages";
+        let mut messages = Vec::with_capacity(history.len() * 2 + 1);
+        for turn in history {
+            messages.push(Message {
+                role: "user".to_string(),
+                content: turn.user.clone(),
+            });
+            messages.push(Message {
+                role: "assistant".to_string(),
+                content: turn.assistant.clone(),
+            });
+        }
+        messages.push(Message {
+            role: "user".to_string(),
+            content: prompt.to_string(),
+        });
 
-        let resp = self
-            .client
-            .post(url)
-            .header("x-api-key", &self.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
-            .json(&MessagesRequest {
+        let body = self
+            .send_messages(&MessagesRequest {
                 model: self.model.clone(),
-                max_tokens: 1024,
-                system: system_prompt,
-                messages: vec![Message {
-                    role: "user".to_string(),
-                    content: prompt.to_string(),
-                }],
+                max_tokens: self.max_tokens,
+                system: Some(system_prompt),
+                messages,
+                temperature: self.temperature,
+                // Newer models reject requests that set both
+                top_p: self.top_p.filter(|_| self.temperature.is_none()),
             })
-            .timeout(std::time::Duration::from_secs(60))
-            .send()
-            .await
-            .map_err(|e| format!("Claude API request failed: {}", e))?;
-
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            return Err(format!("Claude API error ({}): {}", status, body));
-        }
-
-        let body: MessagesResponse = resp
-            .json()
-            .await
-            .map_err(|e| format!("Failed to parse Claude response: {}", e))?;
+            .await?;
 
         let content = body
             .content
@@ -119,7 +209,7 @@ impl super::AiProvider for ClaudeProvider {
         Ok(AiResponse {
             content,
             model: self.model.clone(),
-            tokens_used: Some(body.usage.output_tokens),
+            tokens_used: Some(body.usage.input_tokens + body.usage.output_tokens),
         })
     }
 
@@ -131,3 +221,134 @@ impl super::AiProvider for ClaudeProvider {
         "claude"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{generate_with_fallback, AiProvider};
+    use crate::test_support::spawn_mock_server;
+
+    #[tokio::test]
+    async fn test_configured_model_reaches_request() {
+        let requests = std::sync::Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let url = spawn_mock_server(move |request| {
+            seen.lock().push(request.body);
+            let body = r#"{"content":[{"text":"Hi"}],"usage":{"output_tokens":1}}"#;
+            (200, body.to_string())
+        })
+        .await;
+
+        let provider = ClaudeProvider::with_model("key", "claude-haiku-4-5")
+            .with_max_tokens(4096)
+            .with_api_url(&url);
+        let response = provider.generate("hello", &[], &[]).await.unwrap();
+
+        assert_eq!(response.content, "Hi");
+        assert_eq!(response.model, "claude-haiku-4-5");
+        let requests = requests.lock();
+        assert_eq!(requests[0]["model"], "claude-haiku-4-5");
+        assert_eq!(requests[0]["max_tokens"], 4096);
+    }
+
+    #[tokio::test]
+    async fn test_sampling_settings_reach_request() {
+        let requests = std::sync::Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let url = spawn_mock_server(move |request| {
+            seen.lock().push(request.body);
+            let body = r#"{"content":[{"text":"Hi"}],"usage":{"output_tokens":1}}"#;
+            (200, body.to_string())
+        })
+        .await;
+
+        let provider = ClaudeProvider::new("key")
+            .with_sampling(Some(1.5), Some(0.9))
+            .with_api_url(&url);
+        provider.generate("hello", &[], &[]).await.unwrap();
+        let provider = provider.with_sampling(None, Some(0.9));
+        provider.generate("hello", &[], &[]).await.unwrap();
+
+        let requests = requests.lock();
+        assert_eq!(requests[0]["temperature"], 1.0);
+        assert!(requests[0].get("top_p").is_none());
+        assert!(requests[1].get("temperature").is_none());
+        assert!((requests[1]["top_p"].as_f64().unwrap() - 0.9).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn test_validate_key() {
+        let requests = std::sync::Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let url = spawn_mock_server(move |request| {
+            let valid = request.headers.contains("x-api-key: sk-ant-good");
+            seen.lock().push(request.body);
+            if valid {
+                let body = r#"{"content":[{"text":"p"}],"usage":{"output_tokens":1}}"#;
+                (200, body.to_string())
+            } else {
+                let body = r#"{"type":"error","error":{"message":"invalid x-api-key"}}"#;
+                (401, body.to_string())
+            }
+        })
+        .await;
+
+        let good = ClaudeProvider::new("sk-ant-good").with_api_url(&url);
+        assert_eq!(good.validate_key().await, Ok(()));
+
+        let bad = ClaudeProvider::new("sk-ant-bad").with_api_url(&url);
+        let err = bad.validate_key().await.unwrap_err();
+        assert!(err.contains("401") && err.contains("invalid x-api-key"), "{}", err);
+
+        // Checking a key costs a single output token
+        assert_eq!(requests.lock()[0]["max_tokens"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_api_error_is_propagated() {
+        let url = spawn_mock_server(|_| {
+            let body = r#"{"type":"error","error":{"message":"invalid x-api-key"}}"#;
+            (401, body.to_string())
+        })
+        .await;
+
+        let providers: Vec<Box<dyn AiProvider>> =
+            vec![Box::new(ClaudeProvider::new("bad-key").with_api_url(&url))];
+        let err = generate_with_fallback(&providers, "hello", &[], &[], None)
+            .await
+            .unwrap_err();
+
+        assert!(err.contains("401"), "{}", err);
+        assert!(err.contains("invalid x-api-key"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_custom_system_prompt_keeps_memory_context() {
+        let requests = std::sync::Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let url = spawn_mock_server(move |request| {
+            seen.lock().push(request.body);
+            let body = r#"{"content":[{"text":"Wien"}],"usage":{"output_tokens":1}}"#;
+            (200, body.to_string())
+        })
+        .await;
+
+        let memory = RecallResult {
+            id: "m1".to_string(),
+            content: "The user lives in Vienna".to_string(),
+            similarity: 0.9,
+            memory_type: "semantic".to_string(),
+            tags: vec![],
+        };
+        let provider = ClaudeProvider::new("key")
+            .with_system_prompt(Some("Answer in German, be terse."))
+            .with_api_url(&url);
+        provider.generate("Where do I live?", &[memory], &[]).await.unwrap();
+
+        let requests = requests.lock();
+        let system = requests[0]["system"].as_str().unwrap();
+        assert!(system.starts_with("Answer in German, be terse.\n"));
+        assert!(!system.contains("You are SuperBrain"));
+        assert!(system.contains("The user lives in Vienna"));
+    }
+}
@@ -0,0 +1,91 @@
+//! Conversation history for multi-turn `think` requests
+//!
+//! Each conversation id (chosen by the frontend) keeps its most recent
+//! exchanges so follow-up questions reach the AI with their context.
+
+use std::collections::{HashMap, VecDeque};
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+/// Exchanges kept per conversation unless configured otherwise
+pub const DEFAULT_MAX_TURNS: usize = 10;
+
+/// One user message and the assistant's reply
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatTurn {
+    pub user: String,
+    pub assistant: String,
+}
+
+/// Bounded per-conversation history
+pub struct Conversations {
+    turns: RwLock<HashMap<String, VecDeque<ChatTurn>>>,
+    max_turns: usize,
+}
+
+impl Conversations {
+    pub fn new(max_turns: usize) -> Self {
+        Self {
+            turns: RwLock::new(HashMap::new()),
+            max_turns: max_turns.max(1),
+        }
+    }
+
+    /// Earlier exchanges of a conversation, oldest first
+    pub fn history(&self, id: &str) -> Vec<ChatTurn> {
+        self.turns
+            .read()
+            .get(id)
+            .map(|turns| turns.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Append an exchange, dropping the oldest beyond the limit
+    pub fn record(&self, id: &str, user: &str, assistant: &str) {
+        let mut conversations = self.turns.write();
+        let turns = conversations.entry(id.to_string()).or_default();
+        turns.push_back(ChatTurn {
+            user: user.to_string(),
+            assistant: assistant.to_string(),
+        });
+        while turns.len() > self.max_turns {
+            turns.pop_front();
+        }
+    }
+
+    /// Forget a conversation. Returns false if it had no history.
+    pub fn clear(&self, id: &str) -> bool {
+        self.turns.write().remove(id).is_some()
+    }
+}
+
+impl Default for Conversations {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_TURNS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_is_bounded_per_conversation() {
+        let conversations = Conversations::new(2);
+        conversations.record("a", "q1", "a1");
+        conversations.record("a", "q2", "a2");
+        conversations.record("a", "q3", "a3");
+        conversations.record("b", "other", "reply");
+
+        let users: Vec<String> = conversations.history("a").into_iter().map(|t| t.user).collect();
+        assert_eq!(users, vec!["q2", "q3"]);
+        assert_eq!(conversations.history("b").len(), 1);
+        assert!(conversations.history("missing").is_empty());
+
+        assert!(conversations.clear("a"));
+        assert!(!conversations.clear("a"));
+        assert!(conversations.history("a").is_empty());
+        assert_eq!(conversations.history("b").len(), 1);
+    }
+}
@@ -2,12 +2,19 @@
 //!
 //! Supports local (Ollama) and cloud (Claude) LLM providers.
 
+pub mod cancel;
 pub mod claude;
+pub mod conversation;
 pub mod ollama;
+pub mod retry;
+pub mod usage;
 
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
 
+use crate::ai::conversation::ChatTurn;
 use crate::brain::cognitive::RecallResult;
+use crate::indexer::FileResult;
 
 /// Response from an AI provider
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,33 +27,287 @@ pub struct AiResponse {
 /// AI provider trait
 #[async_trait::async_trait]
 pub trait AiProvider: Send + Sync {
+    /// Answer `prompt`, continuing the conversation in `history` (oldest first)
     async fn generate(
         &self,
         prompt: &str,
         context_memories: &[RecallResult],
+        history: &[ChatTurn],
     ) -> Result<AiResponse, String>;
 
+    /// Like `generate`, but sends the response text to `tokens` as it is
+    /// produced. Providers that can't stream send it in one piece.
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        context_memories: &[RecallResult],
+        history: &[ChatTurn],
+        tokens: &UnboundedSender<String>,
+    ) -> Result<AiResponse, String> {
+        let response = self.generate(prompt, context_memories, history).await?;
+        let _ = tokens.send(response.content.clone());
+        Ok(response)
+    }
+
     async fn is_available(&self) -> bool;
 
     fn name(&self) -> &str;
 }
 
-/// Format memory context for LLM prompts
-pub fn format_memory_context(memories: &[RecallResult]) -> String {
-    if memories.is_empty() {
-        return String::new();
+/// What `generate_with_fallback` streams while a provider answers
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    /// The next piece of the answer
+    Token(String),
+    /// The provider failed after streaming part of its answer; drop what was
+    /// received, the next provider starts over
+    Reset,
+}
+
+/// Ask each provider in turn until one answers, returning the name of the
+/// provider that did. Fails with the last error if none of them answer.
+pub async fn generate_with_fallback(
+    providers: &[Box<dyn AiProvider>],
+    prompt: &str,
+    context_memories: &[RecallResult],
+    history: &[ChatTurn],
+    events: Option<&UnboundedSender<StreamEvent>>,
+) -> Result<(String, AiResponse), String> {
+    let mut last_error = "No AI provider configured".to_string();
+    for provider in providers {
+        let result = match events {
+            Some(events) => {
+                stream_answer(provider.as_ref(), prompt, context_memories, history, events).await
+            }
+            None => provider.generate(prompt, context_memories, history).await,
+        };
+        match result {
+            Ok(response) => return Ok((provider.name().to_string(), response)),
+            Err(e) => {
+                tracing::warn!("AI provider {} failed: {}", provider.name(), e);
+                last_error = e;
+            }
+        }
     }
+    Err(last_error)
+}
+
+/// Stream one provider's answer to `events`, following a failure with
+/// `Reset` if part of the answer was already sent
+async fn stream_answer(
+    provider: &dyn AiProvider,
+    prompt: &str,
+    context_memories: &[RecallResult],
+    history: &[ChatTurn],
+    events: &UnboundedSender<StreamEvent>,
+) -> Result<AiResponse, String> {
+    let (tokens, mut token_rx) = tokio::sync::mpsc::unbounded_channel();
+    // `tokens` is dropped when the provider finishes, which ends the forwarding
+    let generation = async move {
+        provider
+            .generate_stream(prompt, context_memories, history, &tokens)
+            .await
+    };
+    let mut streamed = false;
+    let forward = async {
+        while let Some(token) = token_rx.recv().await {
+            streamed = true;
+            let _ = events.send(StreamEvent::Token(token));
+        }
+    };
+    let (result, ()) = tokio::join!(generation, forward);
 
-    let mut context = String::from("\n--- Relevant Memories ---\n");
-    for (i, mem) in memories.iter().enumerate() {
-        context.push_str(&format!(
-            "{}. [{}] (similarity: {:.2}): {}\n",
-            i + 1,
-            mem.memory_type,
-            mem.similarity,
-            mem.content
-        ));
+    if result.is_err() && streamed {
+        let _ = events.send(StreamEvent::Reset);
+    }
+    result
+}
+
+/// Memory type of context entries that are indexed file chunks rather than
+/// memories; their `id` is the file path
+pub const FILE_CONTEXT_TYPE: &str = "file";
+
+/// An indexed file chunk as a context entry for `AiProvider::generate`
+pub fn file_context_entry(file: FileResult) -> RecallResult {
+    RecallResult {
+        id: file.path,
+        content: file.chunk,
+        similarity: file.similarity,
+        memory_type: FILE_CONTEXT_TYPE.to_string(),
+        tags: vec![],
+    }
+}
+
+/// Format memory context for LLM prompts. File chunks get their own section
+/// so the model can tell them apart from memories.
+pub fn format_memory_context(memories: &[RecallResult]) -> String {
+    let (files, memories): (Vec<&RecallResult>, Vec<&RecallResult>) = memories
+        .iter()
+        .partition(|mem| mem.memory_type == FILE_CONTEXT_TYPE);
+
+    let mut context = String::new();
+    if !memories.is_empty() {
+        context.push_str("\n--- Relevant Memories ---\n");
+        for (i, mem) in memories.iter().enumerate() {
+            context.push_str(&format!(
+                "{}. [{}] (similarity: {:.2}): {}\n",
+                i + 1,
+                mem.memory_type,
+                mem.similarity,
+                mem.content
+            ));
+        }
+        context.push_str("--- End Memories ---\n\n");
+    }
+    if !files.is_empty() {
+        if context.is_empty() {
+            context.push('\n');
+        }
+        context.push_str("--- Relevant Files (from the user's indexed documents) ---\n");
+        for (i, file) in files.iter().enumerate() {
+            context.push_str(&format!("{}. Source: {}\n{}\n", i + 1, file.id, file.content));
+        }
+        context.push_str("--- End Files ---\n\n");
     }
-    context.push_str("--- End Memories ---\n\n");
     context
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Provider that always answers, or always fails, without a network
+    struct StubProvider {
+        name: &'static str,
+        answer: Result<&'static str, &'static str>,
+    }
+
+    #[async_trait::async_trait]
+    impl AiProvider for StubProvider {
+        async fn generate(
+            &self,
+            _prompt: &str,
+            _context_memories: &[RecallResult],
+            _history: &[ChatTurn],
+        ) -> Result<AiResponse, String> {
+            self.answer
+                .map(|content| AiResponse {
+                    content: content.to_string(),
+                    model: self.name.to_string(),
+                    tokens_used: None,
+                })
+                .map_err(str::to_string)
+        }
+
+        async fn is_available(&self) -> bool {
+            self.answer.is_ok()
+        }
+
+        fn name(&self) -> &str {
+            self.name
+        }
+    }
+
+    fn stub(name: &'static str, answer: Result<&'static str, &'static str>) -> Box<dyn AiProvider> {
+        Box::new(StubProvider { name, answer })
+    }
+
+    /// Provider whose stream breaks off after its first token
+    struct CutOffProvider;
+
+    #[async_trait::async_trait]
+    impl AiProvider for CutOffProvider {
+        async fn generate(
+            &self,
+            _prompt: &str,
+            _context_memories: &[RecallResult],
+            _history: &[ChatTurn],
+        ) -> Result<AiResponse, String> {
+            Err("connection reset".to_string())
+        }
+
+        async fn generate_stream(
+            &self,
+            _prompt: &str,
+            _context_memories: &[RecallResult],
+            _history: &[ChatTurn],
+            tokens: &UnboundedSender<String>,
+        ) -> Result<AiResponse, String> {
+            let _ = tokens.send("Half an".to_string());
+            Err("Ollama stream ended before completion".to_string())
+        }
+
+        async fn is_available(&self) -> bool {
+            true
+        }
+
+        fn name(&self) -> &str {
+            "ollama"
+        }
+    }
+
+    async fn streamed_events(providers: &[Box<dyn AiProvider>]) -> Vec<StreamEvent> {
+        let (events, mut event_rx) = tokio::sync::mpsc::unbounded_channel();
+        generate_with_fallback(providers, "hi", &[], &[], Some(&events))
+            .await
+            .unwrap();
+        drop(events);
+        let mut received = Vec::new();
+        while let Some(event) = event_rx.recv().await {
+            received.push(event);
+        }
+        received
+    }
+
+    #[tokio::test]
+    async fn test_stream_resets_before_fallback_answer() {
+        let providers: Vec<Box<dyn AiProvider>> =
+            vec![Box::new(CutOffProvider), stub("claude", Ok("Cloud answer"))];
+        assert_eq!(
+            streamed_events(&providers).await,
+            vec![
+                StreamEvent::Token("Half an".to_string()),
+                StreamEvent::Reset,
+                StreamEvent::Token("Cloud answer".to_string()),
+            ]
+        );
+
+        // Nothing to take back when the failed provider sent nothing
+        let providers = vec![stub("claude", Err("offline")), stub("ollama", Ok("Local answer"))];
+        assert_eq!(
+            streamed_events(&providers).await,
+            vec![StreamEvent::Token("Local answer".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fallback_uses_next_provider_after_failure() {
+        let providers = vec![
+            stub("claude", Err("Claude API error (401): invalid x-api-key")),
+            stub("ollama", Ok("Local answer")),
+        ];
+
+        let (provider, response) = generate_with_fallback(&providers, "hi", &[], &[], None)
+            .await
+            .unwrap();
+
+        assert_eq!(provider, "ollama");
+        assert_eq!(response.content, "Local answer");
+    }
+
+    #[tokio::test]
+    async fn test_fallback_reports_last_error_when_all_fail() {
+        let providers = vec![
+            stub("claude", Err("Claude API error (401): invalid x-api-key")),
+            stub("ollama", Err("Ollama request failed: connection refused")),
+        ];
+
+        let err = generate_with_fallback(&providers, "hi", &[], &[], None)
+            .await
+            .unwrap_err();
+        assert!(err.contains("connection refused"), "{}", err);
+
+        let err = generate_with_fallback(&[], "hi", &[], &[], None).await.unwrap_err();
+        assert!(err.contains("No AI provider"), "{}", err);
+    }
+}
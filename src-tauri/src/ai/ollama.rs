@@ -1,33 +1,115 @@
 //! Ollama local LLM provider
 
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
 
+use crate::ai::conversation::ChatTurn;
+use crate::ai::retry::{send_with_retry, RetryPolicy};
 use crate::ai::{format_memory_context, AiResponse};
 use crate::brain::cognitive::RecallResult;
 
+/// Where Ollama listens unless settings say otherwise
+pub const DEFAULT_BASE_URL: &str = "http://localhost:11434";
+
+/// Instructions used when settings don't provide a system prompt
+const DEFAULT_SYSTEM_PROMPT: &str = "You are SuperBrain, an intelligent cognitive assistant. \
+     Use the following memory context to inform your response.";
+
 /// Ollama provider configuration
 pub struct OllamaProvider {
     base_url: String,
     model: String,
+    system_prompt: Option<String>,
+    options: GenerateOptions,
     client: reqwest::Client,
+    retry: RetryPolicy,
 }
 
 impl OllamaProvider {
     pub fn new(model: &str) -> Self {
-        Self {
-            base_url: "http://localhost:11434".to_string(),
-            model: model.to_string(),
-            client: reqwest::Client::new(),
-        }
+        Self::with_url(DEFAULT_BASE_URL, model)
     }
 
     pub fn with_url(base_url: &str, model: &str) -> Self {
         Self {
-            base_url: base_url.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
             model: model.to_string(),
+            system_prompt: None,
+            options: GenerateOptions::default(),
             client: reqwest::Client::new(),
+            retry: RetryPolicy::default(),
         }
     }
+
+    /// Replace the default instructions; blank prompts keep the default
+    pub fn with_system_prompt(mut self, system_prompt: Option<&str>) -> Self {
+        self.system_prompt = system_prompt
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(str::to_string);
+        self
+    }
+
+    /// Sampling settings; `None` keeps the model's default. Temperature is
+    /// clamped to 0–2 and top_p to 0–1.
+    pub fn with_sampling(mut self, temperature: Option<f32>, top_p: Option<f32>) -> Self {
+        self.options = GenerateOptions {
+            temperature: temperature.map(|t| t.clamp(0.0, 2.0)),
+            top_p: top_p.map(|p| p.clamp(0.0, 1.0)),
+        };
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// POST a generate request, retrying transient failures
+    async fn send_generate(
+        &self,
+        prompt: &str,
+        context_memories: &[RecallResult],
+        history: &[ChatTurn],
+        stream: bool,
+    ) -> Result<reqwest::Response, String> {
+        let memory_context = format_memory_context(context_memories);
+        let earlier_turns: String = history
+            .iter()
+            .map(|turn| format!("User: {}\nAssistant: {}\n", turn.user, turn.assistant))
+            .collect();
+
+        let instructions = self.system_prompt.as_deref().unwrap_or(DEFAULT_SYSTEM_PROMPT);
+        let full_prompt = format!(
+            "{instructions}\n\
+             {memory_context}\
+             {earlier_turns}\
+             User: {prompt}\n\
+             Assistant:"
+        );
+
+        let url = format!("{}/api/generate", self.base_url);
+        let request = GenerateRequest {
+            model: self.model.clone(),
+            prompt: full_prompt,
+            stream,
+            options: self.options.clone(),
+        };
+
+        let resp = send_with_retry(&self.retry, || {
+            self.client
+                .post(&url)
+                .json(&request)
+                .timeout(std::time::Duration::from_secs(120))
+        })
+        .await
+        .map_err(|e| format!("Ollama request failed: {}", e))?;
+
+        if !resp.status().is_success() {
+            return Err(format!("Ollama returned status: {}", resp.status()));
+        }
+        Ok(resp)
+    }
 }
 
 #[derive(Serialize)]
@@ -35,11 +117,58 @@ struct GenerateRequest {
     model: String,
     prompt: String,
     stream: bool,
+    #[serde(skip_serializing_if = "GenerateOptions::is_default")]
+    options: GenerateOptions,
+}
+
+/// Model parameters sent in a generate request's `options`
+#[derive(Serialize, Clone, Default, PartialEq)]
+struct GenerateOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+}
+
+impl GenerateOptions {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 #[derive(Deserialize)]
 struct GenerateResponse {
     response: String,
+    #[serde(flatten)]
+    counts: TokenCounts,
+}
+
+/// Token counts Ollama reports with a finished response
+#[derive(Deserialize, Default)]
+struct TokenCounts {
+    prompt_eval_count: Option<u32>,
+    eval_count: Option<u32>,
+}
+
+impl TokenCounts {
+    fn total(&self) -> Option<u32> {
+        match (self.prompt_eval_count, self.eval_count) {
+            (None, None) => None,
+            (prompt, output) => Some(prompt.unwrap_or(0) + output.unwrap_or(0)),
+        }
+    }
+}
+
+/// One line of a streamed generate response
+#[derive(Deserialize)]
+struct StreamChunk {
+    #[serde(default)]
+    response: String,
+    #[serde(default)]
+    done: bool,
+    error: Option<String>,
+    #[serde(flatten)]
+    counts: TokenCounts,
 }
 
 #[derive(Deserialize)]
@@ -58,35 +187,9 @@ impl super::AiProvider for OllamaProvider {
         &self,
         prompt: &str,
         context_memories: &[RecallResult],
+        history: &[ChatTurn],
     ) -> Result<AiResponse, String> {
-        let memory_context = format_memory_context(context_memories);
-
-        let full_prompt = format!(
-            "You are SuperBrain, an intelligent cognitive assistant. \
-             Use the following memory context to inform your response.\n\
-             {memory_context}\
-             User: {prompt}\n\
-             Assistant:"
-        );
-
-        let url = format!("{}/api/generate", self.base_url);
-
-        let resp = self
-            .client
-            .post(&url)
-            .json(&GenerateRequest {
-                model: self.model.clone(),
-                prompt: full_prompt,
-                stream: false,
-            })
-            .timeout(std::time::Duration::from_secs(120))
-            .send()
-            .await
-            .map_err(|e| format!("Ollama request failed: {}", e))?;
-
-        if !resp.status().is_success() {
-            return Err(format!("Ollama returned status: {}", resp.status()));
-        }
+        let resp = self.send_generate(prompt, context_memories, history, false).await?;
 
         let body: GenerateResponse = resp
             .json()
@@ -96,7 +199,63 @@ impl super::AiProvider for OllamaProvider {
         Ok(AiResponse {
             content: body.response.trim().to_string(),
             model: self.model.clone(),
-            tokens_used: None,
+            tokens_used: body.counts.total(),
+        })
+    }
+
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        context_memories: &[RecallResult],
+        history: &[ChatTurn],
+        tokens: &UnboundedSender<String>,
+    ) -> Result<AiResponse, String> {
+        let mut resp = self.send_generate(prompt, context_memories, history, true).await?;
+
+        // Ollama streams one JSON object per line; a read may end mid-line
+        let mut content = String::new();
+        let mut pending: Vec<u8> = Vec::new();
+        let mut tokens_used = None;
+        let mut done = false;
+        while !done {
+            let Some(bytes) = resp
+                .chunk()
+                .await
+                .map_err(|e| format!("Ollama stream failed: {}", e))?
+            else {
+                break;
+            };
+            pending.extend_from_slice(&bytes);
+            while let Some(newline) = pending.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = pending.drain(..=newline).collect();
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                let chunk: StreamChunk = serde_json::from_slice(&line)
+                    .map_err(|e| format!("Failed to parse stream chunk: {}", e))?;
+                if let Some(error) = chunk.error {
+                    return Err(format!("Ollama stream failed: {}", error));
+                }
+                if !chunk.response.is_empty() {
+                    let _ = tokens.send(chunk.response.clone());
+                    content.push_str(&chunk.response);
+                }
+                done = chunk.done;
+                if done {
+                    tokens_used = chunk.counts.total();
+                    break;
+                }
+            }
+        }
+        // A connection closed before the final line leaves the answer cut short
+        if !done {
+            return Err("Ollama stream ended before completion".to_string());
+        }
+
+        Ok(AiResponse {
+            content: content.trim().to_string(),
+            model: self.model.clone(),
+            tokens_used,
         })
     }
 
@@ -119,7 +278,7 @@ impl super::AiProvider for OllamaProvider {
 /// List available Ollama models
 pub async fn list_models(base_url: &str) -> Result<Vec<String>, String> {
     let client = reqwest::Client::new();
-    let url = format!("{}/api/tags", base_url);
+    let url = format!("{}/api/tags", base_url.trim_end_matches('/'));
 
     let resp = client
         .get(&url)
@@ -135,3 +294,222 @@ pub async fn list_models(base_url: &str) -> Result<Vec<String>, String> {
 
     Ok(tags.models.into_iter().map(|m| m.name).collect())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::conversation::Conversations;
+    use crate::ai::usage::{ProviderUsage, UsageTracker};
+    use crate::ai::AiProvider;
+    use crate::test_support::{spawn_mock_server, spawn_streaming_server};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    fn fast_retry() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generate_retries_server_errors() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let url = spawn_mock_server(move |_| {
+            if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                (503, r#"{"error":"model is loading"}"#.to_string())
+            } else {
+                (200, r#"{"response":" hello "}"#.to_string())
+            }
+        })
+        .await;
+
+        let provider = OllamaProvider::with_url(&url, "llama3.2").with_retry(fast_retry());
+        let response = provider.generate("hi", &[], &[]).await.unwrap();
+
+        assert_eq!(response.content, "hello");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_generate_does_not_retry_client_errors() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let url = spawn_mock_server(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            (404, r#"{"error":"model not found"}"#.to_string())
+        })
+        .await;
+
+        let provider = OllamaProvider::with_url(&url, "missing").with_retry(fast_retry());
+        let err = provider.generate("hi", &[], &[]).await.unwrap_err();
+
+        assert!(err.contains("404"), "{}", err);
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream_sends_tokens_as_they_arrive() {
+        let parts = [
+            r#"{"response":"Hel","done":false}"#,
+            "\n",
+            r#"{"response":"lo","#,
+            "\"done\":false}\n{\"response\":\" world\",\"done\":false}\n",
+            r#"{"response":"","done":true}"#,
+            "\n",
+        ];
+        let (gate, gate_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (url, request) =
+            spawn_streaming_server(parts.iter().map(|p| p.to_string()).collect(), gate_rx).await;
+
+        let (tokens, mut token_rx) = tokio::sync::mpsc::unbounded_channel();
+        let provider = OllamaProvider::with_url(&url, "llama3.2");
+        let generation =
+            tokio::spawn(async move { provider.generate_stream("hi", &[], &[], &tokens).await });
+
+        async fn next_token(rx: &mut UnboundedReceiver<String>) -> Option<String> {
+            tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.unwrap()
+        }
+
+        // A token is delivered once its line is complete, before the rest exists
+        gate.send(()).unwrap();
+        assert_eq!(request.await.unwrap().body["stream"], true);
+        assert!(token_rx.try_recv().is_err());
+        gate.send(()).unwrap();
+        assert_eq!(next_token(&mut token_rx).await.as_deref(), Some("Hel"));
+
+        // Lines split across reads are reassembled
+        gate.send(()).unwrap();
+        gate.send(()).unwrap();
+        assert_eq!(next_token(&mut token_rx).await.as_deref(), Some("lo"));
+        assert_eq!(next_token(&mut token_rx).await.as_deref(), Some(" world"));
+
+        gate.send(()).unwrap();
+        gate.send(()).unwrap();
+        let response = generation.await.unwrap().unwrap();
+        assert_eq!(response.content, "Hello world");
+        assert_eq!(response.tokens_used, None);
+        assert!(token_rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream_fails_when_cut_short() {
+        let parts = vec![r#"{"response":"Half an","done":false}"#.to_string() + "\n"];
+        let (gate, gate_rx) = tokio::sync::mpsc::unbounded_channel();
+        gate.send(()).unwrap();
+        let (url, _request) = spawn_streaming_server(parts, gate_rx).await;
+
+        let (tokens, mut token_rx) = tokio::sync::mpsc::unbounded_channel();
+        let provider = OllamaProvider::with_url(&url, "llama3.2");
+        let err = provider.generate_stream("hi", &[], &[], &tokens).await.unwrap_err();
+
+        assert!(err.contains("ended before completion"), "{}", err);
+        assert_eq!(token_rx.try_recv().as_deref(), Ok("Half an"));
+    }
+
+    #[tokio::test]
+    async fn test_generations_accumulate_token_usage() {
+        let url = spawn_mock_server(|_| {
+            let body = r#"{"response":"ok","done":true,"prompt_eval_count":12,"eval_count":30}"#;
+            (200, body.to_string())
+        })
+        .await;
+
+        let provider = OllamaProvider::with_url(&url, "llama3.2");
+        let usage = UsageTracker::new();
+        for prompt in ["first", "second"] {
+            let response = provider.generate(prompt, &[], &[]).await.unwrap();
+            assert_eq!(response.tokens_used, Some(42));
+            usage.record(provider.name(), response.tokens_used);
+        }
+
+        let totals = usage.totals();
+        assert_eq!(totals["ollama"], ProviderUsage { requests: 2, tokens: 84 });
+    }
+
+    #[tokio::test]
+    async fn test_sampling_settings_reach_request() {
+        let bodies = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen = bodies.clone();
+        let url = spawn_mock_server(move |request| {
+            seen.lock().push(request.body);
+            (200, r#"{"response":"ok"}"#.to_string())
+        })
+        .await;
+
+        let provider = OllamaProvider::with_url(&url, "llama3.2");
+        provider.generate("hi", &[], &[]).await.unwrap();
+        let provider = provider.with_sampling(Some(0.25), Some(7.0));
+        provider.generate("hi", &[], &[]).await.unwrap();
+
+        let bodies = bodies.lock();
+        assert!(bodies[0].get("options").is_none());
+        assert_eq!(bodies[1]["options"]["temperature"], 0.25);
+        assert_eq!(bodies[1]["options"]["top_p"], 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_custom_system_prompt_keeps_memory_context() {
+        let prompts = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen = prompts.clone();
+        let url = spawn_mock_server(move |request| {
+            let prompt = request.body["prompt"].as_str().unwrap_or_default().to_string();
+            seen.lock().push(prompt);
+            (200, r#"{"response":"Gut"}"#.to_string())
+        })
+        .await;
+
+        let memory = RecallResult {
+            id: "m1".to_string(),
+            content: "The user lives in Vienna".to_string(),
+            similarity: 0.9,
+            memory_type: "semantic".to_string(),
+            tags: vec![],
+        };
+        let provider = OllamaProvider::with_url(&url, "llama3.2")
+            .with_system_prompt(Some("Answer in German, be terse."));
+        provider.generate("Where do I live?", &[memory], &[]).await.unwrap();
+
+        let prompts = prompts.lock();
+        assert!(prompts[0].starts_with("Answer in German, be terse.\n"));
+        assert!(!prompts[0].contains("You are SuperBrain"));
+        assert!(prompts[0].contains("The user lives in Vienna"));
+    }
+
+    #[tokio::test]
+    async fn test_second_turn_includes_first_turn() {
+        let prompts = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen = prompts.clone();
+        let url = spawn_mock_server(move |request| {
+            let prompt = request.body["prompt"].as_str().unwrap_or_default().to_string();
+            let reply = if seen.lock().is_empty() { "Paris" } else { "About 2 million" };
+            seen.lock().push(prompt);
+            (200, serde_json::json!({ "response": reply }).to_string())
+        })
+        .await;
+
+        let provider = OllamaProvider::with_url(&url, "llama3.2");
+        let conversations = Conversations::default();
+        let first_question = "What is the capital of France?";
+        let first = provider
+            .generate(first_question, &[], &conversations.history("chat"))
+            .await
+            .unwrap();
+        conversations.record("chat", first_question, &first.content);
+
+        provider
+            .generate("How many people live there?", &[], &conversations.history("chat"))
+            .await
+            .unwrap();
+
+        let prompts = prompts.lock();
+        assert!(!prompts[0].contains("Paris"));
+        assert!(prompts[1].contains(
+            "User: What is the capital of France?\nAssistant: Paris\n\
+             User: How many people live there?\nAssistant:"
+        ));
+    }
+}
@@ -0,0 +1,66 @@
+//! Retry with exponential backoff for local model HTTP calls
+//!
+//! Only transient failures are retried: connection errors, timeouts and 5xx
+//! responses (e.g. Ollama still loading a model). 4xx responses are returned
+//! to the caller untouched.
+
+use std::time::Duration;
+
+/// How many times to try a request and how long to wait between attempts
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    /// Delay before the second attempt; doubles after each further failure
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay * 2u32.saturating_pow(attempt.saturating_sub(1))
+    }
+}
+
+/// Send the request produced by `build`, rebuilding it for each attempt
+pub async fn send_with_retry<F>(
+    policy: &RetryPolicy,
+    build: F,
+) -> Result<reqwest::Response, reqwest::Error>
+where
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    let mut attempt = 1;
+    loop {
+        let last_attempt = attempt >= policy.max_attempts;
+        match build().send().await {
+            Ok(resp) if resp.status().is_server_error() && !last_attempt => {
+                tracing::warn!(
+                    "Request returned {} (attempt {}/{}), retrying",
+                    resp.status(),
+                    attempt,
+                    policy.max_attempts
+                );
+            }
+            Err(e) if (e.is_connect() || e.is_timeout()) && !last_attempt => {
+                tracing::warn!(
+                    "Request failed: {} (attempt {}/{}), retrying",
+                    e,
+                    attempt,
+                    policy.max_attempts
+                );
+            }
+            result => return result,
+        }
+
+        tokio::time::sleep(policy.delay(attempt)).await;
+        attempt += 1;
+    }
+}
@@ -0,0 +1,45 @@
+//! Per-provider AI usage totals
+
+use std::collections::BTreeMap;
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+/// Requests answered and tokens used by one provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProviderUsage {
+    pub requests: u64,
+    pub tokens: u64,
+}
+
+/// Running usage totals, keyed by provider name
+#[derive(Default)]
+pub struct UsageTracker {
+    totals: RwLock<BTreeMap<String, ProviderUsage>>,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from previously saved totals
+    pub fn with_totals(totals: BTreeMap<String, ProviderUsage>) -> Self {
+        Self {
+            totals: RwLock::new(totals),
+        }
+    }
+
+    /// Count one answered request. Providers that don't report tokens only
+    /// add to the request count.
+    pub fn record(&self, provider: &str, tokens_used: Option<u32>) {
+        let mut totals = self.totals.write();
+        let usage = totals.entry(provider.to_string()).or_default();
+        usage.requests += 1;
+        usage.tokens += u64::from(tokens_used.unwrap_or(0));
+    }
+
+    pub fn totals(&self) -> BTreeMap<String, ProviderUsage> {
+        self.totals.read().clone()
+    }
+}
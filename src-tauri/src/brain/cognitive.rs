@@ -7,13 +7,19 @@ use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 
 use crate::brain::learning::NativeLearner;
-use crate::brain::memory::NativeMemory;
+use crate::brain::memory::{NativeMemory, SearchOptions};
 use crate::brain::types::{CognitiveConfig, CognitiveStats, Thought, ThoughtType};
-use crate::brain::utils::{generate_id, now_millis};
+use crate::brain::utils::{generate_id, now_millis, truncate_str};
+
+/// Similarity floor used by recall when the caller doesn't specify one
+const DEFAULT_MIN_SIMILARITY: f64 = 0.2;
+
+/// Most thoughts kept in the thought stream; older half is dropped past this
+pub const MAX_THOUGHTS: usize = 1000;
 
 /// Goal tracking
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub(crate) struct Goal {
+pub struct Goal {
     pub id: String,
     pub description: String,
     pub priority: f64,
@@ -23,7 +29,7 @@ pub(crate) struct Goal {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
-pub(crate) enum GoalStatus {
+pub enum GoalStatus {
     Pending,
     Active,
     Completed,
@@ -32,7 +38,7 @@ pub(crate) enum GoalStatus {
 
 /// Belief with confidence
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub(crate) struct Belief {
+pub struct Belief {
     pub id: String,
     pub content: String,
     pub confidence: f64,
@@ -72,7 +78,7 @@ impl CognitiveEngine {
         Self {
             memory: Arc::new(NativeMemory::new(dimensions)),
             learner: Arc::new(NativeLearner::new(dimensions, action_count)),
-            thoughts: RwLock::new(Vec::with_capacity(1000)),
+            thoughts: RwLock::new(Vec::with_capacity(MAX_THOUGHTS)),
             goals: RwLock::new(Vec::new()),
             beliefs: RwLock::new(Vec::new()),
             config: RwLock::new(cfg),
@@ -107,16 +113,21 @@ impl CognitiveEngine {
         self.memory.store(content, vector, memory_type, imp)
     }
 
-    /// Recall memories by similarity (f32 interface)
+    /// Recall memories by similarity (f32 interface).
+    /// `options.min_similarity` defaults to 0.2 and is clamped to [0, 1].
     pub fn recall_f32(
         &self,
         query_vector: &[f32],
         k: Option<u32>,
-        memory_types: Option<Vec<String>>,
+        mut options: SearchOptions,
     ) -> Result<Vec<RecallResult>, String> {
-        let results =
-            self.memory
-                .search_f32(query_vector, k.unwrap_or(10), memory_types, Some(0.2))?;
+        let floor = options
+            .min_similarity
+            .unwrap_or(DEFAULT_MIN_SIMILARITY)
+            .clamp(0.0, 1.0);
+        options.min_similarity = Some(floor);
+
+        let results = self.memory.search_f32(query_vector, k.unwrap_or(10), options)?;
 
         Ok(results
             .into_iter()
@@ -125,6 +136,7 @@ impl CognitiveEngine {
                 content: r.content,
                 similarity: r.similarity,
                 memory_type: r.memory_type,
+                tags: r.tags,
             })
             .collect())
     }
@@ -136,9 +148,12 @@ impl CognitiveEngine {
         k: Option<u32>,
         memory_types: Option<Vec<String>>,
     ) -> Result<Vec<RecallResult>, String> {
-        let results = self
-            .memory
-            .search(query_vector, k.unwrap_or(10), memory_types, Some(0.2))?;
+        let options = SearchOptions {
+            memory_types,
+            min_similarity: Some(DEFAULT_MIN_SIMILARITY),
+            ..Default::default()
+        };
+        let results = self.memory.search(query_vector, k.unwrap_or(10), options)?;
 
         Ok(results
             .into_iter()
@@ -147,6 +162,7 @@ impl CognitiveEngine {
                 content: r.content,
                 similarity: r.similarity,
                 memory_type: r.memory_type,
+                tags: r.tags,
             })
             .collect())
     }
@@ -162,6 +178,23 @@ impl CognitiveEngine {
     ) -> Result<LearnResult, String> {
         use crate::brain::types::Experience;
 
+        let dimensions = self.config.read().dimensions as usize;
+        if state.len() != dimensions || next_state.len() != dimensions {
+            return Err(format!(
+                "State dimension mismatch: expected {}, got state={} next_state={}",
+                dimensions,
+                state.len(),
+                next_state.len()
+            ));
+        }
+        let action_count = self.learner.action_count();
+        if action as usize >= action_count {
+            return Err(format!(
+                "Action {} out of range: expected 0..{}",
+                action, action_count
+            ));
+        }
+
         let experience = Experience {
             state,
             action,
@@ -205,11 +238,11 @@ impl CognitiveEngine {
         input: &str,
         embedding: &[f32],
     ) -> Result<ThinkResult, String> {
-        let memories = self.recall_f32(embedding, Some(5), None)?;
+        let memories = self.recall_f32(embedding, Some(5), SearchOptions::default())?;
 
         let thought = self.generate_thought(
             ThoughtType::Inference,
-            format!("Processing: {}", &input[..input.len().min(100)]),
+            format!("Processing: {}", truncate_str(input, 100)),
             0.7,
         );
 
@@ -242,7 +275,7 @@ impl CognitiveEngine {
 
         let thought = self.generate_thought(
             ThoughtType::Inference,
-            format!("Processing: {}", &input[..input.len().min(100)]),
+            format!("Processing: {}", truncate_str(&input, 100)),
             0.7,
         );
 
@@ -269,12 +302,12 @@ impl CognitiveEngine {
         })
     }
 
-    /// Add a goal
+    /// Add a goal. Priority is clamped to [0, 1].
     pub fn add_goal(&self, description: String, priority: f64) -> String {
         let goal = Goal {
             id: generate_id(),
             description,
-            priority,
+            priority: priority.clamp(0.0, 1.0),
             progress: 0.0,
             status: GoalStatus::Pending,
             created_at: now_millis(),
@@ -289,7 +322,7 @@ impl CognitiveEngine {
     pub fn update_goal(&self, goal_id: &str, progress: f64) -> bool {
         let mut goals = self.goals.write();
         if let Some(goal) = goals.iter_mut().find(|g| g.id == goal_id) {
-            goal.progress = progress.min(1.0);
+            goal.progress = progress.clamp(0.0, 1.0);
             if goal.progress >= 1.0 {
                 goal.status = GoalStatus::Completed;
             } else if goal.progress > 0.0 {
@@ -301,12 +334,29 @@ impl CognitiveEngine {
         }
     }
 
-    /// Add a belief
+    /// Get a goal by id
+    pub fn get_goal(&self, goal_id: &str) -> Option<Goal> {
+        self.goals.read().iter().find(|g| g.id == goal_id).cloned()
+    }
+
+    /// All goals, highest priority first
+    pub fn goals(&self) -> Vec<Goal> {
+        let mut goals = self.goals.read().clone();
+        goals.sort_by(|a, b| b.priority.total_cmp(&a.priority));
+        goals
+    }
+
+    /// Replace the goal list with previously saved goals
+    pub fn restore_goals(&self, goals: Vec<Goal>) {
+        *self.goals.write() = goals;
+    }
+
+    /// Add a belief. Confidence is clamped to [0, 1].
     pub fn add_belief(&self, content: String, confidence: f64, source: String) -> String {
         let belief = Belief {
             id: generate_id(),
             content,
-            confidence,
+            confidence: confidence.clamp(0.0, 1.0),
             source,
             timestamp: now_millis(),
         };
@@ -316,6 +366,23 @@ impl CognitiveEngine {
         id
     }
 
+    /// Get a belief by id
+    pub fn get_belief(&self, belief_id: &str) -> Option<Belief> {
+        self.beliefs.read().iter().find(|b| b.id == belief_id).cloned()
+    }
+
+    /// All beliefs, most confident first
+    pub fn beliefs(&self) -> Vec<Belief> {
+        let mut beliefs = self.beliefs.read().clone();
+        beliefs.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        beliefs
+    }
+
+    /// Replace the belief list with previously saved beliefs
+    pub fn restore_beliefs(&self, beliefs: Vec<Belief>) {
+        *self.beliefs.write() = beliefs;
+    }
+
     /// Generate a thought
     fn generate_thought(
         &self,
@@ -336,8 +403,8 @@ impl CognitiveEngine {
         let mut thoughts = self.thoughts.write();
         thoughts.push(thought.clone());
 
-        if thoughts.len() > 1000 {
-            thoughts.drain(0..500);
+        if thoughts.len() > MAX_THOUGHTS {
+            thoughts.drain(0..MAX_THOUGHTS / 2);
         }
 
         thought
@@ -360,8 +427,8 @@ impl CognitiveEngine {
         if memory_stats.avg_decay > 0.5 {
             let result = self.memory.consolidate();
             adaptations.push(format!(
-                "Consolidated memory: pruned {} entries",
-                result.pruned
+                "Consolidated memory: merged {}, pruned {} entries",
+                result.merged, result.pruned
             ));
         }
 
@@ -410,6 +477,7 @@ impl CognitiveEngine {
             total_thoughts: thoughts.len() as u32,
             total_experiences: learner_stats.total_experiences as u32,
             active_goals,
+            total_beliefs: self.beliefs.read().len() as u32,
             avg_reward: learner_stats.avg_reward,
             learning_trend: trend.to_string(),
             exploration_rate: learner_stats.exploration_rate,
@@ -438,6 +506,20 @@ impl CognitiveEngine {
         thoughts.iter().rev().take(n).cloned().collect()
     }
 
+    /// The whole thought stream, oldest first
+    pub fn export_thoughts(&self) -> Vec<Thought> {
+        self.thoughts.read().clone()
+    }
+
+    /// Replace the thought stream with previously saved thoughts (oldest
+    /// first), keeping only the newest `MAX_THOUGHTS`
+    pub fn restore_thoughts(&self, mut thoughts: Vec<Thought>) {
+        if thoughts.len() > MAX_THOUGHTS {
+            thoughts.drain(0..thoughts.len() - MAX_THOUGHTS);
+        }
+        *self.thoughts.write() = thoughts;
+    }
+
     /// Run a cognitive cycle
     pub fn cycle(&self) -> CycleResult {
         self.cycle_count.fetch_add(1, Ordering::Relaxed);
@@ -445,16 +527,23 @@ impl CognitiveEngine {
         let train_result = self.learner.train_batch().ok();
         let insights = train_result.unwrap_or_default();
 
-        let consolidated = if self.cycle_count.load(Ordering::Relaxed) % 100 == 0 {
+        let maintenance = self.cycle_count.load(Ordering::Relaxed) % 100 == 0;
+        let consolidated = if maintenance {
             Some(self.memory.consolidate())
         } else {
             None
         };
+        let q_states_pruned = if maintenance {
+            self.learner.prune_q_table(self.learner.prune_min_visits())
+        } else {
+            0
+        };
 
         CycleResult {
             cycle_number: self.cycle_count.load(Ordering::Relaxed),
             training_insights: insights,
             memories_pruned: consolidated.map(|c| c.pruned).unwrap_or(0),
+            q_states_pruned,
         }
     }
 
@@ -476,6 +565,7 @@ pub struct RecallResult {
     pub content: String,
     pub similarity: f64,
     pub memory_type: String,
+    pub tags: Vec<String>,
 }
 
 /// Result of learning
@@ -514,6 +604,7 @@ pub struct IntrospectionResult {
     pub total_thoughts: u32,
     pub total_experiences: u32,
     pub active_goals: u32,
+    pub total_beliefs: u32,
     pub avg_reward: f64,
     pub learning_trend: String,
     pub exploration_rate: f64,
@@ -525,12 +616,35 @@ pub struct CycleResult {
     pub cycle_number: u64,
     pub training_insights: Vec<String>,
     pub memories_pruned: u32,
+    pub q_states_pruned: u32,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_learn_validates_dimensions() {
+        let engine = CognitiveEngine::new(Some(CognitiveConfig {
+            dimensions: 4,
+            ..CognitiveConfig::default()
+        }));
+        let state = vec![1.0, 0.0, 0.0, 0.0];
+        let next_state = vec![0.0, 1.0, 0.0, 0.0];
+
+        let result = engine
+            .learn(state.clone(), 1, 1.0, next_state.clone(), false)
+            .unwrap();
+        assert!(result.success);
+
+        let err = engine
+            .learn(vec![1.0, 0.0], 1, 1.0, next_state.clone(), false)
+            .unwrap_err();
+        assert!(err.contains("expected 4"), "{}", err);
+        assert!(engine.learn(state.clone(), 1, 1.0, vec![], true).is_err());
+        assert!(engine.learn(state, 100, 1.0, next_state, false).is_err());
+    }
+
     #[test]
     fn test_cognitive_engine() {
         let engine = CognitiveEngine::new(None);
@@ -552,4 +666,91 @@ mod tests {
         assert_eq!(state.status, "healthy");
         assert!(state.total_memories >= 1);
     }
+
+    #[test]
+    fn test_think_preview_keeps_whole_characters() {
+        let engine = CognitiveEngine::new(None);
+        // "é" straddles byte 100, and each emoji takes four bytes
+        let accented = format!("{}é and more", "a".repeat(99));
+        let emoji = format!("x{}", "🧠".repeat(30));
+
+        engine.think_with_embedding(&accented, &[0.1; 384]).unwrap();
+        engine.think(emoji, vec![0.1; 384]).unwrap();
+
+        let thoughts = engine.get_thoughts(Some(2));
+        assert_eq!(thoughts[0].content, format!("Processing: x{}", "🧠".repeat(24)));
+        assert_eq!(thoughts[1].content, format!("Processing: {}", "a".repeat(99)));
+    }
+
+    #[test]
+    fn test_goal_status_transitions() {
+        let engine = CognitiveEngine::new(None);
+        let low = engine.add_goal("Tidy the garage".to_string(), 0.2);
+        let high = engine.add_goal("Ship the release".to_string(), 3.0);
+
+        let goals = engine.goals();
+        assert_eq!(goals[0].id, high);
+        assert_eq!(goals[0].priority, 1.0);
+        assert_eq!(goals[1].status, GoalStatus::Pending);
+
+        assert!(engine.update_goal(&low, 0.4));
+        let goal = engine.get_goal(&low).unwrap();
+        assert_eq!((goal.progress, goal.status), (0.4, GoalStatus::Active));
+
+        assert!(engine.update_goal(&low, 1.5));
+        let goal = engine.get_goal(&low).unwrap();
+        assert_eq!((goal.progress, goal.status), (1.0, GoalStatus::Completed));
+        assert_eq!(engine.introspect().active_goals, 1);
+
+        assert!(!engine.update_goal("missing", 0.5));
+    }
+
+    #[test]
+    fn test_recall_min_similarity_is_clamped() {
+        let engine = CognitiveEngine::new(None);
+        let axis = |i: usize, scale: f32| {
+            let mut v = vec![0.0f32; 384];
+            v[i] = scale;
+            v
+        };
+
+        // ~0.1 similarity to the query: below the default floor
+        let mut weak = axis(1, 1.0);
+        weak[0] = 0.1;
+        engine
+            .remember_with_embedding("weak".to_string(), weak, "semantic".to_string(), None)
+            .unwrap();
+        // Opposite direction: negative similarity
+        engine
+            .remember_with_embedding(
+                "opposite".to_string(),
+                axis(0, -1.0),
+                "semantic".to_string(),
+                None,
+            )
+            .unwrap();
+
+        let query = axis(0, 1.0);
+        let recall = |min: Option<f64>| {
+            engine
+                .recall_f32(
+                    &query,
+                    Some(10),
+                    SearchOptions {
+                        min_similarity: min,
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+                .into_iter()
+                .map(|r| r.content)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(recall(None).is_empty());
+        // Negative floors clamp to 0.0: weak matches appear, opposite ones don't
+        assert_eq!(recall(Some(-3.0)), vec!["weak"]);
+        // Floors above 1.0 behave exactly like 1.0
+        assert_eq!(recall(Some(7.0)), recall(Some(1.0)));
+    }
 }
@@ -0,0 +1,115 @@
+//! Encryption at rest for sensitive database columns
+//!
+//! AES-256-GCM with a random nonce per value. Sealed values are laid out as
+//! `nonce || ciphertext || tag`.
+
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+
+/// Length in bytes of an encryption key
+pub const KEY_LEN: usize = 32;
+
+/// Encrypts and decrypts individual database values with one key
+pub struct Cipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl Cipher {
+    /// Create a cipher from a `KEY_LEN`-byte key
+    pub fn new(key: &[u8]) -> Result<Self, String> {
+        let unbound =
+            UnboundKey::new(&AES_256_GCM, key).map_err(|_| "Invalid encryption key length")?;
+        Ok(Self {
+            key: LessSafeKey::new(unbound),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Create a cipher from a hex-encoded key, as stored in the keychain
+    pub fn from_hex(hex: &str) -> Result<Self, String> {
+        Self::new(&decode_hex(hex)?)
+    }
+
+    /// A fresh random key, hex-encoded for storage in the keychain
+    pub fn generate_key_hex() -> Result<String, String> {
+        let mut key = [0u8; KEY_LEN];
+        SystemRandom::new()
+            .fill(&mut key)
+            .map_err(|_| "Failed to generate encryption key")?;
+        Ok(key.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    /// Encrypt `plaintext` under a fresh random nonce
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| "Failed to generate nonce")?;
+
+        let mut sealed = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut sealed,
+            )
+            .map_err(|_| "Encryption failed")?;
+
+        let mut out = nonce.to_vec();
+        out.extend_from_slice(&sealed);
+        Ok(out)
+    }
+
+    /// Decrypt a value produced by `seal`; fails on a wrong key or tampering
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, String> {
+        if sealed.len() < NONCE_LEN {
+            return Err("Encrypted value is truncated".to_string());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "Invalid nonce")?;
+
+        let mut buf = ciphertext.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut buf)
+            .map_err(|_| "Decryption failed: wrong key or corrupted data")?;
+        Ok(plaintext.to_vec())
+    }
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, String> {
+    let hex = hex.trim();
+    if !hex.len().is_multiple_of(2) {
+        return Err("Encryption key has an odd number of hex digits".to_string());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| "Encryption key is not valid hex".to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let cipher = Cipher::from_hex(&Cipher::generate_key_hex().unwrap()).unwrap();
+        let sealed = cipher.seal(b"my secret memory").unwrap();
+        assert!(!sealed.windows(6).any(|w| w == b"secret"));
+        assert_eq!(cipher.open(&sealed).unwrap(), b"my secret memory");
+
+        // Each seal uses a fresh nonce
+        assert_ne!(cipher.seal(b"my secret memory").unwrap(), sealed);
+
+        let other = Cipher::from_hex(&Cipher::generate_key_hex().unwrap()).unwrap();
+        assert!(other.open(&sealed).is_err());
+        assert!(Cipher::from_hex("abc").is_err());
+        assert!(Cipher::new(&[0u8; 16]).is_err());
+    }
+}
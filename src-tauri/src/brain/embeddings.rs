@@ -3,25 +3,37 @@
 //! Supports:
 //! - ONNX all-MiniLM-L6-v2 (384-dim, local, fast)
 //! - Ollama embeddings API (fallback)
+//! - OpenAI embeddings API (when an API key is configured)
 //! - Simple hash-based embeddings (ultimate fallback)
 
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
+use lru::LruCache;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 
+use crate::ai::retry::{send_with_retry, RetryPolicy};
 use crate::brain::utils::normalize_vector;
 
-const EMBEDDING_DIM: usize = 384;
+/// Width of hash and MiniLM embeddings; other providers report their own
+const DEFAULT_EMBEDDING_DIM: usize = 384;
+const DEFAULT_CACHE_CAPACITY: usize = 1024;
 const MODEL_REPO: &str = "sentence-transformers/all-MiniLM-L6-v2";
 const MODEL_URL: &str = "https://huggingface.co/sentence-transformers/all-MiniLM-L6-v2/resolve/main/onnx/model.onnx";
 const TOKENIZER_URL: &str = "https://huggingface.co/sentence-transformers/all-MiniLM-L6-v2/resolve/main/tokenizer.json";
+const OPENAI_URL: &str = "https://api.openai.com/v1";
+const OPENAI_MODEL: &str = "text-embedding-3-small";
+/// Ollama model used for embeddings unless the settings name another
+pub const DEFAULT_OLLAMA_EMBED_MODEL: &str = "nomic-embed-text";
 
 /// Embedding provider type
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum EmbeddingProvider {
     Onnx,
     Ollama,
+    OpenAI,
     Hash,
 }
 
@@ -31,12 +43,27 @@ struct OnnxSession {
     tokenizer: tokenizers::Tokenizer,
 }
 
+/// Cache key: (provider, model, text)
+type CacheKey = (String, String, String);
+
 /// Embedding model manager
 pub struct EmbeddingModel {
     provider: RwLock<EmbeddingProvider>,
+    /// Width of vectors from the active provider, updated on each successful embed
+    dimensions: AtomicUsize,
+    /// Recently embedded texts; `None` when caching is disabled
+    cache: parking_lot::Mutex<Option<LruCache<CacheKey, Vec<f32>>>>,
     onnx_session: parking_lot::Mutex<Option<OnnxSession>>,
-    ollama_url: String,
-    ollama_model: String,
+    ollama_url: RwLock<String>,
+    ollama_model: RwLock<String>,
+    /// Retry policy for Ollama embedding calls
+    retry: RetryPolicy,
+    openai_url: String,
+    openai_key: RwLock<Option<String>>,
+    /// Width requested from OpenAI so vectors match the memory store
+    openai_dimensions: AtomicUsize,
+    /// Keeps embeddings on this machine: OpenAI is never selected
+    privacy_mode: AtomicBool,
     model_dir: PathBuf,
 }
 
@@ -50,13 +77,34 @@ impl EmbeddingModel {
 
         Self {
             provider: RwLock::new(EmbeddingProvider::Hash),
+            dimensions: AtomicUsize::new(DEFAULT_EMBEDDING_DIM),
+            cache: parking_lot::Mutex::new(
+                NonZeroUsize::new(DEFAULT_CACHE_CAPACITY).map(LruCache::new),
+            ),
             onnx_session: parking_lot::Mutex::new(None),
-            ollama_url: "http://localhost:11434".to_string(),
-            ollama_model: "nomic-embed-text".to_string(),
+            ollama_url: RwLock::new(crate::ai::ollama::DEFAULT_BASE_URL.to_string()),
+            ollama_model: RwLock::new(DEFAULT_OLLAMA_EMBED_MODEL.to_string()),
+            retry: RetryPolicy::default(),
+            openai_url: OPENAI_URL.to_string(),
+            openai_key: RwLock::new(None),
+            openai_dimensions: AtomicUsize::new(DEFAULT_EMBEDDING_DIM),
+            privacy_mode: AtomicBool::new(false),
             model_dir,
         }
     }
 
+    /// Use an Ollama server other than the default local one
+    pub fn with_ollama_url(mut self, url: &str) -> Self {
+        *self.ollama_url.get_mut() = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Override how transient Ollama failures are retried
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
     /// Try to initialize the best available embedding provider
     /// Priority: ONNX (local, fast) > Ollama > Hash (fallback)
     pub async fn try_init_ollama(&self) -> bool {
@@ -66,20 +114,111 @@ impl EmbeddingModel {
         }
 
         // Then try Ollama
-        let client = reqwest::Client::new();
-        let url = format!("{}/api/tags", self.ollama_url);
+        if self.ollama_available().await {
+            self.set_provider(EmbeddingProvider::Ollama);
+            tracing::info!("Ollama embedding provider initialized");
+            return true;
+        }
+
+        if self.privacy_mode.load(Ordering::Relaxed) {
+            tracing::info!("Privacy mode is on, not using OpenAI embeddings");
+        } else if self.openai_key.read().is_some() {
+            self.set_provider(EmbeddingProvider::OpenAI);
+            tracing::info!("OpenAI embedding provider initialized");
+            return true;
+        }
+        tracing::warn!("Ollama not available, using hash embeddings");
+        false
+    }
 
-        match client.get(&url).timeout(std::time::Duration::from_secs(2)).send().await {
-            Ok(resp) if resp.status().is_success() => {
-                *self.provider.write() = EmbeddingProvider::Ollama;
-                tracing::info!("Ollama embedding provider initialized");
+    /// Follow Ollama coming and going after startup: upgrade from hash
+    /// embeddings when it appears, fall back to hash when it disappears.
+    /// Returns true if the active provider changed.
+    pub async fn refresh_provider(&self) -> bool {
+        match self.provider() {
+            EmbeddingProvider::Hash if self.ollama_available().await => {
+                self.set_provider(EmbeddingProvider::Ollama);
+                tracing::info!("Ollama became available, switched to Ollama embeddings");
                 true
             }
-            _ => {
-                tracing::warn!("Ollama not available, using hash embeddings");
-                false
+            EmbeddingProvider::Ollama if !self.ollama_available().await => {
+                self.set_provider(EmbeddingProvider::Hash);
+                tracing::warn!("Ollama went away, falling back to hash embeddings");
+                true
             }
+            _ => false,
+        }
+    }
+
+    /// Whether the Ollama server answers on its tags endpoint
+    async fn ollama_available(&self) -> bool {
+        let url = format!("{}/api/tags", self.ollama_url.read());
+        matches!(
+            reqwest::Client::new()
+                .get(&url)
+                .timeout(std::time::Duration::from_secs(2))
+                .send()
+                .await,
+            Ok(resp) if resp.status().is_success()
+        )
+    }
+
+    /// Configure the OpenAI key and the width to request. Clearing the key
+    /// while OpenAI is active falls back to hash embeddings.
+    pub fn configure_openai(&self, api_key: Option<String>, dimensions: usize) {
+        let api_key = api_key.filter(|k| !k.is_empty());
+        let has_key = api_key.is_some();
+        *self.openai_key.write() = api_key;
+        self.openai_dimensions.store(dimensions, Ordering::Relaxed);
+
+        if matches!(self.provider(), EmbeddingProvider::OpenAI) {
+            // Re-apply so the width and cache follow the new settings
+            self.set_provider(if has_key && !self.privacy_mode.load(Ordering::Relaxed) {
+                EmbeddingProvider::OpenAI
+            } else {
+                EmbeddingProvider::Hash
+            });
+        }
+    }
+
+    /// Keep embeddings local. Enabling it while OpenAI is active falls back to
+    /// hash embeddings.
+    pub fn set_privacy_mode(&self, enabled: bool) {
+        self.privacy_mode.store(enabled, Ordering::Relaxed);
+        if enabled && matches!(self.provider(), EmbeddingProvider::OpenAI) {
+            tracing::info!("Privacy mode is on, switching away from OpenAI embeddings");
+            self.set_provider(EmbeddingProvider::Hash);
+        }
+    }
+
+    /// Name of the Ollama model used for embeddings
+    pub fn ollama_model(&self) -> String {
+        self.ollama_model.read().clone()
+    }
+
+    /// Switch the Ollama embedding model. Returns true if it changed; the new
+    /// model's width is learned on its first embed.
+    pub fn set_ollama_model(&self, model: &str) -> bool {
+        let model = model.trim();
+        if model.is_empty() || *self.ollama_model.read() == model {
+            return false;
+        }
+        *self.ollama_model.write() = model.to_string();
+        if matches!(self.provider(), EmbeddingProvider::Ollama) {
+            self.set_provider(EmbeddingProvider::Ollama);
+        }
+        true
+    }
+
+    /// Point Ollama embeddings at another server. Returns true if it changed;
+    /// the background refresh notices if the new server is unreachable.
+    pub fn set_ollama_url(&self, url: &str) -> bool {
+        let url = url.trim().trim_end_matches('/');
+        if url.is_empty() || *self.ollama_url.read() == url {
+            return false;
         }
+        *self.ollama_url.write() = url.to_string();
+        true
     }
 
     /// Try to initialize ONNX model (download if needed)
@@ -96,11 +235,8 @@ impl EmbeddingModel {
             }
         }
 
-        // Load ONNX session
-        match self.load_onnx_session(&model_path, &tokenizer_path) {
-            Ok(session) => {
-                *self.onnx_session.lock() = Some(session);
-                *self.provider.write() = EmbeddingProvider::Onnx;
+        match self.load_local_onnx() {
+            Ok(()) => {
                 tracing::info!("ONNX embedding model loaded (all-MiniLM-L6-v2)");
                 true
             }
@@ -111,6 +247,17 @@ impl EmbeddingModel {
         }
     }
 
+    /// Load the ONNX model already present in the model directory and switch
+    /// to it as the active provider
+    fn load_local_onnx(&self) -> Result<(), String> {
+        let model_path = self.model_dir.join("model.onnx");
+        let tokenizer_path = self.model_dir.join("tokenizer.json");
+        let session = self.load_onnx_session(&model_path, &tokenizer_path)?;
+        *self.onnx_session.lock() = Some(session);
+        self.set_provider(EmbeddingProvider::Onnx);
+        Ok(())
+    }
+
     /// Download model and tokenizer files from HuggingFace
     async fn download_model_files(&self) -> Result<(), String> {
         std::fs::create_dir_all(&self.model_dir)
@@ -189,24 +336,130 @@ impl EmbeddingModel {
         self.provider.read().clone()
     }
 
+    /// Switch provider, dropping cached vectors from the previous one
+    fn set_provider(&self, provider: EmbeddingProvider) {
+        // Ollama's width depends on the model and is learned on first embed
+        match provider {
+            EmbeddingProvider::Ollama => {}
+            EmbeddingProvider::OpenAI => self
+                .dimensions
+                .store(self.openai_dimensions.load(Ordering::Relaxed), Ordering::Relaxed),
+            EmbeddingProvider::Onnx | EmbeddingProvider::Hash => {
+                self.dimensions.store(DEFAULT_EMBEDDING_DIM, Ordering::Relaxed)
+            }
+        }
+        *self.provider.write() = provider;
+        if let Some(cache) = self.cache.lock().as_mut() {
+            cache.clear();
+        }
+    }
+
+    /// Set how many embeddings are cached (0 disables the cache)
+    pub fn set_cache_capacity(&self, capacity: usize) {
+        let mut cache = self.cache.lock();
+        match (NonZeroUsize::new(capacity), cache.as_mut()) {
+            (Some(cap), Some(existing)) => existing.resize(cap),
+            (Some(cap), None) => *cache = Some(LruCache::new(cap)),
+            (None, _) => *cache = None,
+        }
+    }
+
+    /// Identifies the provider and model behind the current vectors, e.g.
+    /// "Ollama:nomic-embed-text". Vectors with different IDs aren't comparable.
+    pub fn provider_id(&self) -> String {
+        let provider = self.provider();
+        let model = self.model_name(&provider);
+        if model.is_empty() {
+            format!("{:?}", provider)
+        } else {
+            format!("{:?}:{}", provider, model)
+        }
+    }
+
+    fn cache_key(&self, provider: &EmbeddingProvider, text: &str) -> CacheKey {
+        (format!("{:?}", provider), self.model_name(provider), text.to_string())
+    }
+
+    fn model_name(&self, provider: &EmbeddingProvider) -> String {
+        match provider {
+            EmbeddingProvider::Ollama => self.ollama_model.read().clone(),
+            EmbeddingProvider::Onnx => MODEL_REPO.to_string(),
+            EmbeddingProvider::OpenAI => format!(
+                "{}@{}",
+                OPENAI_MODEL,
+                self.openai_dimensions.load(Ordering::Relaxed)
+            ),
+            EmbeddingProvider::Hash => String::new(),
+        }
+    }
+
+    fn cache_get(&self, provider: &EmbeddingProvider, text: &str) -> Option<Vec<f32>> {
+        let key = self.cache_key(provider, text);
+        self.cache.lock().as_mut()?.get(&key).cloned()
+    }
+
+    fn cache_put(&self, provider: &EmbeddingProvider, text: &str, vector: &[f32]) {
+        let key = self.cache_key(provider, text);
+        if let Some(cache) = self.cache.lock().as_mut() {
+            cache.put(key, vector.to_vec());
+        }
+    }
+
     /// Get embedding dimension
     pub fn dimensions(&self) -> usize {
-        EMBEDDING_DIM
+        self.dimensions.load(Ordering::Relaxed)
+    }
+
+    /// Embed a probe text so the active provider reports its real width
+    pub async fn detect_dimensions(&self) -> Result<usize, String> {
+        let probe = self.embed("dimension probe").await?;
+        Ok(probe.len())
     }
 
     /// Embed a single text
     pub async fn embed(&self, text: &str) -> Result<Vec<f32>, String> {
         // Clone the provider to avoid holding lock across await
         let provider = self.provider.read().clone();
-        match provider {
-            EmbeddingProvider::Ollama => self.embed_ollama(text).await,
-            EmbeddingProvider::Onnx => self.embed_onnx(text),
-            EmbeddingProvider::Hash => Ok(self.embed_hash(text)),
+        if let Some(cached) = self.cache_get(&provider, text) {
+            return Ok(cached);
         }
+
+        let vector = match provider {
+            EmbeddingProvider::Ollama => self.embed_ollama(text).await?,
+            EmbeddingProvider::Onnx => self.embed_onnx(text)?,
+            EmbeddingProvider::OpenAI => self.embed_openai(text).await?,
+            EmbeddingProvider::Hash => self.embed_hash(text),
+        };
+        self.cache_put(&provider, text, &vector);
+        Ok(vector)
     }
 
-    /// Embed multiple texts
+    /// Embed multiple texts (one request when the provider is Ollama)
     pub async fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, String> {
+        let provider = self.provider.read().clone();
+        if matches!(provider, EmbeddingProvider::Ollama) && !texts.is_empty() {
+            let mut results: Vec<Option<Vec<f32>>> =
+                texts.iter().map(|t| self.cache_get(&provider, t)).collect();
+            let missing: Vec<&str> = texts
+                .iter()
+                .zip(&results)
+                .filter(|(_, cached)| cached.is_none())
+                .map(|(t, _)| *t)
+                .collect();
+
+            if !missing.is_empty() {
+                let mut fresh = self.embed_many_ollama(&missing).await?.into_iter();
+                for (text, slot) in texts.iter().zip(results.iter_mut()) {
+                    if slot.is_none() {
+                        let vector = fresh.next().ok_or("Missing Ollama embedding")?;
+                        self.cache_put(&provider, text, &vector);
+                        *slot = Some(vector);
+                    }
+                }
+            }
+            return Ok(results.into_iter().flatten().collect());
+        }
+
         let mut results = Vec::with_capacity(texts.len());
         for text in texts {
             results.push(self.embed(text).await?);
@@ -216,13 +469,21 @@ impl EmbeddingModel {
 
     /// Ollama embedding via REST API
     async fn embed_ollama(&self, text: &str) -> Result<Vec<f32>, String> {
+        self.embed_many_ollama(&[text])
+            .await?
+            .pop()
+            .ok_or_else(|| "No embeddings returned from Ollama".to_string())
+    }
+
+    /// Embed several texts with a single `/api/embed` call, preserving order
+    pub async fn embed_many_ollama(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, String> {
         let client = reqwest::Client::new();
-        let url = format!("{}/api/embed", self.ollama_url);
+        let url = format!("{}/api/embed", self.ollama_url.read());
 
         #[derive(Serialize)]
         struct EmbedRequest<'a> {
             model: &'a str,
-            input: &'a str,
+            input: &'a [&'a str],
         }
 
         #[derive(Deserialize)]
@@ -230,16 +491,19 @@ impl EmbeddingModel {
             embeddings: Vec<Vec<f64>>,
         }
 
-        let resp = client
-            .post(&url)
-            .json(&EmbedRequest {
-                model: &self.ollama_model,
-                input: text,
-            })
-            .timeout(std::time::Duration::from_secs(30))
-            .send()
-            .await
-            .map_err(|e| format!("Ollama request failed: {}", e))?;
+        let model = self.ollama_model.read().clone();
+        let request = EmbedRequest {
+            model: &model,
+            input: texts,
+        };
+        let resp = send_with_retry(&self.retry, || {
+            client
+                .post(&url)
+                .json(&request)
+                .timeout(std::time::Duration::from_secs(30))
+        })
+        .await
+        .map_err(|e| format!("Ollama request failed: {}", e))?;
 
         if !resp.status().is_success() {
             return Err(format!("Ollama returned status: {}", resp.status()));
@@ -250,23 +514,95 @@ impl EmbeddingModel {
             .await
             .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
 
-        if body.embeddings.is_empty() {
-            return Err("No embeddings returned from Ollama".to_string());
+        if body.embeddings.len() != texts.len() {
+            return Err(format!(
+                "Ollama returned {} embeddings for {} inputs",
+                body.embeddings.len(),
+                texts.len()
+            ));
         }
 
-        let embedding: Vec<f32> = body.embeddings[0].iter().map(|&x| x as f32).collect();
+        let width = body.embeddings.first().map(|e| e.len()).unwrap_or(0);
+        if width == 0 || body.embeddings.iter().any(|e| e.len() != width) {
+            return Err("Ollama returned embeddings of inconsistent width".to_string());
+        }
+        self.dimensions.store(width, Ordering::Relaxed);
 
-        // Pad or truncate to EMBEDDING_DIM
-        let mut result = if embedding.len() >= EMBEDDING_DIM {
-            embedding[..EMBEDDING_DIM].to_vec()
-        } else {
-            let mut padded = embedding;
-            padded.resize(EMBEDDING_DIM, 0.0);
-            padded
-        };
+        Ok(body.embeddings.into_iter().map(normalize_embedding).collect())
+    }
+
+    /// OpenAI embedding via `/v1/embeddings`, at the configured width
+    async fn embed_openai(&self, text: &str) -> Result<Vec<f32>, String> {
+        let api_key = self
+            .openai_key
+            .read()
+            .clone()
+            .ok_or("OpenAI API key not configured")?;
+        let dimensions = self.openai_dimensions.load(Ordering::Relaxed);
+
+        #[derive(Serialize)]
+        struct EmbedRequest<'a> {
+            model: &'a str,
+            input: &'a str,
+            dimensions: usize,
+        }
+
+        #[derive(Deserialize)]
+        struct EmbedData {
+            embedding: Vec<f64>,
+        }
+
+        #[derive(Deserialize)]
+        struct EmbedResponse {
+            data: Vec<EmbedData>,
+        }
+
+        let resp = reqwest::Client::new()
+            .post(format!("{}/embeddings", self.openai_url))
+            .bearer_auth(api_key)
+            .json(&EmbedRequest {
+                model: OPENAI_MODEL,
+                input: text,
+                dimensions,
+            })
+            .timeout(std::time::Duration::from_secs(30))
+            .send()
+            .await
+            .map_err(|e| format!("OpenAI request failed: {}", e))?;
+
+        let status = resp.status();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = resp
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| format!(", retry after {}s", v))
+                .unwrap_or_default();
+            return Err(format!("OpenAI rate limit exceeded{}", retry_after));
+        }
+        if !status.is_success() {
+            return Err(format!("OpenAI returned status: {}", status));
+        }
 
-        normalize_vector(&mut result);
-        Ok(result)
+        let body: EmbedResponse = resp
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse OpenAI response: {}", e))?;
+        let embedding = body
+            .data
+            .into_iter()
+            .next()
+            .ok_or("No embeddings returned from OpenAI")?
+            .embedding;
+
+        if embedding.len() != dimensions {
+            return Err(format!(
+                "OpenAI returned {}-dim embedding, expected {}",
+                embedding.len(),
+                dimensions
+            ));
+        }
+        Ok(normalize_embedding(embedding))
     }
 
     /// ONNX embedding using all-MiniLM-L6-v2
@@ -310,34 +646,35 @@ impl EmbeddingModel {
             ]
         ).map_err(|e| format!("ONNX inference failed: {}", e))?;
 
-        // Extract last_hidden_state [1, seq_len, 384]
+        // Extract last_hidden_state [1, seq_len, hidden_dim]
         let output = &outputs[0];
         let (output_shape, output_data) = output.try_extract_tensor::<f32>()
             .map_err(|e| format!("Failed to extract output tensor: {}", e))?;
 
-        // output_shape should be [1, seq_len, 384]
-        let hidden_dim = *output_shape.last().unwrap_or(&(EMBEDDING_DIM as i64)) as usize;
+        // output_shape should be [1, seq_len, hidden_dim]
+        let hidden_dim = *output_shape.last().unwrap_or(&(DEFAULT_EMBEDDING_DIM as i64)) as usize;
 
         // Mean pooling with attention mask
-        let mut embedding = vec![0.0f32; EMBEDDING_DIM];
+        let mut embedding = vec![0.0f32; hidden_dim];
         let mut mask_sum = 0.0f32;
 
         for token_idx in 0..seq_len {
             let mask_val = attention_mask[token_idx] as f32;
             mask_sum += mask_val;
             let offset = token_idx * hidden_dim;
-            for dim in 0..EMBEDDING_DIM.min(hidden_dim) {
+            for dim in 0..hidden_dim {
                 embedding[dim] += output_data[offset + dim] * mask_val;
             }
         }
 
         if mask_sum > 0.0 {
-            for dim in 0..EMBEDDING_DIM {
+            for dim in 0..hidden_dim {
                 embedding[dim] /= mask_sum;
             }
         }
 
         normalize_vector(&mut embedding);
+        self.dimensions.store(hidden_dim, Ordering::Relaxed);
         Ok(embedding)
     }
 
@@ -348,7 +685,7 @@ impl EmbeddingModel {
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
 
-        let mut embedding = vec![0.0f32; EMBEDDING_DIM];
+        let mut embedding = vec![0.0f32; DEFAULT_EMBEDDING_DIM];
         let text_lower = text.to_lowercase();
         let words: Vec<&str> = text_lower.split_whitespace().collect();
 
@@ -357,7 +694,7 @@ impl EmbeddingModel {
         for window in chars.windows(3) {
             let mut hasher = DefaultHasher::new();
             window.hash(&mut hasher);
-            let idx = (hasher.finish() % EMBEDDING_DIM as u64) as usize;
+            let idx = (hasher.finish() % DEFAULT_EMBEDDING_DIM as u64) as usize;
             embedding[idx] += 1.0;
         }
 
@@ -366,8 +703,8 @@ impl EmbeddingModel {
             let mut hasher = DefaultHasher::new();
             word.hash(&mut hasher);
             let hash = hasher.finish();
-            let idx1 = (hash % EMBEDDING_DIM as u64) as usize;
-            let idx2 = ((hash >> 16) % EMBEDDING_DIM as u64) as usize;
+            let idx1 = (hash % DEFAULT_EMBEDDING_DIM as u64) as usize;
+            let idx2 = ((hash >> 16) % DEFAULT_EMBEDDING_DIM as u64) as usize;
             embedding[idx1] += 2.0;
             embedding[idx2] += 1.0;
         }
@@ -377,7 +714,7 @@ impl EmbeddingModel {
             let mut hasher = DefaultHasher::new();
             pair[0].hash(&mut hasher);
             pair[1].hash(&mut hasher);
-            let idx = (hasher.finish() % EMBEDDING_DIM as u64) as usize;
+            let idx = (hasher.finish() % DEFAULT_EMBEDDING_DIM as u64) as usize;
             embedding[idx] += 1.5;
         }
 
@@ -386,6 +723,14 @@ impl EmbeddingModel {
     }
 }
 
+/// Convert a raw provider embedding to a normalized f32 vector, keeping its
+/// full width
+fn normalize_embedding(raw: Vec<f64>) -> Vec<f32> {
+    let mut result: Vec<f32> = raw.into_iter().map(|x| x as f32).collect();
+    normalize_vector(&mut result);
+    result
+}
+
 impl Default for EmbeddingModel {
     fn default() -> Self {
         Self::new()
@@ -396,6 +741,145 @@ impl Default for EmbeddingModel {
 mod tests {
     use super::*;
     use crate::brain::utils::cosine_similarity;
+    use crate::test_support::spawn_mock_server;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    /// Minimal `/api/embed` stand-in: counts requests and answers each input
+    /// `i` with a `width`-dim one-hot vector at position `i`
+    async fn spawn_mock_ollama(requests: Arc<AtomicUsize>, width: usize) -> String {
+        spawn_mock_server(move |request| {
+            requests.fetch_add(1, Ordering::SeqCst);
+            let inputs = request.body["input"].as_array().map(|a| a.len()).unwrap_or(1);
+            let embeddings: Vec<Vec<f64>> = (0..inputs)
+                .map(|i| {
+                    let mut v = vec![0.0; width];
+                    v[i] = 3.0;
+                    v
+                })
+                .collect();
+            (200, serde_json::json!({ "embeddings": embeddings }).to_string())
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_ollama_batch_uses_one_request() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let mut model = EmbeddingModel::new();
+        *model.ollama_url.get_mut() = spawn_mock_ollama(requests.clone(), 16).await;
+        model.set_provider(EmbeddingProvider::Ollama);
+
+        let texts = ["alpha", "beta", "gamma", "delta", "epsilon"];
+        let vectors = model.embed_batch(&texts).await.unwrap();
+
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert_eq!(vectors.len(), texts.len());
+        for (i, v) in vectors.iter().enumerate() {
+            assert_eq!(v.len(), 16);
+            // Order preserved and each vector normalized
+            assert!((v[i] - 1.0).abs() < 1e-6);
+        }
+
+        let single = model.embed("zeta").await.unwrap();
+        assert!((single[0] - 1.0).abs() < 1e-6);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // Only uncached inputs are sent
+        model.embed_batch(&["alpha", "eta"]).await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_ollama_embed_retries_server_errors() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let mut model = EmbeddingModel::new().with_retry(RetryPolicy {
+            max_attempts: 3,
+            base_delay: std::time::Duration::from_millis(1),
+        });
+        *model.ollama_url.get_mut() = spawn_mock_server(move |_| {
+            if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                (500, r#"{"error":"busy"}"#.to_string())
+            } else {
+                (200, r#"{"embeddings":[[0.0,2.0,0.0,0.0]]}"#.to_string())
+            }
+        })
+        .await;
+
+        let vectors = model.embed_many_ollama(&["retry me"]).await.unwrap();
+        assert_eq!(vectors, vec![vec![0.0, 1.0, 0.0, 0.0]]);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_provider_follows_ollama() {
+        let available = Arc::new(AtomicBool::new(false));
+        let flag = available.clone();
+        let mut model = EmbeddingModel::new();
+        *model.ollama_url.get_mut() = spawn_mock_server(move |_| {
+            if flag.load(Ordering::SeqCst) {
+                (200, r#"{"models":[]}"#.to_string())
+            } else {
+                (503, "{}".to_string())
+            }
+        })
+        .await;
+
+        assert!(!model.refresh_provider().await);
+        assert!(matches!(model.provider(), EmbeddingProvider::Hash));
+
+        available.store(true, Ordering::SeqCst);
+        assert!(model.refresh_provider().await);
+        assert!(matches!(model.provider(), EmbeddingProvider::Ollama));
+        assert!(!model.refresh_provider().await);
+
+        available.store(false, Ordering::SeqCst);
+        assert!(model.refresh_provider().await);
+        assert!(matches!(model.provider(), EmbeddingProvider::Hash));
+    }
+
+    #[tokio::test]
+    async fn test_ollama_model_reaches_request() {
+        let seen = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let mut model = EmbeddingModel::new();
+        *model.ollama_url.get_mut() = spawn_mock_server(move |request| {
+            log.lock().push(request.body["model"].as_str().unwrap_or("").to_string());
+            (200, r#"{"embeddings":[[1.0,0.0]]}"#.to_string())
+        })
+        .await;
+
+        assert!(model.set_ollama_model("mxbai-embed-large"));
+        assert!(!model.set_ollama_model("mxbai-embed-large"));
+        model.embed_many_ollama(&["hello"]).await.unwrap();
+
+        assert_eq!(*seen.lock(), vec!["mxbai-embed-large".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_embed_cache() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let mut model = EmbeddingModel::new();
+        *model.ollama_url.get_mut() = spawn_mock_ollama(requests.clone(), 16).await;
+        model.set_provider(EmbeddingProvider::Ollama);
+
+        let first = model.embed("x").await.unwrap();
+        let second = model.embed("x").await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // Switching providers invalidates cached vectors
+        model.set_provider(EmbeddingProvider::Hash);
+        assert_ne!(model.embed("x").await.unwrap(), first);
+        model.set_provider(EmbeddingProvider::Ollama);
+        model.embed("x").await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        model.set_cache_capacity(0);
+        model.embed("x").await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
 
     #[tokio::test]
     async fn test_hash_embedding_similarity() {
@@ -421,10 +905,111 @@ mod tests {
         );
     }
 
+    /// Needs the MiniLM model downloaded to the app's model directory.
+    /// Run with `cargo test -- --ignored test_onnx_embeddings`
+    #[tokio::test]
+    #[ignore]
+    async fn test_onnx_embeddings_are_semantic() {
+        let model = EmbeddingModel::new();
+        assert!(
+            model.model_dir.join("model.onnx").exists()
+                && model.model_dir.join("tokenizer.json").exists(),
+            "ONNX model not downloaded to {:?}",
+            model.model_dir
+        );
+        model.load_local_onnx().unwrap();
+        assert!(matches!(model.provider(), EmbeddingProvider::Onnx));
+
+        let a = model.embed("The cat sat on the mat").await.unwrap();
+        let b = model.embed("A kitten was resting on the rug").await.unwrap();
+        let c = model.embed("Quarterly tax filing deadline").await.unwrap();
+        assert_eq!(a.len(), DEFAULT_EMBEDDING_DIM);
+
+        let synonyms = cosine_similarity(&a, &b);
+        let unrelated = cosine_similarity(&a, &c);
+        assert!(
+            synonyms > unrelated,
+            "synonyms {} should beat unrelated {}",
+            synonyms,
+            unrelated
+        );
+    }
+
+    #[tokio::test]
+    async fn test_provider_width_is_kept() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let mut model = EmbeddingModel::new();
+        *model.ollama_url.get_mut() = spawn_mock_ollama(requests, 768).await;
+        model.set_provider(EmbeddingProvider::Ollama);
+
+        assert_eq!(model.detect_dimensions().await.unwrap(), 768);
+        assert_eq!(model.dimensions(), 768);
+        let vectors = model.embed_batch(&["a", "b"]).await.unwrap();
+        assert!(vectors.iter().all(|v| v.len() == 768));
+        assert!((vectors[1][1] - 1.0).abs() < 1e-6);
+
+        model.set_provider(EmbeddingProvider::Hash);
+        assert_eq!(model.dimensions(), DEFAULT_EMBEDDING_DIM);
+    }
+
+    #[tokio::test]
+    async fn test_openai_request_and_response() {
+        let seen = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen_by_server = seen.clone();
+        let url = spawn_mock_server(move |request| {
+            let input = request.body["input"].as_str().unwrap_or("").to_string();
+            seen_by_server.lock().push(request);
+            if input == "too fast" {
+                return (429, r#"{"error":{"message":"slow down"}}"#.to_string());
+            }
+            let embedding = vec![0.5; 8];
+            let body = serde_json::json!({
+                "object": "list",
+                "data": [{ "object": "embedding", "index": 0, "embedding": embedding }],
+                "model": OPENAI_MODEL,
+            });
+            (200, body.to_string())
+        })
+        .await;
+
+        let mut model = EmbeddingModel::new();
+        model.openai_url = url;
+        model.configure_openai(Some("sk-test".to_string()), 8);
+        model.set_provider(EmbeddingProvider::OpenAI);
+        assert_eq!(model.dimensions(), 8);
+
+        let vector = model.embed("hello").await.unwrap();
+        assert_eq!(vector.len(), 8);
+        assert!((vector.iter().map(|x| x * x).sum::<f32>() - 1.0).abs() < 1e-5);
+
+        {
+            let requests = seen.lock();
+            let request = &requests[0];
+            assert_eq!(request.path, "/embeddings");
+            assert!(request.headers.contains("authorization: bearer sk-test"));
+            assert_eq!(request.body["model"], OPENAI_MODEL);
+            assert_eq!(request.body["input"], "hello");
+            assert_eq!(request.body["dimensions"], 8);
+        }
+
+        let err = model.embed("too fast").await.unwrap_err();
+        assert!(err.contains("rate limit"), "{}", err);
+
+        // Privacy mode keeps embeddings local
+        model.set_privacy_mode(true);
+        assert!(matches!(model.provider(), EmbeddingProvider::Hash));
+        model.set_privacy_mode(false);
+        model.set_provider(EmbeddingProvider::OpenAI);
+
+        // Removing the key falls back to hash embeddings
+        model.configure_openai(None, 8);
+        assert!(matches!(model.provider(), EmbeddingProvider::Hash));
+    }
+
     #[tokio::test]
     async fn test_embedding_dimensions() {
         let model = EmbeddingModel::new();
         let embedding = model.embed("test text").await.unwrap();
-        assert_eq!(embedding.len(), EMBEDDING_DIM);
+        assert_eq!(embedding.len(), DEFAULT_EMBEDDING_DIM);
     }
 }
@@ -0,0 +1,356 @@
+//! Hierarchical Navigable Small World index for approximate vector search
+//!
+//! Used by `NativeMemory` once the store grows past the point where a linear
+//! scan stays cheap. Vectors are expected to be normalized, so similarity is a
+//! plain dot product. Nodes share the store's `NodeVector` buffers rather than
+//! keeping their own copy, so quantized memories stay quantized in the index.
+//! Deletions are tombstoned and skipped in results.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use ahash::{AHashMap, AHashSet};
+use rand::prelude::*;
+
+use crate::brain::memory::NodeVector;
+
+/// Max neighbours per node on upper layers
+const M: usize = 16;
+/// Max neighbours per node on the base layer
+const M_MAX0: usize = 32;
+/// Candidate list size while building
+const EF_CONSTRUCTION: usize = 100;
+
+/// Similarity paired with a node index, ordered by similarity
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scored(f32, usize);
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+struct HnswNode {
+    id: String,
+    vector: NodeVector,
+    /// Neighbour lists, one per layer this node lives on
+    layers: Vec<Vec<usize>>,
+    deleted: bool,
+}
+
+/// Approximate nearest-neighbour index
+pub struct HnswIndex {
+    nodes: Vec<HnswNode>,
+    id_to_idx: AHashMap<String, usize>,
+    entry_point: Option<usize>,
+    max_level: usize,
+    level_mult: f64,
+    deleted: usize,
+}
+
+impl HnswIndex {
+    /// Create an empty index
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            id_to_idx: AHashMap::new(),
+            entry_point: None,
+            max_level: 0,
+            level_mult: 1.0 / (M as f64).ln(),
+            deleted: 0,
+        }
+    }
+
+    /// Number of live (non-deleted) vectors
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.deleted
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// True once tombstones make up more than half the graph
+    pub fn needs_rebuild(&self) -> bool {
+        self.deleted > self.nodes.len() / 2
+    }
+
+    /// Insert a vector, replacing any previous vector with the same ID
+    pub fn insert(&mut self, id: &str, vector: NodeVector) {
+        self.remove(id);
+
+        // Full-precision copy only for the duration of the insert
+        let query = vector.to_f32();
+        let level = self.random_level();
+        let idx = self.nodes.len();
+        self.nodes.push(HnswNode {
+            id: id.to_string(),
+            vector,
+            layers: vec![Vec::new(); level + 1],
+            deleted: false,
+        });
+        self.id_to_idx.insert(id.to_string(), idx);
+
+        let mut entry = match self.entry_point {
+            Some(ep) => ep,
+            None => {
+                self.entry_point = Some(idx);
+                self.max_level = level;
+                return;
+            }
+        };
+
+        // Greedy descent through layers above the new node's level
+        for layer in (level + 1..=self.max_level).rev() {
+            entry = self.greedy_closest(&query, entry, layer);
+        }
+
+        let mut entries = vec![entry];
+        for layer in (0..=level.min(self.max_level)).rev() {
+            let candidates = self.search_layer(&query, &entries, EF_CONSTRUCTION, layer);
+            let max_conn = if layer == 0 { M_MAX0 } else { M };
+            let neighbors = self.select_neighbors(&candidates, max_conn);
+
+            self.nodes[idx].layers[layer] = neighbors.clone();
+            for &n in &neighbors {
+                self.link(n, idx, layer, max_conn);
+            }
+
+            entries = candidates.into_iter().map(|s| s.1).collect();
+        }
+
+        if level > self.max_level {
+            self.max_level = level;
+            self.entry_point = Some(idx);
+        }
+    }
+
+    /// Tombstone a vector. Returns false if the ID was not indexed.
+    pub fn remove(&mut self, id: &str) -> bool {
+        match self.id_to_idx.remove(id) {
+            Some(idx) => {
+                self.nodes[idx].deleted = true;
+                self.deleted += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return up to `k` (id, similarity) pairs, best first, exploring `ef` candidates
+    pub fn search(&self, query: &[f32], k: usize, ef: usize) -> Vec<(String, f32)> {
+        let mut entry = match self.entry_point {
+            Some(ep) => ep,
+            None => return Vec::new(),
+        };
+
+        for layer in (1..=self.max_level).rev() {
+            entry = self.greedy_closest(query, entry, layer);
+        }
+
+        self.search_layer(query, &[entry], ef.max(k), 0)
+            .into_iter()
+            .filter(|s| !self.nodes[s.1].deleted)
+            .take(k)
+            .map(|s| (self.nodes[s.1].id.clone(), s.0))
+            .collect()
+    }
+
+    fn random_level(&self) -> usize {
+        let r: f64 = thread_rng().gen_range(f64::EPSILON..1.0);
+        (-r.ln() * self.level_mult).floor() as usize
+    }
+
+    fn similarity(&self, query: &[f32], idx: usize) -> f32 {
+        self.nodes[idx].vector.dot(query)
+    }
+
+    /// Walk greedily towards the query on a single layer
+    fn greedy_closest(&self, query: &[f32], start: usize, layer: usize) -> usize {
+        let mut best = start;
+        let mut best_sim = self.similarity(query, start);
+
+        loop {
+            let mut improved = false;
+            for &n in &self.nodes[best].layers[layer] {
+                let sim = self.similarity(query, n);
+                if sim > best_sim {
+                    best_sim = sim;
+                    best = n;
+                    improved = true;
+                }
+            }
+            if !improved {
+                return best;
+            }
+        }
+    }
+
+    /// Beam search on one layer; returns up to `ef` candidates sorted best first
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entries: &[usize],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Scored> {
+        let mut visited: AHashSet<usize> = entries.iter().copied().collect();
+        // Max-heap of candidates to expand, min-heap (via Reverse) of current results
+        let mut candidates: BinaryHeap<Scored> = BinaryHeap::new();
+        let mut results: BinaryHeap<std::cmp::Reverse<Scored>> = BinaryHeap::new();
+
+        for &e in entries {
+            let scored = Scored(self.similarity(query, e), e);
+            candidates.push(scored);
+            results.push(std::cmp::Reverse(scored));
+        }
+        while results.len() > ef {
+            results.pop();
+        }
+
+        while let Some(current) = candidates.pop() {
+            let worst = results.peek().map(|r| r.0 .0).unwrap_or(f32::NEG_INFINITY);
+            if current.0 < worst && results.len() >= ef {
+                break;
+            }
+
+            let node = &self.nodes[current.1];
+            if layer >= node.layers.len() {
+                continue;
+            }
+
+            for &n in &node.layers[layer] {
+                if !visited.insert(n) {
+                    continue;
+                }
+                let sim = self.similarity(query, n);
+                let worst = results.peek().map(|r| r.0 .0).unwrap_or(f32::NEG_INFINITY);
+                if results.len() < ef || sim > worst {
+                    candidates.push(Scored(sim, n));
+                    results.push(std::cmp::Reverse(Scored(sim, n)));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        let mut sorted: Vec<Scored> = results.into_iter().map(|r| r.0).collect();
+        sorted.sort_by(|a, b| b.cmp(a));
+        sorted
+    }
+
+    /// Pick up to `max_conn` neighbours from candidates sorted best first.
+    /// A candidate is preferred when it is closer to the base node than to any
+    /// neighbour already chosen, which keeps edges spread across directions;
+    /// remaining slots are filled with the closest leftovers.
+    fn select_neighbors(&self, candidates: &[Scored], max_conn: usize) -> Vec<usize> {
+        let mut selected: Vec<usize> = Vec::with_capacity(max_conn);
+        let mut skipped: Vec<usize> = Vec::new();
+
+        for candidate in candidates {
+            if selected.len() >= max_conn {
+                break;
+            }
+            let vector = self.nodes[candidate.1].vector.as_f32();
+            let diverse = selected
+                .iter()
+                .all(|&s| self.similarity(&vector, s) < candidate.0);
+            if diverse {
+                selected.push(candidate.1);
+            } else {
+                skipped.push(candidate.1);
+            }
+        }
+
+        for idx in skipped {
+            if selected.len() >= max_conn {
+                break;
+            }
+            selected.push(idx);
+        }
+
+        selected
+    }
+
+    /// Add `to` as a neighbour of `from`, re-selecting when over `max_conn`
+    fn link(&mut self, from: usize, to: usize, layer: usize, max_conn: usize) {
+        if layer >= self.nodes[from].layers.len() {
+            return;
+        }
+        self.nodes[from].layers[layer].push(to);
+
+        if self.nodes[from].layers[layer].len() > max_conn {
+            let base = self.nodes[from].vector.to_f32();
+            let mut scored: Vec<Scored> = self.nodes[from].layers[layer]
+                .iter()
+                .map(|&n| Scored(self.similarity(&base, n), n))
+                .collect();
+            scored.sort_by(|a, b| b.cmp(a));
+            self.nodes[from].layers[layer] = self.select_neighbors(&scored, max_conn);
+        }
+    }
+}
+
+impl Default for HnswIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brain::utils::normalize_vector;
+
+    fn random_vectors(n: usize, dim: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..n)
+            .map(|_| {
+                let mut v: Vec<f32> = (0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect();
+                normalize_vector(&mut v);
+                v
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_insert_search_remove() {
+        let mut index = HnswIndex::new();
+        let vectors = random_vectors(500, 16, 7);
+        for (i, v) in vectors.iter().enumerate() {
+            index.insert(&format!("v{}", i), v.clone().into());
+        }
+        assert_eq!(index.len(), 500);
+
+        let hits = index.search(&vectors[42], 1, 32);
+        assert_eq!(hits[0].0, "v42");
+
+        assert!(index.remove("v42"));
+        let hits = index.search(&vectors[42], 5, 32);
+        assert!(hits.iter().all(|(id, _)| id != "v42"));
+        assert_eq!(index.len(), 499);
+    }
+
+    #[test]
+    fn test_quantized_vectors_are_not_expanded() {
+        let mut index = HnswIndex::new();
+        let vectors = random_vectors(300, 16, 11);
+        for (i, v) in vectors.iter().enumerate() {
+            index.insert(&format!("v{}", i), NodeVector::quantize(v));
+        }
+
+        assert!(index.nodes.iter().all(|n| n.vector.is_quantized()));
+        let hits = index.search(&vectors[17], 1, 32);
+        assert_eq!(hits[0].0, "v17");
+    }
+}
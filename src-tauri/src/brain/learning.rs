@@ -3,14 +3,71 @@
 use std::sync::atomic::{AtomicU64, Ordering};
 
 use dashmap::DashMap;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use rand::prelude::*;
+use rand::rngs::StdRng;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::brain::types::{Experience, LearningOutcome, LearningType};
 use crate::brain::utils::{generate_id, now_millis, softmax};
 
+/// Rewards kept for trend detection and meta-learning
+const RECENT_REWARDS_CAP: usize = 100;
+
+/// Neutral `exploration_rate`; meta-learning scales the epsilon schedule
+/// relative to this value
+const BASE_EXPLORATION_RATE: f64 = 0.1;
+
+/// Shape of the epsilon decay curve
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EpsilonDecay {
+    Linear,
+    Exponential,
+}
+
+/// Epsilon-greedy exploration schedule over total experiences
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EpsilonSchedule {
+    pub epsilon_start: f64,
+    pub epsilon_end: f64,
+    /// Experiences over which epsilon moves from start to end
+    pub epsilon_decay_steps: u64,
+    pub decay: EpsilonDecay,
+}
+
+impl Default for EpsilonSchedule {
+    fn default() -> Self {
+        Self {
+            epsilon_start: 0.5,
+            epsilon_end: 0.05,
+            epsilon_decay_steps: 5_000,
+            decay: EpsilonDecay::Linear,
+        }
+    }
+}
+
+impl EpsilonSchedule {
+    /// Scheduled epsilon after `step` experiences
+    pub fn at(&self, step: u64) -> f64 {
+        if self.epsilon_decay_steps == 0 {
+            return self.epsilon_end;
+        }
+        let progress = (step as f64 / self.epsilon_decay_steps as f64).min(1.0);
+        match self.decay {
+            EpsilonDecay::Linear => {
+                self.epsilon_start + (self.epsilon_end - self.epsilon_start) * progress
+            }
+            EpsilonDecay::Exponential => {
+                // Geometric interpolation so the curve lands exactly on epsilon_end
+                let start = self.epsilon_start.max(1e-6);
+                let end = self.epsilon_end.max(1e-6);
+                start * (end / start).powf(progress)
+            }
+        }
+    }
+}
+
 /// Experience buffer entry
 #[derive(Debug, Clone)]
 struct ExperienceEntry {
@@ -49,6 +106,10 @@ pub struct NativeLearner {
     experience_buffer: RwLock<Vec<ExperienceEntry>>,
     /// Q-table for value-based methods
     q_table: DashMap<u64, QEntry, ahash::RandomState>,
+    /// Second Q-table, only trained when `double_q` is enabled
+    q_table_b: DashMap<u64, QEntry, ahash::RandomState>,
+    /// Alternates which table a Double Q-learning update targets
+    double_q_turn: AtomicU64,
     /// Learning strategies
     strategies: RwLock<Vec<Strategy>>,
     /// Configuration
@@ -60,7 +121,13 @@ pub struct NativeLearner {
     total_updates: AtomicU64,
     #[allow(dead_code)]
     state_dimension: usize,
-    action_count: usize,
+    /// Length of every `QEntry.values`; held for reading while training so a
+    /// resize can't interleave with updates
+    action_count: RwLock<usize>,
+    /// Source of every random draw the learner makes
+    rng: Mutex<StdRng>,
+    /// Seeded learners train sequentially so runs are reproducible
+    seeded: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -73,6 +140,11 @@ pub(crate) struct LearnerConfig {
     #[allow(dead_code)]
     pub target_update_freq: u32,
     pub curiosity_weight: f64,
+    pub epsilon_schedule: EpsilonSchedule,
+    /// Double Q-learning: one table picks the next action, the other values it
+    pub double_q: bool,
+    /// States visited fewer times than this are dropped by periodic pruning
+    pub prune_min_visits: u32,
 }
 
 impl Default for LearnerConfig {
@@ -80,11 +152,14 @@ impl Default for LearnerConfig {
         Self {
             learning_rate: 0.01,
             discount_factor: 0.99,
-            exploration_rate: 0.1,
+            exploration_rate: BASE_EXPLORATION_RATE,
             batch_size: 32,
             buffer_size: 10_000,
             target_update_freq: 100,
             curiosity_weight: 0.5,
+            epsilon_schedule: EpsilonSchedule::default(),
+            double_q: false,
+            prune_min_visits: 2,
         }
     }
 }
@@ -92,16 +167,30 @@ impl Default for LearnerConfig {
 impl NativeLearner {
     /// Create a new native learner
     pub fn new(state_dim: u32, action_count: u32) -> Self {
+        Self::build(state_dim, action_count, StdRng::from_entropy(), false)
+    }
+
+    /// Create a learner whose random draws (exploration, replay sampling,
+    /// SARSA bootstrapping) are reproducible from `seed`
+    pub fn with_seed(state_dim: u32, action_count: u32, seed: u64) -> Self {
+        Self::build(state_dim, action_count, StdRng::seed_from_u64(seed), true)
+    }
+
+    fn build(state_dim: u32, action_count: u32, rng: StdRng, seeded: bool) -> Self {
         let mut learner = Self {
             experience_buffer: RwLock::new(Vec::with_capacity(10_000)),
             q_table: DashMap::with_hasher(ahash::RandomState::new()),
+            q_table_b: DashMap::with_hasher(ahash::RandomState::new()),
+            double_q_turn: AtomicU64::new(0),
             strategies: RwLock::new(Vec::new()),
             config: RwLock::new(LearnerConfig::default()),
-            recent_rewards: RwLock::new(Vec::with_capacity(100)),
+            recent_rewards: RwLock::new(Vec::with_capacity(RECENT_REWARDS_CAP)),
             total_experiences: AtomicU64::new(0),
             total_updates: AtomicU64::new(0),
             state_dimension: state_dim as usize,
-            action_count: action_count as usize,
+            action_count: RwLock::new((action_count as usize).max(1)),
+            rng: Mutex::new(rng),
+            seeded,
         };
 
         learner.initialize_strategies();
@@ -148,8 +237,12 @@ impl NativeLearner {
         let next_state: Vec<f64> = experience.next_state.clone();
 
         let curiosity_bonus = self.calculate_curiosity(&state);
-        let config = self.config.read();
-        let total_reward = experience.reward + curiosity_bonus * config.curiosity_weight;
+        // Copy what we need and release the guard: meta_learn takes the write lock
+        let (curiosity_weight, buffer_size, batch_size) = {
+            let config = self.config.read();
+            (config.curiosity_weight, config.buffer_size, config.batch_size)
+        };
+        let total_reward = experience.reward + curiosity_bonus * curiosity_weight;
 
         {
             let mut buffer = self.experience_buffer.write();
@@ -163,7 +256,7 @@ impl NativeLearner {
                 timestamp: now_millis(),
             });
 
-            if buffer.len() > config.buffer_size {
+            if buffer.len() > buffer_size {
                 buffer.remove(0);
             }
         }
@@ -171,14 +264,14 @@ impl NativeLearner {
         {
             let mut rewards = self.recent_rewards.write();
             rewards.push(total_reward);
-            if rewards.len() > 100 {
+            if rewards.len() > RECENT_REWARDS_CAP {
                 rewards.remove(0);
             }
         }
 
         self.total_experiences.fetch_add(1, Ordering::Relaxed);
 
-        let insights = if self.experience_buffer.read().len() >= config.batch_size {
+        let insights = if self.experience_buffer.read().len() >= batch_size {
             self.train_batch()?
         } else {
             Vec::new()
@@ -198,7 +291,7 @@ impl NativeLearner {
         })
     }
 
-    /// Train on a batch of experiences (parallel)
+    /// Train on a batch of experiences (parallel unless the learner is seeded)
     pub fn train_batch(&self) -> Result<Vec<String>, String> {
         let config = self.config.read();
         let buffer = self.experience_buffer.read();
@@ -208,45 +301,84 @@ impl NativeLearner {
         }
 
         let batch = self.sample_prioritized_batch(&buffer, config.batch_size);
+        let action_count_guard = self.action_count.read();
+        let action_count = *action_count_guard;
+
+        let (strategy_idx, rule, novelty_weight) = self.select_strategy();
+        let epsilon = self.effective_epsilon_with(&config);
+
+        // Draw SARSA's exploratory next actions up front so the draws don't
+        // depend on how the updates are scheduled across threads
+        let sarsa_actions: Vec<Option<usize>> = if rule == LearningType::SARSA {
+            let mut rng = self.rng.lock();
+            batch
+                .iter()
+                .map(|_| (rng.gen::<f64>() < epsilon).then(|| rng.gen_range(0..action_count)))
+                .collect()
+        } else {
+            vec![None; batch.len()]
+        };
+        let turn = self
+            .double_q_turn
+            .fetch_add(batch.len() as u64, Ordering::Relaxed);
 
-        let td_errors: Vec<f64> = batch
-            .par_iter()
-            .map(|exp| {
-                let state_hash = self.hash_state(&exp.state);
-
-                let mut q_entry = self.q_table.entry(state_hash).or_insert_with(|| QEntry {
-                    values: vec![0.0; self.action_count],
-                    visits: 0,
-                });
-
-                let next_state_hash = self.hash_state(&exp.next_state);
-                let next_max_q = if exp.done {
-                    0.0
-                } else {
-                    self.q_table
-                        .get(&next_state_hash)
-                        .map(|e| {
-                            e.values
-                                .iter()
-                                .cloned()
-                                .fold(f64::NEG_INFINITY, f64::max)
-                        })
-                        .unwrap_or(0.0)
-                };
-
-                let td_target = exp.reward + config.discount_factor * next_max_q;
-                let current_q = q_entry.values[exp.action as usize];
-                let td_error = td_target - current_q;
-
-                q_entry.values[exp.action as usize] += config.learning_rate * td_error;
-                q_entry.visits += 1;
-
-                td_error
-            })
-            .collect();
+        let update = |(i, exp): (usize, &ExperienceEntry)| {
+            if exp.action as usize >= action_count {
+                return None;
+            }
+
+            let state_hash = self.hash_state(&exp.state);
+            let update_a = !config.double_q || (turn + i as u64) & 1 == 0;
+            let double = config.double_q.then_some(update_a);
+
+            let table = if update_a { &self.q_table } else { &self.q_table_b };
+
+            // Read the next state before taking the entry guard: both keys can
+            // share a shard, and DashMap locks are not reentrant
+            let next_state_hash = self.hash_state(&exp.next_state);
+            let next_q = if exp.done {
+                0.0
+            } else if rule == LearningType::SARSA {
+                self.sarsa_next_value(table, next_state_hash, sarsa_actions[i])
+            } else {
+                self.next_state_value(next_state_hash, double)
+            };
+            let bonus = if rule == LearningType::CuriosityDriven {
+                novelty_weight * self.calculate_curiosity(&exp.next_state)
+            } else {
+                0.0
+            };
+
+            let mut q_entry = table.entry(state_hash).or_insert_with(|| QEntry {
+                values: vec![0.0; action_count],
+                visits: 0,
+            });
+            q_entry.values.resize(action_count, 0.0);
+
+            let td_target = exp.reward + bonus + config.discount_factor * next_q;
+            let current_q = q_entry.values[exp.action as usize];
+            let td_error = td_target - current_q;
+
+            q_entry.values[exp.action as usize] += config.learning_rate * td_error;
+            q_entry.visits += 1;
+
+            Some(td_error)
+        };
+        let td_errors: Vec<f64> = if self.seeded {
+            batch.iter().enumerate().filter_map(update).collect()
+        } else {
+            batch.par_iter().enumerate().filter_map(update).collect()
+        };
 
         self.total_updates
-            .fetch_add(batch.len() as u64, Ordering::Relaxed);
+            .fetch_add(td_errors.len() as u64, Ordering::Relaxed);
+
+        if td_errors.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let batch_reward = batch.iter().map(|e| e.reward).sum::<f64>() / batch.len() as f64;
+        self.update_strategy(strategy_idx, &td_errors, batch_reward);
 
         let avg_td_error: f64 = td_errors.iter().sum::<f64>() / td_errors.len() as f64;
         let mut insights = Vec::new();
@@ -260,46 +392,161 @@ impl NativeLearner {
         Ok(insights)
     }
 
+    /// Pick the update rule for the next batch with UCB1 over strategy
+    /// success rates. Returns the strategy index, its rule and its novelty
+    /// weight (only meaningful for curiosity-driven updates).
+    fn select_strategy(&self) -> (usize, LearningType, f64) {
+        let strategies = self.strategies.read();
+        let total: u64 = strategies.iter().map(|s| s.usage_count).sum();
+
+        let score = |s: &Strategy| {
+            if s.usage_count == 0 {
+                f64::INFINITY
+            } else {
+                s.success_rate + (2.0 * (total as f64).ln() / s.usage_count as f64).sqrt()
+            }
+        };
+        let idx = strategies
+            .iter()
+            .enumerate()
+            .max_by(|a, b| {
+                score(a.1)
+                    .partial_cmp(&score(b.1))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+
+        match strategies.get(idx) {
+            Some(s) => (idx, s.learning_type, s.parameters.first().copied().unwrap_or(0.0)),
+            None => (0, LearningType::QLearning, 0.0),
+        }
+    }
+
+    /// Credit the strategy used for a batch: smaller TD errors count as success
+    fn update_strategy(&self, idx: usize, td_errors: &[f64], batch_reward: f64) {
+        let mut strategies = self.strategies.write();
+        let Some(strategy) = strategies.get_mut(idx) else {
+            return;
+        };
+
+        let mean_abs_td = td_errors.iter().map(|e| e.abs()).sum::<f64>() / td_errors.len() as f64;
+        let success = 1.0 / (1.0 + mean_abs_td);
+
+        strategy.usage_count += 1;
+        strategy.success_rate = 0.9 * strategy.success_rate + 0.1 * success;
+        strategy.avg_reward += (batch_reward - strategy.avg_reward) / strategy.usage_count as f64;
+    }
+
+    /// On-policy (SARSA) bootstrap: value of the action the current
+    /// epsilon-greedy policy takes in the next state. `explored` is the
+    /// random action when the policy explores, `None` when it acts greedily.
+    fn sarsa_next_value(
+        &self,
+        table: &DashMap<u64, QEntry, ahash::RandomState>,
+        next_hash: u64,
+        explored: Option<usize>,
+    ) -> f64 {
+        table
+            .get(&next_hash)
+            .and_then(|e| {
+                let action = explored.or_else(|| argmax(&e.values))?;
+                e.values.get(action).copied()
+            })
+            .unwrap_or(0.0)
+    }
+
+    /// Per-strategy usage and performance
+    pub fn strategy_stats(&self) -> Vec<StrategyStats> {
+        self.strategies
+            .read()
+            .iter()
+            .map(|s| StrategyStats {
+                name: s.name.clone(),
+                learning_type: s.learning_type,
+                success_rate: s.success_rate,
+                usage_count: s.usage_count,
+                avg_reward: s.avg_reward,
+            })
+            .collect()
+    }
+
     /// Select action using epsilon-greedy policy
     pub fn select_action(&self, state: Vec<f64>) -> u32 {
-        let config = self.config.read();
-        let mut rng = thread_rng();
+        let epsilon = self.effective_epsilon();
+        let action_count = self.action_count();
+        let (explore, random_action) = {
+            let mut rng = self.rng.lock();
+            (rng.gen::<f64>() < epsilon, rng.gen_range(0..action_count as u32))
+        };
 
-        if rng.gen::<f64>() < config.exploration_rate {
-            return rng.gen_range(0..self.action_count as u32);
+        if explore {
+            return random_action;
         }
 
         let state_hash = self.hash_state(&state);
 
-        self.q_table
-            .get(&state_hash)
-            .map(|entry| {
-                entry
-                    .values
-                    .iter()
-                    .enumerate()
-                    .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
-                    .map(|(i, _)| i as u32)
-                    .unwrap_or(0)
+        self.policy_values(state_hash)
+            .map(|values| {
+                let n = values.len().min(action_count);
+                argmax(&values[..n]).unwrap_or(0) as u32
             })
-            .unwrap_or_else(|| rng.gen_range(0..self.action_count as u32))
+            .unwrap_or(random_action)
+    }
+
+    /// Exploration probability used by `select_action`: the scheduled epsilon
+    /// for the current experience count, scaled by how far meta-learning (or
+    /// `explore`/`exploit`) has moved `exploration_rate` from its baseline.
+    pub fn effective_epsilon(&self) -> f64 {
+        self.effective_epsilon_with(&self.config.read())
+    }
+
+    fn effective_epsilon_with(&self, config: &LearnerConfig) -> f64 {
+        let scheduled = config
+            .epsilon_schedule
+            .at(self.total_experiences.load(Ordering::Relaxed));
+        (scheduled * config.exploration_rate / BASE_EXPLORATION_RATE).clamp(0.0, 1.0)
+    }
+
+    pub fn epsilon_schedule(&self) -> EpsilonSchedule {
+        self.config.read().epsilon_schedule
+    }
+
+    pub fn set_epsilon_schedule(&self, schedule: EpsilonSchedule) {
+        self.config.write().epsilon_schedule = schedule;
+    }
+
+    pub fn action_count(&self) -> usize {
+        *self.action_count.read()
+    }
+
+    /// Resize the action space, padding existing Q-values with 0.0 or
+    /// truncating them
+    pub fn set_action_count(&self, n: usize) {
+        let n = n.max(1);
+        let mut count = self.action_count.write();
+        for table in [&self.q_table, &self.q_table_b] {
+            for mut entry in table.iter_mut() {
+                entry.values.resize(n, 0.0);
+            }
+        }
+        *count = n;
     }
 
     /// Select action using softmax policy
     pub fn select_action_softmax(&self, state: Vec<f64>, temperature: f64) -> u32 {
         let state_hash = self.hash_state(&state);
-        let mut rng = thread_rng();
 
-        let q_values = self
-            .q_table
-            .get(&state_hash)
-            .map(|e| e.values.clone())
-            .unwrap_or_else(|| vec![0.0; self.action_count]);
+        let action_count = self.action_count();
+        let mut q_values = self
+            .policy_values(state_hash)
+            .unwrap_or_else(|| vec![0.0; action_count]);
+        q_values.resize(action_count, 0.0);
 
         let mut probs: Vec<f64> = q_values.iter().map(|&q| q / temperature).collect();
         softmax(&mut probs);
 
-        let r: f64 = rng.gen();
+        let r: f64 = self.rng.lock().gen();
         let mut cumsum = 0.0;
         for (i, &p) in probs.iter().enumerate() {
             cumsum += p;
@@ -308,7 +555,7 @@ impl NativeLearner {
             }
         }
 
-        (self.action_count - 1) as u32
+        (action_count - 1) as u32
     }
 
     fn calculate_curiosity(&self, state: &[f64]) -> f64 {
@@ -361,12 +608,7 @@ impl NativeLearner {
     }
 
     fn compute_td_error(&self, exp: &Experience) -> f64 {
-        let config = self.config.read();
-        let state = exp.state.clone();
-        let next_state = exp.next_state.clone();
-
-        let state_hash = self.hash_state(&state);
-        let next_hash = self.hash_state(&next_state);
+        let state_hash = self.hash_state(&exp.state);
 
         let current_q = self
             .q_table
@@ -374,22 +616,56 @@ impl NativeLearner {
             .map(|e| e.values.get(exp.action as usize).copied().unwrap_or(0.0))
             .unwrap_or(0.0);
 
-        let next_max_q = if exp.done {
+        self.td_target(exp) - current_q
+    }
+
+    /// TD target for `exp` as the primary table would be updated
+    fn td_target(&self, exp: &Experience) -> f64 {
+        let config = self.config.read();
+        let next_q = if exp.done {
             0.0
         } else {
-            self.q_table
+            let next_hash = self.hash_state(&exp.next_state);
+            self.next_state_value(next_hash, config.double_q.then_some(true))
+        };
+        exp.reward + config.discount_factor * next_q
+    }
+
+    /// Bootstrapped value of the next state. `None` takes the max of the
+    /// primary table; `Some(update_a)` lets the table being updated pick the
+    /// action and the other table evaluate it.
+    fn next_state_value(&self, next_hash: u64, double: Option<bool>) -> f64 {
+        let Some(update_a) = double else {
+            return self
+                .q_table
                 .get(&next_hash)
-                .map(|e| {
-                    e.values
-                        .iter()
-                        .cloned()
-                        .fold(f64::NEG_INFINITY, f64::max)
-                })
-                .unwrap_or(0.0)
+                .map(|e| e.values.iter().cloned().fold(f64::NEG_INFINITY, f64::max))
+                .unwrap_or(0.0);
         };
 
-        let td_target = exp.reward + config.discount_factor * next_max_q;
-        td_target - current_q
+        let (selector, evaluator) = if update_a {
+            (&self.q_table, &self.q_table_b)
+        } else {
+            (&self.q_table_b, &self.q_table)
+        };
+        let best_action = selector.get(&next_hash).and_then(|e| argmax(&e.values));
+        best_action
+            .and_then(|a| evaluator.get(&next_hash).and_then(|e| e.values.get(a).copied()))
+            .unwrap_or(0.0)
+    }
+
+    /// Action values the policy acts on: the primary table, or the sum of both
+    /// tables under Double Q-learning
+    fn policy_values(&self, state_hash: u64) -> Option<Vec<f64>> {
+        let a = self.q_table.get(&state_hash).map(|e| e.values.clone());
+        if !self.config.read().double_q {
+            return a;
+        }
+        let b = self.q_table_b.get(&state_hash).map(|e| e.values.clone());
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a.iter().zip(&b).map(|(x, y)| x + y).collect()),
+            (a, b) => a.or(b),
+        }
     }
 
     fn sample_prioritized_batch(
@@ -397,7 +673,7 @@ impl NativeLearner {
         buffer: &[ExperienceEntry],
         size: usize,
     ) -> Vec<ExperienceEntry> {
-        let mut rng = thread_rng();
+        let mut rng = self.rng.lock();
         let total_priority: f64 = buffer.iter().map(|e| e.priority.abs() + 0.01).sum();
 
         let mut batch = Vec::with_capacity(size);
@@ -475,7 +751,7 @@ impl NativeLearner {
             total_updates: self.total_updates.load(Ordering::Relaxed) as f64,
             q_table_size: self.q_table.len() as u32,
             avg_reward,
-            exploration_rate: config.exploration_rate,
+            exploration_rate: self.effective_epsilon_with(&config),
             learning_rate: config.learning_rate,
             trend: self.calculate_trend(&rewards),
         }
@@ -494,7 +770,7 @@ impl NativeLearner {
     }
 
     pub fn exploit(&self) {
-        self.config.write().exploration_rate = 0.1;
+        self.config.write().exploration_rate = BASE_EXPLORATION_RATE;
     }
 
     /// Export Q-table for persistence
@@ -507,11 +783,60 @@ impl NativeLearner {
 
     /// Import Q-table from persistence
     pub fn import_q_table(&self, entries: Vec<(u64, Vec<f64>, u32)>) {
-        for (key, values, visits) in entries {
-            self.q_table.insert(key, QEntry { values, visits });
+        Self::import_entries(&self.q_table, entries, self.action_count());
+    }
+
+    /// Export the secondary Double Q-learning table for persistence
+    pub fn export_q_table_b(&self) -> Vec<(u64, Vec<f64>, u32)> {
+        self.q_table_b
+            .iter()
+            .map(|e| (*e.key(), e.value().values.clone(), e.value().visits))
+            .collect()
+    }
+
+    /// Import the secondary Double Q-learning table from persistence
+    pub fn import_q_table_b(&self, entries: Vec<(u64, Vec<f64>, u32)>) {
+        Self::import_entries(&self.q_table_b, entries, self.action_count());
+    }
+
+    /// Saved entries may predate an action-count change; fit them to the
+    /// current action space
+    fn import_entries(
+        table: &DashMap<u64, QEntry, ahash::RandomState>,
+        entries: Vec<(u64, Vec<f64>, u32)>,
+        action_count: usize,
+    ) {
+        for (key, mut values, visits) in entries {
+            values.resize(action_count, 0.0);
+            table.insert(key, QEntry { values, visits });
         }
     }
 
+    /// Drop Q-table states with `visits < min_visits` from both tables,
+    /// returning how many entries were removed
+    pub fn prune_q_table(&self, min_visits: u32) -> u32 {
+        let before = self.q_table.len() + self.q_table_b.len();
+        self.q_table.retain(|_, e| e.visits >= min_visits);
+        self.q_table_b.retain(|_, e| e.visits >= min_visits);
+        (before - self.q_table.len() - self.q_table_b.len()) as u32
+    }
+
+    pub fn prune_min_visits(&self) -> u32 {
+        self.config.read().prune_min_visits
+    }
+
+    pub fn set_prune_min_visits(&self, min_visits: u32) {
+        self.config.write().prune_min_visits = min_visits;
+    }
+
+    pub fn double_q(&self) -> bool {
+        self.config.read().double_q
+    }
+
+    pub fn set_double_q(&self, enabled: bool) {
+        self.config.write().double_q = enabled;
+    }
+
     /// Export experience buffer for persistence
     pub fn export_experiences(&self) -> Vec<Experience> {
         self.experience_buffer
@@ -526,6 +851,57 @@ impl NativeLearner {
             })
             .collect()
     }
+
+    /// Load experiences into the replay buffer (e.g. from persistence).
+    /// Priorities are recomputed from reward magnitude; when the buffer
+    /// overflows `buffer_size` the oldest entries are dropped.
+    pub fn import_experiences(&self, exps: Vec<Experience>) {
+        let buffer_size = self.config.read().buffer_size;
+        let now = now_millis();
+
+        let mut buffer = self.experience_buffer.write();
+        buffer.extend(exps.into_iter().map(|e| ExperienceEntry {
+            priority: e.reward.abs(),
+            state: e.state,
+            action: e.action,
+            reward: e.reward,
+            next_state: e.next_state,
+            done: e.done,
+            timestamp: now,
+        }));
+
+        if buffer.len() > buffer_size {
+            let excess = buffer.len() - buffer_size;
+            buffer.drain(..excess);
+        }
+    }
+
+    /// Export the rolling reward window for persistence
+    pub fn export_recent_rewards(&self) -> Vec<f64> {
+        self.recent_rewards.read().clone()
+    }
+
+    /// Forget learned values, replay experiences, and recent rewards, e.g.
+    /// before loading a restored database. Configuration is kept.
+    pub fn clear_learned(&self) {
+        self.q_table.clear();
+        self.q_table_b.clear();
+        self.experience_buffer.write().clear();
+        self.recent_rewards.write().clear();
+    }
+
+    /// Restore the rolling reward window, keeping the newest entries
+    pub fn import_recent_rewards(&self, mut rewards: Vec<f64>) {
+        if rewards.len() > RECENT_REWARDS_CAP {
+            rewards.drain(..rewards.len() - RECENT_REWARDS_CAP);
+        }
+        *self.recent_rewards.write() = rewards;
+    }
+
+    /// Maximum number of experiences kept for replay
+    pub fn buffer_size(&self) -> usize {
+        self.config.read().buffer_size
+    }
 }
 
 /// Learner statistics
@@ -540,6 +916,24 @@ pub struct LearnerStats {
     pub trend: f64,
 }
 
+/// Strategy statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StrategyStats {
+    pub name: String,
+    pub learning_type: LearningType,
+    pub success_rate: f64,
+    pub usage_count: u64,
+    pub avg_reward: f64,
+}
+
+fn argmax(values: &[f64]) -> Option<usize> {
+    values
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(i, _)| i)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -562,4 +956,276 @@ mod tests {
         let action = learner.select_action(vec![1.0, 0.0, 0.0, 0.0]);
         assert!(action < 3);
     }
+
+    #[test]
+    fn test_epsilon_decays_toward_end() {
+        let learner = NativeLearner::new(2, 3);
+        learner.set_epsilon_schedule(EpsilonSchedule {
+            epsilon_start: 0.8,
+            epsilon_end: 0.05,
+            epsilon_decay_steps: 200,
+            decay: EpsilonDecay::Linear,
+        });
+
+        let mut samples = vec![learner.effective_epsilon()];
+        for i in 0..300 {
+            learner
+                .learn(Experience {
+                    state: vec![i as f64, 0.0],
+                    action: i % 3,
+                    reward: 0.5,
+                    next_state: vec![i as f64 + 1.0, 0.0],
+                    done: false,
+                })
+                .unwrap();
+            if i % 50 == 49 {
+                samples.push(learner.effective_epsilon());
+            }
+        }
+
+        assert!((samples[0] - 0.8).abs() < 1e-9);
+        assert!(samples.windows(2).all(|w| w[1] <= w[0]), "{:?}", samples);
+        assert!((samples.last().unwrap() - 0.05).abs() < 1e-9);
+
+        // Meta-learning/explore scale the schedule rather than replace it
+        learner.explore();
+        assert!(learner.effective_epsilon() > 0.05);
+        learner.exploit();
+
+        let exponential = EpsilonSchedule {
+            decay: EpsilonDecay::Exponential,
+            ..learner.epsilon_schedule()
+        };
+        assert!((exponential.at(0) - 0.8).abs() < 1e-9);
+        let linear = EpsilonSchedule {
+            decay: EpsilonDecay::Linear,
+            ..exponential
+        };
+        assert!(exponential.at(100) < linear.at(100));
+        assert!((exponential.at(1_000) - 0.05).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_double_q_reduces_overestimation() {
+        let learner = NativeLearner::new(2, 3);
+        let exp = Experience {
+            state: vec![0.0, 0.0],
+            action: 0,
+            reward: 0.0,
+            next_state: vec![1.0, 0.0],
+            done: false,
+        };
+        let next_hash = learner.hash_state(&exp.next_state);
+
+        // Noisy estimates: table A thinks action 1 is great, table B knows
+        // every action is worth ~0 in the next state
+        learner.import_q_table(vec![(next_hash, vec![0.1, 2.0, 0.3], 5)]);
+        learner.import_q_table_b(vec![(next_hash, vec![0.0, 0.1, 0.05], 5)]);
+
+        let single = learner.td_target(&exp);
+        assert!((single - 0.99 * 2.0).abs() < 1e-9);
+
+        learner.set_double_q(true);
+        let double = learner.td_target(&exp);
+        assert!((double - 0.99 * 0.1).abs() < 1e-9);
+        assert!(double < single);
+
+        // Acting uses both tables
+        learner.set_epsilon_schedule(EpsilonSchedule {
+            epsilon_decay_steps: 0,
+            epsilon_end: 0.0,
+            ..EpsilonSchedule::default()
+        });
+        assert_eq!(learner.select_action(exp.next_state.clone()), 1);
+        assert_eq!(learner.export_q_table_b().len(), 1);
+    }
+
+    #[test]
+    fn test_prune_q_table() {
+        let learner = NativeLearner::new(2, 3);
+        learner.import_q_table(vec![
+            (1, vec![0.0; 3], 0),
+            (2, vec![0.0; 3], 1),
+            (3, vec![0.0; 3], 5),
+            (4, vec![0.0; 3], 20),
+        ]);
+        learner.import_q_table_b(vec![(1, vec![0.0; 3], 1), (3, vec![0.0; 3], 5)]);
+
+        assert_eq!(learner.prune_q_table(2), 3);
+
+        let mut kept: Vec<u64> = learner.export_q_table().iter().map(|e| e.0).collect();
+        kept.sort();
+        assert_eq!(kept, vec![3, 4]);
+        assert_eq!(learner.export_q_table_b().len(), 1);
+        assert_eq!(learner.prune_q_table(2), 0);
+    }
+
+    #[test]
+    fn test_set_action_count_resizes_q_values() {
+        let learner = NativeLearner::new(2, 3);
+        learner.set_epsilon_schedule(EpsilonSchedule {
+            epsilon_decay_steps: 0,
+            epsilon_end: 0.0,
+            ..EpsilonSchedule::default()
+        });
+        let state = vec![1.0, 0.0];
+        let hash = learner.hash_state(&state);
+        learner.import_q_table(vec![(hash, vec![0.1, 0.9, 0.2], 4)]);
+
+        learner.set_action_count(6);
+        assert_eq!(learner.action_count(), 6);
+        assert_eq!(learner.export_q_table()[0].1, vec![0.1, 0.9, 0.2, 0.0, 0.0, 0.0]);
+        assert_eq!(learner.select_action(state.clone()), 1);
+
+        // New actions are trainable
+        for i in 0..40 {
+            learner
+                .learn(Experience {
+                    state: vec![i as f64, 5.0],
+                    action: 5,
+                    reward: 1.0,
+                    next_state: vec![i as f64, 6.0],
+                    done: true,
+                })
+                .unwrap();
+        }
+
+        let trained = learner.export_q_table();
+        assert!(trained.iter().any(|(_, values, _)| values[5] > 0.0));
+
+        learner.set_action_count(1);
+        assert!(learner.export_q_table().iter().all(|(_, values, _)| values.len() == 1));
+        assert_eq!(learner.select_action(state.clone()), 0);
+        assert!(learner.select_action_softmax(state, 1.0) < 1);
+        assert!(learner.train_batch().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_strategies_are_selected_and_updated() {
+        let learner = NativeLearner::new(2, 3);
+        for i in 0..64 {
+            learner
+                .learn(Experience {
+                    state: vec![(i % 8) as f64, 0.0],
+                    action: i % 3,
+                    reward: 1.0,
+                    next_state: vec![((i + 1) % 8) as f64, 0.0],
+                    done: false,
+                })
+                .unwrap();
+        }
+        let before = learner.strategy_stats();
+        for _ in 0..6 {
+            learner.train_batch().unwrap();
+        }
+        let after = learner.strategy_stats();
+
+        // UCB tries every strategy, so each rule has been applied
+        assert!(after.iter().all(|s| s.usage_count > 0));
+        let used: u64 = after.iter().map(|s| s.usage_count).sum::<u64>()
+            - before.iter().map(|s| s.usage_count).sum::<u64>();
+        assert_eq!(used, 6);
+        assert!(after
+            .iter()
+            .zip(&before)
+            .all(|(a, b)| a.usage_count >= b.usage_count));
+        assert!(after.iter().all(|s| s.avg_reward > 0.0));
+    }
+
+    #[test]
+    fn test_sarsa_bootstraps_on_policy_action() {
+        let learner = NativeLearner::new(2, 3);
+        learner.import_q_table(vec![(7, vec![0.1, 2.0, 0.3], 1)]);
+
+        // Greedy SARSA agrees with Q-learning's max
+        assert_eq!(learner.sarsa_next_value(&learner.q_table, 7, None), 2.0);
+        // An exploring policy bootstraps from the action it actually takes
+        assert_eq!(learner.sarsa_next_value(&learner.q_table, 7, Some(2)), 0.3);
+        assert_eq!(learner.sarsa_next_value(&learner.q_table, 8, None), 0.0);
+    }
+
+    #[test]
+    fn test_seeded_learners_are_reproducible() {
+        let run = |seed: u64| {
+            let learner = NativeLearner::with_seed(2, 4, seed);
+            for i in 0..200 {
+                learner
+                    .learn(Experience {
+                        state: vec![(i % 10) as f64, 0.0],
+                        action: (i * 7 % 4) as u32,
+                        reward: if i % 3 == 0 { 1.0 } else { -0.5 },
+                        next_state: vec![((i + 1) % 10) as f64, 0.0],
+                        done: i % 25 == 0,
+                    })
+                    .unwrap();
+            }
+            (0..50)
+                .map(|i| {
+                    let state = vec![(i % 10) as f64, 0.0];
+                    (
+                        learner.select_action(state.clone()),
+                        learner.select_action_softmax(state, 0.5),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(7));
+    }
+
+    #[test]
+    fn test_export_import_experiences() {
+        let learner = NativeLearner::new(2, 3);
+        for i in 0..10 {
+            learner
+                .learn(Experience {
+                    state: vec![i as f64, 1.0],
+                    action: i % 3,
+                    reward: if i % 2 == 0 { 1.0 } else { -0.5 },
+                    next_state: vec![i as f64 + 1.0, 1.0],
+                    done: i == 9,
+                })
+                .unwrap();
+        }
+        let exported = learner.export_experiences();
+        assert_eq!(exported.len(), 10);
+
+        let fresh = NativeLearner::new(2, 3);
+        fresh.import_experiences(exported.clone());
+        let restored = fresh.export_experiences();
+        assert_eq!(restored.len(), exported.len());
+
+        let (a, b) = (&exported[7], &restored[7]);
+        assert_eq!(a.state, b.state);
+        assert_eq!(a.action, b.action);
+        assert_eq!(a.next_state, b.next_state);
+        assert_eq!(a.done, b.done);
+        assert!((a.reward - b.reward).abs() < 1e-12);
+
+        let buffer = fresh.experience_buffer.read();
+        assert!((buffer[7].priority - a.reward.abs()).abs() < 1e-12);
+        assert!(buffer[7].timestamp > 0);
+    }
+
+    #[test]
+    fn test_import_experiences_truncates_to_buffer_size() {
+        let learner = NativeLearner::new(1, 2);
+        learner.config.write().buffer_size = 4;
+
+        let exps: Vec<Experience> = (0..6)
+            .map(|i| Experience {
+                state: vec![i as f64],
+                action: 0,
+                reward: 0.1,
+                next_state: vec![i as f64 + 1.0],
+                done: false,
+            })
+            .collect();
+        learner.import_experiences(exps);
+
+        // Oldest entries are dropped first
+        let kept: Vec<f64> = learner.export_experiences().iter().map(|e| e.state[0]).collect();
+        assert_eq!(kept, vec![2.0, 3.0, 4.0, 5.0]);
+    }
 }
@@ -3,18 +3,26 @@
 //! Features:
 //! - Lock-free concurrent access via DashMap
 //! - SIMD-accelerated similarity search
+//! - HNSW approximate index for large stores
 //! - Automatic memory consolidation
 //! - Importance-based retention
+//! - Optional int8 vector quantization
 
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use dashmap::DashMap;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 
-use crate::brain::types::{DistanceMetric, MemoryEntry, MemoryType, parse_memory_type};
+use crate::brain::hnsw::HnswIndex;
+use crate::brain::types::{
+    DistanceMetric, MemoryEntry, MemoryType, parse_memory_type, try_parse_memory_type,
+};
 use crate::brain::utils::{
     cosine_similarity, dot_product, euclidean_distance, generate_id, normalize_vector, now_millis,
 };
@@ -24,25 +32,119 @@ use crate::brain::utils::{
 pub struct MemoryNode {
     pub id: String,
     pub content: String,
-    pub vector: Vec<f32>,
+    pub vector: NodeVector,
     pub memory_type: MemoryType,
     pub importance: f64,
     pub decay: f64,
     pub access_count: u32,
     pub timestamp: i64,
+    /// When the memory was last returned by a search (creation time until then)
+    pub last_accessed: i64,
     pub connections: SmallVec<[String; 8]>,
+    /// Free-form lowercase labels, independent of `memory_type`
+    pub tags: Vec<String>,
+    /// Embedding provider that produced `vector`; empty if unknown
+    pub provider: String,
+}
+
+/// A node's embedding, either full precision or int8-quantized.
+///
+/// Quantization is symmetric: each component is stored as `round(x / scale)`
+/// with `scale = max|x| / 127`, so the per-component error is at most `scale / 2`.
+/// Buffers are reference-counted so the HNSW index shares them instead of
+/// holding its own full-precision copy.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeVector {
+    Full(Arc<[f32]>),
+    Quantized { values: Arc<[i8]>, scale: f32 },
+}
+
+impl NodeVector {
+    /// Quantize an f32 vector to int8
+    pub fn quantize(vector: &[f32]) -> Self {
+        let max_abs = vector.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+        let scale = if max_abs > 0.0 { max_abs / 127.0 } else { 1.0 };
+        let values = vector
+            .iter()
+            .map(|x| (x / scale).round().clamp(-127.0, 127.0) as i8)
+            .collect::<Vec<_>>()
+            .into();
+        NodeVector::Quantized { values, scale }
+    }
+
+    /// Full-precision view, dequantizing if needed
+    pub fn as_f32(&self) -> Cow<'_, [f32]> {
+        match self {
+            NodeVector::Full(v) => Cow::Borrowed(&v[..]),
+            NodeVector::Quantized { values, scale } => {
+                Cow::Owned(values.iter().map(|&q| q as f32 * scale).collect())
+            }
+        }
+    }
+
+    pub fn to_f32(&self) -> Vec<f32> {
+        self.as_f32().into_owned()
+    }
+
+    /// Dot product with a full-precision vector, without dequantizing a copy
+    pub fn dot(&self, other: &[f32]) -> f32 {
+        match self {
+            NodeVector::Full(v) => dot_product(v, other),
+            NodeVector::Quantized { values, scale } => {
+                let sum: f32 = values.iter().zip(other).map(|(&q, x)| q as f32 * x).sum();
+                sum * scale
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            NodeVector::Full(v) => v.len(),
+            NodeVector::Quantized { values, .. } => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_quantized(&self) -> bool {
+        matches!(self, NodeVector::Quantized { .. })
+    }
+}
+
+impl From<Vec<f32>> for NodeVector {
+    fn from(vector: Vec<f32>) -> Self {
+        NodeVector::Full(vector.into())
+    }
 }
 
+/// Lowest threshold `search_radius` accepts
+pub const MIN_RADIUS_SIMILARITY: f64 = 0.3;
+
+/// Characters of content kept in each `graph` node
+const GRAPH_PREVIEW_CHARS: usize = 80;
+
 /// High-performance native memory system
 pub struct NativeMemory {
     /// Memory storage using lock-free DashMap
     memories: DashMap<String, MemoryNode, ahash::RandomState>,
-    /// Type indices for fast filtering
-    type_indices: DashMap<String, Vec<String>, ahash::RandomState>,
-    /// Vector dimension
-    dimensions: usize,
+    /// Type indices for fast filtering; sets so deletes don't scan the bucket
+    type_indices: DashMap<String, ahash::AHashSet<String>, ahash::RandomState>,
+    /// IDs by type and normalized content, so a store finds its duplicates
+    /// without scanning the type's bucket
+    content_index: DashMap<u64, SmallVec<[String; 1]>, ahash::RandomState>,
+    /// Approximate nearest-neighbour index, built once the store passes `ann_threshold`
+    ann_index: RwLock<Option<HnswIndex>>,
+    /// Vector dimension; only changes while the store is empty
+    dimensions: AtomicUsize,
+    /// Embedding provider recorded on new and re-embedded memories
+    embedding_provider: RwLock<String>,
     /// Configuration
     config: RwLock<MemoryConfig>,
+    /// IDs merged away, pruned, or evicted since the last flush. Their
+    /// database rows are still there until the flush deletes them.
+    discarded: Mutex<Vec<String>>,
     /// Statistics
     total_accesses: AtomicU64,
     total_stores: AtomicU64,
@@ -55,6 +157,20 @@ struct MemoryConfig {
     consolidation_threshold: f64,
     importance_threshold: f64,
     metric: DistanceMetric,
+    /// How much a recall reduces a memory's decay
+    recall_reinforcement: f64,
+    /// Importance bump applied to a memory each time it is recalled
+    recall_importance_boost: f64,
+    /// Store size above which search switches to the HNSW index
+    ann_threshold: usize,
+    /// Keep node vectors as int8 instead of f32 (~4x smaller)
+    quantize: bool,
+    /// Return the existing memory instead of storing a duplicate
+    dedup: bool,
+    /// Vector similarity a near-duplicate must reach (exact content always dedups)
+    dedup_threshold: f64,
+    /// Weight of importance in the rank score when a query asks for reranking
+    importance_weight: f64,
 }
 
 impl Default for MemoryConfig {
@@ -65,6 +181,13 @@ impl Default for MemoryConfig {
             consolidation_threshold: 0.85,
             importance_threshold: 0.3,
             metric: DistanceMetric::Cosine,
+            recall_reinforcement: 0.05,
+            recall_importance_boost: 0.01,
+            ann_threshold: 10_000,
+            quantize: false,
+            dedup: true,
+            dedup_threshold: 0.95,
+            importance_weight: 0.2,
         }
     }
 }
@@ -75,59 +198,75 @@ impl NativeMemory {
         Self {
             memories: DashMap::with_hasher(ahash::RandomState::new()),
             type_indices: DashMap::with_hasher(ahash::RandomState::new()),
-            dimensions: dimensions as usize,
+            content_index: DashMap::with_hasher(ahash::RandomState::new()),
+            ann_index: RwLock::new(None),
+            dimensions: AtomicUsize::new(dimensions as usize),
+            embedding_provider: RwLock::new(String::new()),
             config: RwLock::new(MemoryConfig::default()),
+            discarded: Mutex::new(Vec::new()),
             total_accesses: AtomicU64::new(0),
             total_stores: AtomicU64::new(0),
         }
     }
 
-    /// Store a memory with vector embedding
-    pub fn store(
-        &self,
-        content: String,
-        vector: Vec<f64>,
-        memory_type: String,
-        importance: f64,
-    ) -> Result<String, String> {
-        let mut vec_f32: Vec<f32> = vector.iter().map(|&x| x as f32).collect();
+    /// Vector dimension every stored memory must have
+    pub fn dimensions(&self) -> usize {
+        self.dimensions.load(Ordering::Relaxed)
+    }
 
-        if vec_f32.len() != self.dimensions {
+    /// Change the vector dimension. Only allowed while the store is empty so
+    /// vectors of different widths never mix.
+    pub fn set_dimensions(&self, dimensions: usize) -> Result<(), String> {
+        if dimensions == self.dimensions() {
+            return Ok(());
+        }
+        if dimensions == 0 {
+            return Err("Vector dimension must be positive".to_string());
+        }
+        if !self.memories.is_empty() {
             return Err(format!(
-                "Vector dimension mismatch: expected {}, got {}",
-                self.dimensions,
-                vec_f32.len()
+                "Cannot change vector dimension from {} to {} with {} memories stored",
+                self.dimensions(),
+                dimensions,
+                self.memories.len()
             ));
         }
+        *self.ann_index.write() = None;
+        self.dimensions.store(dimensions, Ordering::Relaxed);
+        Ok(())
+    }
 
-        normalize_vector(&mut vec_f32);
-
-        let id = generate_id();
-        let mem_type = parse_memory_type(&memory_type);
-
-        let node = MemoryNode {
-            id: id.clone(),
-            content,
-            vector: vec_f32,
-            memory_type: mem_type,
-            importance,
-            decay: 0.0,
-            access_count: 0,
-            timestamp: now_millis(),
-            connections: SmallVec::new(),
-        };
-
-        self.memories.insert(id.clone(), node);
+    /// Embedding provider recorded on memories stored from now on
+    pub fn embedding_provider(&self) -> String {
+        self.embedding_provider.read().clone()
+    }
 
-        self.type_indices
-            .entry(memory_type)
-            .or_insert_with(Vec::new)
-            .push(id.clone());
+    pub fn set_embedding_provider(&self, provider: &str) {
+        *self.embedding_provider.write() = provider.to_string();
+    }
 
-        self.total_stores.fetch_add(1, Ordering::Relaxed);
-        self.enforce_limits();
+    /// IDs of memories embedded by a provider other than the current one.
+    /// Their vectors aren't comparable with new queries until re-embedded.
+    /// Memories with no recorded provider are not counted.
+    pub fn mismatched_provider_ids(&self) -> Vec<String> {
+        let current = self.embedding_provider();
+        self.memories
+            .iter()
+            .filter(|entry| !entry.provider.is_empty() && entry.provider != current)
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
 
-        Ok(id)
+    /// Store a memory with vector embedding
+    pub fn store(
+        &self,
+        content: String,
+        vector: Vec<f64>,
+        memory_type: String,
+        importance: f64,
+    ) -> Result<String, String> {
+        let vec_f32: Vec<f32> = vector.iter().map(|&x| x as f32).collect();
+        self.store_f32(content, vec_f32, memory_type, importance)
     }
 
     /// Store a memory with a pre-computed f32 vector (no conversion needed)
@@ -138,19 +277,33 @@ impl NativeMemory {
         memory_type: String,
         importance: f64,
     ) -> Result<String, String> {
-        if vector.len() != self.dimensions {
+        if vector.len() != self.dimensions() {
             return Err(format!(
                 "Vector dimension mismatch: expected {}, got {}",
-                self.dimensions,
+                self.dimensions(),
                 vector.len()
             ));
         }
 
         normalize_vector(&mut vector);
 
-        let id = generate_id();
         let mem_type = parse_memory_type(&memory_type);
 
+        if let Some(existing) = self.find_duplicate(&content, &vector, mem_type) {
+            let config = self.config.read();
+            self.reinforce(&existing, &config, now_millis());
+            if let Some(mut node) = self.memories.get_mut(&existing) {
+                node.importance = node.importance.max(importance);
+            }
+            return Ok(existing);
+        }
+
+        let id = generate_id();
+        let now = now_millis();
+
+        let vector = self.encode_vector(vector);
+        self.index_vector(&id, vector.clone());
+
         let node = MemoryNode {
             id: id.clone(),
             content,
@@ -159,16 +312,16 @@ impl NativeMemory {
             importance,
             decay: 0.0,
             access_count: 0,
-            timestamp: now_millis(),
+            timestamp: now,
+            last_accessed: now,
             connections: SmallVec::new(),
+            tags: Vec::new(),
+            provider: self.embedding_provider(),
         };
 
+        self.add_to_content_index(content_key(mem_type, &node.content), &id);
         self.memories.insert(id.clone(), node);
-
-        self.type_indices
-            .entry(memory_type)
-            .or_insert_with(Vec::new)
-            .push(id.clone());
+        self.add_to_type_index(mem_type, &id);
 
         self.total_stores.fetch_add(1, Ordering::Relaxed);
         self.enforce_limits();
@@ -176,33 +329,74 @@ impl NativeMemory {
         Ok(id)
     }
 
+    /// Find an existing memory of the same type that `content` would duplicate.
+    /// Identical content always counts; otherwise the text must match ignoring
+    /// case and whitespace and the vectors must reach `dedup_threshold`. Both
+    /// cases share the normalized text, so only that `content_index` entry is
+    /// checked.
+    fn find_duplicate(
+        &self,
+        content: &str,
+        vector: &[f32],
+        memory_type: MemoryType,
+    ) -> Option<String> {
+        let threshold = {
+            let config = self.config.read();
+            if !config.dedup {
+                return None;
+            }
+            config.dedup_threshold as f32
+        };
+        let normalized = normalize_text(content);
+        let ids = self.content_index.get(&content_key(memory_type, content))?.clone();
+
+        ids.into_iter().find(|id| match self.memories.get(id) {
+            Some(node) => {
+                node.content == content
+                    || (cosine_similarity(vector, &node.vector.as_f32()) >= threshold
+                        && normalize_text(&node.content) == normalized)
+            }
+            None => false,
+        })
+    }
+
     /// Store multiple memories in batch (parallel)
     pub fn store_batch(&self, entries: Vec<BatchEntry>) -> Result<Vec<String>, String> {
+        let provider = self.embedding_provider();
         let ids: Vec<String> = entries
             .into_par_iter()
             .filter_map(|entry| {
                 let mut vec_f32: Vec<f32> = entry.vector.iter().map(|&x| x as f32).collect();
-                if vec_f32.len() != self.dimensions {
+                if vec_f32.len() != self.dimensions() {
                     return None;
                 }
                 normalize_vector(&mut vec_f32);
 
                 let id = generate_id();
                 let mem_type = parse_memory_type(&entry.memory_type);
+                let now = now_millis();
+
+                let vector = self.encode_vector(vec_f32);
+                self.index_vector(&id, vector.clone());
 
                 let node = MemoryNode {
                     id: id.clone(),
                     content: entry.content,
-                    vector: vec_f32,
+                    vector,
                     memory_type: mem_type,
                     importance: entry.importance,
                     decay: 0.0,
                     access_count: 0,
-                    timestamp: now_millis(),
+                    timestamp: now,
+                    last_accessed: now,
                     connections: SmallVec::new(),
+                    tags: Vec::new(),
+                    provider: provider.clone(),
                 };
 
+                self.add_to_content_index(content_key(mem_type, &node.content), &id);
                 self.memories.insert(id.clone(), node);
+                self.add_to_type_index(mem_type, &id);
                 Some(id)
             })
             .collect();
@@ -217,69 +411,74 @@ impl NativeMemory {
         &self,
         query_vector: Vec<f64>,
         k: u32,
-        memory_types: Option<Vec<String>>,
-        min_similarity: Option<f64>,
+        options: SearchOptions,
     ) -> Result<Vec<SearchResult>, String> {
         let query: Vec<f32> = query_vector.iter().map(|&x| x as f32).collect();
+        self.search_f32(&query, k, options)
+    }
 
-        if query.len() != self.dimensions {
+    /// Search with f32 query (no conversion needed).
+    /// Returns up to `k` results after skipping `options.offset` ranked matches.
+    pub fn search_f32(
+        &self,
+        query: &[f32],
+        k: u32,
+        options: SearchOptions,
+    ) -> Result<Vec<SearchResult>, String> {
+        if query.len() != self.dimensions() {
             return Err("Query dimension mismatch".to_string());
         }
 
-        let min_sim = min_similarity.unwrap_or(0.0) as f32;
-        let type_filter: Option<Vec<MemoryType>> = memory_types
-            .map(|types| types.iter().map(|t| parse_memory_type(t)).collect());
+        let filter = SearchFilter {
+            types: options
+                .memory_types
+                .map(|types| types.iter().map(|t| parse_memory_type(t)).collect()),
+            tags: options.tags.map(|tags| normalize_tags(&tags)),
+            after: options.after,
+            before: options.before,
+            min_similarity: options.min_similarity.unwrap_or(0.0) as f32,
+        };
+        let offset = options.offset.unwrap_or(0) as usize;
 
         let config = self.config.read();
 
-        let mut results: Vec<(String, f32, MemoryNode)> = self
-            .memories
-            .iter()
-            .filter_map(|entry| {
-                let node = entry.value();
-
-                if let Some(ref types) = type_filter {
-                    if !types.contains(&node.memory_type) {
-                        return None;
-                    }
-                }
-
-                let similarity = match config.metric {
-                    DistanceMetric::Cosine => cosine_similarity(&query, &node.vector),
-                    DistanceMetric::Euclidean => {
-                        1.0 / (1.0 + euclidean_distance(&query, &node.vector))
-                    }
-                    DistanceMetric::DotProduct => dot_product(&query, &node.vector),
-                    DistanceMetric::Manhattan => {
-                        let dist: f32 = query
-                            .iter()
-                            .zip(node.vector.iter())
-                            .map(|(a, b)| (a - b).abs())
-                            .sum();
-                        1.0 / (1.0 + dist)
-                    }
-                };
-
-                let adjusted_sim = similarity * (1.0 - node.decay as f32);
+        // The approximate index only ranks by cosine and can't apply type/time
+        // filters. Nor can it rank by importance: a weak match with high
+        // importance may sit outside the candidates it returns.
+        let approximate = if !filter.is_narrowing()
+            && config.metric == DistanceMetric::Cosine
+            && !options.rank_by_importance
+        {
+            self.search_approximate(query, offset + k as usize, &filter, &config)
+        } else {
+            None
+        };
 
-                if adjusted_sim >= min_sim {
-                    Some((node.id.clone(), adjusted_sim, node.clone()))
-                } else {
-                    None
-                }
-            })
-            .collect();
+        let mut results = match approximate {
+            Some(results) => results,
+            None => self.search_exact(query, &filter, &config),
+        };
 
-        results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        if options.rank_by_importance {
+            // Rank by decayed similarity plus weighted importance
+            let weight = config.importance_weight;
+            let rank = |r: &(String, f32, MemoryNode)| r.1 as f64 + weight * r.2.importance;
+            results.sort_by(|a, b| {
+                rank(b)
+                    .partial_cmp(&rank(a))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+        } else {
+            results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        }
 
+        let now = now_millis();
         let top_k: Vec<SearchResult> = results
             .into_iter()
+            .skip(offset)
             .take(k as usize)
             .map(|(id, similarity, node)| {
-                if let Some(mut entry) = self.memories.get_mut(&id) {
-                    entry.access_count += 1;
-                }
-                self.total_accesses.fetch_add(1, Ordering::Relaxed);
+                self.reinforce(&id, &config, now);
 
                 SearchResult {
                     id,
@@ -287,6 +486,8 @@ impl NativeMemory {
                     similarity: similarity as f64,
                     memory_type: format!("{:?}", node.memory_type),
                     importance: node.importance,
+                    last_accessed: now,
+                    tags: node.tags,
                 }
             })
             .collect();
@@ -294,110 +495,270 @@ impl NativeMemory {
         Ok(top_k)
     }
 
-    /// Search with f32 query (no conversion needed)
-    pub fn search_f32(
+    /// Every memory scoring at least `min_similarity`, best first, with no cap
+    /// on the count. The threshold is clamped to [`MIN_RADIUS_SIMILARITY`, 1] so
+    /// a near-zero value can't return the whole store. Unlike `search`, this
+    /// does not count as a recall. Returns nothing on a dimension mismatch.
+    pub fn search_radius(
         &self,
         query: &[f32],
-        k: u32,
+        min_similarity: f64,
         memory_types: Option<Vec<String>>,
-        min_similarity: Option<f64>,
-    ) -> Result<Vec<SearchResult>, String> {
-        if query.len() != self.dimensions {
-            return Err("Query dimension mismatch".to_string());
+    ) -> Vec<SearchResult> {
+        if query.len() != self.dimensions() {
+            return Vec::new();
         }
 
-        let min_sim = min_similarity.unwrap_or(0.0) as f32;
-        let type_filter: Option<Vec<MemoryType>> = memory_types
-            .map(|types| types.iter().map(|t| parse_memory_type(t)).collect());
-
+        let filter = SearchFilter {
+            types: memory_types.map(|types| types.iter().map(|t| parse_memory_type(t)).collect()),
+            min_similarity: min_similarity.clamp(MIN_RADIUS_SIMILARITY, 1.0) as f32,
+            ..Default::default()
+        };
         let config = self.config.read();
 
-        let mut results: Vec<(String, f32, MemoryNode)> = self
-            .memories
-            .iter()
-            .filter_map(|entry| {
-                let node = entry.value();
+        let mut results = self.search_exact(query, &filter, &config);
+        results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
 
-                if let Some(ref types) = type_filter {
-                    if !types.contains(&node.memory_type) {
-                        return None;
-                    }
-                }
+        results
+            .into_iter()
+            .map(|(id, similarity, node)| SearchResult {
+                id,
+                content: node.content,
+                similarity: similarity as f64,
+                memory_type: format!("{:?}", node.memory_type),
+                importance: node.importance,
+                last_accessed: node.last_accessed,
+                tags: node.tags,
+            })
+            .collect()
+    }
 
-                let similarity = match config.metric {
-                    DistanceMetric::Cosine => cosine_similarity(query, &node.vector),
-                    DistanceMetric::Euclidean => {
-                        1.0 / (1.0 + euclidean_distance(query, &node.vector))
-                    }
-                    DistanceMetric::DotProduct => dot_product(query, &node.vector),
-                    DistanceMetric::Manhattan => {
-                        let dist: f32 = query
-                            .iter()
-                            .zip(node.vector.iter())
-                            .map(|(a, b)| (a - b).abs())
-                            .sum();
-                        1.0 / (1.0 + dist)
-                    }
-                };
+    /// Exact scan. With a type filter only the matching `type_indices` buckets
+    /// are visited; otherwise every memory is scored.
+    fn search_exact(
+        &self,
+        query: &[f32],
+        filter: &SearchFilter,
+        config: &MemoryConfig,
+    ) -> Vec<(String, f32, MemoryNode)> {
+        let candidate = |node: &MemoryNode| {
+            if !filter.admits(node) {
+                return None;
+            }
+
+            let adjusted_sim = score(query, node, config.metric);
+
+            if adjusted_sim >= filter.min_similarity {
+                Some((node.id.clone(), adjusted_sim, node.clone()))
+            } else {
+                None
+            }
+        };
+
+        match filter.types {
+            Some(ref types) => {
+                let mut types = types.clone();
+                types.sort_by_key(|t| *t as u8);
+                types.dedup();
+
+                types
+                    .iter()
+                    .filter_map(|t| self.type_indices.get(&type_key(*t)).map(|ids| ids.clone()))
+                    .flatten()
+                    .filter_map(|id| self.memories.get(&id).and_then(|node| candidate(&node)))
+                    .collect()
+            }
+            None => self
+                .memories
+                .iter()
+                .filter_map(|entry| candidate(entry.value()))
+                .collect(),
+        }
+    }
+
+    /// HNSW-backed search for large stores. Returns `None` when the index isn't
+    /// worth using or the similarity floor leaves fewer than `k` candidates, in
+    /// which case the caller falls back to the exact scan.
+    fn search_approximate(
+        &self,
+        query: &[f32],
+        k: usize,
+        filter: &SearchFilter,
+        config: &MemoryConfig,
+    ) -> Option<Vec<(String, f32, MemoryNode)>> {
+        let count = self.memories.len();
+        if count < config.ann_threshold {
+            return None;
+        }
+        self.ensure_ann_index();
 
-                let adjusted_sim = similarity * (1.0 - node.decay as f32);
+        let ef = (k * 10).max(128);
+        let hits = self.ann_index.read().as_ref()?.search(query, ef, ef);
 
-                if adjusted_sim >= min_sim {
-                    Some((node.id.clone(), adjusted_sim, node.clone()))
+        let results: Vec<(String, f32, MemoryNode)> = hits
+            .into_iter()
+            .filter_map(|(id, _)| {
+                let node = self.memories.get(&id)?;
+                let adjusted_sim = score(query, &node, config.metric);
+                if adjusted_sim >= filter.min_similarity {
+                    Some((id, adjusted_sim, node.clone()))
                 } else {
                     None
                 }
             })
             .collect();
 
-        results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        if results.len() < k.min(count) {
+            return None;
+        }
+        Some(results)
+    }
 
-        let top_k: Vec<SearchResult> = results
-            .into_iter()
-            .take(k as usize)
-            .map(|(id, similarity, node)| {
-                if let Some(mut entry) = self.memories.get_mut(&id) {
-                    entry.access_count += 1;
-                }
-                self.total_accesses.fetch_add(1, Ordering::Relaxed);
+    /// Build (or rebuild, once tombstones dominate) the approximate index
+    fn ensure_ann_index(&self) {
+        let usable = |index: &Option<HnswIndex>| index.as_ref().is_some_and(|i| !i.needs_rebuild());
+        if usable(&self.ann_index.read()) {
+            return;
+        }
 
-                SearchResult {
-                    id,
-                    content: node.content,
-                    similarity: similarity as f64,
-                    memory_type: format!("{:?}", node.memory_type),
-                    importance: node.importance,
-                }
-            })
-            .collect();
+        let mut index = self.ann_index.write();
+        if usable(&index) {
+            return;
+        }
 
-        Ok(top_k)
+        let mut fresh = HnswIndex::new();
+        for entry in self.memories.iter() {
+            fresh.insert(&entry.id, entry.vector.clone());
+        }
+        tracing::debug!("Built HNSW index over {} memories", fresh.len());
+        *index = Some(fresh);
     }
 
-    /// Connect two memories
-    pub fn connect(&self, id1: &str, id2: &str) -> bool {
-        if let Some(mut node1) = self.memories.get_mut(id1) {
-            if !node1.connections.contains(&id2.to_string()) {
-                node1.connections.push(id2.to_string());
-            }
+    /// Wrap a normalized vector for storage, quantizing if enabled
+    fn encode_vector(&self, vector: Vec<f32>) -> NodeVector {
+        if self.config.read().quantize {
+            NodeVector::quantize(&vector)
         } else {
+            NodeVector::Full(vector.into())
+        }
+    }
+
+    /// Add a vector to the approximate index if it has been built
+    fn index_vector(&self, id: &str, vector: NodeVector) {
+        if let Some(index) = self.ann_index.write().as_mut() {
+            index.insert(id, vector);
+        }
+    }
+
+    /// Remove a node from the store, its type bucket, the approximate index
+    /// and its neighbours' connections
+    fn remove_node(&self, id: &str) -> Option<MemoryNode> {
+        let (_, node) = self.memories.remove(id)?;
+        self.remove_from_type_index(node.memory_type, id);
+        self.remove_from_content_index(content_key(node.memory_type, &node.content), id);
+        if let Some(index) = self.ann_index.write().as_mut() {
+            index.remove(id);
+        }
+        self.unlink_neighbors(id, &node.connections);
+        Some(node)
+    }
+
+    /// Drop a removed node from the connections of each of its neighbours
+    fn unlink_neighbors(&self, id: &str, connections: &[String]) {
+        for conn in connections {
+            if let Some(mut neighbor) = self.memories.get_mut(conn) {
+                neighbor.connections.retain(|c| c != id);
+            }
+        }
+    }
+
+    fn add_to_type_index(&self, memory_type: MemoryType, id: &str) {
+        self.type_indices
+            .entry(type_key(memory_type))
+            .or_default()
+            .insert(id.to_string());
+    }
+
+    fn remove_from_type_index(&self, memory_type: MemoryType, id: &str) {
+        if let Some(mut ids) = self.type_indices.get_mut(&type_key(memory_type)) {
+            ids.remove(id);
+        }
+    }
+
+    fn add_to_content_index(&self, key: u64, id: &str) {
+        self.content_index.entry(key).or_default().push(id.to_string());
+    }
+
+    fn remove_from_content_index(&self, key: u64, id: &str) {
+        if let Some(mut ids) = self.content_index.get_mut(&key) {
+            ids.retain(|existing| existing != id);
+        }
+        self.content_index.remove_if(&key, |_, ids| ids.is_empty());
+    }
+
+    /// Record a recall at `now`: bump access count and strengthen the memory against decay
+    fn reinforce(&self, id: &str, config: &MemoryConfig, now: i64) {
+        if let Some(mut entry) = self.memories.get_mut(id) {
+            entry.access_count += 1;
+            entry.last_accessed = entry.last_accessed.max(now);
+            entry.decay = (entry.decay - config.recall_reinforcement).max(0.0);
+            entry.importance = (entry.importance + config.recall_importance_boost).min(1.0);
+        }
+        self.total_accesses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Connect two memories in both directions. Connecting already linked
+    /// memories changes nothing. Returns false unless both exist and differ.
+    pub fn connect(&self, id1: &str, id2: &str) -> bool {
+        if id1 == id2 || !self.memories.contains_key(id1) || !self.memories.contains_key(id2) {
             return false;
         }
 
-        if let Some(mut node2) = self.memories.get_mut(id2) {
-            if !node2.connections.contains(&id1.to_string()) {
-                node2.connections.push(id1.to_string());
+        for (from, to) in [(id1, id2), (id2, id1)] {
+            if let Some(mut node) = self.memories.get_mut(from) {
+                if !node.connections.iter().any(|c| c == to) {
+                    node.connections.push(to.to_string());
+                }
             }
         }
 
         true
     }
 
+    /// Remove the link between two memories in both directions.
+    /// Returns false if they weren't connected.
+    pub fn disconnect(&self, id1: &str, id2: &str) -> bool {
+        let mut removed = false;
+        for (from, to) in [(id1, id2), (id2, id1)] {
+            if let Some(mut node) = self.memories.get_mut(from) {
+                let before = node.connections.len();
+                node.connections.retain(|c| c != to);
+                removed |= node.connections.len() != before;
+            }
+        }
+        removed
+    }
+
     /// Consolidate memories - merge similar, prune weak
     pub fn consolidate(&self) -> ConsolidationResult {
         let config = self.config.read();
-        let merged = 0u32;
+        let mut merged = 0u32;
         let mut pruned = 0u32;
+        let mut removed_ids = Vec::new();
+
+        // Only compare memories of the same type to avoid an O(n²) scan of the whole store
+        let buckets: Vec<(String, Vec<String>)> = self
+            .type_indices
+            .iter()
+            .map(|e| (e.key().clone(), e.value().iter().cloned().collect()))
+            .collect();
+
+        // Absorbed nodes leave their bucket via remove_node
+        for (_, ids) in buckets {
+            let absorbed = self.merge_similar(&ids, config.consolidation_threshold);
+            merged += absorbed.len() as u32;
+            removed_ids.extend(absorbed);
+        }
 
         let to_prune: Vec<String> = self
             .memories
@@ -413,9 +774,12 @@ impl NativeMemory {
             .collect();
 
         for id in to_prune {
-            self.memories.remove(&id);
-            pruned += 1;
+            if self.remove_node(&id).is_some() {
+                pruned += 1;
+                removed_ids.push(id);
+            }
         }
+        self.discarded.lock().extend(removed_ids.iter().cloned());
 
         self.memories.iter_mut().for_each(|mut entry| {
             entry.value_mut().decay += config.decay_rate;
@@ -425,52 +789,413 @@ impl NativeMemory {
             merged,
             pruned,
             total_remaining: self.memories.len() as u32,
+            removed_ids,
         }
     }
 
-    /// Delete a memory
-    pub fn delete(&self, id: &str) -> bool {
-        self.memories.remove(id).is_some()
-    }
+    /// Merge memories within one bucket whose similarity exceeds `threshold`.
+    /// Returns the IDs that were absorbed into another memory.
+    fn merge_similar(&self, ids: &[String], threshold: f64) -> ahash::AHashSet<String> {
+        let mut candidates: Vec<(String, Vec<f32>, f64, i64)> = ids
+            .iter()
+            .filter_map(|id| {
+                self.memories
+                    .get(id)
+                    .map(|n| (n.id.clone(), n.vector.to_f32(), n.importance, n.timestamp))
+            })
+            .collect();
+        // Oldest first, so a tie in importance keeps the earlier memory
+        // whatever order the bucket hands the IDs out in
+        candidates.sort_by(|a, b| (a.3, &a.0).cmp(&(b.3, &b.0)));
 
-    /// Get memory count
-    pub fn len(&self) -> u32 {
-        self.memories.len() as u32
-    }
+        let mut absorbed = ahash::AHashSet::new();
 
-    /// Check if memory is empty
-    pub fn is_empty(&self) -> bool {
-        self.memories.is_empty()
-    }
+        for i in 0..candidates.len() {
+            if absorbed.contains(&candidates[i].0) {
+                continue;
+            }
+            for j in (i + 1)..candidates.len() {
+                if absorbed.contains(&candidates[j].0) {
+                    continue;
+                }
 
-    /// Get a specific memory
-    pub fn get(&self, id: &str) -> Option<MemoryEntry> {
-        self.memories.get(id).map(|node| MemoryEntry {
-            id: node.id.clone(),
-            content: node.content.clone(),
-            memory_type: format!("{:?}", node.memory_type),
-            importance: node.importance,
-            decay: node.decay,
-            access_count: node.access_count,
-            timestamp: node.timestamp,
-            connections: node.connections.to_vec(),
-        })
-    }
+                let similarity = cosine_similarity(&candidates[i].1, &candidates[j].1) as f64;
+                if similarity < threshold {
+                    continue;
+                }
 
-    /// Get all memory nodes (for persistence)
-    pub fn all_nodes(&self) -> Vec<MemoryNode> {
-        self.memories.iter().map(|e| e.value().clone()).collect()
-    }
+                // Keep the more important memory, fold the other into it
+                let (keep, drop) = if candidates[j].2 > candidates[i].2 {
+                    (&candidates[j].0, &candidates[i].0)
+                } else {
+                    (&candidates[i].0, &candidates[j].0)
+                };
 
-    /// Restore a memory node (for persistence loading)
-    pub fn restore_node(&self, node: MemoryNode) {
-        let type_str = format!("{:?}", node.memory_type);
-        let id = node.id.clone();
-        self.memories.insert(id.clone(), node);
-        self.type_indices
-            .entry(type_str)
-            .or_insert_with(Vec::new)
-            .push(id);
+                if self.merge_into(keep, drop) {
+                    absorbed.insert(drop.clone());
+                }
+
+                if absorbed.contains(&candidates[i].0) {
+                    break;
+                }
+            }
+        }
+
+        absorbed
+    }
+
+    /// Fold `drop_id` into `keep_id`: union connections, sum access counts,
+    /// take the max importance, and repoint edges at the surviving node.
+    fn merge_into(&self, keep_id: &str, drop_id: &str) -> bool {
+        if !self.memories.contains_key(keep_id) {
+            return false;
+        }
+        let dropped = match self.remove_node(drop_id) {
+            Some(node) => node,
+            None => return false,
+        };
+
+        if let Some(mut keep) = self.memories.get_mut(keep_id) {
+            keep.importance = keep.importance.max(dropped.importance);
+            keep.access_count = keep.access_count.saturating_add(dropped.access_count);
+            keep.connections.retain(|c| c != drop_id);
+            for conn in &dropped.connections {
+                if conn != keep_id && !keep.connections.contains(conn) {
+                    keep.connections.push(conn.clone());
+                }
+            }
+        }
+
+        for conn in &dropped.connections {
+            if let Some(mut neighbor) = self.memories.get_mut(conn) {
+                neighbor.connections.retain(|c| c != drop_id);
+                if conn != keep_id && !neighbor.connections.iter().any(|c| c == keep_id) {
+                    neighbor.connections.push(keep_id.to_string());
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Delete a memory
+    pub fn delete(&self, id: &str) -> bool {
+        self.remove_node(id).is_some()
+    }
+
+    /// Delete every memory, e.g. before loading a restored database
+    pub fn clear(&self) {
+        let ids: Vec<String> = self.memories.iter().map(|e| e.key().clone()).collect();
+        for id in ids {
+            self.remove_node(&id);
+        }
+        // Pending row deletions belonged to the database being replaced
+        self.discarded.lock().clear();
+    }
+
+    /// Take the IDs of memories merged, pruned, or evicted since the last
+    /// call, so their database rows can be deleted
+    pub fn take_discarded(&self) -> Vec<String> {
+        std::mem::take(&mut *self.discarded.lock())
+    }
+
+    /// Put back IDs from `take_discarded` whose rows could not be deleted
+    pub fn requeue_discarded(&self, ids: Vec<String>) {
+        self.discarded.lock().extend(ids);
+    }
+
+    /// Delete every memory of the given type. Unknown type names delete nothing.
+    /// Returns the number of memories removed.
+    pub fn delete_by_type(&self, memory_type: &str) -> u32 {
+        let memory_type = match try_parse_memory_type(memory_type) {
+            Some(t) => t,
+            None => return 0,
+        };
+        let ids = match self.type_indices.remove(&type_key(memory_type)) {
+            Some((_, ids)) => ids,
+            None => return 0,
+        };
+
+        let mut removed = Vec::new();
+        {
+            let mut index = self.ann_index.write();
+            for id in ids {
+                if let Some((_, node)) = self.memories.remove(&id) {
+                    self.remove_from_content_index(content_key(memory_type, &node.content), &id);
+                    removed.push(node);
+                }
+                if let Some(index) = index.as_mut() {
+                    index.remove(&id);
+                }
+            }
+        }
+        for node in &removed {
+            self.unlink_neighbors(&node.id, &node.connections);
+        }
+        removed.len() as u32
+    }
+
+    /// Get memory count
+    pub fn len(&self) -> u32 {
+        self.memories.len() as u32
+    }
+
+    /// Check if memory is empty
+    pub fn is_empty(&self) -> bool {
+        self.memories.is_empty()
+    }
+
+    /// Get a specific memory
+    pub fn get(&self, id: &str) -> Option<MemoryEntry> {
+        self.memories.get(id).map(|node| to_entry(&node))
+    }
+
+    /// Get a clone of the raw node (for persistence)
+    pub fn get_node(&self, id: &str) -> Option<MemoryNode> {
+        self.memories.get(id).map(|node| node.clone())
+    }
+
+    /// Edit a memory in place. A new `vector` should accompany changed content;
+    /// connections, timestamp and access count are preserved.
+    /// Returns false if the memory doesn't exist or the vector has the wrong dimension.
+    pub fn update_memory(
+        &self,
+        id: &str,
+        content: Option<String>,
+        vector: Option<Vec<f32>>,
+        importance: Option<f64>,
+    ) -> bool {
+        let vector = match vector {
+            Some(mut v) => {
+                if v.len() != self.dimensions() {
+                    return false;
+                }
+                normalize_vector(&mut v);
+                Some(v)
+            }
+            None => None,
+        };
+
+        let encoded = vector.map(|v| self.encode_vector(v));
+        let indexed = encoded.clone();
+
+        {
+            let mut node = match self.memories.get_mut(id) {
+                Some(node) => node,
+                None => return false,
+            };
+            if let Some(content) = content {
+                self.remove_from_content_index(content_key(node.memory_type, &node.content), id);
+                self.add_to_content_index(content_key(node.memory_type, &content), id);
+                node.content = content;
+            }
+            if let Some(importance) = importance {
+                node.importance = importance;
+            }
+            if let Some(encoded) = encoded {
+                node.vector = encoded;
+                node.provider = self.embedding_provider();
+            }
+        }
+
+        if let Some(v) = indexed {
+            self.index_vector(id, v);
+        }
+
+        true
+    }
+
+    /// Add tags to a memory. Tags are trimmed and lowercased; duplicates and
+    /// empty tags are ignored. Returns false if the memory doesn't exist.
+    pub fn add_tags(&self, id: &str, tags: &[String]) -> bool {
+        let mut node = match self.memories.get_mut(id) {
+            Some(node) => node,
+            None => return false,
+        };
+        for tag in normalize_tags(tags) {
+            if !node.tags.contains(&tag) {
+                node.tags.push(tag);
+            }
+        }
+        true
+    }
+
+    /// Breadth-first walk of the connection graph, up to `depth` hops from `id`.
+    /// The starting memory is not included; dangling connections are skipped.
+    pub fn get_connected(&self, id: &str, depth: u32) -> Vec<MemoryEntry> {
+        let mut visited: ahash::AHashSet<String> = ahash::AHashSet::new();
+        visited.insert(id.to_string());
+
+        let mut frontier = vec![id.to_string()];
+        let mut connected = Vec::new();
+
+        for _ in 0..depth {
+            let mut next = Vec::new();
+            for current in &frontier {
+                let neighbors = match self.memories.get(current) {
+                    Some(node) => node.connections.to_vec(),
+                    None => continue,
+                };
+                for neighbor in neighbors {
+                    if !visited.insert(neighbor.clone()) {
+                        continue;
+                    }
+                    if let Some(node) = self.memories.get(&neighbor) {
+                        connected.push(to_entry(&node));
+                        next.push(neighbor);
+                    }
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+
+        connected
+    }
+
+    /// The `limit` most important memories and the links between them, for
+    /// drawing the memory network. Each undirected link appears once.
+    pub fn graph(&self, limit: usize) -> MemoryGraph {
+        let mut ranked: Vec<(String, f64)> = self
+            .memories
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.importance))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(limit);
+        let selected: ahash::AHashSet<&str> = ranked.iter().map(|(id, _)| id.as_str()).collect();
+
+        let mut nodes = Vec::with_capacity(ranked.len());
+        let mut links = BTreeSet::new();
+        for (id, _) in &ranked {
+            let Some(node) = self.memories.get(id) else {
+                continue;
+            };
+            nodes.push(GraphNode {
+                id: node.id.clone(),
+                content_preview: node.content.chars().take(GRAPH_PREVIEW_CHARS).collect(),
+                memory_type: format!("{:?}", node.memory_type),
+                importance: node.importance,
+            });
+            for conn in node.connections.iter().filter(|c| selected.contains(c.as_str())) {
+                let (from, to) = if id < conn { (id, conn) } else { (conn, id) };
+                links.insert((from.clone(), to.clone()));
+            }
+        }
+
+        MemoryGraph {
+            nodes,
+            edges: links
+                .into_iter()
+                .map(|(from, to)| GraphEdge { from, to })
+                .collect(),
+        }
+    }
+
+    /// Visit every node without cloning, e.g. to stream them to disk.
+    /// Each DashMap shard is read-locked while its nodes are visited, so `f`
+    /// must not call back into this store's mutating methods.
+    pub fn for_each_node<F: FnMut(&MemoryNode)>(&self, mut f: F) {
+        for entry in self.memories.iter() {
+            f(entry.value());
+        }
+    }
+
+    /// Get all memory nodes (for persistence)
+    pub fn all_nodes(&self) -> Vec<MemoryNode> {
+        self.memories.iter().map(|e| e.value().clone()).collect()
+    }
+
+    /// Restore a memory node (for persistence loading)
+    pub fn restore_node(&self, mut node: MemoryNode) {
+        let memory_type = node.memory_type;
+        let id = node.id.clone();
+        if self.config.read().quantize && !node.vector.is_quantized() {
+            node.vector = NodeVector::quantize(&node.vector.as_f32());
+        }
+        self.index_vector(&id, node.vector.clone());
+        let key = content_key(memory_type, &node.content);
+        if let Some(previous) = self.memories.insert(id.clone(), node) {
+            self.remove_from_type_index(previous.memory_type, &id);
+            let previous_key = content_key(previous.memory_type, &previous.content);
+            self.remove_from_content_index(previous_key, &id);
+        }
+        self.add_to_type_index(memory_type, &id);
+        self.add_to_content_index(key, &id);
+    }
+
+    /// Serialize every memory, vectors included, as JSON
+    pub fn export_json(&self) -> String {
+        let export = MemoryExport {
+            dimensions: self.dimensions(),
+            memories: self
+                .memories
+                .iter()
+                .map(|entry| {
+                    let node = entry.value();
+                    ExportedNode {
+                        id: node.id.clone(),
+                        content: node.content.clone(),
+                        vector: node.vector.to_f32(),
+                        memory_type: node.memory_type,
+                        importance: node.importance,
+                        decay: node.decay,
+                        access_count: node.access_count,
+                        timestamp: node.timestamp,
+                        last_accessed: node.last_accessed,
+                        connections: node.connections.to_vec(),
+                        tags: node.tags.clone(),
+                        provider: node.provider.clone(),
+                    }
+                })
+                .collect(),
+        };
+
+        serde_json::to_string(&export).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Load memories from `export_json` output. Nodes whose vector doesn't match
+    /// this store's dimension are skipped; existing IDs are overwritten.
+    /// Returns the number of memories imported.
+    pub fn import_json(&self, json: &str) -> Result<u32, String> {
+        let export: MemoryExport =
+            serde_json::from_str(json).map_err(|e| format!("Failed to parse export: {}", e))?;
+
+        let mut imported = 0u32;
+        let mut skipped = 0u32;
+        for node in export.memories {
+            if node.vector.len() != self.dimensions() {
+                skipped += 1;
+                continue;
+            }
+            self.restore_node(MemoryNode {
+                id: node.id,
+                content: node.content,
+                vector: node.vector.into(),
+                memory_type: node.memory_type,
+                importance: node.importance,
+                decay: node.decay,
+                access_count: node.access_count,
+                timestamp: node.timestamp,
+                // Exports from before last_accessed existed fall back to creation time
+                last_accessed: node.last_accessed.max(node.timestamp),
+                connections: node.connections.into_iter().collect(),
+                tags: node.tags,
+                provider: node.provider,
+            });
+            imported += 1;
+        }
+
+        if skipped > 0 {
+            tracing::warn!(
+                "Skipped {} imported memories with dimension != {}",
+                skipped,
+                self.dimensions()
+            );
+        }
+        Ok(imported)
     }
 
     /// Get statistics
@@ -498,8 +1223,9 @@ impl NativeMemory {
         }
     }
 
-    /// Set distance metric
-    pub fn set_metric(&self, metric: &str) {
+    /// Set distance metric; unknown names fall back to cosine.
+    /// Returns the metric now in use.
+    pub fn set_metric(&self, metric: &str) -> DistanceMetric {
         let mut config = self.config.write();
         config.metric = match metric.to_lowercase().as_str() {
             "euclidean" => DistanceMetric::Euclidean,
@@ -507,6 +1233,48 @@ impl NativeMemory {
             "manhattan" => DistanceMetric::Manhattan,
             _ => DistanceMetric::Cosine,
         };
+        config.metric
+    }
+
+    /// Current distance metric
+    pub fn metric(&self) -> DistanceMetric {
+        self.config.read().metric
+    }
+
+    /// Set how strongly recall reinforces a memory (decay reduction, importance bump)
+    pub fn set_reinforcement(&self, decay_reduction: f64, importance_boost: f64) {
+        let mut config = self.config.write();
+        config.recall_reinforcement = decay_reduction.max(0.0);
+        config.recall_importance_boost = importance_boost.max(0.0);
+    }
+
+    /// Switch int8 vector storage on or off, converting existing memories.
+    /// Turning it off keeps the dequantized values; precision already lost stays lost.
+    pub fn set_quantization(&self, enabled: bool) {
+        self.config.write().quantize = enabled;
+        self.memories.iter_mut().for_each(|mut entry| {
+            let node = entry.value_mut();
+            if enabled && !node.vector.is_quantized() {
+                node.vector = NodeVector::quantize(&node.vector.as_f32());
+            } else if !enabled && node.vector.is_quantized() {
+                node.vector = NodeVector::Full(node.vector.to_f32().into());
+            }
+        });
+        // The index still shares the old buffers; rebuild it from the new ones
+        *self.ann_index.write() = None;
+    }
+
+    /// Configure store-time deduplication. Exact content matches always dedup
+    /// while enabled; `threshold` only applies to near-duplicates.
+    pub fn set_dedup(&self, enabled: bool, threshold: f64) {
+        let mut config = self.config.write();
+        config.dedup = enabled;
+        config.dedup_threshold = threshold.clamp(0.0, 1.0);
+    }
+
+    /// Set how much importance counts when a search ranks by importance
+    pub fn set_importance_weight(&self, weight: f64) {
+        self.config.write().importance_weight = weight.max(0.0);
     }
 
     /// Enforce memory limits
@@ -525,13 +1293,119 @@ impl NativeMemory {
                 .sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
 
             let remove_count = count - config.max_memories + config.max_memories / 10;
+            let mut discarded = self.discarded.lock();
             for (id, _) in to_remove.into_iter().take(remove_count) {
-                self.memories.remove(&id);
+                if self.remove_node(&id).is_some() {
+                    discarded.push(id);
+                }
             }
         }
     }
 }
 
+/// Candidate filters applied before similarity scoring
+#[derive(Debug, Default)]
+struct SearchFilter {
+    types: Option<Vec<MemoryType>>,
+    /// Required tags, already normalized
+    tags: Option<Vec<String>>,
+    after: Option<i64>,
+    before: Option<i64>,
+    min_similarity: f32,
+}
+
+impl SearchFilter {
+    /// True if any type, tag or time restriction is set
+    fn is_narrowing(&self) -> bool {
+        self.types.is_some() || self.tags.is_some() || self.after.is_some() || self.before.is_some()
+    }
+
+    /// Whether a node passes the type, tag and time-range filters
+    fn admits(&self, node: &MemoryNode) -> bool {
+        if let Some(ref types) = self.types {
+            if !types.contains(&node.memory_type) {
+                return false;
+            }
+        }
+        if let Some(ref tags) = self.tags {
+            if !tags.iter().all(|tag| node.tags.contains(tag)) {
+                return false;
+            }
+        }
+        self.after.is_none_or(|after| node.timestamp >= after)
+            && self.before.is_none_or(|before| node.timestamp <= before)
+    }
+}
+
+/// Trim and lowercase tags, dropping empties and duplicates
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+/// Lowercase text with whitespace runs collapsed, for near-duplicate checks
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Key used for a `content_index` entry: the type plus normalized text
+fn content_key(memory_type: MemoryType, content: &str) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    (memory_type as u8).hash(&mut hasher);
+    normalize_text(content).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Key used for a type's `type_indices` bucket
+fn type_key(memory_type: MemoryType) -> String {
+    format!("{:?}", memory_type)
+}
+
+/// Similarity under `metric`, attenuated by the node's decay
+fn score(query: &[f32], node: &MemoryNode, metric: DistanceMetric) -> f32 {
+    let vector = node.vector.as_f32();
+    let similarity = match metric {
+        DistanceMetric::Cosine => cosine_similarity(query, &vector),
+        DistanceMetric::Euclidean => 1.0 / (1.0 + euclidean_distance(query, &vector)),
+        DistanceMetric::DotProduct => dot_product(query, &vector),
+        DistanceMetric::Manhattan => {
+            let dist: f32 = query
+                .iter()
+                .zip(vector.iter())
+                .map(|(a, b)| (a - b).abs())
+                .sum();
+            1.0 / (1.0 + dist)
+        }
+    };
+
+    similarity * (1.0 - node.decay as f32)
+}
+
+fn to_entry(node: &MemoryNode) -> MemoryEntry {
+    MemoryEntry {
+        id: node.id.clone(),
+        content: node.content.clone(),
+        memory_type: format!("{:?}", node.memory_type),
+        importance: node.importance,
+        decay: node.decay,
+        access_count: node.access_count,
+        timestamp: node.timestamp,
+        last_accessed: node.last_accessed,
+        connections: node.connections.to_vec(),
+        tags: node.tags.clone(),
+    }
+}
+
 /// Batch entry for bulk insert
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatchEntry {
@@ -541,6 +1415,25 @@ pub struct BatchEntry {
     pub importance: f64,
 }
 
+/// Filters and paging for `NativeMemory::search` / `search_f32`
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    /// Only consider these memory types
+    pub memory_types: Option<Vec<String>>,
+    /// Only consider memories carrying every one of these tags
+    pub tags: Option<Vec<String>>,
+    /// Drop results scoring below this
+    pub min_similarity: Option<f64>,
+    /// Inclusive lower bound on creation time (ms)
+    pub after: Option<i64>,
+    /// Inclusive upper bound on creation time (ms)
+    pub before: Option<i64>,
+    /// Ranked results to skip before taking `k`, for paging
+    pub offset: Option<u32>,
+    /// Rank by `similarity + importance_weight * importance` instead of similarity alone
+    pub rank_by_importance: bool,
+}
+
 /// Search result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
@@ -549,6 +1442,8 @@ pub struct SearchResult {
     pub similarity: f64,
     pub memory_type: String,
     pub importance: f64,
+    pub last_accessed: i64,
+    pub tags: Vec<String>,
 }
 
 /// Consolidation result
@@ -557,6 +1452,57 @@ pub struct ConsolidationResult {
     pub merged: u32,
     pub pruned: u32,
     pub total_remaining: u32,
+    /// Memories merged away or pruned
+    #[serde(default)]
+    pub removed_ids: Vec<String>,
+}
+
+/// On-disk format for `export_json` / `import_json`
+#[derive(Debug, Serialize, Deserialize)]
+struct MemoryExport {
+    dimensions: usize,
+    memories: Vec<ExportedNode>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedNode {
+    id: String,
+    content: String,
+    vector: Vec<f32>,
+    memory_type: MemoryType,
+    importance: f64,
+    decay: f64,
+    access_count: u32,
+    timestamp: i64,
+    #[serde(default)]
+    last_accessed: i64,
+    connections: Vec<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    provider: String,
+}
+
+/// Memories and their links, as returned by `NativeMemory::graph`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphNode {
+    pub id: String,
+    pub content_preview: String,
+    pub memory_type: String,
+    pub importance: f64,
+}
+
+/// An undirected link; `from` sorts before `to`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
 }
 
 /// Memory statistics
@@ -591,13 +1537,58 @@ mod tests {
         assert_eq!(memory.len(), 1);
 
         let results = memory
-            .search(vec![1.0, 0.0, 0.0, 0.0], 5, None, None)
+            .search(vec![1.0, 0.0, 0.0, 0.0], 5, SearchOptions::default())
             .unwrap();
 
         assert_eq!(results.len(), 1);
         assert!(results[0].similarity > 0.99);
     }
 
+    #[test]
+    fn test_set_dimensions_only_when_empty() {
+        let memory = NativeMemory::new(4);
+        memory.set_dimensions(8).unwrap();
+        assert_eq!(memory.dimensions(), 8);
+
+        memory
+            .store_f32("wide".to_string(), vec![1.0; 8], "semantic".to_string(), 0.5)
+            .unwrap();
+        // Mismatched inserts are refused and the width is locked in
+        assert!(memory
+            .store_f32("narrow".to_string(), vec![1.0; 4], "semantic".to_string(), 0.5)
+            .is_err());
+        assert!(memory.set_dimensions(4).is_err());
+        assert_eq!(memory.dimensions(), 8);
+    }
+
+    #[test]
+    fn test_mismatched_provider_ids() {
+        let memory = NativeMemory::new(4);
+        let store = |content: &str| {
+            let vector = vec![1.0, 0.0, 0.0, 0.0];
+            memory
+                .store_f32(content.to_string(), vector, "semantic".to_string(), 0.5)
+                .unwrap()
+        };
+
+        let legacy = store("stored before providers were recorded");
+        memory.set_embedding_provider("Hash");
+        let hashed = store("hashed");
+        memory.set_embedding_provider("Ollama:nomic-embed-text");
+        let ollama = store("from ollama");
+
+        assert_eq!(memory.get_node(&legacy).unwrap().provider, "");
+        assert_eq!(memory.get_node(&ollama).unwrap().provider, "Ollama:nomic-embed-text");
+        assert_eq!(memory.mismatched_provider_ids(), vec![hashed.clone()]);
+
+        // Re-embedding with the current provider clears the mismatch
+        assert!(memory.update_memory(&hashed, None, Some(vec![0.0, 1.0, 0.0, 0.0]), None));
+        assert!(memory.mismatched_provider_ids().is_empty());
+
+        memory.set_embedding_provider("Hash");
+        assert_eq!(memory.mismatched_provider_ids().len(), 2);
+    }
+
     #[test]
     fn test_store_f32_and_search() {
         let memory = NativeMemory::new(4);
@@ -614,10 +1605,986 @@ mod tests {
         assert!(!id.is_empty());
 
         let results = memory
-            .search_f32(&[1.0, 0.0, 0.0, 0.0], 5, None, None)
+            .search_f32(&[1.0, 0.0, 0.0, 0.0], 5, SearchOptions::default())
             .unwrap();
 
         assert_eq!(results.len(), 1);
         assert!(results[0].similarity > 0.99);
     }
+
+    #[test]
+    fn test_store_dedups_exact_content() {
+        let memory = NativeMemory::new(4);
+        let first = memory
+            .store_f32(
+                "Wifi password is hunter2".to_string(),
+                vec![1.0, 0.0, 0.0, 0.0],
+                "semantic".to_string(),
+                0.4,
+            )
+            .unwrap();
+
+        // Identical text dedups even if the embedding differs
+        let second = memory
+            .store_f32(
+                "Wifi password is hunter2".to_string(),
+                vec![0.0, 1.0, 0.0, 0.0],
+                "semantic".to_string(),
+                0.8,
+            )
+            .unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(memory.len(), 1);
+        let entry = memory.get(&first).unwrap();
+        assert_eq!(entry.access_count, 1);
+        assert!((entry.importance - 0.8).abs() < 1e-9);
+
+        // A different type is a different memory
+        memory
+            .store_f32(
+                "Wifi password is hunter2".to_string(),
+                vec![1.0, 0.0, 0.0, 0.0],
+                "episodic".to_string(),
+                0.4,
+            )
+            .unwrap();
+        assert_eq!(memory.len(), 2);
+
+        memory.set_dedup(false, 0.95);
+        memory
+            .store_f32(
+                "Wifi password is hunter2".to_string(),
+                vec![1.0, 0.0, 0.0, 0.0],
+                "semantic".to_string(),
+                0.4,
+            )
+            .unwrap();
+        assert_eq!(memory.len(), 3);
+    }
+
+    #[test]
+    fn test_store_dedups_near_duplicate() {
+        let memory = NativeMemory::new(4);
+        let first = memory
+            .store_f32(
+                "Standup at 9am".to_string(),
+                vec![1.0, 0.0, 0.0, 0.0],
+                "episodic".to_string(),
+                0.5,
+            )
+            .unwrap();
+
+        let near = memory
+            .store_f32(
+                "  standup at  9AM ".to_string(),
+                vec![0.99, 0.05, 0.0, 0.0],
+                "episodic".to_string(),
+                0.5,
+            )
+            .unwrap();
+        assert_eq!(near, first);
+
+        // Same normalized text but the vectors disagree: keep both
+        let far = memory
+            .store_f32(
+                "standup at 9am".to_string(),
+                vec![0.0, 1.0, 0.0, 0.0],
+                "episodic".to_string(),
+                0.5,
+            )
+            .unwrap();
+        assert_ne!(far, first);
+
+        // Similar vector, different text: keep both
+        let other = memory
+            .store_f32(
+                "Standup at 10am".to_string(),
+                vec![1.0, 0.0, 0.0, 0.0],
+                "episodic".to_string(),
+                0.5,
+            )
+            .unwrap();
+        assert_ne!(other, first);
+        assert_eq!(memory.len(), 3);
+    }
+
+    #[test]
+    fn test_dedup_follows_edits_and_deletes() {
+        let memory = NativeMemory::new(4);
+        let store = |content: &str| {
+            memory
+                .store_f32(content.to_string(), vec![1.0, 0.0, 0.0, 0.0], "semantic".into(), 0.5)
+                .unwrap()
+        };
+        let id = store("Buy milk");
+        assert!(memory.update_memory(&id, Some("Buy bread".to_string()), None, None));
+
+        // The old text no longer matches; the new one does
+        assert_ne!(store("Buy milk"), id);
+        assert_eq!(store("buy  BREAD"), id);
+
+        // Deleted memories are forgotten by the duplicate check too
+        assert!(memory.delete(&id));
+        let fresh = store("Buy bread");
+        assert_ne!(fresh, id);
+        assert_eq!(memory.len(), 2);
+
+        // Restoring a node over itself keeps it findable
+        memory.restore_node(memory.get_node(&fresh).unwrap());
+        assert_eq!(store("Buy bread"), fresh);
+    }
+
+    #[test]
+    fn test_delete_by_type() {
+        let memory = NativeMemory::new(4);
+        for (i, memory_type) in ["working", "semantic", "working", "episodic"].iter().enumerate() {
+            memory
+                .store_f32(
+                    format!("Note {}", i),
+                    vec![1.0, i as f32, 0.0, 0.0],
+                    memory_type.to_string(),
+                    0.5,
+                )
+                .unwrap();
+        }
+
+        assert_eq!(memory.delete_by_type("Working"), 2);
+        assert_eq!(memory.len(), 2);
+        assert!(memory.all_nodes().iter().all(|n| n.memory_type != MemoryType::Working));
+        assert!(memory.type_indices.get("Working").is_none());
+
+        let options = SearchOptions {
+            memory_types: Some(vec!["working".to_string()]),
+            ..Default::default()
+        };
+        let results = memory
+            .search_f32(&[1.0, 0.0, 0.0, 0.0], 10, options)
+            .unwrap();
+        assert!(results.is_empty());
+
+        // Unknown names must not fall back to the default type
+        assert_eq!(memory.delete_by_type("scratch"), 0);
+        assert_eq!(memory.len(), 2);
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let memory = NativeMemory::new(4);
+        let a = memory
+            .store_f32(
+                "Rust 1.0 shipped in 2015".to_string(),
+                vec![0.2, 0.4, 0.0, 0.9],
+                "semantic".to_string(),
+                0.7,
+            )
+            .unwrap();
+        let b = memory
+            .store_f32(
+                "Went hiking on Sunday".to_string(),
+                vec![0.0, 1.0, 0.3, 0.0],
+                "episodic".to_string(),
+                0.4,
+            )
+            .unwrap();
+        memory.connect(&a, &b);
+        memory.consolidate();
+
+        let json = memory.export_json();
+
+        let restored = NativeMemory::new(4);
+        assert_eq!(restored.import_json(&json).unwrap(), 2);
+        assert_eq!(restored.len(), memory.len());
+
+        let original = memory.get_node(&a).unwrap();
+        let copy = restored.get_node(&a).unwrap();
+        assert_eq!(copy.content, original.content);
+        assert_eq!(copy.vector, original.vector);
+        assert_eq!(copy.memory_type, original.memory_type);
+        assert_eq!(copy.connections, original.connections);
+        assert!((copy.importance - original.importance).abs() < 1e-12);
+        assert!((copy.decay - original.decay).abs() < 1e-12);
+        assert_eq!(copy.timestamp, original.timestamp);
+
+        // Imported memories are searchable by type
+        let options = SearchOptions {
+            memory_types: Some(vec!["episodic".to_string()]),
+            ..Default::default()
+        };
+        let results = restored
+            .search_f32(&[0.0, 1.0, 0.3, 0.0], 1, options)
+            .unwrap();
+        assert_eq!(results[0].id, b);
+
+        // Vectors of the wrong dimension are skipped
+        let narrow = NativeMemory::new(3);
+        assert_eq!(narrow.import_json(&json).unwrap(), 0);
+        assert!(narrow.import_json("not json").is_err());
+    }
+
+    #[test]
+    fn test_consolidate_merges_similar() {
+        let memory = NativeMemory::new(4);
+
+        let a = memory
+            .store_f32(
+                "Rust is fast".to_string(),
+                vec![1.0, 0.0, 0.0, 0.0],
+                "semantic".to_string(),
+                0.9,
+            )
+            .unwrap();
+        let b = memory
+            .store_f32(
+                "Rust is quick".to_string(),
+                vec![0.99, 0.01, 0.0, 0.0],
+                "semantic".to_string(),
+                0.4,
+            )
+            .unwrap();
+        let c = memory
+            .store_f32(
+                "Unrelated".to_string(),
+                vec![0.0, 0.0, 1.0, 0.0],
+                "semantic".to_string(),
+                0.5,
+            )
+            .unwrap();
+        memory.connect(&b, &c);
+
+        let before = memory.len();
+        let result = memory.consolidate();
+
+        assert_eq!(result.merged, 1);
+        assert_eq!(memory.len(), before - 1);
+        assert_eq!(result.removed_ids, vec![b.clone()]);
+        assert_eq!(memory.take_discarded(), vec![b.clone()]);
+        assert!(memory.take_discarded().is_empty());
+
+        // The higher-importance node survives and inherits the absorbed node's edges
+        let kept = memory.get(&a).unwrap();
+        assert!(memory.get(&b).is_none());
+        assert!((kept.importance - 0.9).abs() < 1e-9);
+        assert!(kept.connections.contains(&c));
+        assert!(memory.get(&c).unwrap().connections.contains(&a));
+    }
+
+    #[test]
+    fn test_recall_reinforces_memory() {
+        let memory = NativeMemory::new(4);
+
+        memory.restore_node(MemoryNode {
+            id: "faded".to_string(),
+            content: "Half-forgotten fact".to_string(),
+            vector: vec![1.0, 0.0, 0.0, 0.0].into(),
+            memory_type: MemoryType::Semantic,
+            importance: 0.5,
+            decay: 0.6,
+            access_count: 0,
+            timestamp: now_millis(),
+            last_accessed: now_millis(),
+            connections: SmallVec::new(),
+            tags: Vec::new(),
+            provider: String::new(),
+        });
+
+        let results = memory
+            .search_f32(&[1.0, 0.0, 0.0, 0.0], 5, SearchOptions::default())
+            .unwrap();
+        assert_eq!(results.len(), 1);
+
+        let node = memory.get("faded").unwrap();
+        assert!(node.decay < 0.6);
+        assert!(node.importance > 0.5);
+        assert_eq!(node.access_count, 1);
+    }
+
+    #[test]
+    fn test_update_memory_in_place() {
+        let memory = NativeMemory::new(4);
+        let id = memory
+            .store_f32(
+                "Meeting on Monday".to_string(),
+                vec![1.0, 0.0, 0.0, 0.0],
+                "episodic".to_string(),
+                0.4,
+            )
+            .unwrap();
+        let other = memory
+            .store_f32(
+                "Project kickoff".to_string(),
+                vec![0.0, 0.0, 1.0, 0.0],
+                "episodic".to_string(),
+                0.4,
+            )
+            .unwrap();
+        memory.connect(&id, &other);
+        let before = memory.get(&id).unwrap();
+
+        assert!(memory.update_memory(
+            &id,
+            Some("Meeting moved to Tuesday".to_string()),
+            Some(vec![0.0, 1.0, 0.0, 0.0]),
+            Some(0.9),
+        ));
+
+        let after = memory.get(&id).unwrap();
+        assert_eq!(after.id, id);
+        assert_eq!(after.content, "Meeting moved to Tuesday");
+        assert!((after.importance - 0.9).abs() < 1e-9);
+        assert_eq!(after.timestamp, before.timestamp);
+        assert_eq!(after.connections, vec![other.clone()]);
+
+        // Search follows the new vector
+        let results = memory
+            .search_f32(
+                &[0.0, 1.0, 0.0, 0.0],
+                1,
+                SearchOptions {
+                    min_similarity: Some(0.9),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(results[0].id, id);
+
+        // Importance-only update leaves content alone
+        assert!(memory.update_memory(&id, None, None, Some(0.2)));
+        assert_eq!(memory.get(&id).unwrap().content, "Meeting moved to Tuesday");
+
+        assert!(!memory.update_memory("missing", None, None, Some(0.5)));
+        assert!(!memory.update_memory(&id, None, Some(vec![1.0; 3]), None));
+    }
+
+    #[test]
+    fn test_search_updates_last_accessed() {
+        let memory = NativeMemory::new(4);
+        memory.restore_node(MemoryNode {
+            id: "stale".to_string(),
+            content: "Old note".to_string(),
+            vector: vec![1.0, 0.0, 0.0, 0.0].into(),
+            memory_type: MemoryType::Semantic,
+            importance: 0.5,
+            decay: 0.0,
+            access_count: 0,
+            timestamp: 1_000,
+            last_accessed: 1_000,
+            connections: SmallVec::new(),
+            tags: Vec::new(),
+            provider: String::new(),
+        });
+
+        let results = memory
+            .search_f32(&[1.0, 0.0, 0.0, 0.0], 1, SearchOptions::default())
+            .unwrap();
+        let entry = memory.get("stale").unwrap();
+
+        assert!(entry.last_accessed > 1_000);
+        assert_eq!(results[0].last_accessed, entry.last_accessed);
+        assert_eq!(entry.timestamp, 1_000);
+    }
+
+    #[test]
+    fn test_for_each_node_visits_all_once() {
+        let (memory, _) = random_store(300, 8, usize::MAX);
+
+        let mut seen: ahash::AHashMap<String, u32> = ahash::AHashMap::new();
+        memory.for_each_node(|node| *seen.entry(node.id.clone()).or_default() += 1);
+
+        assert_eq!(seen.len(), 300);
+        assert!(seen.values().all(|&visits| visits == 1));
+        assert!(memory.all_nodes().iter().all(|n| seen.contains_key(&n.id)));
+    }
+
+    #[test]
+    fn test_graph_has_one_edge_per_link() {
+        let memory = NativeMemory::new(4);
+        let store = |content: &str, importance: f64| {
+            memory
+                .store_f32(
+                    content.to_string(),
+                    vec![1.0, 0.0, 0.0, 0.0],
+                    "semantic".to_string(),
+                    importance,
+                )
+                .unwrap()
+        };
+        let a = store("a", 0.9);
+        let b = store("b", 0.8);
+        let low = store("low", 0.1);
+        memory.connect(&a, &b);
+        memory.connect(&b, &low);
+
+        let graph = memory.graph(10);
+        assert_eq!(graph.nodes.len(), 3);
+        assert_eq!(graph.edges.len(), 2);
+
+        // Links to memories outside the limit are left out
+        let graph = memory.graph(2);
+        let ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec![a.as_str(), b.as_str()]);
+        assert_eq!(graph.edges.len(), 1);
+        let mut pair = [a.clone(), b.clone()];
+        pair.sort();
+        assert_eq!((&graph.edges[0].from, &graph.edges[0].to), (&pair[0], &pair[1]));
+    }
+
+    #[test]
+    fn test_connect_and_disconnect() {
+        let memory = NativeMemory::new(4);
+        let store = |content: &str| {
+            memory
+                .store_f32(
+                    content.to_string(),
+                    vec![1.0, 0.0, 0.0, 0.0],
+                    "semantic".to_string(),
+                    0.5,
+                )
+                .unwrap()
+        };
+        let a = store("a");
+        let b = store("b");
+        let connections = |id: &str| memory.get(id).unwrap().connections;
+
+        assert!(memory.connect(&a, &b));
+        assert_eq!(connections(&a), vec![b.clone()]);
+        assert_eq!(connections(&b), vec![a.clone()]);
+
+        // Reconnecting, in either order, doesn't duplicate the link
+        assert!(memory.connect(&a, &b));
+        assert!(memory.connect(&b, &a));
+        assert_eq!(connections(&a).len(), 1);
+        assert_eq!(connections(&b).len(), 1);
+
+        assert!(!memory.connect(&a, "missing"));
+        assert!(!memory.connect(&a, &a));
+        assert_eq!(connections(&a).len(), 1);
+
+        assert!(memory.disconnect(&b, &a));
+        assert!(connections(&a).is_empty());
+        assert!(connections(&b).is_empty());
+        assert!(!memory.disconnect(&a, &b));
+    }
+
+    #[test]
+    fn test_deleted_memory_leaves_neighbor_connections() {
+        let memory = NativeMemory::new(4);
+        let store = |content: &str, memory_type: &str| {
+            memory
+                .store_f32(
+                    content.to_string(),
+                    vec![1.0, 0.0, 0.0, 0.0],
+                    memory_type.to_string(),
+                    0.5,
+                )
+                .unwrap()
+        };
+        let a = store("a", "semantic");
+        let b = store("b", "semantic");
+        let c = store("c", "working");
+        assert!(memory.connect(&a, &b));
+        assert!(memory.connect(&a, &c));
+
+        assert!(memory.delete(&b));
+        assert_eq!(memory.get(&a).unwrap().connections, vec![c.clone()]);
+
+        assert_eq!(memory.delete_by_type("working"), 1);
+        assert!(memory.get(&a).unwrap().connections.is_empty());
+        assert!(memory.get_connected(&a, 2).is_empty());
+    }
+
+    #[test]
+    fn test_get_connected_traversal() {
+        let memory = NativeMemory::new(4);
+        let store = |content: &str| {
+            memory
+                .store_f32(
+                    content.to_string(),
+                    vec![1.0, 0.0, 0.0, 0.0],
+                    "semantic".to_string(),
+                    0.5,
+                )
+                .unwrap()
+        };
+
+        let a = store("a");
+        let b = store("b");
+        let c = store("c");
+        let d = store("d");
+        memory.connect(&a, &b);
+        memory.connect(&b, &c);
+        memory.connect(&c, &a); // cycle
+        memory.connect(&c, &d);
+        memory.delete(&d); // dangling edge from c
+
+        let one_hop: Vec<String> = memory
+            .get_connected(&a, 1)
+            .into_iter()
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(one_hop.len(), 2);
+        assert!(one_hop.contains(&b) && one_hop.contains(&c));
+
+        let two_hops = memory.get_connected(&a, 2);
+        assert_eq!(two_hops.len(), 2);
+
+        assert!(memory.get_connected(&a, 0).is_empty());
+        assert!(memory.get_connected("missing", 3).is_empty());
+    }
+
+    #[test]
+    fn test_type_filtered_search_uses_index() {
+        let memory = NativeMemory::new(4);
+        for i in 0..20 {
+            memory
+                .store_f32(
+                    format!("Fact {}", i),
+                    vec![1.0, i as f32 * 0.01, 0.0, 0.0],
+                    "semantic".to_string(),
+                    0.5,
+                )
+                .unwrap();
+        }
+        let keep = memory
+            .store_f32(
+                "How to deploy".to_string(),
+                vec![1.0, 0.0, 0.0, 0.0],
+                "procedural".to_string(),
+                0.5,
+            )
+            .unwrap();
+        let gone = memory
+            .store_f32(
+                "How to rollback".to_string(),
+                vec![1.0, 0.1, 0.0, 0.0],
+                "procedural".to_string(),
+                0.5,
+            )
+            .unwrap();
+
+        let options = SearchOptions {
+            memory_types: Some(vec!["procedural".to_string()]),
+            ..Default::default()
+        };
+        let results = memory
+            .search_f32(&[1.0, 0.0, 0.0, 0.0], 10, options.clone())
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.memory_type == "Procedural"));
+
+        assert!(memory.delete(&gone));
+        let results = memory
+            .search_f32(&[1.0, 0.0, 0.0, 0.0], 10, options)
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, keep);
+        assert_eq!(memory.type_indices.get("Procedural").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_search_time_range() {
+        let memory = NativeMemory::new(4);
+        for (id, timestamp) in [("old", 1_000), ("mid", 2_000), ("new", 3_000)] {
+            memory.restore_node(MemoryNode {
+                id: id.to_string(),
+                content: id.to_string(),
+                vector: vec![1.0, 0.0, 0.0, 0.0].into(),
+                memory_type: MemoryType::Semantic,
+                importance: 0.5,
+                decay: 0.0,
+                access_count: 0,
+                timestamp,
+                last_accessed: timestamp,
+                connections: SmallVec::new(),
+                tags: Vec::new(),
+                provider: String::new(),
+            });
+        }
+
+        let ids = |after: Option<i64>, before: Option<i64>| -> Vec<String> {
+            let mut ids: Vec<String> = memory
+                .search_f32(
+                    &[1.0, 0.0, 0.0, 0.0],
+                    10,
+                    SearchOptions {
+                        after,
+                        before,
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+                .into_iter()
+                .map(|r| r.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(ids(None, None), vec!["mid", "new", "old"]);
+        assert_eq!(ids(Some(2_000), None), vec!["mid", "new"]);
+        assert_eq!(ids(None, Some(2_000)), vec!["mid", "old"]);
+        assert_eq!(ids(Some(1_500), Some(2_500)), vec!["mid"]);
+        assert!(ids(Some(4_000), None).is_empty());
+
+        // Combines with the type filter
+        let episodic = memory
+            .search_f32(
+                &[1.0, 0.0, 0.0, 0.0],
+                10,
+                SearchOptions {
+                    memory_types: Some(vec!["episodic".to_string()]),
+                    after: Some(0),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(episodic.is_empty());
+    }
+
+    /// Fill a store with random vectors and return (store, queries). Vectors are
+    /// scattered around random centroids, which is closer to how embeddings of
+    /// related text cluster than uniform noise.
+    fn random_store(
+        count: usize,
+        dim: usize,
+        ann_threshold: usize,
+    ) -> (NativeMemory, Vec<Vec<f32>>) {
+        use rand::prelude::*;
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let centroids: Vec<Vec<f32>> = (0..100)
+            .map(|_| (0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect();
+        let random_vec = |rng: &mut StdRng| -> Vec<f32> {
+            let centroid = &centroids[rng.gen_range(0..centroids.len())];
+            centroid.iter().map(|c| c + rng.gen_range(-0.6..0.6)).collect()
+        };
+
+        let memory = NativeMemory::new(dim as u32);
+        {
+            let mut config = memory.config.write();
+            config.ann_threshold = ann_threshold;
+            config.max_memories = count * 2;
+            config.dedup = false;
+        }
+        for i in 0..count {
+            let v = random_vec(&mut rng);
+            memory
+                .store_f32(format!("memory {}", i), v, "semantic".to_string(), 0.5)
+                .unwrap();
+        }
+
+        let queries = (0..20).map(|_| random_vec(&mut rng)).collect();
+        (memory, queries)
+    }
+
+    /// Average fraction of the exact top-k that the approximate path also returns
+    fn recall_at_k(memory: &NativeMemory, queries: &[Vec<f32>], k: usize) -> f64 {
+        let config = memory.config.read().clone();
+        let filter = SearchFilter {
+            min_similarity: -1.0,
+            ..Default::default()
+        };
+        let mut total = 0.0;
+
+        for query in queries {
+            let mut exact = memory.search_exact(query, &filter, &config);
+            exact.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+            let truth: Vec<String> = exact.into_iter().take(k).map(|r| r.0).collect();
+
+            let approx = memory
+                .search_approximate(query, k, &filter, &config)
+                .expect("approximate path should be used");
+            let mut approx: Vec<(String, f32)> = approx.into_iter().map(|r| (r.0, r.1)).collect();
+            approx.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+            let found = approx
+                .iter()
+                .take(k)
+                .filter(|(id, _)| truth.contains(id))
+                .count();
+            total += found as f64 / k as f64;
+        }
+
+        total / queries.len() as f64
+    }
+
+    #[test]
+    fn test_approximate_search_recall() {
+        let (memory, queries) = random_store(1_000, 16, 500);
+
+        let recall = recall_at_k(&memory, &queries, 10);
+        assert!(recall >= 0.9, "recall@10 too low: {:.3}", recall);
+
+        // Deleted memories must never come back from the index
+        let results = memory.search_f32(&queries[0], 10, SearchOptions::default()).unwrap();
+        let top = results[0].id.clone();
+        memory.delete(&top);
+        let results = memory.search_f32(&queries[0], 10, SearchOptions::default()).unwrap();
+        assert!(results.iter().all(|r| r.id != top));
+    }
+
+    #[test]
+    fn test_search_by_tags() {
+        let memory = NativeMemory::new(4);
+        let store = |content: &str, memory_type: &str, tags: &[&str]| {
+            let id = memory
+                .store_f32(
+                    content.to_string(),
+                    vec![1.0, content.len() as f32 * 0.01, 0.0, 0.0],
+                    memory_type.to_string(),
+                    0.5,
+                )
+                .unwrap();
+            let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+            assert!(memory.add_tags(&id, &tags));
+            id
+        };
+
+        let urgent_x = store("Fix login bug", "procedural", &["project-x", "Urgent "]);
+        let plain_x = store("Project X kickoff notes", "episodic", &["project-x"]);
+        let urgent_y = store("Renew domain", "procedural", &["project-y", "urgent"]);
+        assert_eq!(memory.get(&urgent_x).unwrap().tags, vec!["project-x", "urgent"]);
+
+        let ids = |tags: &[&str], types: Option<Vec<String>>| -> Vec<String> {
+            let options = SearchOptions {
+                tags: Some(tags.iter().map(|t| t.to_string()).collect()),
+                memory_types: types,
+                ..Default::default()
+            };
+            let mut ids: Vec<String> = memory
+                .search_f32(&[1.0, 0.0, 0.0, 0.0], 10, options)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        let sorted = |mut v: Vec<String>| {
+            v.sort();
+            v
+        };
+
+        assert_eq!(ids(&["project-x"], None), sorted(vec![urgent_x.clone(), plain_x]));
+        assert_eq!(ids(&["URGENT"], None), sorted(vec![urgent_x.clone(), urgent_y]));
+        // Multiple tags must all match
+        assert_eq!(ids(&["project-x", "urgent"], None), vec![urgent_x.clone()]);
+        // Tags combine with the type filter
+        assert_eq!(
+            ids(&["project-x"], Some(vec!["procedural".to_string()])),
+            vec![urgent_x]
+        );
+        assert!(ids(&["project-z"], None).is_empty());
+    }
+
+    #[test]
+    fn test_search_radius_returns_cluster() {
+        let memory = NativeMemory::new(4);
+        let mut cluster = Vec::new();
+        for i in 0..12 {
+            let id = memory
+                .store_f32(
+                    format!("Cluster {}", i),
+                    vec![1.0, 0.02 * i as f32, 0.01, 0.0],
+                    "semantic".to_string(),
+                    0.5,
+                )
+                .unwrap();
+            cluster.push(id);
+        }
+        for (i, v) in [[0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0], [-1.0, 0.0, 0.0, 0.0]]
+            .iter()
+            .enumerate()
+        {
+            memory
+                .store_f32(format!("Far {}", i), v.to_vec(), "semantic".to_string(), 0.5)
+                .unwrap();
+        }
+
+        let results = memory.search_radius(&[1.0, 0.1, 0.0, 0.0], 0.8, None);
+        assert_eq!(results.len(), cluster.len());
+        assert!(results.iter().all(|r| cluster.contains(&r.id)));
+        assert!(results.windows(2).all(|w| w[0].similarity >= w[1].similarity));
+        // Radius queries don't count as recalls
+        assert!(results.iter().all(|r| memory.get(&r.id).unwrap().access_count == 0));
+
+        // A near-zero threshold is clamped rather than returning everything
+        let results = memory.search_radius(&[1.0, 0.1, 0.0, 0.0], 0.0, None);
+        assert_eq!(results.len(), cluster.len());
+
+        let types = Some(vec!["episodic".to_string()]);
+        assert!(memory.search_radius(&[1.0, 0.1, 0.0, 0.0], 0.8, types).is_empty());
+    }
+
+    #[test]
+    fn test_rank_by_importance() {
+        let memory = NativeMemory::new(4);
+        let minor = memory
+            .store_f32(
+                "Lunch was pasta".to_string(),
+                vec![1.0, 0.0, 0.0, 0.0],
+                "episodic".to_string(),
+                0.1,
+            )
+            .unwrap();
+        let major = memory
+            .store_f32(
+                "Signed the lease".to_string(),
+                vec![1.0, 0.0, 0.0, 0.0],
+                "episodic".to_string(),
+                0.9,
+            )
+            .unwrap();
+        // Keep importance fixed across the queries below
+        memory.set_reinforcement(0.0, 0.0);
+
+        let options = SearchOptions {
+            rank_by_importance: true,
+            ..Default::default()
+        };
+        let results = memory
+            .search_f32(&[1.0, 0.0, 0.0, 0.0], 2, options.clone())
+            .unwrap();
+        assert_eq!(results[0].id, major);
+        assert_eq!(results[1].id, minor);
+        // Reported similarity is unaffected by the rerank
+        assert!((results[0].similarity - results[1].similarity).abs() < 1e-6);
+
+        // A much better match still wins over importance
+        let close = memory
+            .store_f32(
+                "Pasta recipe".to_string(),
+                vec![0.0, 1.0, 0.0, 0.0],
+                "semantic".to_string(),
+                0.1,
+            )
+            .unwrap();
+        let results = memory
+            .search_f32(&[0.1, 1.0, 0.0, 0.0], 1, options)
+            .unwrap();
+        assert_eq!(results[0].id, close);
+    }
+
+    #[test]
+    fn test_rank_by_importance_past_ann_threshold() {
+        let (memory, queries) = random_store(1_000, 16, 500);
+        memory.set_reinforcement(0.0, 0.0);
+        memory.set_importance_weight(10.0);
+        let query = &queries[0];
+
+        // A weak match, far outside the candidates the index would return
+        let config = memory.config.read().clone();
+        let filter = SearchFilter {
+            min_similarity: -1.0,
+            ..Default::default()
+        };
+        let mut exact = memory.search_exact(query, &filter, &config);
+        exact.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        let weak = exact[600].0.clone();
+        assert!(memory.update_memory(&weak, None, None, Some(1.0)));
+
+        let options = SearchOptions {
+            rank_by_importance: true,
+            min_similarity: Some(-1.0),
+            ..Default::default()
+        };
+        let results = memory.search_f32(query, 1, options).unwrap();
+        assert_eq!(results[0].id, weak);
+    }
+
+    #[test]
+    fn test_search_offset_pages() {
+        let (memory, queries) = random_store(200, 16, usize::MAX);
+        let query = &queries[0];
+        let page = |k: u32, offset: Option<u32>| -> Vec<String> {
+            let options = SearchOptions {
+                min_similarity: Some(-1.0),
+                offset,
+                ..Default::default()
+            };
+            memory
+                .search_f32(query, k, options)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.id)
+                .collect()
+        };
+
+        // Reinforcement from earlier pages must not reshuffle later ones
+        memory.set_reinforcement(0.0, 0.0);
+
+        let all = page(10, None);
+        assert_eq!(page(5, Some(0)), all[..5]);
+        assert_eq!(page(5, Some(5)), all[5..]);
+        assert!(page(5, Some(1_000)).is_empty());
+    }
+
+    #[test]
+    fn test_quantized_ranking_matches_full_precision() {
+        let (memory, queries) = random_store(1_000, 64, usize::MAX);
+        let filter = SearchFilter {
+            min_similarity: -1.0,
+            ..Default::default()
+        };
+        let ranked = |memory: &NativeMemory, query: &[f32]| -> Vec<(String, f32)> {
+            let config = memory.config.read().clone();
+            let mut hits = memory.search_exact(query, &filter, &config);
+            hits.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+            hits.into_iter().take(10).map(|r| (r.0, r.1)).collect()
+        };
+
+        let full: Vec<_> = queries.iter().map(|q| ranked(&memory, q)).collect();
+        memory.set_quantization(true);
+        assert!(memory.memories.iter().all(|e| e.vector.is_quantized()));
+        let quantized: Vec<_> = queries.iter().map(|q| ranked(&memory, q)).collect();
+
+        let mut overlap = 0;
+        for (full, quantized) in full.iter().zip(quantized.iter()) {
+            assert_eq!(full[0].0, quantized[0].0);
+            for (id, sim) in quantized {
+                if let Some((_, exact)) = full.iter().find(|(f, _)| f == id) {
+                    overlap += 1;
+                    assert!((sim - exact).abs() < 0.01, "{} vs {}", sim, exact);
+                }
+            }
+        }
+        let recall = overlap as f64 / (queries.len() * 10) as f64;
+        assert!(recall >= 0.9, "quantized recall@10 too low: {:.3}", recall);
+    }
+
+    /// Run with `cargo test --release -- --ignored bench_hnsw --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_hnsw_recall_50k() {
+        let (memory, queries) = random_store(50_000, 384, 10_000);
+        memory.ensure_ann_index();
+
+        let config = memory.config.read().clone();
+        let filter = SearchFilter {
+            min_similarity: -1.0,
+            ..Default::default()
+        };
+        let started = std::time::Instant::now();
+        for q in &queries {
+            memory.search_exact(q, &filter, &config);
+        }
+        let exact_time = started.elapsed();
+
+        let started = std::time::Instant::now();
+        for q in &queries {
+            memory.search_approximate(q, 10, &filter, &config);
+        }
+        let approx_time = started.elapsed();
+
+        let recall = recall_at_k(&memory, &queries, 10);
+        println!(
+            "recall@10={:.3} exact={:?} approx={:?} ({} queries)",
+            recall,
+            exact_time,
+            approx_time,
+            queries.len()
+        );
+        assert!(recall >= 0.9);
+    }
 }
@@ -3,7 +3,9 @@
 //! Ported from the native NAPI module to pure Rust for Tauri integration.
 
 pub mod cognitive;
+pub mod crypto;
 pub mod embeddings;
+pub mod hnsw;
 pub mod learning;
 pub mod memory;
 pub mod persistence;
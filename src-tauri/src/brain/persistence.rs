@@ -1,19 +1,34 @@
 //! SQLite persistence layer for SuperBrain
 //!
-//! Persists memories, Q-table, experiences, goals, and configuration
+//! Persists memories, Q-table, experiences, goals, beliefs, clipboard history,
+//! and configuration
 //! to ~/Library/Application Support/SuperBrain/brain.db
+//!
+//! With a cipher set, memory content and vectors and clipboard text are
+//! encrypted per row; each row records whether it is encrypted.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use rusqlite::{params, Connection};
+use parking_lot::{Mutex, MutexGuard, RwLock};
+use rusqlite::types::Value;
+use rusqlite::{params, Connection, OpenFlags, Statement};
+use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 
+use crate::brain::cognitive::{Belief, Goal, GoalStatus};
+use crate::brain::crypto::Cipher;
 use crate::brain::memory::MemoryNode;
-use crate::brain::types::MemoryType;
+use crate::brain::types::{Experience, MemoryType, Thought};
+use crate::brain::utils::now_millis;
+use crate::context::ClipboardEntry;
 
 /// Persistence manager for the cognitive engine
 pub struct BrainPersistence {
     db_path: PathBuf,
+    /// One connection shared by every call, opened and configured once
+    conn: Mutex<Connection>,
+    /// Encrypts sensitive columns on write; `None` stores plaintext
+    cipher: RwLock<Option<Cipher>>,
 }
 
 impl BrainPersistence {
@@ -26,12 +41,7 @@ impl BrainPersistence {
         std::fs::create_dir_all(&data_dir)
             .map_err(|e| format!("Failed to create data directory: {}", e))?;
 
-        let db_path = data_dir.join("brain.db");
-
-        let persistence = Self { db_path };
-        persistence.initialize_db()?;
-
-        Ok(persistence)
+        Self::with_path(data_dir.join("brain.db"))
     }
 
     /// Create with custom path (for testing)
@@ -41,24 +51,52 @@ impl BrainPersistence {
                 .map_err(|e| format!("Failed to create directory: {}", e))?;
         }
 
-        let persistence = Self { db_path };
-        persistence.initialize_db()?;
+        let conn = Self::open_initialized(&db_path)?;
+        let persistence = Self {
+            db_path,
+            conn: Mutex::new(conn),
+            cipher: RwLock::new(None),
+        };
 
         Ok(persistence)
     }
 
-    fn open_connection(&self) -> Result<Connection, String> {
-        Connection::open(&self.db_path).map_err(|e| format!("Failed to open database: {}", e))
+    /// The shared connection, held for the duration of one operation
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock()
     }
 
-    /// Initialize database tables
-    fn initialize_db(&self) -> Result<(), String> {
-        let conn = self.open_connection()?;
+    /// Run `f` inside one transaction: every write made through the batch is
+    /// committed together, or rolled back together if `f` fails. The
+    /// connection stays locked throughout, so `f` must write through the
+    /// batch rather than calling back into `self`.
+    pub fn transaction<T, F>(&self, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&WriteBatch<'_>) -> Result<T, String>,
+    {
+        let conn = self.connection();
+        let cipher = self.cipher.read();
 
-        // Enable WAL mode for better concurrent read performance
-        conn.execute_batch("PRAGMA journal_mode=WAL;")
-            .map_err(|e| format!("Failed to set WAL mode: {}", e))?;
+        conn.execute_batch("BEGIN TRANSACTION;")
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
 
+        let batch = WriteBatch {
+            conn: &conn,
+            cipher: cipher.as_ref(),
+        };
+        let result = f(&batch).and_then(|value| {
+            conn.execute_batch("COMMIT;")
+                .map_err(|e| format!("Failed to commit: {}", e))?;
+            Ok(value)
+        });
+        if result.is_err() {
+            let _ = conn.execute_batch("ROLLBACK;");
+        }
+        result
+    }
+
+    /// Create missing tables and bring the schema up to date
+    fn initialize_db(conn: &Connection) -> Result<(), String> {
         conn.execute_batch(
             "
             CREATE TABLE IF NOT EXISTS memories (
@@ -70,7 +108,9 @@ impl BrainPersistence {
                 decay REAL NOT NULL DEFAULT 0.0,
                 access_count INTEGER NOT NULL DEFAULT 0,
                 timestamp INTEGER NOT NULL,
-                connections TEXT NOT NULL DEFAULT '[]'
+                connections TEXT NOT NULL DEFAULT '[]',
+                last_accessed INTEGER NOT NULL DEFAULT 0,
+                tags TEXT NOT NULL DEFAULT '[]'
             );
 
             CREATE TABLE IF NOT EXISTS q_table (
@@ -79,6 +119,12 @@ impl BrainPersistence {
                 visits INTEGER NOT NULL DEFAULT 0
             );
 
+            CREATE TABLE IF NOT EXISTS q_table_b (
+                state_hash INTEGER PRIMARY KEY,
+                values_json TEXT NOT NULL,
+                visits INTEGER NOT NULL DEFAULT 0
+            );
+
             CREATE TABLE IF NOT EXISTS experiences (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 state_json TEXT NOT NULL,
@@ -98,142 +144,306 @@ impl BrainPersistence {
                 created_at INTEGER NOT NULL
             );
 
+            CREATE TABLE IF NOT EXISTS beliefs (
+                id TEXT PRIMARY KEY,
+                content TEXT NOT NULL,
+                confidence REAL NOT NULL,
+                source TEXT NOT NULL,
+                timestamp INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS thoughts (
+                id TEXT PRIMARY KEY,
+                content TEXT NOT NULL,
+                thought_type TEXT NOT NULL,
+                confidence REAL NOT NULL,
+                novelty REAL NOT NULL,
+                utility REAL NOT NULL,
+                timestamp INTEGER NOT NULL,
+                encrypted INTEGER NOT NULL DEFAULT 0
+            );
+
+            CREATE TABLE IF NOT EXISTS clipboard (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                content TEXT NOT NULL,
+                timestamp INTEGER NOT NULL
+            );
+
             CREATE TABLE IF NOT EXISTS config (
                 key TEXT PRIMARY KEY,
                 value TEXT NOT NULL
             );
 
+            CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER NOT NULL
+            );
+
             CREATE INDEX IF NOT EXISTS idx_memories_type ON memories(memory_type);
             CREATE INDEX IF NOT EXISTS idx_memories_importance ON memories(importance);
             CREATE INDEX IF NOT EXISTS idx_memories_timestamp ON memories(timestamp);
+            CREATE INDEX IF NOT EXISTS idx_thoughts_timestamp ON thoughts(timestamp);
             ",
         )
         .map_err(|e| format!("Failed to create tables: {}", e))?;
 
-        Ok(())
+        run_migrations(conn)
     }
 
-    // ---- Memory Persistence ----
+    // ---- Backup / Restore ----
 
-    /// Store a single memory
-    pub fn store_memory(&self, node: &MemoryNode) -> Result<(), String> {
-        let conn = self.open_connection()?;
-        let vector_bytes = vector_to_bytes(&node.vector);
-        let connections_json =
-            serde_json::to_string(&node.connections.to_vec()).unwrap_or_else(|_| "[]".to_string());
+    /// Copy the database to `dest`. The WAL is checkpointed into the main file
+    /// first and the connection held during the copy, so the copy is complete
+    /// and consistent on its own. Returns the number of bytes written.
+    pub fn backup_to(&self, dest: &Path) -> Result<u64, String> {
+        let conn = self.connection();
+        conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);")
+            .map_err(|e| format!("Failed to checkpoint database: {}", e))?;
+        std::fs::copy(&self.db_path, dest).map_err(|e| format!("Failed to write backup: {}", e))
+    }
 
-        conn.execute(
-            "INSERT OR REPLACE INTO memories (id, content, vector, memory_type, importance, decay, access_count, timestamp, connections)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-            params![
-                node.id,
-                node.content,
-                vector_bytes,
-                format!("{:?}", node.memory_type),
-                node.importance,
-                node.decay,
-                node.access_count,
-                node.timestamp,
-                connections_json,
-            ],
-        )
-        .map_err(|e| format!("Failed to store memory: {}", e))?;
+    /// Fold the WAL into the main file and rebuild it without free pages.
+    /// The connection is held throughout, so no other write lands mid-way.
+    pub fn compact(&self) -> Result<CompactStats, String> {
+        let conn = self.connection();
+        compact_database(&conn, &self.db_path)
+    }
 
+    /// Check that `src` is an intact SuperBrain database
+    pub fn validate_backup(src: &Path) -> Result<(), String> {
+        let conn = Connection::open_with_flags(src, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| format!("Failed to open backup: {}", e))?;
+        let check: String = conn
+            .query_row("PRAGMA quick_check", [], |row| row.get(0))
+            .map_err(|_| "Backup is not a SQLite database".to_string())?;
+        if check != "ok" {
+            return Err(format!("Backup is corrupted: {}", check));
+        }
+        for table in BACKUP_TABLES {
+            let found: bool = conn
+                .query_row(
+                    "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                    params![table],
+                    |row| row.get(0),
+                )
+                .map_err(|e| format!("Failed to read backup schema: {}", e))?;
+            if !found {
+                return Err(format!("Not a SuperBrain backup: missing {} table", table));
+            }
+        }
         Ok(())
     }
 
-    /// Store multiple memories in a transaction
-    pub fn store_memories_batch(&self, nodes: &[MemoryNode]) -> Result<(), String> {
-        let conn = self.open_connection()?;
-
-        conn.execute_batch("BEGIN TRANSACTION;")
-            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+    /// Replace the database with the backup at `src`, upgrading its schema if
+    /// it came from an older version. The live database is left untouched if
+    /// `src` doesn't validate.
+    pub fn restore_from(&self, src: &Path) -> Result<(), String> {
+        Self::validate_backup(src)?;
+        let staged = sidecar_path(&self.db_path, "-restore");
+        std::fs::copy(src, &staged).map_err(|e| format!("Failed to read backup: {}", e))?;
+
+        let mut conn = self.connection();
+        // Close the live file before replacing it
+        let placeholder = Connection::open_in_memory()
+            .map_err(|e| format!("Failed to open database: {}", e))?;
+        let _ = std::mem::replace(&mut *conn, placeholder).close();
+        self.remove_wal_files();
+
+        // Keep the live file aside until the backup has been opened
+        let previous = sidecar_path(&self.db_path, "-previous");
+        let restored = std::fs::rename(&self.db_path, &previous)
+            .map_err(|e| format!("Failed to replace database: {}", e))
+            .and_then(|_| {
+                std::fs::rename(&staged, &self.db_path).map_err(|e| {
+                    let _ = std::fs::rename(&previous, &self.db_path);
+                    format!("Failed to replace database: {}", e)
+                })
+            })
+            .and_then(|_| Self::open_initialized(&self.db_path));
 
-        for node in nodes {
-            let vector_bytes = vector_to_bytes(&node.vector);
-            let connections_json = serde_json::to_string(&node.connections.to_vec())
-                .unwrap_or_else(|_| "[]".to_string());
-
-            if let Err(e) = conn.execute(
-                "INSERT OR REPLACE INTO memories (id, content, vector, memory_type, importance, decay, access_count, timestamp, connections)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-                params![
-                    node.id,
-                    node.content,
-                    vector_bytes,
-                    format!("{:?}", node.memory_type),
-                    node.importance,
-                    node.decay,
-                    node.access_count,
-                    node.timestamp,
-                    connections_json,
-                ],
-            ) {
-                let _ = conn.execute_batch("ROLLBACK;");
-                return Err(format!("Failed to store memory: {}", e));
+        match restored {
+            Ok(reopened) => {
+                *conn = reopened;
+                let _ = std::fs::remove_file(&previous);
+                Ok(())
+            }
+            Err(e) => {
+                // Put the original file back and reopen it
+                let _ = std::fs::remove_file(&staged);
+                if previous.exists() {
+                    self.remove_wal_files();
+                    let _ = std::fs::rename(&previous, &self.db_path);
+                }
+                *conn = Self::open_initialized(&self.db_path)?;
+                Err(e)
             }
         }
+    }
 
-        conn.execute_batch("COMMIT;")
-            .map_err(|e| format!("Failed to commit: {}", e))?;
+    /// Open the database at `db_path` with its schema brought up to date
+    fn open_initialized(db_path: &Path) -> Result<Connection, String> {
+        let conn = open_connection(db_path)?;
+        Self::initialize_db(&conn)?;
+        Ok(conn)
+    }
+
+    /// Delete the WAL and shared-memory files next to a closed database
+    fn remove_wal_files(&self) {
+        for suffix in ["-wal", "-shm"] {
+            let _ = std::fs::remove_file(sidecar_path(&self.db_path, suffix));
+        }
+    }
+
+    // ---- Memory Persistence ----
+
+    /// Store a single memory
+    pub fn store_memory(&self, node: &MemoryNode) -> Result<(), String> {
+        let conn = self.connection();
+        let cipher = self.cipher.read();
+        let mut stmt = conn
+            .prepare(INSERT_MEMORY)
+            .map_err(|e| format!("Failed to prepare insert: {}", e))?;
+        insert_memory(&mut stmt, node, cipher.as_ref())
+            .map_err(|e| format!("Failed to store memory: {}", e))?;
 
         Ok(())
     }
 
-    /// Load all memories from database
+    /// Store multiple memories in a transaction
+    pub fn store_memories_batch(&self, nodes: &[MemoryNode]) -> Result<(), String> {
+        self.store_memories_with(|sink| nodes.iter().for_each(sink))
+            .map(|_| ())
+    }
+
+    /// Store memories in one transaction without collecting them first.
+    /// `source` is handed a sink and calls it once per node; rows are written
+    /// as they arrive. Returns the number of memories stored.
+    pub fn store_memories_with<F>(&self, source: F) -> Result<u32, String>
+    where
+        F: FnOnce(&mut dyn FnMut(&MemoryNode)),
+    {
+        self.transaction(|batch| batch.store_memories_with(source))
+    }
+
+    /// Load all memories from database. Encrypted memories that can't be
+    /// decrypted with the current cipher are skipped.
     pub fn load_memories(&self) -> Result<Vec<MemoryNode>, String> {
-        let conn = self.open_connection()?;
+        let conn = self.connection();
+        let cipher = self.cipher.read();
         let mut stmt = conn
-            .prepare("SELECT id, content, vector, memory_type, importance, decay, access_count, timestamp, connections FROM memories")
+            .prepare("SELECT id, content, vector, memory_type, importance, decay, access_count, timestamp, connections, last_accessed, tags, provider, encrypted FROM memories")
             .map_err(|e| format!("Failed to prepare query: {}", e))?;
 
-        let memories = stmt
+        let mut unreadable = 0u32;
+        let memories: Vec<MemoryNode> = stmt
             .query_map([], |row| {
+                let encrypted: bool = row.get(12)?;
                 let id: String = row.get(0)?;
-                let content: String = row.get(1)?;
-                let vector_bytes: Vec<u8> = row.get(2)?;
+                let content = open_text(cipher.as_ref(), row.get(1)?, encrypted)?;
+                let vector_bytes = open_bytes(cipher.as_ref(), row.get(2)?, encrypted)?;
                 let memory_type_str: String = row.get(3)?;
                 let importance: f64 = row.get(4)?;
                 let decay: f64 = row.get(5)?;
                 let access_count: u32 = row.get(6)?;
                 let timestamp: i64 = row.get(7)?;
                 let connections_json: String = row.get(8)?;
+                let last_accessed: i64 = row.get(9)?;
+                let tags_json: String = row.get(10)?;
+                let provider: String = row.get(11)?;
 
                 let vector = bytes_to_vector(&vector_bytes);
                 let memory_type = parse_memory_type_from_debug(&memory_type_str);
                 let connections: Vec<String> =
                     serde_json::from_str(&connections_json).unwrap_or_default();
+                let tags: Vec<String> = serde_json::from_str(&tags_json).unwrap_or_default();
 
                 Ok(MemoryNode {
                     id,
                     content,
-                    vector,
+                    vector: vector.into(),
                     memory_type,
                     importance,
                     decay,
                     access_count,
                     timestamp,
+                    last_accessed,
                     connections: SmallVec::from_vec(connections),
+                    tags,
+                    provider,
                 })
             })
             .map_err(|e| format!("Failed to query memories: {}", e))?
-            .filter_map(|r| r.ok())
+            .filter_map(|r| r.map_err(|_| unreadable += 1).ok())
             .collect();
 
+        if unreadable > 0 {
+            tracing::warn!("Skipped {} memories that could not be read", unreadable);
+        }
         Ok(memories)
     }
 
+    // ---- Encryption ----
+
+    /// Set the cipher used for new writes, without touching stored rows
+    pub fn set_cipher(&self, cipher: Option<Cipher>) {
+        *self.cipher.write() = cipher;
+    }
+
+    /// Switch encryption on (`Some`) or off (`None`) and rewrite every memory,
+    /// clipboard entry and thought to match in one transaction, then vacuum so
+    /// no stale plaintext pages stay in the file. Rows are read with the
+    /// current cipher first; if the rewrite fails, nothing is changed and the
+    /// current cipher stays in place. Returns the number of memories rewritten.
+    pub fn reencrypt(&self, cipher: Option<Cipher>) -> Result<u32, String> {
+        let memories = self.load_memories()?;
+        let clipboard = self.load_clipboard(usize::MAX)?;
+        let thoughts = self.load_thoughts(usize::MAX)?;
+        let previous = std::mem::replace(&mut *self.cipher.write(), cipher);
+
+        let rewritten = self.transaction(|batch| {
+            let rewritten = batch.store_memories_with(|sink| memories.iter().for_each(sink))?;
+            batch.store_clipboard(&clipboard)?;
+            batch.store_thoughts(&thoughts)?;
+            Ok(rewritten)
+        });
+        let rewritten = match rewritten {
+            Ok(rewritten) => rewritten,
+            Err(e) => {
+                *self.cipher.write() = previous;
+                return Err(e);
+            }
+        };
+
+        // The rows already match the new cipher, so a failed vacuum only
+        // leaves old pages behind until the next one
+        if let Err(e) = self.connection().execute_batch("VACUUM;") {
+            tracing::warn!("Failed to vacuum after re-encrypting: {}", e);
+        }
+        Ok(rewritten)
+    }
+
     /// Delete a memory by ID
     pub fn delete_memory(&self, id: &str) -> Result<(), String> {
-        let conn = self.open_connection()?;
+        let conn = self.connection();
         conn.execute("DELETE FROM memories WHERE id = ?1", params![id])
             .map_err(|e| format!("Failed to delete memory: {}", e))?;
         Ok(())
     }
 
+    /// Delete every memory of a type, returning the number of rows removed
+    pub fn delete_memories_by_type(&self, memory_type: MemoryType) -> Result<u32, String> {
+        let conn = self.connection();
+        let removed = conn
+            .execute(
+                "DELETE FROM memories WHERE memory_type = ?1",
+                params![format!("{:?}", memory_type)],
+            )
+            .map_err(|e| format!("Failed to delete memories: {}", e))?;
+        Ok(removed as u32)
+    }
+
     /// Get memory count
     pub fn memory_count(&self) -> Result<u32, String> {
-        let conn = self.open_connection()?;
+        let conn = self.connection();
         let count: u32 = conn
             .query_row("SELECT COUNT(*) FROM memories", [], |row| row.get(0))
             .map_err(|e| format!("Failed to count memories: {}", e))?;
@@ -242,36 +452,30 @@ impl BrainPersistence {
 
     // ---- Q-Table Persistence ----
 
-    /// Store Q-table entries
+    /// Replace the stored Q-table with `entries`
     pub fn store_q_table(&self, entries: &[(u64, Vec<f64>, u32)]) -> Result<(), String> {
-        let conn = self.open_connection()?;
-
-        conn.execute_batch("BEGIN TRANSACTION;")
-            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
-
-        for (state_hash, values, visits) in entries {
-            let values_json = serde_json::to_string(values).unwrap_or_else(|_| "[]".to_string());
+        self.transaction(|batch| batch.store_q_table(entries))
+    }
 
-            if let Err(e) = conn.execute(
-                "INSERT OR REPLACE INTO q_table (state_hash, values_json, visits) VALUES (?1, ?2, ?3)",
-                params![*state_hash as i64, values_json, *visits],
-            ) {
-                let _ = conn.execute_batch("ROLLBACK;");
-                return Err(format!("Failed to store Q-table entry: {}", e));
-            }
-        }
+    /// Load Q-table entries
+    pub fn load_q_table(&self) -> Result<Vec<(u64, Vec<f64>, u32)>, String> {
+        self.load_q_entries("q_table")
+    }
 
-        conn.execute_batch("COMMIT;")
-            .map_err(|e| format!("Failed to commit: {}", e))?;
+    /// Replace the stored secondary Double Q-learning table
+    pub fn store_q_table_b(&self, entries: &[(u64, Vec<f64>, u32)]) -> Result<(), String> {
+        self.transaction(|batch| batch.store_q_table_b(entries))
+    }
 
-        Ok(())
+    /// Load the secondary Double Q-learning table
+    pub fn load_q_table_b(&self) -> Result<Vec<(u64, Vec<f64>, u32)>, String> {
+        self.load_q_entries("q_table_b")
     }
 
-    /// Load Q-table entries
-    pub fn load_q_table(&self) -> Result<Vec<(u64, Vec<f64>, u32)>, String> {
-        let conn = self.open_connection()?;
+    fn load_q_entries(&self, table: &str) -> Result<Vec<(u64, Vec<f64>, u32)>, String> {
+        let conn = self.connection();
         let mut stmt = conn
-            .prepare("SELECT state_hash, values_json, visits FROM q_table")
+            .prepare(&format!("SELECT state_hash, values_json, visits FROM {}", table))
             .map_err(|e| format!("Failed to prepare query: {}", e))?;
 
         let entries = stmt
@@ -291,22 +495,224 @@ impl BrainPersistence {
         Ok(entries)
     }
 
-    // ---- Config Persistence ----
+    // ---- Experience Persistence ----
 
-    /// Store a config value
-    pub fn store_config(&self, key: &str, value: &str) -> Result<(), String> {
-        let conn = self.open_connection()?;
+    /// Replace the stored replay buffer with `experiences`
+    pub fn store_experiences(&self, experiences: &[Experience]) -> Result<(), String> {
+        self.transaction(|batch| batch.store_experiences(experiences))
+    }
+
+    /// Load up to `limit` of the most recent experiences, oldest first
+    pub fn load_experiences(&self, limit: usize) -> Result<Vec<Experience>, String> {
+        let conn = self.connection();
+        let mut stmt = conn
+            .prepare(
+                "SELECT state_json, action, reward, next_state_json, done FROM (
+                     SELECT * FROM experiences ORDER BY id DESC LIMIT ?1
+                 ) ORDER BY id ASC",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let experiences = stmt
+            .query_map(params![limit as i64], |row| {
+                let state_json: String = row.get(0)?;
+                let next_state_json: String = row.get(3)?;
+                Ok(Experience {
+                    state: serde_json::from_str(&state_json).unwrap_or_default(),
+                    action: row.get(1)?,
+                    reward: row.get(2)?,
+                    next_state: serde_json::from_str(&next_state_json).unwrap_or_default(),
+                    done: row.get(4)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query experiences: {}", e))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(experiences)
+    }
+
+    // ---- Goal Persistence ----
+
+    /// Insert or update a goal
+    pub fn store_goal(&self, goal: &Goal) -> Result<(), String> {
+        let conn = self.connection();
+        conn.execute(
+            "INSERT OR REPLACE INTO goals (id, description, priority, progress, status, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                goal.id,
+                goal.description,
+                goal.priority,
+                goal.progress,
+                format!("{:?}", goal.status),
+                goal.created_at
+            ],
+        )
+        .map_err(|e| format!("Failed to store goal: {}", e))?;
+        Ok(())
+    }
+
+    /// Load all goals, oldest first
+    pub fn load_goals(&self) -> Result<Vec<Goal>, String> {
+        let conn = self.connection();
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, description, priority, progress, status, created_at
+                 FROM goals ORDER BY created_at ASC",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let goals = stmt
+            .query_map([], |row| {
+                let status: String = row.get(4)?;
+                Ok(Goal {
+                    id: row.get(0)?,
+                    description: row.get(1)?,
+                    priority: row.get(2)?,
+                    progress: row.get(3)?,
+                    status: parse_goal_status(&status),
+                    created_at: row.get(5)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query goals: {}", e))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(goals)
+    }
+
+    // ---- Belief Persistence ----
+
+    /// Insert or update a belief
+    pub fn store_belief(&self, belief: &Belief) -> Result<(), String> {
+        let conn = self.connection();
         conn.execute(
-            "INSERT OR REPLACE INTO config (key, value) VALUES (?1, ?2)",
-            params![key, value],
+            "INSERT OR REPLACE INTO beliefs (id, content, confidence, source, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                belief.id,
+                belief.content,
+                belief.confidence,
+                belief.source,
+                belief.timestamp
+            ],
         )
-        .map_err(|e| format!("Failed to store config: {}", e))?;
+        .map_err(|e| format!("Failed to store belief: {}", e))?;
         Ok(())
     }
 
+    /// Load all beliefs, oldest first
+    pub fn load_beliefs(&self) -> Result<Vec<Belief>, String> {
+        let conn = self.connection();
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, content, confidence, source, timestamp
+                 FROM beliefs ORDER BY timestamp ASC",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let beliefs = stmt
+            .query_map([], |row| {
+                Ok(Belief {
+                    id: row.get(0)?,
+                    content: row.get(1)?,
+                    confidence: row.get(2)?,
+                    source: row.get(3)?,
+                    timestamp: row.get(4)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query beliefs: {}", e))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(beliefs)
+    }
+
+    // ---- Thought Persistence ----
+
+    /// Replace the saved thought stream with `thoughts`
+    pub fn store_thoughts(&self, thoughts: &[Thought]) -> Result<(), String> {
+        self.transaction(|batch| batch.store_thoughts(thoughts))
+    }
+
+    /// Load up to `limit` of the most recent thoughts, oldest first.
+    /// Encrypted thoughts that can't be decrypted are skipped.
+    pub fn load_thoughts(&self, limit: usize) -> Result<Vec<Thought>, String> {
+        let conn = self.connection();
+        let cipher = self.cipher.read();
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, content, thought_type, confidence, novelty, utility, timestamp, encrypted
+                 FROM thoughts ORDER BY timestamp DESC, rowid DESC LIMIT ?1",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let mut thoughts: Vec<Thought> = stmt
+            .query_map(params![limit], |row| {
+                Ok(Thought {
+                    id: row.get(0)?,
+                    content: open_text(cipher.as_ref(), row.get(1)?, row.get(7)?)?,
+                    thought_type: row.get(2)?,
+                    confidence: row.get(3)?,
+                    novelty: row.get(4)?,
+                    utility: row.get(5)?,
+                    timestamp: row.get(6)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query thoughts: {}", e))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        thoughts.reverse();
+        Ok(thoughts)
+    }
+
+    // ---- Clipboard Persistence ----
+
+    /// Replace the saved clipboard history with `entries`
+    pub fn store_clipboard(&self, entries: &[ClipboardEntry]) -> Result<(), String> {
+        self.transaction(|batch| batch.store_clipboard(entries))
+    }
+
+    /// Load up to `limit` of the most recent clipboard entries, newest first.
+    /// Encrypted entries that can't be decrypted are skipped.
+    pub fn load_clipboard(&self, limit: usize) -> Result<Vec<ClipboardEntry>, String> {
+        let conn = self.connection();
+        let cipher = self.cipher.read();
+        let mut stmt = conn
+            .prepare(
+                "SELECT content, timestamp, encrypted FROM clipboard
+                 ORDER BY timestamp DESC, id ASC LIMIT ?1",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let entries = stmt
+            .query_map(params![limit], |row| {
+                Ok(ClipboardEntry {
+                    content: open_text(cipher.as_ref(), row.get(0)?, row.get(2)?)?,
+                    timestamp: row.get(1)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query clipboard: {}", e))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(entries)
+    }
+
+    // ---- Config Persistence ----
+
+    /// Store a config value
+    pub fn store_config(&self, key: &str, value: &str) -> Result<(), String> {
+        self.transaction(|batch| batch.store_config(key, value))
+    }
+
     /// Load a config value
     pub fn load_config(&self, key: &str) -> Result<Option<String>, String> {
-        let conn = self.open_connection()?;
+        let conn = self.connection();
         let result = conn.query_row(
             "SELECT value FROM config WHERE key = ?1",
             params![key],
@@ -320,6 +726,20 @@ impl BrainPersistence {
         }
     }
 
+    /// Store the learner's rolling reward window
+    pub fn store_recent_rewards(&self, rewards: &[f64]) -> Result<(), String> {
+        self.transaction(|batch| batch.store_recent_rewards(rewards))
+    }
+
+    /// Load the learner's rolling reward window (empty if never saved)
+    pub fn load_recent_rewards(&self) -> Result<Vec<f64>, String> {
+        match self.load_config("recent_rewards")? {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| format!("Failed to parse rewards: {}", e)),
+            None => Ok(Vec::new()),
+        }
+    }
+
     /// Get database path
     pub fn db_path(&self) -> &PathBuf {
         &self.db_path
@@ -328,6 +748,428 @@ impl BrainPersistence {
 
 // ---- Helper Functions ----
 
+/// Writes made inside `BrainPersistence::transaction`. Nothing is visible
+/// to other connections until the whole transaction commits.
+pub struct WriteBatch<'a> {
+    conn: &'a Connection,
+    cipher: Option<&'a Cipher>,
+}
+
+impl WriteBatch<'_> {
+    /// Store memories streamed from `source`, as in
+    /// `BrainPersistence::store_memories_with`
+    pub fn store_memories_with<F>(&self, source: F) -> Result<u32, String>
+    where
+        F: FnOnce(&mut dyn FnMut(&MemoryNode)),
+    {
+        let mut stmt = self
+            .conn
+            .prepare(INSERT_MEMORY)
+            .map_err(|e| format!("Failed to prepare insert: {}", e))?;
+
+        let mut stored = 0u32;
+        let mut error: Option<String> = None;
+        let mut sink = |node: &MemoryNode| {
+            if error.is_some() {
+                return;
+            }
+            match insert_memory(&mut stmt, node, self.cipher) {
+                Ok(_) => stored += 1,
+                Err(e) => error = Some(format!("Failed to store memory: {}", e)),
+            }
+        };
+        source(&mut sink);
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(stored),
+        }
+    }
+
+    /// Delete the rows of the given memories, returning how many were found
+    pub fn delete_memories(&self, ids: &[String]) -> Result<u32, String> {
+        let mut stmt = self
+            .conn
+            .prepare("DELETE FROM memories WHERE id = ?1")
+            .map_err(|e| format!("Failed to prepare delete: {}", e))?;
+        let mut deleted = 0u32;
+        for id in ids {
+            deleted += stmt
+                .execute(params![id])
+                .map_err(|e| format!("Failed to delete memory: {}", e))? as u32;
+        }
+        Ok(deleted)
+    }
+
+    /// Replace the stored Q-table with `entries`
+    pub fn store_q_table(&self, entries: &[(u64, Vec<f64>, u32)]) -> Result<(), String> {
+        self.store_q_entries("q_table", entries)
+    }
+
+    /// Replace the stored secondary Double Q-learning table
+    pub fn store_q_table_b(&self, entries: &[(u64, Vec<f64>, u32)]) -> Result<(), String> {
+        self.store_q_entries("q_table_b", entries)
+    }
+
+    fn store_q_entries(&self, table: &str, entries: &[(u64, Vec<f64>, u32)]) -> Result<(), String> {
+        // Full replace so pruned states don't linger on disk
+        self.conn
+            .execute(&format!("DELETE FROM {}", table), [])
+            .map_err(|e| format!("Failed to clear Q-table: {}", e))?;
+
+        let sql = format!(
+            "INSERT OR REPLACE INTO {} (state_hash, values_json, visits) VALUES (?1, ?2, ?3)",
+            table
+        );
+        for (state_hash, values, visits) in entries {
+            let values_json = serde_json::to_string(values).unwrap_or_else(|_| "[]".to_string());
+            self.conn
+                .execute(&sql, params![*state_hash as i64, values_json, *visits])
+                .map_err(|e| format!("Failed to store Q-table entry: {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Replace the stored replay buffer with `experiences`
+    pub fn store_experiences(&self, experiences: &[Experience]) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM experiences", [])
+            .map_err(|e| format!("Failed to clear experiences: {}", e))?;
+
+        let now = now_millis();
+        for exp in experiences {
+            let state_json = serde_json::to_string(&exp.state).unwrap_or_else(|_| "[]".to_string());
+            let next_state_json =
+                serde_json::to_string(&exp.next_state).unwrap_or_else(|_| "[]".to_string());
+
+            self.conn
+                .execute(
+                    "INSERT INTO experiences (state_json, action, reward, next_state_json, done, timestamp)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![state_json, exp.action, exp.reward, next_state_json, exp.done, now],
+                )
+                .map_err(|e| format!("Failed to store experience: {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Replace the saved thought stream with `thoughts`
+    pub fn store_thoughts(&self, thoughts: &[Thought]) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM thoughts", [])
+            .map_err(|e| format!("Failed to clear thoughts: {}", e))?;
+
+        for thought in thoughts {
+            seal_text(self.cipher, &thought.content)
+                .and_then(|content| {
+                    self.conn.execute(
+                        "INSERT OR REPLACE INTO thoughts
+                         (id, content, thought_type, confidence, novelty, utility, timestamp, encrypted)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                        params![
+                            thought.id,
+                            content,
+                            thought.thought_type,
+                            thought.confidence,
+                            thought.novelty,
+                            thought.utility,
+                            thought.timestamp,
+                            self.cipher.is_some()
+                        ],
+                    )
+                })
+                .map_err(|e| format!("Failed to store thought: {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Replace the saved clipboard history with `entries`
+    pub fn store_clipboard(&self, entries: &[ClipboardEntry]) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM clipboard", [])
+            .map_err(|e| format!("Failed to clear clipboard: {}", e))?;
+
+        for entry in entries {
+            seal_text(self.cipher, &entry.content)
+                .and_then(|content| {
+                    self.conn.execute(
+                        "INSERT INTO clipboard (content, timestamp, encrypted) VALUES (?1, ?2, ?3)",
+                        params![content, entry.timestamp, self.cipher.is_some()],
+                    )
+                })
+                .map_err(|e| format!("Failed to store clipboard entry: {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Store a config value
+    pub fn store_config(&self, key: &str, value: &str) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO config (key, value) VALUES (?1, ?2)",
+                params![key, value],
+            )
+            .map_err(|e| format!("Failed to store config: {}", e))?;
+        Ok(())
+    }
+
+    /// Store the learner's rolling reward window
+    pub fn store_recent_rewards(&self, rewards: &[f64]) -> Result<(), String> {
+        let json = serde_json::to_string(rewards)
+            .map_err(|e| format!("Failed to serialize rewards: {}", e))?;
+        self.store_config("recent_rewards", &json)
+    }
+}
+
+const INSERT_MEMORY: &str =
+    "INSERT OR REPLACE INTO memories (id, content, vector, memory_type, importance, decay, access_count, timestamp, connections, last_accessed, tags, provider, encrypted)
+     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)";
+
+/// Run a prepared `INSERT_MEMORY` for one node, encrypting content and
+/// vector when `cipher` is set
+fn insert_memory(
+    stmt: &mut Statement<'_>,
+    node: &MemoryNode,
+    cipher: Option<&Cipher>,
+) -> rusqlite::Result<usize> {
+    let content = seal_text(cipher, &node.content)?;
+    let vector_bytes = seal_bytes(cipher, vector_to_bytes(&node.vector.as_f32()))?;
+    let connections_json =
+        serde_json::to_string(&node.connections.to_vec()).unwrap_or_else(|_| "[]".to_string());
+    let tags_json = serde_json::to_string(&node.tags).unwrap_or_else(|_| "[]".to_string());
+
+    stmt.execute(params![
+        node.id,
+        content,
+        vector_bytes,
+        format!("{:?}", node.memory_type),
+        node.importance,
+        node.decay,
+        node.access_count,
+        node.timestamp,
+        connections_json,
+        node.last_accessed,
+        tags_json,
+        node.provider,
+        cipher.is_some(),
+    ])
+}
+
+/// Column value for `text`: sealed bytes under `cipher`, else the text itself
+fn seal_text(cipher: Option<&Cipher>, text: &str) -> rusqlite::Result<Value> {
+    match cipher {
+        Some(cipher) => Ok(Value::Blob(
+            cipher.seal(text.as_bytes()).map_err(seal_error)?,
+        )),
+        None => Ok(Value::Text(text.to_string())),
+    }
+}
+
+fn seal_bytes(cipher: Option<&Cipher>, bytes: Vec<u8>) -> rusqlite::Result<Vec<u8>> {
+    match cipher {
+        Some(cipher) => cipher.seal(&bytes).map_err(seal_error),
+        None => Ok(bytes),
+    }
+}
+
+/// Read back a `seal_text` value; encrypted rows need the cipher
+fn open_text(cipher: Option<&Cipher>, value: Value, encrypted: bool) -> rusqlite::Result<String> {
+    match value {
+        Value::Text(text) if !encrypted => Ok(text),
+        Value::Blob(sealed) if encrypted => {
+            let bytes = open_bytes(cipher, sealed, true)?;
+            String::from_utf8(bytes).map_err(|e| open_error(e.to_string()))
+        }
+        _ => Err(open_error("Unexpected column type".to_string())),
+    }
+}
+
+fn open_bytes(
+    cipher: Option<&Cipher>,
+    bytes: Vec<u8>,
+    encrypted: bool,
+) -> rusqlite::Result<Vec<u8>> {
+    if !encrypted {
+        return Ok(bytes);
+    }
+    let cipher = cipher.ok_or_else(|| open_error("Database key unavailable".to_string()))?;
+    cipher.open(&bytes).map_err(open_error)
+}
+
+fn seal_error(e: String) -> rusqlite::Error {
+    rusqlite::Error::ToSqlConversionFailure(e.into())
+}
+
+fn open_error(e: String) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Blob, e.into())
+}
+
+/// Tables a file must have to be accepted as a backup
+const BACKUP_TABLES: &[&str] = &["memories", "q_table", "config"];
+
+/// On-disk size of a database in bytes before and after `compact`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactStats {
+    pub before_bytes: u64,
+    pub after_bytes: u64,
+}
+
+/// Checkpoint and vacuum the database at `db_path` through `conn`.
+/// VACUUM goes through the WAL too, so it is checkpointed again afterwards.
+pub fn compact_database(conn: &Connection, db_path: &Path) -> Result<CompactStats, String> {
+    let before_bytes = database_size(db_path);
+    conn.execute_batch(
+        "PRAGMA wal_checkpoint(TRUNCATE);
+         VACUUM;
+         PRAGMA wal_checkpoint(TRUNCATE);",
+    )
+    .map_err(|e| format!("Failed to compact database: {}", e))?;
+    Ok(CompactStats {
+        before_bytes,
+        after_bytes: database_size(db_path),
+    })
+}
+
+/// Size of the database at `db_path` plus its WAL, in bytes
+pub fn database_size(db_path: &Path) -> u64 {
+    [db_path.to_path_buf(), sidecar_path(db_path, "-wal")]
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|meta| meta.len())
+        .sum()
+}
+
+/// `db_path` with `suffix` appended, e.g. the `-wal` file next to it
+fn sidecar_path(db_path: &Path, suffix: &str) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Open `db_path` and apply the connection-level pragmas
+fn open_connection(db_path: &Path) -> Result<Connection, String> {
+    let conn =
+        Connection::open(db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    // Enable WAL mode for better concurrent read performance
+    conn.execute_batch("PRAGMA journal_mode=WAL;")
+        .map_err(|e| format!("Failed to set WAL mode: {}", e))?;
+    Ok(conn)
+}
+
+/// A schema change applied once to databases older than its version
+type Migration = fn(&Connection) -> Result<(), String>;
+
+/// Migrations in order: entry `i` upgrades a database from version `i` to
+/// `i + 1`. Append new ones; never reorder or edit shipped entries.
+const MIGRATIONS: &[Migration] = &[
+    migrate_last_accessed,
+    migrate_tags,
+    migrate_provider,
+    migrate_encrypted_flag,
+];
+
+/// Schema version of databases this build creates and expects
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Apply every migration newer than the database's recorded version, each in
+/// its own transaction together with the version bump
+fn run_migrations(conn: &Connection) -> Result<(), String> {
+    let current = schema_version(conn)?;
+    for (version, migrate) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+        conn.execute_batch("BEGIN TRANSACTION;")
+            .map_err(|e| format!("Failed to begin migration: {}", e))?;
+        let result = migrate(conn).and_then(|_| {
+            conn.execute("DELETE FROM schema_version", [])
+                .and_then(|_| {
+                    conn.execute(
+                        "INSERT INTO schema_version (version) VALUES (?1)",
+                        params![version as u32 + 1],
+                    )
+                })
+                .map_err(|e| format!("Failed to record schema version: {}", e))
+        });
+        if let Err(e) = result {
+            let _ = conn.execute_batch("ROLLBACK;");
+            return Err(format!("Migration to schema version {} failed: {}", version + 1, e));
+        }
+        conn.execute_batch("COMMIT;")
+            .map_err(|e| format!("Failed to commit migration: {}", e))?;
+        tracing::info!("Migrated database to schema version {}", version + 1);
+    }
+    Ok(())
+}
+
+/// Recorded schema version; 0 for databases that predate versioning
+fn schema_version(conn: &Connection) -> Result<u32, String> {
+    conn.query_row("SELECT MAX(version) FROM schema_version", [], |row| {
+        row.get::<_, Option<u32>>(0)
+    })
+    .map(|v| v.unwrap_or(0))
+    .map_err(|e| format!("Failed to read schema version: {}", e))
+}
+
+/// v1: track when memories were last recalled, backfilled with creation time
+fn migrate_last_accessed(conn: &Connection) -> Result<(), String> {
+    let definition = "INTEGER NOT NULL DEFAULT 0";
+    if add_column_if_missing(conn, "memories", "last_accessed", definition)? {
+        conn.execute("UPDATE memories SET last_accessed = timestamp", [])
+            .map_err(|e| format!("Failed to backfill last_accessed: {}", e))?;
+    }
+    Ok(())
+}
+
+/// v2: free-form memory tags
+fn migrate_tags(conn: &Connection) -> Result<(), String> {
+    add_column_if_missing(conn, "memories", "tags", "TEXT NOT NULL DEFAULT '[]'")?;
+    Ok(())
+}
+
+/// v3: embedding provider per memory; older memories stay unknown ('')
+fn migrate_provider(conn: &Connection) -> Result<(), String> {
+    add_column_if_missing(conn, "memories", "provider", "TEXT NOT NULL DEFAULT ''")?;
+    Ok(())
+}
+
+/// v4: per-row flag marking content encrypted at rest
+fn migrate_encrypted_flag(conn: &Connection) -> Result<(), String> {
+    let definition = "INTEGER NOT NULL DEFAULT 0";
+    add_column_if_missing(conn, "memories", "encrypted", definition)?;
+    add_column_if_missing(conn, "clipboard", "encrypted", definition)?;
+    Ok(())
+}
+
+/// Add a column to an existing table unless it is already there.
+/// Returns true if the column was added.
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<bool, String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| format!("Failed to read schema: {}", e))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| format!("Failed to read schema: {}", e))?
+        .filter_map(|r| r.ok())
+        .any(|name| name == column);
+    if exists {
+        return Ok(false);
+    }
+
+    conn.execute(
+        &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+        [],
+    )
+    .map_err(|e| format!("Failed to add column {}.{}: {}", table, column, e))?;
+    Ok(true)
+}
+
 fn vector_to_bytes(vector: &[f32]) -> Vec<u8> {
     let mut bytes = Vec::with_capacity(vector.len() * 4);
     for &val in vector {
@@ -357,6 +1199,15 @@ fn parse_memory_type_from_debug(s: &str) -> MemoryType {
     }
 }
 
+fn parse_goal_status(s: &str) -> GoalStatus {
+    match s {
+        "Active" => GoalStatus::Active,
+        "Completed" => GoalStatus::Completed,
+        "Failed" => GoalStatus::Failed,
+        _ => GoalStatus::Pending,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -375,13 +1226,16 @@ mod tests {
         let node = MemoryNode {
             id: "test-1".to_string(),
             content: "Hello world".to_string(),
-            vector: vec![0.1, 0.2, 0.3, 0.4],
+            vector: vec![0.1, 0.2, 0.3, 0.4].into(),
             memory_type: MemoryType::Semantic,
             importance: 0.8,
             decay: 0.0,
             access_count: 0,
             timestamp: 1000,
+            last_accessed: 1500,
             connections: SmallVec::new(),
+            tags: vec!["work".to_string()],
+            provider: "Ollama:nomic-embed-text".to_string(),
         };
 
         p.store_memory(&node).unwrap();
@@ -390,13 +1244,141 @@ mod tests {
         assert_eq!(loaded.len(), 1);
         assert_eq!(loaded[0].id, "test-1");
         assert_eq!(loaded[0].content, "Hello world");
-        assert!((loaded[0].vector[0] - 0.1).abs() < 1e-6);
+        assert!((loaded[0].vector.as_f32()[0] - 0.1).abs() < 1e-6);
         assert!((loaded[0].importance - 0.8).abs() < 1e-6);
+        assert_eq!(loaded[0].last_accessed, 1500);
+        assert_eq!(loaded[0].tags, vec!["work".to_string()]);
+        assert_eq!(loaded[0].provider, "Ollama:nomic-embed-text");
 
         // Cleanup
         let _ = std::fs::remove_file(p.db_path());
     }
 
+    #[test]
+    fn test_migrates_last_accessed_column() {
+        let path = std::env::temp_dir().join(format!("superbrain_test_{}.db", uuid::Uuid::new_v4()));
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE memories (
+                    id TEXT PRIMARY KEY,
+                    content TEXT NOT NULL,
+                    vector BLOB NOT NULL,
+                    memory_type TEXT NOT NULL,
+                    importance REAL NOT NULL DEFAULT 0.5,
+                    decay REAL NOT NULL DEFAULT 0.0,
+                    access_count INTEGER NOT NULL DEFAULT 0,
+                    timestamp INTEGER NOT NULL,
+                    connections TEXT NOT NULL DEFAULT '[]'
+                );",
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO memories (id, content, vector, memory_type, timestamp)
+                 VALUES ('old', 'Old memory', ?1, 'Semantic', 4242)",
+                params![vector_to_bytes(&[1.0, 0.0])],
+            )
+            .unwrap();
+        }
+
+        let p = BrainPersistence::with_path(path).unwrap();
+        let loaded = p.load_memories().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].content, "Old memory");
+        assert_eq!(loaded[0].vector.to_f32(), vec![1.0, 0.0]);
+        assert_eq!(loaded[0].last_accessed, 4242);
+        assert_eq!(loaded[0].tags, Vec::<String>::new());
+        assert_eq!(loaded[0].provider, "");
+
+        let conn = p.connection();
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+
+        // Reopening doesn't rerun migrations: the backfill would reset this
+        conn.execute("UPDATE memories SET last_accessed = 9000", []).unwrap();
+        let p = BrainPersistence::with_path(p.db_path().clone()).unwrap();
+        assert_eq!(p.load_memories().unwrap()[0].last_accessed, 9000);
+        let conn = p.connection();
+        let rows: u32 = conn
+            .query_row("SELECT COUNT(*) FROM schema_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 1);
+
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
+    #[test]
+    fn test_new_database_is_at_current_schema_version() {
+        let p = temp_persistence();
+        let conn = p.connection();
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
+    fn numbered_node(i: i64) -> MemoryNode {
+        MemoryNode {
+            id: format!("mem-{}", i),
+            content: format!("Memory number {}", i),
+            vector: vec![0.5; 384].into(),
+            memory_type: MemoryType::Semantic,
+            importance: 0.5,
+            decay: 0.0,
+            access_count: 0,
+            timestamp: i,
+            last_accessed: i,
+            connections: SmallVec::new(),
+            tags: Vec::new(),
+            provider: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_sequential_stores_share_one_connection() {
+        let p = temp_persistence();
+        for i in 0..1_000 {
+            p.store_memory(&numbered_node(i)).unwrap();
+        }
+        assert_eq!(p.memory_count().unwrap(), 1_000);
+
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
+    /// Run with `cargo test --release -- --ignored bench_store_memory --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_store_memory_shared_vs_fresh_connection() {
+        use std::time::Instant;
+        let rounds = 1_000;
+
+        // The old behavior: open (and configure) a connection per call
+        let fresh = temp_persistence();
+        let start = Instant::now();
+        for i in 0..rounds {
+            let conn = open_connection(fresh.db_path()).unwrap();
+            let mut stmt = conn.prepare(INSERT_MEMORY).unwrap();
+            insert_memory(&mut stmt, &numbered_node(i), None).unwrap();
+        }
+        let per_call = start.elapsed();
+
+        let shared = temp_persistence();
+        let start = Instant::now();
+        for i in 0..rounds {
+            shared.store_memory(&numbered_node(i)).unwrap();
+        }
+        let reused = start.elapsed();
+
+        println!(
+            "{} store_memory calls: fresh connection {:?}, shared {:?} ({:.1}x)",
+            rounds,
+            per_call,
+            reused,
+            per_call.as_secs_f64() / reused.as_secs_f64()
+        );
+
+        let _ = std::fs::remove_file(fresh.db_path());
+        let _ = std::fs::remove_file(shared.db_path());
+    }
+
     #[test]
     fn test_batch_store_and_count() {
         let p = temp_persistence();
@@ -405,13 +1387,16 @@ mod tests {
             .map(|i| MemoryNode {
                 id: format!("mem-{}", i),
                 content: format!("Memory number {}", i),
-                vector: vec![i as f32 / 100.0; 4],
+                vector: vec![i as f32 / 100.0; 4].into(),
                 memory_type: MemoryType::Episodic,
                 importance: 0.5,
                 decay: 0.0,
                 access_count: 0,
                 timestamp: 1000 + i,
+                last_accessed: 1000 + i,
                 connections: SmallVec::new(),
+                tags: Vec::new(),
+                provider: String::new(),
             })
             .collect();
 
@@ -426,6 +1411,58 @@ mod tests {
         let _ = std::fs::remove_file(p.db_path());
     }
 
+    #[test]
+    fn test_compact_shrinks_file_after_deletes() {
+        let p = temp_persistence();
+        let nodes: Vec<MemoryNode> = (0..500).map(numbered_node).collect();
+        p.store_memories_batch(&nodes).unwrap();
+        for node in &nodes[10..] {
+            p.delete_memory(&node.id).unwrap();
+        }
+
+        let stats = p.compact().unwrap();
+        assert!(
+            stats.after_bytes * 4 < stats.before_bytes,
+            "{} -> {} bytes",
+            stats.before_bytes,
+            stats.after_bytes
+        );
+        assert_eq!(stats.after_bytes, database_size(p.db_path()));
+        assert_eq!(p.memory_count().unwrap(), 10);
+
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
+    #[test]
+    fn test_delete_memories_by_type() {
+        let p = temp_persistence();
+
+        let nodes: Vec<MemoryNode> = (0..10)
+            .map(|i| MemoryNode {
+                id: format!("mem-{}", i),
+                content: format!("Memory number {}", i),
+                vector: vec![0.5; 4].into(),
+                memory_type: if i % 2 == 0 { MemoryType::Working } else { MemoryType::Semantic },
+                importance: 0.5,
+                decay: 0.0,
+                access_count: 0,
+                timestamp: 1000 + i,
+                last_accessed: 1000 + i,
+                connections: SmallVec::new(),
+                tags: Vec::new(),
+                provider: String::new(),
+            })
+            .collect();
+        p.store_memories_batch(&nodes).unwrap();
+
+        assert_eq!(p.delete_memories_by_type(MemoryType::Working).unwrap(), 5);
+        let remaining = p.load_memories().unwrap();
+        assert_eq!(remaining.len(), 5);
+        assert!(remaining.iter().all(|n| n.memory_type == MemoryType::Semantic));
+
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
     #[test]
     fn test_q_table_round_trip() {
         let p = temp_persistence();
@@ -440,6 +1477,368 @@ mod tests {
         let loaded = p.load_q_table().unwrap();
         assert_eq!(loaded.len(), 2);
 
+        // The secondary table is stored separately
+        p.store_q_table_b(&entries[..1]).unwrap();
+        assert_eq!(p.load_q_table_b().unwrap().len(), 1);
+        assert_eq!(p.load_q_table().unwrap().len(), 2);
+
+        // Storing replaces, so pruned states disappear
+        p.store_q_table(&entries[1..]).unwrap();
+        let loaded = p.load_q_table().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].0, 67890);
+
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
+    #[test]
+    fn test_failed_transaction_writes_nothing() {
+        let p = temp_persistence();
+        p.store_memories_batch(&[numbered_node(0)]).unwrap();
+        p.store_config("theme", "dark").unwrap();
+
+        let result: Result<(), String> = p.transaction(|batch| {
+            batch.store_memories_with(|sink| (1..4).for_each(|i| sink(&numbered_node(i))))?;
+            batch.store_q_table(&[(1, vec![0.5], 1)])?;
+            batch.store_config("theme", "light")?;
+            Err("simulated failure".to_string())
+        });
+        assert_eq!(result.unwrap_err(), "simulated failure");
+
+        assert_eq!(p.memory_count().unwrap(), 1);
+        assert!(p.load_q_table().unwrap().is_empty());
+        assert_eq!(p.load_config("theme").unwrap().as_deref(), Some("dark"));
+
+        // The connection is usable again afterwards
+        p.store_config("theme", "light").unwrap();
+        assert_eq!(p.load_config("theme").unwrap().as_deref(), Some("light"));
+
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
+    #[test]
+    fn test_restore_that_cannot_open_keeps_original() {
+        let p = temp_persistence();
+        p.store_memories_batch(&[numbered_node(0), numbered_node(1)]).unwrap();
+
+        // A backup that validates but whose schema can't be brought up to date
+        let backup = temp_persistence();
+        backup.store_memories_batch(&[numbered_node(5)]).unwrap();
+        let backup_path = backup.db_path().to_path_buf();
+        drop(backup);
+        let raw = Connection::open(&backup_path).unwrap();
+        raw.execute_batch("DROP TABLE schema_version; CREATE TABLE schema_version (v INTEGER);")
+            .unwrap();
+        drop(raw);
+
+        assert!(p.restore_from(&backup_path).is_err());
+
+        // The original database is back in place and usable
+        assert_eq!(p.memory_count().unwrap(), 2);
+        p.store_memories_batch(&[numbered_node(2)]).unwrap();
+        assert_eq!(p.memory_count().unwrap(), 3);
+        assert!(!sidecar_path(p.db_path(), "-previous").exists());
+
+        let _ = std::fs::remove_file(&backup_path);
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
+    #[test]
+    fn test_recent_rewards_preserve_trend() {
+        use crate::brain::learning::NativeLearner;
+
+        let p = temp_persistence();
+        assert!(p.load_recent_rewards().unwrap().is_empty());
+
+        let learner = NativeLearner::new(2, 3);
+        learner.import_recent_rewards((0..150).map(|i| i as f64 / 100.0).collect());
+        let rewards = learner.export_recent_rewards();
+        assert_eq!(rewards.len(), 100);
+        assert!(learner.stats().trend > 0.0);
+
+        p.store_recent_rewards(&rewards).unwrap();
+
+        let restored = NativeLearner::new(2, 3);
+        restored.import_recent_rewards(p.load_recent_rewards().unwrap());
+        assert_eq!(restored.export_recent_rewards(), rewards);
+        assert_eq!(restored.stats().trend, learner.stats().trend);
+
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
+    #[test]
+    fn test_experience_round_trip() {
+        let p = temp_persistence();
+
+        let experiences: Vec<Experience> = (0..5)
+            .map(|i| Experience {
+                state: vec![i as f64, 0.5],
+                action: i % 3,
+                reward: i as f64 * 0.1,
+                next_state: vec![i as f64 + 1.0, 0.5],
+                done: i == 4,
+            })
+            .collect();
+
+        p.store_experiences(&experiences).unwrap();
+
+        let loaded = p.load_experiences(100).unwrap();
+        assert_eq!(loaded.len(), 5);
+        assert_eq!(loaded[2].state, vec![2.0, 0.5]);
+        assert_eq!(loaded[2].action, 2);
+        assert!((loaded[2].reward - 0.2).abs() < 1e-9);
+        assert!(loaded[4].done && !loaded[3].done);
+
+        // The limit keeps the newest entries, still in insertion order
+        let newest = p.load_experiences(2).unwrap();
+        assert_eq!(newest.len(), 2);
+        assert_eq!(newest[0].state, vec![3.0, 0.5]);
+        assert_eq!(newest[1].state, vec![4.0, 0.5]);
+
+        // Storing again replaces rather than appends
+        p.store_experiences(&experiences[..1]).unwrap();
+        assert_eq!(p.load_experiences(100).unwrap().len(), 1);
+
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
+    #[test]
+    fn test_goal_round_trip() {
+        let p = temp_persistence();
+        let mut goal = Goal {
+            id: "g1".to_string(),
+            description: "Learn Rust".to_string(),
+            priority: 0.7,
+            progress: 0.0,
+            status: GoalStatus::Pending,
+            created_at: 1_000,
+        };
+        p.store_goal(&goal).unwrap();
+
+        goal.progress = 0.5;
+        goal.status = GoalStatus::Active;
+        p.store_goal(&goal).unwrap();
+
+        let loaded = p.load_goals().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].description, "Learn Rust");
+        assert_eq!(loaded[0].priority, 0.7);
+        assert_eq!(loaded[0].progress, 0.5);
+        assert_eq!(loaded[0].status, GoalStatus::Active);
+        assert_eq!(loaded[0].created_at, 1_000);
+
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
+    #[test]
+    fn test_belief_round_trip() {
+        let p = temp_persistence();
+        let belief = Belief {
+            id: "b1".to_string(),
+            content: "Ana prefers tea".to_string(),
+            confidence: 0.6,
+            source: "conversation".to_string(),
+            timestamp: 2_000,
+        };
+        p.store_belief(&belief).unwrap();
+
+        let loaded = p.load_beliefs().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].id, "b1");
+        assert_eq!(loaded[0].content, "Ana prefers tea");
+        assert_eq!(loaded[0].confidence, 0.6);
+        assert_eq!(loaded[0].source, "conversation");
+        assert_eq!(loaded[0].timestamp, 2_000);
+
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
+    fn test_cipher() -> Cipher {
+        Cipher::from_hex(&Cipher::generate_key_hex().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_encrypted_database_needs_the_key() {
+        let key = Cipher::generate_key_hex().unwrap();
+        let p = temp_persistence();
+        p.set_cipher(Some(Cipher::from_hex(&key).unwrap()));
+        let mut node = numbered_node(7);
+        node.content = "Bank PIN hint: birthday".to_string();
+        p.store_memory(&node).unwrap();
+        p.store_clipboard(&[ClipboardEntry {
+            content: "private clipboard".to_string(),
+            timestamp: 1,
+        }])
+        .unwrap();
+
+        // Nothing readable is left in the stored row
+        let raw: Vec<u8> = p
+            .connection()
+            .query_row("SELECT content FROM memories", [], |row| row.get(0))
+            .unwrap();
+        assert!(!raw.windows(4).any(|w| w == b"Bank"));
+
+        // Without the key, or with the wrong one, nothing loads
+        let path = p.db_path().clone();
+        let locked = BrainPersistence::with_path(path.clone()).unwrap();
+        assert!(locked.load_memories().unwrap().is_empty());
+        assert!(locked.load_clipboard(10).unwrap().is_empty());
+        locked.set_cipher(Some(test_cipher()));
+        assert!(locked.load_memories().unwrap().is_empty());
+
+        // With the key everything round-trips
+        let unlocked = BrainPersistence::with_path(path).unwrap();
+        unlocked.set_cipher(Some(Cipher::from_hex(&key).unwrap()));
+        let loaded = unlocked.load_memories().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].content, "Bank PIN hint: birthday");
+        assert_eq!(loaded[0].vector.to_f32(), node.vector.to_f32());
+        assert_eq!(unlocked.load_clipboard(10).unwrap()[0].content, "private clipboard");
+
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
+    #[test]
+    fn test_reencrypt_existing_plaintext_database() {
+        let p = temp_persistence();
+        p.store_memories_batch(&[numbered_node(1), numbered_node(2)]).unwrap();
+        p.store_clipboard(&[ClipboardEntry {
+            content: "copied".to_string(),
+            timestamp: 1,
+        }])
+        .unwrap();
+
+        let key = Cipher::generate_key_hex().unwrap();
+        assert_eq!(p.reencrypt(Some(Cipher::from_hex(&key).unwrap())).unwrap(), 2);
+        let encrypted: u32 = p
+            .connection()
+            .query_row("SELECT COUNT(*) FROM memories WHERE encrypted = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(encrypted, 2);
+        assert!(BrainPersistence::with_path(p.db_path().clone())
+            .unwrap()
+            .load_memories()
+            .unwrap()
+            .is_empty());
+
+        // Turning encryption off decrypts everything again
+        assert_eq!(p.reencrypt(None).unwrap(), 2);
+        let plain = BrainPersistence::with_path(p.db_path().clone()).unwrap();
+        assert_eq!(plain.load_memories().unwrap().len(), 2);
+        assert_eq!(plain.load_clipboard(10).unwrap()[0].content, "copied");
+
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
+    #[test]
+    fn test_failed_reencrypt_keeps_original_cipher() {
+        let key = Cipher::generate_key_hex().unwrap();
+        let p = temp_persistence();
+        p.set_cipher(Some(Cipher::from_hex(&key).unwrap()));
+        p.store_memories_batch(&[numbered_node(1), numbered_node(2)]).unwrap();
+        p.store_thoughts(&[Thought {
+            id: "t1".to_string(),
+            content: "private thought".to_string(),
+            thought_type: "Reflection".to_string(),
+            confidence: 0.5,
+            novelty: 0.5,
+            utility: 0.5,
+            timestamp: 1,
+        }])
+        .unwrap();
+
+        // Thoughts are rewritten last, after memories and clipboard
+        p.connection()
+            .execute_batch(
+                "CREATE TRIGGER fail_thought BEFORE INSERT ON thoughts
+                 BEGIN SELECT RAISE(ABORT, 'disk full'); END;",
+            )
+            .unwrap();
+        assert!(p.reencrypt(None).is_err());
+
+        // Every row is still encrypted and still reads with the original key
+        let encrypted: u32 = p
+            .connection()
+            .query_row("SELECT COUNT(*) FROM memories WHERE encrypted = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(encrypted, 2);
+        assert_eq!(p.load_memories().unwrap().len(), 2);
+        assert_eq!(p.load_thoughts(10).unwrap()[0].content, "private thought");
+
+        let reopened = BrainPersistence::with_path(p.db_path().clone()).unwrap();
+        reopened.set_cipher(Some(Cipher::from_hex(&key).unwrap()));
+        assert_eq!(reopened.load_memories().unwrap().len(), 2);
+
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
+    #[test]
+    fn test_clipboard_round_trip() {
+        let p = temp_persistence();
+        let saved = [("newest", 3_000), ("middle", 2_000), ("oldest", 1_000)];
+        let entries: Vec<ClipboardEntry> = saved
+            .iter()
+            .map(|(content, timestamp)| ClipboardEntry {
+                content: content.to_string(),
+                timestamp: *timestamp,
+            })
+            .collect();
+        p.store_clipboard(&entries).unwrap();
+
+        let loaded = p.load_clipboard(10).unwrap();
+        let contents: Vec<&str> = loaded.iter().map(|e| e.content.as_str()).collect();
+        assert_eq!(contents, vec!["newest", "middle", "oldest"]);
+        assert_eq!(loaded[0].timestamp, 3_000);
+
+        // The limit keeps the newest entries
+        let loaded = p.load_clipboard(2).unwrap();
+        assert_eq!(loaded.last().unwrap().content, "middle");
+
+        // Storing again replaces rather than appends
+        p.store_clipboard(&entries[..1]).unwrap();
+        assert_eq!(p.load_clipboard(10).unwrap().len(), 1);
+
+        let _ = std::fs::remove_file(p.db_path());
+    }
+
+    #[test]
+    fn test_thought_round_trip() {
+        let p = temp_persistence();
+        p.set_cipher(Some(test_cipher()));
+        let thoughts: Vec<Thought> = (0..3)
+            .map(|i| Thought {
+                id: format!("t{}", i),
+                content: format!("thought {}", i),
+                thought_type: "Reflection".to_string(),
+                confidence: 0.5 + i as f64 / 10.0,
+                novelty: 0.5,
+                utility: 0.25,
+                timestamp: 1_000 + i,
+            })
+            .collect();
+        p.store_thoughts(&thoughts).unwrap();
+
+        let loaded = p.load_thoughts(10).unwrap();
+        let ids: Vec<&str> = loaded.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["t0", "t1", "t2"]);
+        assert_eq!(loaded[2].content, "thought 2");
+        assert_eq!(loaded[2].thought_type, "Reflection");
+        assert_eq!(loaded[2].confidence, 0.7);
+        assert_eq!(loaded[2].utility, 0.25);
+        assert_eq!(loaded[2].timestamp, 1_002);
+
+        // The limit keeps the newest thoughts, still oldest first
+        let ids: Vec<String> = p.load_thoughts(2).unwrap().into_iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec!["t1", "t2"]);
+
+        // Storing again replaces rather than appends
+        p.store_thoughts(&thoughts[..1]).unwrap();
+        assert_eq!(p.load_thoughts(10).unwrap().len(), 1);
+
         let _ = std::fs::remove_file(p.db_path());
     }
 
@@ -60,7 +60,9 @@ pub struct MemoryEntry {
     pub decay: f64,
     pub access_count: u32,
     pub timestamp: i64,
+    pub last_accessed: i64,
     pub connections: Vec<String>,
+    pub tags: Vec<String>,
 }
 
 /// Thought generated by cognitive processing
@@ -131,16 +133,33 @@ pub struct CognitiveStats {
     pub learning_trend: f64,
 }
 
+impl DistanceMetric {
+    /// Lowercase name, as accepted by `NativeMemory::set_metric`
+    pub fn name(self) -> &'static str {
+        match self {
+            DistanceMetric::Cosine => "cosine",
+            DistanceMetric::Euclidean => "euclidean",
+            DistanceMetric::DotProduct => "dotproduct",
+            DistanceMetric::Manhattan => "manhattan",
+        }
+    }
+}
+
 pub fn parse_memory_type(s: &str) -> MemoryType {
+    try_parse_memory_type(s).unwrap_or(MemoryType::Semantic)
+}
+
+/// Parse a memory type name, returning `None` for unknown names
+pub fn try_parse_memory_type(s: &str) -> Option<MemoryType> {
     match s.to_lowercase().as_str() {
-        "episodic" => MemoryType::Episodic,
-        "semantic" => MemoryType::Semantic,
-        "procedural" => MemoryType::Procedural,
-        "working" => MemoryType::Working,
-        "meta" => MemoryType::Meta,
-        "causal" => MemoryType::Causal,
-        "goal" => MemoryType::Goal,
-        "emotional" => MemoryType::Emotional,
-        _ => MemoryType::Semantic,
+        "episodic" => Some(MemoryType::Episodic),
+        "semantic" => Some(MemoryType::Semantic),
+        "procedural" => Some(MemoryType::Procedural),
+        "working" => Some(MemoryType::Working),
+        "meta" => Some(MemoryType::Meta),
+        "causal" => Some(MemoryType::Causal),
+        "goal" => Some(MemoryType::Goal),
+        "emotional" => Some(MemoryType::Emotional),
+        _ => None,
     }
 }
@@ -2,6 +2,8 @@
 
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use wide::f32x8;
+
 /// Get current timestamp in milliseconds
 pub fn now_millis() -> i64 {
     SystemTime::now()
@@ -15,6 +17,18 @@ pub fn generate_id() -> String {
     uuid::Uuid::new_v4().to_string()
 }
 
+/// Longest prefix of `s` that fits in `max_bytes` without splitting a character
+pub fn truncate_str(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
 /// Normalize a vector in-place
 #[inline]
 pub fn normalize_vector(vec: &mut [f32]) {
@@ -26,21 +40,67 @@ pub fn normalize_vector(vec: &mut [f32]) {
     }
 }
 
-/// Compute cosine similarity between two vectors
+/// Compute cosine similarity between two vectors.
+/// Processes 8 lanes at a time; any tail shorter than 8 falls back to scalar.
 #[inline]
 pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     debug_assert_eq!(a.len(), b.len());
 
+    let split = a.len().min(b.len()) / LANES * LANES;
+    let mut dot = f32x8::ZERO;
+    let mut norm_a = f32x8::ZERO;
+    let mut norm_b = f32x8::ZERO;
+
+    for (ca, cb) in a[..split].chunks_exact(LANES).zip(b[..split].chunks_exact(LANES)) {
+        let va = load(ca);
+        let vb = load(cb);
+        dot = va.mul_add(vb, dot);
+        norm_a = va.mul_add(va, norm_a);
+        norm_b = vb.mul_add(vb, norm_b);
+    }
+
+    let (tail_dot, tail_a, tail_b) = cosine_terms(&a[split..], &b[split..]);
+    finish_cosine(
+        dot.reduce_add() + tail_dot,
+        norm_a.reduce_add() + tail_a,
+        norm_b.reduce_add() + tail_b,
+    )
+}
+
+/// Scalar reference implementation of [`cosine_similarity`]
+pub fn cosine_similarity_scalar(a: &[f32], b: &[f32]) -> f32 {
+    debug_assert_eq!(a.len(), b.len());
+    let (dot, norm_a, norm_b) = cosine_terms(a, b);
+    finish_cosine(dot, norm_a, norm_b)
+}
+
+/// SIMD width used by the similarity kernels
+const LANES: usize = 8;
+
+#[inline]
+fn load(chunk: &[f32]) -> f32x8 {
+    let lanes: [f32; LANES] = chunk.try_into().unwrap();
+    f32x8::from(lanes)
+}
+
+/// Dot product and squared norms, one element at a time
+#[inline]
+fn cosine_terms(a: &[f32], b: &[f32]) -> (f32, f32, f32) {
     let mut dot = 0.0f32;
     let mut norm_a = 0.0f32;
     let mut norm_b = 0.0f32;
 
-    for i in 0..a.len() {
-        dot += a[i] * b[i];
-        norm_a += a[i] * a[i];
-        norm_b += b[i] * b[i];
+    for (x, y) in a.iter().zip(b.iter()) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
     }
 
+    (dot, norm_a, norm_b)
+}
+
+#[inline]
+fn finish_cosine(dot: f32, norm_a: f32, norm_b: f32) -> f32 {
     let denom = (norm_a * norm_b).sqrt();
     if denom > 1e-10 {
         dot / denom
@@ -62,11 +122,19 @@ pub fn euclidean_distance(a: &[f32], b: &[f32]) -> f32 {
     sum.sqrt()
 }
 
-/// Compute dot product of two vectors
+/// Compute dot product of two vectors (8 lanes at a time, scalar tail)
 #[inline]
 pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
     debug_assert_eq!(a.len(), b.len());
-    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+
+    let split = a.len().min(b.len()) / LANES * LANES;
+    let mut acc = f32x8::ZERO;
+    for (ca, cb) in a[..split].chunks_exact(LANES).zip(b[..split].chunks_exact(LANES)) {
+        acc = load(ca).mul_add(load(cb), acc);
+    }
+
+    let tail: f32 = a[split..].iter().zip(b[split..].iter()).map(|(x, y)| x * y).sum();
+    acc.reduce_add() + tail
 }
 
 /// Softmax function for probability distribution
@@ -95,6 +163,15 @@ pub fn hash_state(state: &[f32]) -> u64 {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_truncate_str_keeps_whole_characters() {
+        assert_eq!(truncate_str("hello", 10), "hello");
+        assert_eq!(truncate_str("hello", 3), "hel");
+        // "é" is two bytes; cutting through it drops the whole character
+        assert_eq!(truncate_str("café", 4), "caf");
+        assert_eq!(truncate_str("café", 5), "café");
+    }
+
     #[test]
     fn test_cosine_similarity() {
         let a = vec![1.0, 0.0, 0.0];
@@ -105,6 +182,70 @@ mod tests {
         assert!(cosine_similarity(&a, &c).abs() < 1e-6);
     }
 
+    fn random_pair(rng: &mut impl rand::Rng, dim: usize) -> (Vec<f32>, Vec<f32>) {
+        let a = (0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        let b = (0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        (a, b)
+    }
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+
+        // 384 is the embedding width; the others exercise the scalar tail
+        for dim in [1, 7, 8, 13, 100, 384, 385] {
+            for _ in 0..50 {
+                let (a, b) = random_pair(&mut rng, dim);
+
+                let simd = cosine_similarity(&a, &b);
+                let scalar = cosine_similarity_scalar(&a, &b);
+                assert!((simd - scalar).abs() < 1e-5, "dim {}: {} vs {}", dim, simd, scalar);
+
+                let dot_scalar: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+                assert!((dot_product(&a, &b) - dot_scalar).abs() < 1e-4);
+            }
+        }
+    }
+
+    /// Run with `cargo test --release -- --ignored bench_cosine --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_cosine_simd_vs_scalar() {
+        use rand::SeedableRng;
+        use std::hint::black_box;
+        use std::time::Instant;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+        let pairs: Vec<(Vec<f32>, Vec<f32>)> =
+            (0..1_000).map(|_| random_pair(&mut rng, 384)).collect();
+        let rounds = 1_000;
+
+        let start = Instant::now();
+        for _ in 0..rounds {
+            for (a, b) in &pairs {
+                black_box(cosine_similarity_scalar(black_box(a), black_box(b)));
+            }
+        }
+        let scalar = start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..rounds {
+            for (a, b) in &pairs {
+                black_box(cosine_similarity(black_box(a), black_box(b)));
+            }
+        }
+        let simd = start.elapsed();
+
+        println!(
+            "cosine 384-d x {}: scalar {:?}, simd {:?} ({:.1}x)",
+            pairs.len() * rounds,
+            scalar,
+            simd,
+            scalar.as_secs_f64() / simd.as_secs_f64()
+        );
+    }
+
     #[test]
     fn test_normalize() {
         let mut v = vec![3.0, 4.0];
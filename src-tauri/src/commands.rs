@@ -1,100 +1,252 @@
 //! Tauri IPC command handlers for SuperBrain
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use tauri::{Emitter, Manager, State};
 
-use crate::ai::AiProvider;
-use crate::state::{AppSettings, AppState, SystemStatus};
+use crate::ai::claude::ClaudeProvider;
+use crate::ai::{generate_with_fallback, StreamEvent};
+use crate::ai::usage::ProviderUsage;
+use crate::brain::cognitive::{Belief, Goal};
+use crate::brain::memory::{MemoryGraph, SearchOptions};
+use crate::brain::utils::truncate_str;
+use crate::state::{AppSettings, AppState, CompactReport, FlushStatus, SystemStatus};
 
 // ---- Think / Chat ----
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThinkResponse {
     pub response: String,
     pub confidence: f64,
     pub thought_id: String,
     pub memory_count: u32,
     pub ai_enhanced: bool,
+    /// AI provider that answered, or "memory" for a memory-only answer
+    pub provider: String,
+    /// Why the AI providers failed, when the answer fell back to memory only
+    pub ai_error: Option<String>,
+    /// The AI answer was stopped by `cancel_think`
+    pub cancelled: bool,
+    /// Indexed files given to the AI as context, for citing
+    pub sources: Vec<String>,
 }
 
+/// With `stream` set, AI output is emitted as `think-token` events while it
+/// is generated, and the final response as `think-done`. A `request_id` lets
+/// `cancel_think` stop the AI answer early.
 #[tauri::command]
-pub async fn think(input: String, app: tauri::AppHandle, state: State<'_, AppState>) -> Result<ThinkResponse, String> {
+pub async fn think(
+    input: String,
+    stream: Option<bool>,
+    conversation_id: Option<String>,
+    request_id: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ThinkResponse, String> {
+    let stream = stream.unwrap_or(false);
     crate::tray::set_status(&app, crate::tray::TrayStatus::Thinking);
     let embedding = state.embeddings.embed(&input).await?;
 
     // Get memory-based response and recall relevant memories
     let brain_result = state.engine.think_with_embedding(&input, &embedding)?;
-    let memories = state.engine.recall_f32(&embedding, Some(5), None).unwrap_or_default();
-
-    // Try AI-enhanced response if a provider is configured
-    let ai_provider_name = state.ai_provider.read().as_ref().map(|p| p.name().to_string());
-    if let Some(_provider_name) = ai_provider_name {
-        // Clone what we need, then drop the lock before awaiting
-        let ai_result = {
-            let provider_guard = state.ai_provider.read();
-            if let Some(ref provider) = *provider_guard {
-                // We need to drop the guard before awaiting, so check availability first
-                let provider_ref: &dyn crate::ai::AiProvider = provider.as_ref();
-                // Unfortunately we can't hold the guard across await, so we build
-                // a quick non-async check here and do the generate outside
-                Some(provider_ref.name().to_string())
-            } else {
-                None
+    let memories = state
+        .engine
+        .recall_f32(&embedding, Some(5), SearchOptions::default())
+        .unwrap_or_default();
+
+    // Try AI-enhanced response, walking the configured providers in order.
+    // Built from the same settings, so privacy mode applies here too
+    let settings = state.settings.read().clone();
+    let providers = AppState::build_ai_chain(&settings);
+    let mut ai_error = None;
+    let mut cancelled = false;
+    if !providers.is_empty() {
+        let history = conversation_id
+            .as_deref()
+            .map(|id| state.conversations.history(id))
+            .unwrap_or_default();
+
+        // Retrieval: the most relevant indexed file chunks join the memories
+        let files = state.file_context(&input, settings.file_context_chunks).await;
+        let mut sources: Vec<String> = Vec::new();
+        for file in &files {
+            if !sources.contains(&file.id) {
+                sources.push(file.id.clone());
             }
+        }
+        let mut context = memories;
+        context.extend(files);
+        let (tokens, forwarder) = if stream {
+            let (tokens, mut token_rx) = tokio::sync::mpsc::unbounded_channel::<StreamEvent>();
+            let token_handle = app.clone();
+            let forwarder = tauri::async_runtime::spawn(async move {
+                while let Some(event) = token_rx.recv().await {
+                    let _ = match event {
+                        StreamEvent::Token(token) => token_handle.emit("think-token", token),
+                        // A provider failed mid-answer; the next one starts over
+                        StreamEvent::Reset => token_handle.emit("think-reset", ()),
+                    };
+                }
+            });
+            (Some(tokens), Some(forwarder))
+        } else {
+            (None, None)
         };
 
-        if ai_result.is_some() {
-            // Re-acquire and generate (the provider is behind RwLock, can't hold across await)
-            // Instead, extract what we need to call the provider
-            let settings = state.settings.read().clone();
-            let ai_response = match settings.ai_provider.as_str() {
-                "ollama" => {
-                    let provider = crate::ai::ollama::OllamaProvider::new(&settings.ollama_model);
-                    provider.generate(&input, &memories).await
-                }
-                "claude" => {
-                    if let Some(ref key) = settings.claude_api_key {
-                        let provider = crate::ai::claude::ClaudeProvider::new(key);
-                        provider.generate(&input, &memories).await
-                    } else {
-                        Err("No Claude API key".to_string())
-                    }
+        let generation =
+            generate_with_fallback(&providers, &input, &context, &history, tokens.as_ref());
+        let ai_response = match request_id.as_deref() {
+            Some(id) => state.cancellations.run(id, generation).await,
+            None => Some(generation.await),
+        };
+        drop(tokens);
+        if let Some(forwarder) = forwarder {
+            let _ = forwarder.await;
+        }
+
+        match ai_response {
+            None => {
+                tracing::info!("AI answer cancelled, using memory only");
+                cancelled = true;
+            }
+            Some(Ok((provider, ai_resp))) => {
+                state.record_ai_usage(&provider, ai_resp.tokens_used);
+                if let Some(id) = conversation_id.as_deref() {
+                    state.conversations.record(id, &input, &ai_resp.content);
                 }
-                _ => Err("No AI provider".to_string()),
-            };
 
-            if let Ok(ai_resp) = ai_response {
                 // Store the AI interaction as an episodic memory
                 let _ = state.engine.remember_with_embedding(
-                    format!("Q: {} A: {}", input, &ai_resp.content[..ai_resp.content.len().min(200)]),
+                    format!("Q: {} A: {}", input, truncate_str(&ai_resp.content, 200)),
                     embedding,
                     "episodic".to_string(),
                     Some(0.5),
                 );
 
-                crate::tray::set_status(&app, crate::tray::TrayStatus::Idle);
-                return Ok(ThinkResponse {
-                    response: ai_resp.content,
-                    confidence: brain_result.confidence,
-                    thought_id: brain_result.thought_id,
-                    memory_count: brain_result.memory_count,
-                    ai_enhanced: true,
-                });
+                return Ok(finish_think(
+                    &app,
+                    stream,
+                    ThinkResponse {
+                        response: ai_resp.content,
+                        confidence: brain_result.confidence,
+                        thought_id: brain_result.thought_id,
+                        memory_count: brain_result.memory_count,
+                        ai_enhanced: true,
+                        provider,
+                        ai_error: None,
+                        cancelled: false,
+                        sources,
+                    },
+                ));
+            }
+            Some(Err(e)) => {
+                tracing::warn!("No AI provider answered, using memory only: {}", e);
+                ai_error = Some(e);
             }
         }
     }
 
     // Fallback: memory-only response
-    crate::tray::set_status(&app, crate::tray::TrayStatus::Idle);
-    Ok(ThinkResponse {
-        response: brain_result.response,
-        confidence: brain_result.confidence,
-        thought_id: brain_result.thought_id,
-        memory_count: brain_result.memory_count,
-        ai_enhanced: false,
+    Ok(finish_think(
+        &app,
+        stream,
+        ThinkResponse {
+            response: brain_result.response,
+            confidence: brain_result.confidence,
+            thought_id: brain_result.thought_id,
+            memory_count: brain_result.memory_count,
+            ai_enhanced: false,
+            provider: "memory".to_string(),
+            ai_error,
+            cancelled,
+            sources: vec![],
+        },
+    ))
+}
+
+/// Reset the tray and, for streamed requests, announce the final response
+fn finish_think(app: &tauri::AppHandle, stream: bool, response: ThinkResponse) -> ThinkResponse {
+    crate::tray::set_status(app, crate::tray::TrayStatus::Idle);
+    if stream {
+        let _ = app.emit("think-done", response.clone());
+    }
+    response
+}
+
+// ---- Validate Claude Key ----
+
+#[derive(Debug, Serialize)]
+pub struct KeyValidation {
+    pub valid: bool,
+    pub error: Option<String>,
+}
+
+/// Check a Claude API key with a one-token request. Only a working key is
+/// saved to the Keychain and used for later requests.
+#[tauri::command]
+pub async fn validate_claude_key(
+    key: String,
+    state: State<'_, AppState>,
+) -> Result<KeyValidation, String> {
+    let key = key.trim().to_string();
+    let (privacy_mode, model) = {
+        let settings = state.settings.read();
+        (settings.privacy_mode, settings.claude_model.clone())
+    };
+    let rejected = if key.is_empty() {
+        Some("API key is empty".to_string())
+    } else if privacy_mode {
+        Some("Privacy mode is on, cloud AI is disabled".to_string())
+    } else {
+        ClaudeProvider::with_model(&key, &model).validate_key().await.err()
+    };
+    if let Some(error) = rejected {
+        return Ok(KeyValidation {
+            valid: false,
+            error: Some(error),
+        });
+    }
+
+    crate::keychain::store_secret("claude_api_key", &key)?;
+    state.settings.write().claude_api_key = Some(key);
+    state.refresh_ai_provider();
+
+    Ok(KeyValidation {
+        valid: true,
+        error: None,
     })
 }
 
+// ---- Cancel Think ----
+
+/// Stop the AI answer of the `think` call made with `request_id`.
+/// Returns false if it already finished.
+#[tauri::command]
+pub async fn cancel_think(request_id: String, state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.cancellations.cancel(&request_id))
+}
+
+// ---- AI Usage ----
+
+/// Requests and tokens used per AI provider since the brain was created
+#[tauri::command]
+pub async fn get_ai_usage(
+    state: State<'_, AppState>,
+) -> Result<BTreeMap<String, ProviderUsage>, String> {
+    Ok(state.ai_usage.totals())
+}
+
+// ---- Clear Conversation ----
+
+#[tauri::command]
+pub async fn clear_conversation(
+    conversation_id: String,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    Ok(state.conversations.clear(&conversation_id))
+}
+
 // ---- Remember ----
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -108,6 +260,7 @@ pub async fn remember(
     content: String,
     memory_type: String,
     importance: Option<f64>,
+    tags: Option<Vec<String>>,
     state: State<'_, AppState>,
 ) -> Result<RememberResponse, String> {
     let embedding = state.embeddings.embed(&content).await?;
@@ -119,12 +272,12 @@ pub async fn remember(
         importance,
     )?;
 
+    if let Some(tags) = tags {
+        state.engine.memory.add_tags(&id, &tags);
+    }
+
     // Persist to disk
-    if let Some(node) = {
-        // Get the node we just stored
-        let nodes = state.engine.memory.all_nodes();
-        nodes.into_iter().find(|n| n.id == id)
-    } {
+    if let Some(node) = state.engine.memory.get_node(&id) {
         let _ = state.persistence.store_memory(&node);
     }
 
@@ -141,19 +294,33 @@ pub struct RecallItem {
     pub content: String,
     pub similarity: f64,
     pub memory_type: String,
+    pub tags: Vec<String>,
 }
 
 #[tauri::command]
 pub async fn recall(
     query: String,
     limit: Option<u32>,
+    tags: Option<Vec<String>>,
+    min_similarity: Option<f64>,
+    after: Option<i64>,
+    before: Option<i64>,
+    offset: Option<u32>,
+    rank_by_importance: Option<bool>,
     state: State<'_, AppState>,
 ) -> Result<Vec<RecallItem>, String> {
     let embedding = state.embeddings.embed(&query).await?;
 
-    let results = state
-        .engine
-        .recall_f32(&embedding, limit, None)?;
+    let options = SearchOptions {
+        tags,
+        min_similarity,
+        after,
+        before,
+        offset,
+        rank_by_importance: rank_by_importance.unwrap_or(false),
+        ..Default::default()
+    };
+    let results = state.engine.recall_f32(&embedding, limit, options)?;
 
     Ok(results
         .into_iter()
@@ -162,10 +329,191 @@ pub async fn recall(
             content: r.content,
             similarity: r.similarity,
             memory_type: r.memory_type,
+            tags: r.tags,
         })
         .collect())
 }
 
+// ---- Update Memory ----
+
+#[tauri::command]
+pub async fn update_memory(
+    id: String,
+    content: Option<String>,
+    importance: Option<f64>,
+    state: State<'_, AppState>,
+) -> Result<crate::brain::types::MemoryEntry, String> {
+    // Only re-embed when the text actually changes
+    let vector = match content {
+        Some(ref text) => Some(state.embeddings.embed(text).await?),
+        None => None,
+    };
+
+    if !state
+        .engine
+        .memory
+        .update_memory(&id, content, vector, importance)
+    {
+        return Err(format!("Memory not found: {}", id));
+    }
+
+    if let Some(node) = state.engine.memory.get_node(&id) {
+        state.persistence.store_memory(&node)?;
+    }
+
+    state
+        .engine
+        .memory
+        .get(&id)
+        .ok_or_else(|| format!("Memory not found: {}", id))
+}
+
+// ---- Get / Delete Memory ----
+
+#[tauri::command]
+pub fn get_memory(
+    id: String,
+    state: State<'_, AppState>,
+) -> Result<Option<crate::brain::types::MemoryEntry>, String> {
+    Ok(state.engine.memory.get(&id))
+}
+
+/// Remove a memory from the brain and from disk. Returns false if it didn't exist.
+#[tauri::command]
+pub fn delete_memory(id: String, state: State<'_, AppState>) -> Result<bool, String> {
+    state.delete_memory(&id)
+}
+
+// ---- Re-embed ----
+
+/// Re-embed memories whose vectors came from another embedding provider so
+/// they are searchable again. Returns the number of memories updated.
+#[tauri::command]
+pub async fn reembed_memories(state: State<'_, AppState>) -> Result<u32, String> {
+    let memory = &state.engine.memory;
+    let nodes: Vec<_> = memory
+        .mismatched_provider_ids()
+        .iter()
+        .filter_map(|id| memory.get_node(id))
+        .collect();
+    if nodes.is_empty() {
+        return Ok(0);
+    }
+
+    let texts: Vec<&str> = nodes.iter().map(|n| n.content.as_str()).collect();
+    let vectors = state.embeddings.embed_batch(&texts).await?;
+    if let Some(v) = vectors.first() {
+        if v.len() != memory.dimensions() {
+            return Err(format!(
+                "Current embedding provider produces {}-dim vectors but the store is {}-dim",
+                v.len(),
+                memory.dimensions()
+            ));
+        }
+    }
+
+    let mut updated = Vec::with_capacity(nodes.len());
+    for (node, vector) in nodes.iter().zip(vectors) {
+        if memory.update_memory(&node.id, None, Some(vector), None) {
+            if let Some(node) = memory.get_node(&node.id) {
+                updated.push(node);
+            }
+        }
+    }
+    state.persistence.store_memories_batch(&updated)?;
+
+    tracing::info!("Re-embedded {} memories", updated.len());
+    Ok(updated.len() as u32)
+}
+
+// ---- Clear Memory Type ----
+
+#[tauri::command]
+pub fn clear_memory_type(memory_type: String, state: State<'_, AppState>) -> Result<u32, String> {
+    let parsed = crate::brain::types::try_parse_memory_type(&memory_type)
+        .ok_or_else(|| format!("Unknown memory type: {}", memory_type))?;
+
+    let removed = state.engine.memory.delete_by_type(&memory_type);
+    state.persistence.delete_memories_by_type(parsed)?;
+    Ok(removed)
+}
+
+// ---- Export / Import ----
+
+/// Write all memories to a JSON file. Returns the number exported.
+#[tauri::command]
+pub fn export_brain(path: String, state: State<'_, AppState>) -> Result<u32, String> {
+    let json = state.engine.memory.export_json();
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(state.engine.memory.len())
+}
+
+/// Load memories from a JSON file written by `export_brain` and persist them
+#[tauri::command]
+pub fn import_brain(path: String, state: State<'_, AppState>) -> Result<u32, String> {
+    let json =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let imported = state.engine.memory.import_json(&json)?;
+    let memory = &state.engine.memory;
+    state
+        .persistence
+        .store_memories_with(|sink| memory.for_each_node(sink))?;
+    Ok(imported)
+}
+
+// ---- Connect Memories ----
+
+/// Link two memories in the knowledge graph. Returns false if either is missing.
+#[tauri::command]
+pub fn connect_memories(
+    id1: String,
+    id2: String,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    state.connect_memories(&id1, &id2)
+}
+
+/// Remove the link between two memories. Returns false if they weren't linked.
+#[tauri::command]
+pub fn disconnect_memories(
+    id1: String,
+    id2: String,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    state.disconnect_memories(&id1, &id2)
+}
+
+// ---- Connected Memories ----
+
+#[tauri::command]
+pub fn get_connected_memories(
+    id: String,
+    depth: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<crate::brain::types::MemoryEntry>, String> {
+    Ok(state.engine.memory.get_connected(&id, depth.unwrap_or(1)))
+}
+
+// ---- Memory Graph ----
+
+/// The most important memories and the links between them, for visualization
+#[tauri::command]
+pub fn get_memory_graph(
+    limit: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<MemoryGraph, String> {
+    Ok(state.engine.memory.graph(limit.unwrap_or(200) as usize))
+}
+
+// ---- Distance Metric ----
+
+/// Switch the similarity metric: cosine, euclidean, dotproduct, or manhattan.
+/// Unknown names fall back to cosine; returns the metric now in use.
+#[tauri::command]
+pub fn set_distance_metric(metric: String, state: State<'_, AppState>) -> Result<String, String> {
+    state.set_distance_metric(metric.trim())
+}
+
 // ---- Status ----
 
 #[tauri::command]
@@ -173,6 +521,7 @@ pub fn get_status(state: State<'_, AppState>) -> Result<SystemStatus, String> {
     let introspection = state.engine.introspect();
     let settings = state.settings.read();
     let embedding_provider = format!("{:?}", state.embeddings.provider());
+    let mismatched_embeddings = state.engine.memory.mismatched_provider_ids().len() as u32;
     let ai_available = state.ai_provider.read().is_some();
 
     let index_stats = state.indexer.stats().unwrap_or(crate::indexer::IndexStats {
@@ -190,6 +539,8 @@ pub fn get_status(state: State<'_, AppState>) -> Result<SystemStatus, String> {
         ai_provider: settings.ai_provider.clone(),
         ai_available,
         embedding_provider,
+        mismatched_embeddings,
+        belief_count: introspection.total_beliefs,
         learning_trend: introspection.learning_trend,
         indexed_files: index_stats.file_count,
         indexed_chunks: index_stats.chunk_count,
@@ -204,10 +555,15 @@ pub fn get_settings(state: State<'_, AppState>) -> Result<AppSettings, String> {
 }
 
 #[tauri::command]
-pub fn update_settings(
-    settings: AppSettings,
+pub async fn update_settings(
+    mut settings: AppSettings,
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
+    // Reject an invalid clipboard denylist or hotkey before applying anything
+    let secret_filter = AppState::secret_filter(&settings)?;
+    let hotkey = crate::overlay::parse_hotkey(&settings.hotkey)?;
+
     // Store Claude API key in Keychain if present
     if let Some(ref key) = settings.claude_api_key {
         if !key.is_empty() {
@@ -216,6 +572,22 @@ pub fn update_settings(
     } else {
         let _ = crate::keychain::delete_secret("claude_api_key");
     }
+    match settings.openai_api_key.as_deref() {
+        Some(key) if !key.is_empty() => crate::keychain::store_secret("openai_api_key", key)?,
+        Some(_) => {}
+        None => {
+            let _ = crate::keychain::delete_secret("openai_api_key");
+        }
+    }
+    state.embeddings.set_privacy_mode(settings.privacy_mode);
+    state.embeddings.configure_openai(
+        settings.openai_api_key.clone(),
+        state.engine.memory.dimensions(),
+    );
+    let ollama_url_changed = state.embeddings.set_ollama_url(&settings.ollama_base_url);
+    let embedding_model_changed =
+        state.embeddings.set_ollama_model(&settings.embedding_model) || ollama_url_changed;
+    state.sync_embedding_provider();
 
     // Update auto-start login item
     #[cfg(target_os = "macos")]
@@ -223,21 +595,153 @@ pub fn update_settings(
         let _ = crate::autostart::set_auto_start(settings.auto_start);
     }
 
-    *state.settings.write() = settings.clone();
+    // Store the lists the indexer actually uses
+    let scan_config = AppState::scan_config(&settings);
+    settings.skip_dirs = scan_config.skip_dirs.clone();
+    settings.index_extensions = scan_config.extensions.clone();
+    state.indexer.set_scan_config(scan_config);
+    state.indexer.set_concurrency(settings.index_concurrency);
+    state.indexer.set_privacy_mode(settings.privacy_mode);
+    state.context.set_max_entry_bytes(settings.clipboard_max_bytes);
+    state.context.set_secret_filter(secret_filter);
+
+    // Move the overlay shortcut when the hotkey changes. Undone below if the
+    // settings can't be saved, so the live shortcut always matches the stored one.
+    let old_hotkey = state.settings.read().hotkey.clone();
+    let new_hotkey = settings.hotkey.clone();
+    let hotkey_changed = new_hotkey.trim() != old_hotkey.trim();
+    if hotkey_changed {
+        crate::overlay::replace_hotkey(&app, &old_hotkey, hotkey)?;
+    }
+
+    // Folders dropped from the list; their files are purged once the save succeeds
+    let removed: Vec<std::path::PathBuf> = state
+        .settings
+        .read()
+        .indexed_folders
+        .iter()
+        .filter(|f| !settings.indexed_folders.contains(f))
+        .map(std::path::PathBuf::from)
+        .collect();
+
+    let encrypt_storage = settings.encrypt_storage;
+    let encryption_changed = encrypt_storage != state.settings.read().encrypt_storage;
+    let overlay_pinned = settings.overlay_pinned;
+    let previous = std::mem::replace(&mut *state.settings.write(), settings);
+
+    let undo = |previous: AppSettings| {
+        *state.settings.write() = previous;
+        if hotkey_changed {
+            if let Ok(old) = crate::overlay::parse_hotkey(&old_hotkey) {
+                let _ = crate::overlay::replace_hotkey(&app, &new_hotkey, old);
+            }
+        }
+    };
+
+    // Persist settings to SQLite (strip API keys — they're in Keychain)
+    if let Err(e) = state.persist_settings() {
+        undo(previous);
+        return Err(e);
+    }
+
+    // Encrypt or decrypt what's already stored when the toggle changes. Only
+    // after the save, so the stored rows never disagree with the stored toggle;
+    // a failed rewrite leaves the rows as they were and the old settings saved.
+    if encryption_changed {
+        match state.set_storage_encryption(encrypt_storage) {
+            Ok(rewritten) => {
+                tracing::info!("Rewrote {} memories for database encryption", rewritten)
+            }
+            Err(e) => {
+                undo(previous);
+                if let Err(save) = state.persist_settings() {
+                    tracing::error!("Failed to restore settings: {}", save);
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    // Stop indexing folders dropped from the list and forget their files
+    if let Err(e) = state.unwatch_folders(&removed) {
+        tracing::warn!("Failed to purge removed folders: {}", e);
+    }
 
     // Refresh AI provider with new settings
     state.refresh_ai_provider();
 
-    // Persist settings to SQLite (strip API key — it's in Keychain)
-    let mut persist_settings = settings;
-    persist_settings.claude_api_key = None;
-    let json = serde_json::to_string(&persist_settings)
-        .map_err(|e| format!("Serialize error: {}", e))?;
-    state.persistence.store_config("app_settings", &json)?;
+    // Keep the tray's pin check mark in step with the settings
+    crate::tray::set_pin_checked(&app, overlay_pinned);
+
+    // A different embedding model or server may produce vectors of another width;
+    // this resizes an empty store or warns that the existing one won't fit
+    if embedding_model_changed {
+        state.sync_embedding_dimensions().await;
+    }
 
     Ok(())
 }
 
+// ---- Pin Overlay ----
+
+/// Pin the overlay so it stays open when another window takes focus
+#[tauri::command]
+pub fn set_overlay_pinned(pinned: bool, app: tauri::AppHandle) -> Result<(), String> {
+    crate::overlay::set_pinned(&app, pinned)
+}
+
+// ---- Goals ----
+
+/// Create a goal. Priority is clamped to [0, 1].
+#[tauri::command]
+pub fn add_goal(
+    description: String,
+    priority: Option<f64>,
+    state: State<'_, AppState>,
+) -> Result<Goal, String> {
+    let description = description.trim().to_string();
+    if description.is_empty() {
+        return Err("Goal description is empty".to_string());
+    }
+    state.add_goal(description, priority.unwrap_or(0.5))
+}
+
+/// All goals, highest priority first
+#[tauri::command]
+pub fn list_goals(state: State<'_, AppState>) -> Result<Vec<Goal>, String> {
+    Ok(state.engine.goals())
+}
+
+/// Set a goal's progress (0–1). Any progress makes it active; 1 completes it.
+#[tauri::command]
+pub fn update_goal(id: String, progress: f64, state: State<'_, AppState>) -> Result<Goal, String> {
+    state.update_goal(&id, progress)
+}
+
+// ---- Beliefs ----
+
+/// Record a belief. Confidence is clamped to [0, 1].
+#[tauri::command]
+pub fn add_belief(
+    content: String,
+    confidence: f64,
+    source: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Belief, String> {
+    let content = content.trim().to_string();
+    if content.is_empty() {
+        return Err("Belief content is empty".to_string());
+    }
+    let source = source.unwrap_or_else(|| "user".to_string());
+    state.add_belief(content, confidence, source)
+}
+
+/// All beliefs, most confident first
+#[tauri::command]
+pub fn list_beliefs(state: State<'_, AppState>) -> Result<Vec<Belief>, String> {
+    Ok(state.engine.beliefs())
+}
+
 // ---- Thoughts ----
 
 #[tauri::command]
@@ -275,6 +779,23 @@ pub fn cycle(
     Ok(state.engine.cycle())
 }
 
+// ---- Learn ----
+
+/// Feed a reward signal (e.g. feedback on an answer) into the learner
+#[tauri::command]
+pub fn learn(
+    state: Vec<f64>,
+    action: u32,
+    reward: f64,
+    next_state: Vec<f64>,
+    done: bool,
+    app_state: State<'_, AppState>,
+) -> Result<crate::brain::cognitive::LearnResult, String> {
+    app_state
+        .engine
+        .learn(state, action, reward, next_state, done)
+}
+
 // ---- File Search ----
 
 #[tauri::command]
@@ -289,8 +810,11 @@ pub async fn search_files(
 // ---- Index Files ----
 
 #[tauri::command]
-pub async fn index_files(state: State<'_, AppState>) -> Result<u32, String> {
-    state.indexer.scan_all().await
+pub async fn index_files(
+    force: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<u32, String> {
+    state.indexer.scan_all(force.unwrap_or(false)).await
 }
 
 // ---- Workflows ----
@@ -299,6 +823,9 @@ pub async fn index_files(state: State<'_, AppState>) -> Result<u32, String> {
 pub async fn run_workflow(
     action: String,
     query: Option<String>,
+    dest_path: Option<String>,
+    k: Option<usize>,
+    limit: Option<usize>,
     state: State<'_, AppState>,
 ) -> Result<crate::workflows::WorkflowResult, String> {
     let workflow_action = match action.as_str() {
@@ -308,14 +835,29 @@ pub async fn run_workflow(
         "search_and_remember" => crate::workflows::WorkflowAction::SearchAndRemember {
             query: query.unwrap_or_default(),
         },
+        "answer_from_files" => crate::workflows::WorkflowAction::AnswerFromFiles {
+            query: query.unwrap_or_default(),
+        },
+        "export_markdown" => crate::workflows::WorkflowAction::ExportMarkdown {
+            dest_path: dest_path.ok_or("export_markdown needs a destination path")?,
+        },
+        "cluster_memories" => crate::workflows::WorkflowAction::ClusterMemories {
+            k: k.unwrap_or(crate::workflows::DEFAULT_CLUSTER_COUNT),
+        },
+        "auto_tag" => crate::workflows::WorkflowAction::AutoTag {
+            limit: limit.unwrap_or(crate::workflows::DEFAULT_AUTO_TAG_LIMIT),
+        },
         _ => return Err(format!("Unknown workflow: {}", action)),
     };
 
+    let providers = AppState::build_ai_chain(&state.settings.read().clone());
     crate::workflows::execute_workflow(
         workflow_action,
         &state.engine,
         &state.embeddings,
         &state.context,
+        &state.indexer,
+        &providers,
     )
     .await
 }
@@ -329,8 +871,9 @@ pub struct OllamaStatus {
 }
 
 #[tauri::command]
-pub async fn check_ollama() -> Result<OllamaStatus, String> {
-    match crate::ai::ollama::list_models("http://localhost:11434").await {
+pub async fn check_ollama(state: State<'_, AppState>) -> Result<OllamaStatus, String> {
+    let base_url = state.settings.read().ollama_base_url.clone();
+    match crate::ai::ollama::list_models(&base_url).await {
         Ok(models) => Ok(OllamaStatus {
             available: true,
             models,
@@ -352,6 +895,22 @@ pub fn get_clipboard_history(
     Ok(state.context.recent_clipboard(limit.unwrap_or(20) as usize))
 }
 
+// ---- Search Clipboard ----
+
+/// Search the clipboard history by substring, or by meaning when `semantic`
+#[tauri::command]
+pub async fn search_clipboard(
+    query: String,
+    limit: Option<u32>,
+    semantic: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Vec<crate::context::ClipboardEntry>, String> {
+    let limit = limit.unwrap_or(20) as usize;
+    state
+        .search_clipboard(query.trim(), limit, semantic.unwrap_or(false))
+        .await
+}
+
 // ---- Add Indexed Folder ----
 
 #[tauri::command]
@@ -376,12 +935,72 @@ pub async fn add_indexed_folder(
     }
 
     // Trigger re-scan
-    state.indexer.scan_all().await
+    state.indexer.scan_all(false).await
+}
+
+// ---- Remove Indexed Folder ----
+
+#[tauri::command]
+pub fn remove_indexed_folder(path: String, state: State<'_, AppState>) -> Result<u32, String> {
+    state.remove_indexed_folder(&path)
+}
+
+// ---- Backup / Restore ----
+
+/// Save the whole brain to a single database file at `dest_path`
+#[tauri::command]
+pub fn backup_brain(dest_path: String, state: State<'_, AppState>) -> Result<u64, String> {
+    state.backup_brain(std::path::Path::new(&dest_path))
+}
+
+/// Replace the brain with a backup made by `backup_brain`; returns the memory count
+#[tauri::command]
+pub fn restore_brain(src_path: String, state: State<'_, AppState>) -> Result<u32, String> {
+    state.restore_brain(std::path::Path::new(&src_path))
+}
+
+/// Reclaim space in brain.db and files.db, returning their sizes before and after
+#[tauri::command]
+pub async fn compact_database(app: tauri::AppHandle) -> Result<CompactReport, String> {
+    tauri::async_runtime::spawn_blocking(move || app.state::<AppState>().compact_database())
+        .await
+        .map_err(|e| format!("Compact task failed: {}", e))?
 }
 
 // ---- Flush (save to disk) ----
 
+/// Save everything to disk, waiting for any flush already running
 #[tauri::command]
-pub fn flush(state: State<'_, AppState>) -> Result<(), String> {
-    state.flush()
+pub async fn flush(app: tauri::AppHandle) -> Result<(), String> {
+    flush_off_thread(app, true).await.map(|_| ())
+}
+
+/// Start a flush in the background and return without waiting for it
+#[tauri::command]
+pub fn flush_in_background(app: tauri::AppHandle) -> FlushStatus {
+    if app.state::<AppState>().is_flushing() {
+        return FlushStatus::AlreadyRunning;
+    }
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = flush_off_thread(app, false).await {
+            tracing::warn!("Background flush failed: {}", e);
+        }
+    });
+    FlushStatus::Started
+}
+
+/// Run a flush on a blocking thread so the calling thread stays free.
+/// With `wait` false, a flush already running makes this one a no-op;
+/// returns whether this call wrote anything.
+pub async fn flush_off_thread(app: tauri::AppHandle, wait: bool) -> Result<bool, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<AppState>();
+        if wait {
+            state.flush().map(|_| true)
+        } else {
+            state.try_flush()
+        }
+    })
+    .await
+    .map_err(|e| format!("Flush task failed: {}", e))?
 }
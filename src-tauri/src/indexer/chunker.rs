@@ -2,13 +2,40 @@
 //!
 //! Splits text into overlapping chunks for embedding.
 
+/// A chunk of text and the lines of the input it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub text: String,
+    /// First line of the chunk (1-based)
+    pub start_line: u32,
+    /// Last line of the chunk (1-based, inclusive)
+    pub end_line: u32,
+}
+
+impl Chunk {
+    /// Join `words[start..end]`, each paired with its line number
+    fn from_words(words: &[(&str, u32)]) -> Self {
+        Self {
+            text: words.iter().map(|(w, _)| *w).collect::<Vec<_>>().join(" "),
+            start_line: words.first().map_or(0, |(_, line)| *line),
+            end_line: words.last().map_or(0, |(_, line)| *line),
+        }
+    }
+}
+
 /// Split text into chunks of approximately `chunk_size` tokens
-/// with `overlap` token overlap between consecutive chunks.
+/// with `overlap` token overlap between consecutive chunks. The chunks
+/// cover every word; the last one ends at the end of the text.
 ///
 /// Uses word boundaries for natural splits.
 /// Token count is approximated as word count (roughly 0.75 tokens per word).
-pub fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
-    let words: Vec<&str> = text.split_whitespace().collect();
+pub fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<Chunk> {
+    // Each word remembers how many newlines precede it
+    let words: Vec<(&str, u32)> = text
+        .lines()
+        .enumerate()
+        .flat_map(|(i, line)| line.split_whitespace().map(move |w| (w, i as u32 + 1)))
+        .collect();
 
     if words.is_empty() {
         return Vec::new();
@@ -16,33 +43,203 @@ pub fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<String>
 
     // If text is small enough for one chunk, return as-is
     if words.len() <= chunk_size {
-        return vec![words.join(" ")];
+        return vec![Chunk::from_words(&words)];
     }
 
     let mut chunks = Vec::new();
     let step = chunk_size.saturating_sub(overlap).max(1);
     let mut start = 0;
 
-    while start < words.len() {
+    loop {
         let end = (start + chunk_size).min(words.len());
-        let chunk = words[start..end].join(" ");
+        chunks.push(Chunk::from_words(&words[start..end]));
+        if end == words.len() {
+            break;
+        }
+        start += step;
+    }
 
-        if !chunk.trim().is_empty() {
-            chunks.push(chunk);
+    chunks
+}
+
+/// Source languages `chunk_code` knows the top-level definitions of
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Language {
+    Rust,
+    Python,
+    JavaScript,
+}
+
+impl Language {
+    fn from_ext(ext: &str) -> Option<Self> {
+        match ext.to_lowercase().as_str() {
+            "rs" => Some(Self::Rust),
+            "py" => Some(Self::Python),
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => Some(Self::JavaScript),
+            _ => None,
         }
+    }
 
-        start += step;
+    /// Whether an unindented line starts a function, class or similar item
+    fn starts_definition(self, line: &str) -> bool {
+        let (prefixes, keywords): (&[&str], &[&str]) = match self {
+            Self::Rust => (
+                &[
+                    "pub(crate) ",
+                    "pub(super) ",
+                    "pub ",
+                    "async ",
+                    "const ",
+                    "unsafe ",
+                    "extern \"C\" ",
+                ],
+                &[
+                    "fn ",
+                    "struct ",
+                    "enum ",
+                    "trait ",
+                    "impl ",
+                    "impl<",
+                    "mod ",
+                    "macro_rules!",
+                ],
+            ),
+            Self::Python => (&["async "], &["def ", "class "]),
+            Self::JavaScript => (
+                &["export ", "default ", "async ", "abstract "],
+                &["function ", "function*", "class "],
+            ),
+        };
+        let mut rest = line;
+        while let Some(stripped) = prefixes.iter().find_map(|p| rest.strip_prefix(p)) {
+            rest = stripped;
+        }
+        keywords.iter().any(|k| rest.starts_with(k))
+    }
 
-        // Avoid tiny trailing chunks
-        if start + overlap >= words.len() && start < words.len() {
-            let final_chunk = words[start..].join(" ");
-            if !final_chunk.trim().is_empty() && final_chunk.split_whitespace().count() > overlap / 2 {
-                chunks.push(final_chunk);
+    /// Whether a line belongs to the item below it (doc comment, attribute,
+    /// decorator)
+    fn is_preamble(self, line: &str) -> bool {
+        match self {
+            Self::Rust => line.starts_with("//") || line.starts_with("#["),
+            Self::Python => line.starts_with('#') || line.starts_with('@'),
+            // JSDoc continuation lines are indented by a space
+            Self::JavaScript => {
+                line.starts_with("//")
+                    || line.starts_with("/*")
+                    || line.trim_start().starts_with('*')
             }
-            break;
         }
     }
+}
+
+/// Whether `chunk_code` splits files with this extension on definitions
+pub fn is_code(ext: &str) -> bool {
+    Language::from_ext(ext).is_some()
+}
+
+/// Split source code at top-level function/class boundaries, keeping each
+/// definition (with its doc comments and attributes) in one chunk. Blocks
+/// over `max_size` words, and files in unknown languages, fall back to
+/// `chunk_text` word windows.
+pub fn chunk_code(text: &str, ext: &str, max_size: usize) -> Vec<Chunk> {
+    let Some(language) = Language::from_ext(ext) else {
+        return chunk_text(text, max_size, max_size / 4);
+    };
+
+    let lines: Vec<&str> = text.lines().collect();
+    let mut starts = vec![0];
+    for (i, line) in lines.iter().enumerate() {
+        if !language.starts_definition(line) {
+            continue;
+        }
+        // Pull in the comments and attributes directly above
+        let mut start = i;
+        while start > 0 && language.is_preamble(lines[start - 1]) {
+            start -= 1;
+        }
+        if start > *starts.last().unwrap_or(&0) {
+            starts.push(start);
+        }
+    }
+    starts.push(lines.len());
+
+    chunk_blocks(&lines, &starts, max_size, |_| false)
+}
 
+/// Split markdown at `#`, `##` and `###` headings, keeping each section
+/// (heading and body) in one chunk. Sections over `max_size` words are split
+/// into word windows that each start with the section's heading.
+pub fn chunk_markdown(text: &str, max_size: usize) -> Vec<Chunk> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut starts = vec![0];
+    let mut in_fence = false;
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        } else if !in_fence && i > 0 && is_section_heading(line) {
+            starts.push(i);
+        }
+    }
+    starts.push(lines.len());
+
+    chunk_blocks(&lines, &starts, max_size, is_section_heading)
+}
+
+/// Whether a line is a level 1-3 ATX heading (`# Title`, not `#tag`)
+fn is_section_heading(line: &str) -> bool {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    (1..=3).contains(&level) && line[level..].chars().next().is_none_or(|c| c == ' ')
+}
+
+/// Turn `lines[starts[i]..starts[i + 1]]` into one chunk each, word-splitting
+/// blocks over `max_size` words. A block whose first line passes
+/// `is_heading` repeats that line at the top of each of its windows.
+fn chunk_blocks(
+    lines: &[&str],
+    starts: &[usize],
+    max_size: usize,
+    is_heading: impl Fn(&str) -> bool,
+) -> Vec<Chunk> {
+    let overlap = max_size / 4;
+    let mut chunks = Vec::new();
+    for block in starts.windows(2) {
+        let (first, end) = (block[0], block[1]);
+        // Trim blank lines so the range covers only the block's content
+        let Some(first) = (first..end).find(|&i| !lines[i].trim().is_empty()) else {
+            continue;
+        };
+        let last = (first..end).rev().find(|&i| !lines[i].trim().is_empty()).unwrap_or(first);
+        let body = lines[first..=last].join("\n");
+
+        if body.split_whitespace().count() <= max_size {
+            chunks.push(Chunk {
+                text: body,
+                start_line: first as u32 + 1,
+                end_line: last as u32 + 1,
+            });
+        } else if is_heading(lines[first]) && first < last {
+            let heading = lines[first].trim();
+            let size = max_size.saturating_sub(heading.split_whitespace().count()).max(1);
+            let rest = lines[first + 1..=last].join("\n");
+            let windows = chunk_text(&rest, size, overlap.min(size / 2));
+            let offset = first as u32 + 1;
+            for (i, mut c) in windows.into_iter().enumerate() {
+                c.text = format!("{}\n{}", heading, c.text);
+                // The first window also covers the heading line
+                c.start_line = if i == 0 { offset } else { c.start_line + offset };
+                c.end_line += offset;
+                chunks.push(c);
+            }
+        } else {
+            chunks.extend(chunk_text(&body, max_size, overlap).into_iter().map(|mut c| {
+                c.start_line += first as u32;
+                c.end_line += first as u32;
+                c
+            }));
+        }
+    }
     chunks
 }
 
@@ -84,7 +281,7 @@ mod tests {
         let text = "Hello world this is a test";
         let chunks = chunk_text(text, 512, 128);
         assert_eq!(chunks.len(), 1);
-        assert_eq!(chunks[0], text);
+        assert_eq!(chunks[0].text, text);
     }
 
     #[test]
@@ -100,16 +297,151 @@ mod tests {
 
         // Each chunk should have at most 30 words
         for chunk in &chunks {
-            assert!(chunk.split_whitespace().count() <= 30);
+            assert!(chunk.text.split_whitespace().count() <= 30);
+        }
+    }
+
+    #[test]
+    fn test_chunks_tile_the_text() {
+        for (count, size, overlap) in [(100, 30, 10), (88, 30, 10), (91, 30, 10), (50, 7, 3)] {
+            let words: Vec<String> = (0..count).map(|i| format!("word{}", i)).collect();
+            let chunks = chunk_text(&words.join(" "), size, overlap);
+            let chunk_words: Vec<Vec<&str>> =
+                chunks.iter().map(|c| c.text.split_whitespace().collect()).collect();
+
+            // Neighbors share exactly `overlap` words and nothing is repeated
+            // beyond that, so dropping each overlap rebuilds the text
+            let mut covered: Vec<&str> = chunk_words[0].clone();
+            for pair in chunk_words.windows(2) {
+                let (prev, next) = (&pair[0], &pair[1]);
+                assert_eq!(prev.len(), size, "only the last chunk may be short");
+                assert!(next.len() > overlap, "{:?} adds no words", next);
+                assert_eq!(prev[size - overlap..], next[..overlap]);
+                covered.extend_from_slice(&next[overlap..]);
+            }
+            assert_eq!(covered, words, "{} words, size {}, overlap {}", count, size, overlap);
         }
     }
 
+    #[test]
+    fn test_chunk_line_ranges() {
+        // 10 lines of 10 words, with a blank line after the fifth
+        let lines: Vec<String> = (0..10)
+            .map(|l| (0..10).map(|w| format!("l{}w{}", l, w)).collect::<Vec<_>>().join(" "))
+            .collect();
+        let text = format!("{}\n\n{}", lines[..5].join("\n"), lines[5..].join("\n"));
+
+        let chunks = chunk_text(&text, 30, 10);
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 3));
+        // Second chunk starts at word 20 (line 3) and ends at word 49 (line 5)
+        assert_eq!((chunks[1].start_line, chunks[1].end_line), (3, 5));
+        // Lines after the blank line are shifted by one
+        assert_eq!((chunks[2].start_line, chunks[2].end_line), (5, 8));
+        assert!(chunks[2].text.starts_with("l4w0"));
+        assert!(chunks[2].text.ends_with("l6w9"));
+        assert_eq!(chunks.last().unwrap().end_line, 11);
+    }
+
     #[test]
     fn test_chunk_empty() {
         let chunks = chunk_text("", 512, 128);
         assert!(chunks.is_empty());
     }
 
+    #[test]
+    fn test_chunk_code_splits_rust_functions() {
+        let source = r#"use std::fmt;
+
+/// Add two numbers
+#[inline]
+pub fn add(a: i32, b: i32) -> i32 {
+    let sum = a + b;
+
+    sum
+}
+
+fn greet(name: &str) -> String {
+    format!("hello {}", name)
+}
+"#;
+        let chunks = chunk_code(source, "rs", 512);
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "use std::fmt;",
+                "/// Add two numbers\n#[inline]\npub fn add(a: i32, b: i32) -> i32 {\n    \
+                 let sum = a + b;\n\n    sum\n}",
+                "fn greet(name: &str) -> String {\n    format!(\"hello {}\", name)\n}",
+            ]
+        );
+        assert_eq!((chunks[1].start_line, chunks[1].end_line), (3, 9));
+        assert_eq!((chunks[2].start_line, chunks[2].end_line), (11, 13));
+    }
+
+    #[test]
+    fn test_chunk_code_other_languages() {
+        // Methods stay with their class; decorators stay with their function
+        let python = "import os\n\nclass Store:\n    def get(self):\n        pass\n\n    \
+                      def put(self):\n        pass\n\n@cached\ndef load():\n    return 1\n";
+        let chunks = chunk_code(python, "py", 512);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[1].text.starts_with("class Store:") && chunks[1].text.contains("def put"));
+        assert!(chunks[2].text.starts_with("@cached\ndef load():"));
+
+        let ts = "/**\n * Sum values\n */\nexport function sum(xs: number[]) {\n  \
+                  return xs.reduce((a, b) => a + b, 0);\n}\nexport default class App {}\n";
+        let chunks = chunk_code(ts, "ts", 512);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].text.starts_with("/**") && chunks[0].text.ends_with('}'));
+
+        // An oversized function falls back to word windows within its lines
+        let body: Vec<String> = (0..40).map(|i| format!("    let x{} = {};", i, i)).collect();
+        let big = format!("fn small() {{}}\n\nfn big() {{\n{}\n}}\n", body.join("\n"));
+        let chunks = chunk_code(&big, "rs", 50);
+        assert_eq!(chunks[0].text, "fn small() {}");
+        assert!(chunks.len() > 2);
+        assert!(chunks[1..].iter().all(|c| c.start_line >= 3 && c.end_line <= 44));
+
+        // Unknown languages use word windows
+        assert_eq!(chunk_code("a b c", "txt", 512)[0].text, "a b c");
+    }
+
+    #[test]
+    fn test_chunk_markdown_sections() {
+        let doc = "Intro line before any heading.\n\n# Project\nOverview text.\n\n\
+                   ## Setup\nInstall things.\n\n```sh\n# not a heading\nmake\n```\n\n\
+                   #hashtag stays in the body\n### Usage\nRun it.\n\
+                   #### Deep detail\nStill usage.\n";
+        let chunks = chunk_markdown(doc, 512);
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "Intro line before any heading.",
+                "# Project\nOverview text.",
+                "## Setup\nInstall things.\n\n```sh\n# not a heading\nmake\n```\n\n\
+                 #hashtag stays in the body",
+                "### Usage\nRun it.\n#### Deep detail\nStill usage.",
+            ]
+        );
+        assert_eq!((chunks[1].start_line, chunks[1].end_line), (3, 4));
+        assert_eq!((chunks[2].start_line, chunks[2].end_line), (6, 14));
+
+        // A long section is word-split, and every window keeps its heading
+        let words: Vec<String> = (0..60).map(|i| format!("w{}", i)).collect();
+        let doc = format!("# Short\nTiny.\n## Long Section\n{}\n", words.join("\n"));
+        let chunks = chunk_markdown(&doc, 20);
+        assert_eq!(chunks[0].text, "# Short\nTiny.");
+        assert!(chunks.len() > 2);
+        for chunk in &chunks[1..] {
+            assert!(chunk.text.starts_with("## Long Section\n"));
+            assert!(chunk.text.split_whitespace().count() <= 20);
+        }
+        assert_eq!(chunks[1].start_line, 3);
+        assert!(chunks[1..].iter().any(|c| c.text.ends_with("w59") && c.end_line == 63));
+    }
+
     #[test]
     fn test_chunk_by_paragraphs() {
         let text = "First paragraph here.\n\nSecond paragraph here.\n\nThird paragraph.";
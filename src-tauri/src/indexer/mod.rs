@@ -7,14 +7,19 @@ pub mod chunker;
 pub mod parser;
 pub mod watcher;
 
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 
-use parking_lot::RwLock;
-use rusqlite::{params, Connection};
+use parking_lot::{Mutex, RwLock};
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinSet;
 
 use crate::brain::embeddings::EmbeddingModel;
+use crate::brain::persistence::{compact_database, CompactStats};
 use crate::brain::utils::cosine_similarity;
 
 /// File search result
@@ -25,6 +30,9 @@ pub struct FileResult {
     pub chunk: String,
     pub similarity: f64,
     pub file_type: String,
+    /// Lines of the file the chunk spans (1-based, inclusive); 0 if unknown
+    pub start_line: u32,
+    pub end_line: u32,
 }
 
 /// File index entry stored in SQLite
@@ -44,14 +52,121 @@ struct FileChunk {
     chunk_index: u32,
     content: String,
     vector: Vec<f32>,
+    start_line: u32,
+    end_line: u32,
+}
+
+/// A chunk held in memory for search
+#[derive(Debug, Clone)]
+struct CachedChunk {
+    content: String,
+    vector: Vec<f32>,
+    start_line: u32,
+    end_line: u32,
+}
+
+/// An indexed file's chunks held in memory for search
+#[derive(Debug, Clone)]
+struct CachedFile {
+    name: String,
+    ext: String,
+    chunks: Vec<CachedChunk>,
+}
+
+/// Emitted as `index-progress` after each file `scan_all` processes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexProgress {
+    pub processed: u32,
+    pub total: u32,
+    pub current_path: String,
+}
+
+/// Emitted as `index-complete` when `scan_all` finishes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexComplete {
+    pub files: u32,
+    pub chunks: u32,
+}
+
+/// Scan progress reported to the progress sender
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexEvent {
+    Progress(IndexProgress),
+    Complete(IndexComplete),
+}
+
+/// Which files a directory scan picks up
+#[derive(Debug, Clone)]
+pub struct ScanConfig {
+    /// Skip paths excluded by `.gitignore`/`.ignore` files
+    pub respect_gitignore: bool,
+    /// Directory names never descended into
+    pub skip_dirs: Vec<String>,
+    /// Lowercase file extensions to index, without the dot
+    pub extensions: Vec<String>,
+    /// Larger files are skipped; 0 means no limit
+    pub max_file_bytes: u64,
+    /// Subfolder levels descended into; 0 scans only the top directory
+    pub max_depth: u32,
+}
+
+impl ScanConfig {
+    /// Build a config, normalizing the extension and directory lists
+    pub fn new<S: AsRef<str>>(respect_gitignore: bool, skip_dirs: &[S], extensions: &[S]) -> Self {
+        let mut dirs: Vec<String> = Vec::with_capacity(skip_dirs.len());
+        for dir in skip_dirs {
+            let dir = dir.as_ref().trim();
+            if !dir.is_empty() && !dirs.iter().any(|d| d == dir) {
+                dirs.push(dir.to_string());
+            }
+        }
+        Self {
+            respect_gitignore,
+            skip_dirs: dirs,
+            extensions: parser::normalize_extensions(extensions),
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    pub fn with_max_file_bytes(mut self, max_file_bytes: u64) -> Self {
+        self.max_file_bytes = max_file_bytes;
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self::new(true, DEFAULT_SKIP_DIRS, parser::DEFAULT_EXTENSIONS)
+    }
 }
 
 /// The file indexer manages scanning, watching, and searching files
 pub struct FileIndexer {
     db_path: PathBuf,
     watched_dirs: RwLock<Vec<PathBuf>>,
+    scan_config: RwLock<ScanConfig>,
+    progress: RwLock<Option<UnboundedSender<IndexEvent>>>,
+    /// Files embedded at once during `scan_all`
+    concurrency: AtomicUsize,
+    /// Serializes index writes so concurrent files don't contend for SQLite
+    write_lock: Mutex<()>,
+    /// Chunk text and vectors by file path, loaded on the first search and
+    /// kept in step with every index write so queries never rescan SQLite
+    chunk_cache: RwLock<Option<HashMap<String, CachedFile>>>,
+    /// Chunk rows read from SQLite into the cache
+    rows_loaded: AtomicUsize,
+    /// When set, nothing on disk is read or indexed
+    privacy_mode: AtomicBool,
     embeddings: Arc<EmbeddingModel>,
-    is_indexing: RwLock<bool>,
+    /// Set while `scan_all` runs; claimed with a compare-exchange so two
+    /// scans can't both start
+    is_indexing: AtomicBool,
 }
 
 impl FileIndexer {
@@ -60,8 +175,15 @@ impl FileIndexer {
         let indexer = Self {
             db_path,
             watched_dirs: RwLock::new(Vec::new()),
+            scan_config: RwLock::new(ScanConfig::default()),
+            progress: RwLock::new(None),
+            concurrency: AtomicUsize::new(DEFAULT_CONCURRENCY),
+            write_lock: Mutex::new(()),
+            chunk_cache: RwLock::new(None),
+            rows_loaded: AtomicUsize::new(0),
+            privacy_mode: AtomicBool::new(false),
             embeddings,
-            is_indexing: RwLock::new(false),
+            is_indexing: AtomicBool::new(false),
         };
         indexer.initialize_db()?;
         Ok(indexer)
@@ -98,6 +220,24 @@ impl FileIndexer {
             ",
         )
         .map_err(|e| format!("DB init failed: {}", e))?;
+
+        // Line ranges were added later; older rows keep 0 until re-indexed
+        for column in ["start_line", "end_line"] {
+            let exists = conn
+                .prepare("SELECT 1 FROM pragma_table_info('file_chunks') WHERE name = ?1")
+                .and_then(|mut stmt| stmt.exists(params![column]))
+                .map_err(|e| format!("DB init failed: {}", e))?;
+            if !exists {
+                conn.execute(
+                    &format!(
+                        "ALTER TABLE file_chunks ADD COLUMN {} INTEGER NOT NULL DEFAULT 0",
+                        column
+                    ),
+                    [],
+                )
+                .map_err(|e| format!("DB init failed: {}", e))?;
+            }
+        }
         Ok(())
     }
 
@@ -111,15 +251,89 @@ impl FileIndexer {
         }
     }
 
-    /// Index a single file
-    pub async fn index_file(&self, path: &Path) -> Result<u32, String> {
+    /// Stop watching a directory. Its files stay indexed until
+    /// `purge_outside_watched` runs. Returns false if it wasn't watched.
+    pub fn remove_watch_dir(&self, dir: &Path) -> bool {
+        let mut watched = self.watched_dirs.write();
+        let before = watched.len();
+        watched.retain(|d| d != dir);
+        watched.len() != before
+    }
+
+    /// Send scan progress to `sender` from now on
+    pub fn set_progress_sender(&self, sender: UnboundedSender<IndexEvent>) {
+        *self.progress.write() = Some(sender);
+    }
+
+    fn report(&self, event: IndexEvent) {
+        if let Some(sender) = self.progress.read().as_ref() {
+            let _ = sender.send(event);
+        }
+    }
+
+    /// Set how many files `scan_all` indexes at once (at least 1)
+    pub fn set_concurrency(&self, concurrency: usize) {
+        self.concurrency.store(concurrency.max(1), Ordering::Relaxed);
+    }
+
+    /// Turn filesystem indexing off (privacy mode) or back on
+    pub fn set_privacy_mode(&self, enabled: bool) {
+        self.privacy_mode.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Whether privacy mode has disabled indexing
+    pub fn privacy_mode(&self) -> bool {
+        self.privacy_mode.load(Ordering::Relaxed)
+    }
+
+    /// Replace the rules used by subsequent scans
+    pub fn set_scan_config(&self, config: ScanConfig) {
+        *self.scan_config.write() = config;
+    }
+
+    /// Index a single file. Unless `force` is set, a file whose modified time
+    /// matches the indexed one is skipped. Returns the number of chunks indexed.
+    pub async fn index_file(&self, path: &Path, force: bool) -> Result<u32, String> {
+        if self.privacy_mode() {
+            tracing::debug!("Privacy mode is on, not indexing {:?}", path);
+            return Ok(0);
+        }
+
         let ext = path
             .extension()
             .and_then(|e| e.to_str())
             .unwrap_or("")
             .to_lowercase();
 
-        if !parser::is_supported(&ext) {
+        let max_file_bytes = {
+            let config = self.scan_config.read();
+            if !parser::is_supported(&ext, &config.extensions) {
+                return Ok(0);
+            }
+            config.max_file_bytes
+        };
+
+        let metadata = path.metadata().ok();
+        let size = metadata.as_ref().map_or(0, |m| m.len());
+        if max_file_bytes > 0 && size > max_file_bytes {
+            tracing::info!(
+                "Skipping {:?}: {} bytes exceeds the {} byte limit",
+                path,
+                size,
+                max_file_bytes
+            );
+            return Ok(0);
+        }
+
+        let modified = metadata
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+
+        let path_str = path.to_string_lossy().to_string();
+
+        if !force && self.indexed_modified(&path_str)? == Some(modified) {
             return Ok(0);
         }
 
@@ -128,7 +342,13 @@ impl FileIndexer {
             return Ok(0);
         }
 
-        let chunks = chunker::chunk_text(&content, 512, 128);
+        let chunks = if chunker::is_code(&ext) {
+            chunker::chunk_code(&content, &ext, 512)
+        } else if ext == "md" {
+            chunker::chunk_markdown(&content, 512)
+        } else {
+            chunker::chunk_text(&content, 512, 128)
+        };
         if chunks.is_empty() {
             return Ok(0);
         }
@@ -139,43 +359,46 @@ impl FileIndexer {
             .unwrap_or("unknown")
             .to_string();
 
-        let modified = path
-            .metadata()
-            .map(|m| {
-                m.modified()
-                    .ok()
-                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                    .map(|d| d.as_secs() as i64)
-                    .unwrap_or(0)
-            })
-            .unwrap_or(0);
-
-        let path_str = path.to_string_lossy().to_string();
+        // Embed the chunks a batch at a time
+        let mut vectors = Vec::with_capacity(chunks.len());
+        for batch in chunks.chunks(EMBED_BATCH_SIZE) {
+            let texts: Vec<&str> = batch.iter().map(|c| c.text.as_str()).collect();
+            vectors.extend(self.embeddings.embed_batch(&texts).await?);
+        }
+        if vectors.len() != chunks.len() {
+            return Err(format!(
+                "Expected {} chunk embeddings, got {}",
+                chunks.len(),
+                vectors.len()
+            ));
+        }
 
-        // Embed all chunks
         let mut file_chunks = Vec::with_capacity(chunks.len());
-        for (i, chunk) in chunks.iter().enumerate() {
-            let vector = self.embeddings.embed(chunk).await?;
+        for (i, (chunk, vector)) in chunks.into_iter().zip(vectors).enumerate() {
             file_chunks.push(FileChunk {
                 file_path: path_str.clone(),
                 chunk_index: i as u32,
-                content: chunk.clone(),
+                content: chunk.text,
                 vector,
+                start_line: chunk.start_line,
+                end_line: chunk.end_line,
             });
         }
 
-        // Store in database
-        let conn = self.open_connection()?;
-
-        // Upsert file entry
-        conn.execute(
-            "INSERT OR REPLACE INTO file_index (path, name, ext, modified, chunk_count) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![path_str, name, ext, modified, chunks.len() as u32],
-        )
-        .map_err(|e| format!("Store file failed: {}", e))?;
+        // Store in database, all or nothing: a file must never be left with
+        // its new modified time but only some of its chunks, or later scans
+        // would skip it for good
+        let mut conn = self.open_connection()?;
+        let _write = self.write_lock.lock();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Begin transaction failed: {}", e))?;
+        // New chunks go in before their file row, so check their foreign key at commit
+        tx.execute_batch("PRAGMA defer_foreign_keys = ON;")
+            .map_err(|e| format!("Begin transaction failed: {}", e))?;
 
         // Delete old chunks
-        conn.execute(
+        tx.execute(
             "DELETE FROM file_chunks WHERE file_path = ?1",
             params![path_str],
         )
@@ -184,85 +407,301 @@ impl FileIndexer {
         // Insert new chunks
         for chunk in &file_chunks {
             let vector_bytes = vector_to_bytes(&chunk.vector);
-            conn.execute(
-                "INSERT INTO file_chunks (file_path, chunk_index, content, vector) VALUES (?1, ?2, ?3, ?4)",
-                params![chunk.file_path, chunk.chunk_index, chunk.content, vector_bytes],
+            tx.execute(
+                "INSERT INTO file_chunks (file_path, chunk_index, content, vector, start_line, end_line)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    chunk.file_path,
+                    chunk.chunk_index,
+                    chunk.content,
+                    vector_bytes,
+                    chunk.start_line,
+                    chunk.end_line
+                ],
             )
             .map_err(|e| format!("Store chunk failed: {}", e))?;
         }
 
-        Ok(file_chunks.len() as u32)
+        // Upsert file entry last, once its chunks are in place. An update
+        // rather than REPLACE, which would cascade-delete the new chunks.
+        tx.execute(
+            "INSERT INTO file_index (path, name, ext, modified, chunk_count) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(path) DO UPDATE SET name = excluded.name, ext = excluded.ext,
+                 modified = excluded.modified, chunk_count = excluded.chunk_count",
+            params![path_str, name, ext, modified, file_chunks.len() as u32],
+        )
+        .map_err(|e| format!("Store file failed: {}", e))?;
+        tx.commit().map_err(|e| format!("Commit failed: {}", e))?;
+
+        let count = file_chunks.len() as u32;
+        if let Some(cache) = self.chunk_cache.write().as_mut() {
+            let chunks = file_chunks
+                .into_iter()
+                .map(|c| CachedChunk {
+                    content: c.content,
+                    vector: c.vector,
+                    start_line: c.start_line,
+                    end_line: c.end_line,
+                })
+                .collect();
+            cache.insert(path_str, CachedFile { name, ext, chunks });
+        }
+
+        Ok(count)
     }
 
-    /// Scan and index all files in watched directories (recursive)
-    pub async fn scan_all(&self) -> Result<u32, String> {
-        {
-            let is_indexing = self.is_indexing.read();
-            if *is_indexing {
-                return Err("Indexing already in progress".to_string());
+    /// Drop a file and its chunks from the index. Returns false if it wasn't indexed.
+    pub fn remove_file(&self, path: &Path) -> Result<bool, String> {
+        let path_str = path.to_string_lossy().to_string();
+        let mut conn = self.open_connection()?;
+        let _write = self.write_lock.lock();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Begin transaction failed: {}", e))?;
+        // Foreign keys aren't enabled on these connections, so no cascade
+        tx.execute("DELETE FROM file_chunks WHERE file_path = ?1", params![path_str])
+            .map_err(|e| format!("Delete chunks failed: {}", e))?;
+        let removed = tx
+            .execute("DELETE FROM file_index WHERE path = ?1", params![path_str])
+            .map_err(|e| format!("Delete file failed: {}", e))?;
+        tx.commit().map_err(|e| format!("Commit failed: {}", e))?;
+        if let Some(cache) = self.chunk_cache.write().as_mut() {
+            cache.remove(&path_str);
+        }
+        Ok(removed > 0)
+    }
+
+    /// Reclaim space left by removed files. Refused while a scan is running;
+    /// index writes wait until it finishes.
+    pub fn compact(&self) -> Result<CompactStats, String> {
+        if self.is_indexing.load(Ordering::Acquire) {
+            return Err("Indexing in progress, try again when it finishes".to_string());
+        }
+        let _write = self.write_lock.lock();
+        let conn = self.open_connection()?;
+        compact_database(&conn, &self.db_path)
+    }
+
+    /// Load every chunk into the in-memory cache unless it is already there
+    fn ensure_chunk_cache(&self) -> Result<(), String> {
+        if self.chunk_cache.read().is_some() {
+            return Ok(());
+        }
+        // Hold off writers so nothing lands between the read and the install
+        let _write = self.write_lock.lock();
+        if self.chunk_cache.read().is_some() {
+            return Ok(());
+        }
+
+        let conn = self.open_connection()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT fc.file_path, fc.content, fc.vector, fi.name, fi.ext,
+                        fc.start_line, fc.end_line
+                 FROM file_chunks fc
+                 JOIN file_index fi ON fc.file_path = fi.path
+                 ORDER BY fc.file_path, fc.chunk_index",
+            )
+            .map_err(|e| format!("Query failed: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                let file_path: String = row.get(0)?;
+                let vector_bytes: Vec<u8> = row.get(2)?;
+                let chunk = CachedChunk {
+                    content: row.get(1)?,
+                    vector: bytes_to_vector(&vector_bytes),
+                    start_line: row.get(5)?,
+                    end_line: row.get(6)?,
+                };
+                let name: String = row.get(3)?;
+                let ext: String = row.get(4)?;
+                Ok((file_path, chunk, name, ext))
+            })
+            .map_err(|e| format!("Search failed: {}", e))?;
+
+        let mut cache: HashMap<String, CachedFile> = HashMap::new();
+        let mut loaded = 0;
+        for (file_path, chunk, name, ext) in rows.filter_map(|r| r.ok()) {
+            cache
+                .entry(file_path)
+                .or_insert_with(|| CachedFile {
+                    name,
+                    ext,
+                    chunks: Vec::new(),
+                })
+                .chunks
+                .push(chunk);
+            loaded += 1;
+        }
+        self.rows_loaded.fetch_add(loaded, Ordering::Relaxed);
+        *self.chunk_cache.write() = Some(cache);
+        Ok(())
+    }
+
+    /// Remove indexed files that no longer exist on disk. Returns how many.
+    pub fn purge_missing(&self) -> Result<u32, String> {
+        let paths = self.indexed_paths()?;
+        let mut purged = 0u32;
+        for path in paths.iter().map(Path::new).filter(|p| !p.exists()) {
+            if self.remove_file(path)? {
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Remove indexed files that aren't under any watched directory, e.g.
+    /// after a folder was dropped from the settings. Returns how many.
+    pub fn purge_outside_watched(&self) -> Result<u32, String> {
+        let paths = self.indexed_paths()?;
+        let watched = self.watched_dirs.read().clone();
+        let mut purged = 0u32;
+        for path in paths.iter().map(Path::new) {
+            if !watched.iter().any(|dir| path.starts_with(dir)) && self.remove_file(path)? {
+                purged += 1;
             }
         }
-        *self.is_indexing.write() = true;
+        if purged > 0 {
+            tracing::info!("Purged {} files outside watched folders", purged);
+        }
+        Ok(purged)
+    }
+
+    /// Paths of every indexed file
+    fn indexed_paths(&self) -> Result<Vec<String>, String> {
+        let conn = self.open_connection()?;
+        let mut stmt = conn
+            .prepare("SELECT path FROM file_index")
+            .map_err(|e| format!("Query failed: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| format!("Query failed: {}", e))?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Modified time stored for an indexed file, if it is in the index
+    fn indexed_modified(&self, path: &str) -> Result<Option<i64>, String> {
+        let conn = self.open_connection()?;
+        conn.query_row(
+            "SELECT modified FROM file_index WHERE path = ?1",
+            params![path],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Lookup file failed: {}", e))
+    }
+
+    /// Scan and index all files in watched directories (recursive), several
+    /// at a time. `force` re-indexes files even if they are unchanged.
+    pub async fn scan_all(self: &Arc<Self>, force: bool) -> Result<u32, String> {
+        if self.privacy_mode() {
+            tracing::info!("Privacy mode is on, skipping file scan");
+            return Ok(0);
+        }
+        if self
+            .is_indexing
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            return Err("Indexing already in progress".to_string());
+        }
+        let _indexing = IndexingFlag(&self.is_indexing);
 
         let dirs: Vec<PathBuf> = self.watched_dirs.read().clone();
+        let config = self.scan_config.read().clone();
         let mut total = 0u32;
 
         // Collect all files recursively first
         let mut files = Vec::new();
         for dir in &dirs {
-            collect_files_recursive(dir, &mut files, 10);
+            collect_files(dir, &config, &mut files);
         }
 
         tracing::info!("Found {} files to index", files.len());
 
-        for path in &files {
-            match self.index_file(path).await {
+        match self.purge_missing() {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("Removed {} deleted files from the index", n),
+            Err(e) => tracing::warn!("Failed to purge deleted files: {}", e),
+        }
+
+        let file_count = files.len() as u32;
+        let concurrency = self.concurrency.load(Ordering::Relaxed);
+        let mut in_flight = JoinSet::new();
+        let mut processed = 0u32;
+        let mut finish = |joined: Result<(PathBuf, Result<u32, String>), _>| {
+            let (path, result) = match joined {
+                Ok(done) => done,
+                Err(e) => {
+                    tracing::warn!("Indexing task failed: {}", e);
+                    return;
+                }
+            };
+            match result {
                 Ok(chunks) => total += chunks,
                 Err(e) => tracing::debug!("Skipped {:?}: {}", path, e),
             }
+            processed += 1;
+            self.report(IndexEvent::Progress(IndexProgress {
+                processed,
+                total: file_count,
+                current_path: path.to_string_lossy().to_string(),
+            }));
+        };
+
+        for path in files {
+            if in_flight.len() >= concurrency {
+                if let Some(joined) = in_flight.join_next().await {
+                    finish(joined);
+                }
+            }
+            let indexer = Arc::clone(self);
+            in_flight.spawn(async move {
+                let result = indexer.index_file(&path, force).await;
+                (path, result)
+            });
+        }
+        while let Some(joined) = in_flight.join_next().await {
+            finish(joined);
         }
 
-        *self.is_indexing.write() = false;
-        tracing::info!("Indexed {} chunks from {} files", total, files.len());
+        self.report(IndexEvent::Complete(IndexComplete {
+            files: file_count,
+            chunks: total,
+        }));
+        tracing::info!("Indexed {} chunks from {} files", total, file_count);
         Ok(total)
     }
 
-    /// Search indexed files by semantic similarity
+    /// Search indexed files by semantic similarity. Scores the in-memory
+    /// chunk cache; SQLite is only read once to fill it.
     pub async fn search(&self, query: &str, limit: u32) -> Result<Vec<FileResult>, String> {
         let query_vector = self.embeddings.embed(query).await?;
+        self.ensure_chunk_cache()?;
 
-        let conn = self.open_connection()?;
-        let mut stmt = conn
-            .prepare(
-                "SELECT fc.file_path, fc.content, fc.vector, fi.name, fi.ext
-                 FROM file_chunks fc
-                 JOIN file_index fi ON fc.file_path = fi.path",
-            )
-            .map_err(|e| format!("Query failed: {}", e))?;
-
-        let mut results: Vec<FileResult> = stmt
-            .query_map([], |row| {
-                let file_path: String = row.get(0)?;
-                let content: String = row.get(1)?;
-                let vector_bytes: Vec<u8> = row.get(2)?;
-                let name: String = row.get(3)?;
-                let ext: String = row.get(4)?;
-
-                let vector = bytes_to_vector(&vector_bytes);
-                let similarity = cosine_similarity(&query_vector, &vector) as f64;
-
-                Ok(FileResult {
-                    path: file_path,
-                    name,
-                    chunk: content,
-                    similarity,
-                    file_type: ext,
-                })
-            })
-            .map_err(|e| format!("Search failed: {}", e))?
-            .filter_map(|r| r.ok())
-            .filter(|r| r.similarity > 0.1)
-            .collect();
+        let cache = self.chunk_cache.read();
+        let mut results: Vec<FileResult> = Vec::new();
+        for (path, file) in cache.iter().flatten() {
+            for chunk in &file.chunks {
+                // Chunks embedded by a provider of a different width can't match
+                if chunk.vector.len() != query_vector.len() {
+                    continue;
+                }
+                let similarity = cosine_similarity(&query_vector, &chunk.vector) as f64;
+                if similarity > 0.1 {
+                    results.push(FileResult {
+                        path: path.clone(),
+                        name: file.name.clone(),
+                        chunk: chunk.content.clone(),
+                        similarity,
+                        file_type: file.ext.clone(),
+                        start_line: chunk.start_line,
+                        end_line: chunk.end_line,
+                    });
+                }
+            }
+        }
+        drop(cache);
 
         results.sort_by(|a, b| b.similarity.partial_cmp(&a.similarity).unwrap_or(std::cmp::Ordering::Equal));
         results.truncate(limit as usize);
@@ -282,7 +721,7 @@ impl FileIndexer {
             .query_row("SELECT COUNT(*) FROM file_chunks", [], |row| row.get(0))
             .unwrap_or(0);
 
-        let is_indexing = *self.is_indexing.read();
+        let is_indexing = self.is_indexing.load(Ordering::Acquire);
 
         Ok(IndexStats {
             file_count,
@@ -293,6 +732,15 @@ impl FileIndexer {
     }
 }
 
+/// Clears `is_indexing` however `scan_all` ends, including when it is dropped
+struct IndexingFlag<'a>(&'a AtomicBool);
+
+impl Drop for IndexingFlag<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
 /// Index statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndexStats {
@@ -317,8 +765,20 @@ fn bytes_to_vector(bytes: &[u8]) -> Vec<f32> {
         .collect()
 }
 
-/// Directories to skip during recursive scanning
-const SKIP_DIRS: &[&str] = &[
+/// Size above which files are skipped unless the settings say otherwise
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Subfolder levels scanned unless the settings say otherwise
+pub const DEFAULT_MAX_DEPTH: u32 = 10;
+
+/// Files indexed at once unless the settings say otherwise
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Chunks sent to the embedding provider in one request
+const EMBED_BATCH_SIZE: usize = 32;
+
+/// Directories skipped during recursive scanning unless the settings name others
+pub const DEFAULT_SKIP_DIRS: &[&str] = &[
     "node_modules",
     "target",
     ".git",
@@ -334,35 +794,590 @@ const SKIP_DIRS: &[&str] = &[
     "Library",
 ];
 
-/// Recursively collect files, skipping hidden/undesirable directories
-fn collect_files_recursive(dir: &Path, files: &mut Vec<PathBuf>, max_depth: u32) {
-    if max_depth == 0 {
-        return;
+/// Recursively collect files with a supported extension up to
+/// `config.max_depth` subfolder levels below `dir`, skipping hidden entries,
+/// `skip_dirs` and (if enabled) gitignored paths
+fn collect_files(dir: &Path, config: &ScanConfig, files: &mut Vec<PathBuf>) {
+    let gitignore = config.respect_gitignore;
+    let skip_dirs = config.skip_dirs.clone();
+    let walker = ignore::WalkBuilder::new(dir)
+        // The walker counts `dir` itself as depth 0 and its files as depth 1
+        .max_depth(Some(config.max_depth as usize + 1))
+        .hidden(true)
+        .ignore(gitignore)
+        .git_ignore(gitignore)
+        .git_global(gitignore)
+        .git_exclude(gitignore)
+        .parents(gitignore)
+        // Folders outside a git checkout can still carry a .gitignore
+        .require_git(false)
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            !(is_dir && skip_dirs.iter().any(|d| *d == *entry.file_name().to_string_lossy()))
+        })
+        .build();
+
+    files.extend(
+        walker
+            .flatten()
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+            .filter(|entry| {
+                let ext = entry.path().extension().and_then(|e| e.to_str()).unwrap_or("");
+                parser::is_supported(ext, &config.extensions)
+            })
+            .map(|entry| entry.into_path()),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::spawn_mock_server;
+
+    /// Indexer backed by a mock Ollama that counts embedding requests
+    async fn indexer_with_mock_ollama(requests: Arc<AtomicUsize>) -> (Arc<FileIndexer>, PathBuf) {
+        let url = spawn_mock_server(move |request| {
+            if request.path != "/api/embed" {
+                return (200, r#"{"models":[]}"#.to_string());
+            }
+            requests.fetch_add(1, Ordering::SeqCst);
+            let inputs = request.body["input"].as_array().map(|a| a.len()).unwrap_or(1);
+            let embeddings = vec![vec![1.0, 0.5, 0.25, 0.0]; inputs];
+            (200, serde_json::json!({ "embeddings": embeddings }).to_string())
+        })
+        .await;
+
+        let embeddings = EmbeddingModel::new().with_ollama_url(&url);
+        embeddings.set_cache_capacity(0);
+        assert!(embeddings.refresh_provider().await);
+
+        let dir = std::env::temp_dir().join(format!("superbrain_index_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let indexer = FileIndexer::new(dir.join("files.db"), Arc::new(embeddings)).unwrap();
+        (Arc::new(indexer), dir)
+    }
+
+    #[test]
+    fn test_collect_files_respects_gitignore() {
+        let dir = std::env::temp_dir().join(format!("superbrain_scan_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("generated")).unwrap();
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::create_dir_all(dir.join("node_modules")).unwrap();
+        std::fs::write(dir.join(".gitignore"), "generated/\n*.secret\n").unwrap();
+        std::fs::write(dir.join("src").join(".gitignore"), "scratch.md\n").unwrap();
+        for file in [
+            "README.md",
+            "api.secret",
+            "generated/out.md",
+            "src/main.rs",
+            "src/scratch.md",
+            "node_modules/pkg.md",
+        ] {
+            std::fs::write(dir.join(file), "content").unwrap();
+        }
+
+        let collected = |config: &ScanConfig| {
+            let mut files = Vec::new();
+            collect_files(&dir, config, &mut files);
+            let mut names: Vec<String> = files
+                .iter()
+                .map(|p| p.strip_prefix(&dir).unwrap().to_string_lossy().replace('\\', "/"))
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(collected(&ScanConfig::default()), vec!["README.md", "src/main.rs"]);
+
+        // Disabled: ignored files come back, the skipped dirs still apply
+        let mut extensions = parser::normalize_extensions(parser::DEFAULT_EXTENSIONS);
+        extensions.push("secret".to_string());
+        let config = ScanConfig::new(false, &["node_modules".to_string()], &extensions);
+        assert_eq!(
+            collected(&config),
+            vec![
+                "README.md",
+                "api.secret",
+                "generated/out.md",
+                "src/main.rs",
+                "src/scratch.md"
+            ]
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_custom_extensions_and_skip_dirs() {
+        let dir = std::env::temp_dir().join(format!("superbrain_scan_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("node_modules").join("lib")).unwrap();
+        for file in ["todo.org", "notes.md", "main.rs", "node_modules/lib/README.md"] {
+            std::fs::write(dir.join(file), "content").unwrap();
+        }
+
+        let collected = |config: &ScanConfig| {
+            let mut files = Vec::new();
+            collect_files(&dir, config, &mut files);
+            let mut names: Vec<String> = files
+                .iter()
+                .map(|p| p.strip_prefix(&dir).unwrap().to_string_lossy().replace('\\', "/"))
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(collected(&ScanConfig::default()), vec!["main.rs", "notes.md"]);
+
+        let config = ScanConfig::new(true, &[] as &[&str], &[".ORG", "md", "org"]);
+        assert_eq!(config.extensions, vec!["org", "md"]);
+        assert_eq!(
+            collected(&config),
+            vec!["node_modules/lib/README.md", "notes.md", "todo.org"]
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_depth_limit() {
+        let dir = std::env::temp_dir().join(format!("superbrain_depth_{}", uuid::Uuid::new_v4()));
+        let deepest = dir.join("l1").join("l2").join("l3");
+        std::fs::create_dir_all(&deepest).unwrap();
+        for file in ["root.md", "l1/one.md", "l1/l2/two.md", "l1/l2/l3/three.md"] {
+            std::fs::write(dir.join(file), "content").unwrap();
+        }
+
+        let collected = |max_depth: u32| {
+            let mut files = Vec::new();
+            collect_files(&dir, &ScanConfig::default().with_max_depth(max_depth), &mut files);
+            let mut names: Vec<String> = files
+                .iter()
+                .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(collected(0), vec!["root.md"]);
+        assert_eq!(collected(1), vec!["one.md", "root.md"]);
+        assert_eq!(collected(2), vec!["one.md", "root.md", "two.md"]);
+        assert_eq!(collected(DEFAULT_MAX_DEPTH).len(), 4);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_scan_reports_progress() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (indexer, dir) = indexer_with_mock_ollama(requests).await;
+        let docs = dir.join("docs");
+        std::fs::create_dir_all(&docs).unwrap();
+        for name in ["a.md", "b.md", "c.txt"] {
+            std::fs::write(docs.join(name), format!("Notes in {}", name)).unwrap();
+        }
+        indexer.add_watch_dirs(vec![docs.clone()]);
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        indexer.set_progress_sender(tx);
+        assert_eq!(indexer.scan_all(false).await.unwrap(), 3);
+
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+        assert_eq!(events.len(), 4);
+        let mut paths = Vec::new();
+        for (i, event) in events[..3].iter().enumerate() {
+            match event {
+                IndexEvent::Progress(p) => {
+                    assert_eq!((p.processed, p.total), (i as u32 + 1, 3));
+                    paths.push(p.current_path.clone());
+                }
+                other => panic!("expected progress, got {:?}", other),
+            }
+        }
+        paths.sort();
+        let expected: Vec<String> = ["a.md", "b.md", "c.txt"]
+            .iter()
+            .map(|n| docs.join(n).to_string_lossy().to_string())
+            .collect();
+        assert_eq!(paths, expected);
+        assert_eq!(
+            events[3],
+            IndexEvent::Complete(IndexComplete {
+                files: 3,
+                chunks: 3
+            })
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_privacy_mode_blocks_indexing() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (indexer, dir) = indexer_with_mock_ollama(requests.clone()).await;
+        let docs = dir.join("docs");
+        std::fs::create_dir_all(&docs).unwrap();
+        std::fs::write(docs.join("notes.md"), "Private notes").unwrap();
+        indexer.add_watch_dirs(vec![docs.clone()]);
+
+        indexer.set_privacy_mode(true);
+        assert_eq!(indexer.scan_all(true).await.unwrap(), 0);
+        assert_eq!(indexer.index_file(&docs.join("notes.md"), true).await.unwrap(), 0);
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
+        assert_eq!(indexer.stats().unwrap().file_count, 0);
+
+        indexer.set_privacy_mode(false);
+        assert_eq!(indexer.scan_all(false).await.unwrap(), 1);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
-    let entries = match std::fs::read_dir(dir) {
-        Ok(e) => e,
-        Err(_) => return,
-    };
+    #[tokio::test]
+    async fn test_overlapping_scans_are_refused() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (indexer, dir) = indexer_with_mock_ollama(requests).await;
+        let docs = dir.join("docs");
+        std::fs::create_dir_all(&docs).unwrap();
+        std::fs::write(docs.join("notes.md"), "Meeting notes").unwrap();
+        indexer.add_watch_dirs(vec![docs]);
+
+        let (first, second) = tokio::join!(indexer.scan_all(true), indexer.scan_all(true));
+        assert_eq!(first.unwrap(), 1);
+        assert!(second.is_err());
+        assert!(!indexer.stats().unwrap().is_indexing);
 
-    for entry in entries.flatten() {
-        let path = entry.path();
-        let name = entry.file_name();
-        let name_str = name.to_string_lossy();
+        // A refused scan must not clear the flag held by the running one
+        indexer.is_indexing.store(true, Ordering::Release);
+        assert!(indexer.scan_all(true).await.is_err());
+        assert!(indexer.stats().unwrap().is_indexing);
 
-        // Skip hidden files/dirs
-        if name_str.starts_with('.') {
-            continue;
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_scan_matches_serial() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (serial, dir) = indexer_with_mock_ollama(requests.clone()).await;
+        let docs = dir.join("docs");
+        std::fs::create_dir_all(&docs).unwrap();
+        for i in 0..12 {
+            let text = format!("Paragraph {} of a long document about indexing. ", i);
+            let text = text.repeat(i * 10 + 1);
+            std::fs::write(docs.join(format!("doc{}.md", i)), text).unwrap();
         }
 
-        if path.is_dir() {
-            // Skip known undesirable directories
-            if SKIP_DIRS.contains(&name_str.as_ref()) {
-                continue;
+        serial.set_concurrency(1);
+        serial.add_watch_dirs(vec![docs.clone()]);
+        let serial_chunks = serial.scan_all(false).await.unwrap();
+
+        let db = dir.join("concurrent.db");
+        let concurrent = Arc::new(FileIndexer::new(db, serial.embeddings.clone()).unwrap());
+        concurrent.set_concurrency(4);
+        concurrent.add_watch_dirs(vec![docs.clone()]);
+        let concurrent_chunks = concurrent.scan_all(false).await.unwrap();
+
+        assert!(serial_chunks > 12);
+        assert_eq!(concurrent_chunks, serial_chunks);
+        let (a, b) = (serial.stats().unwrap(), concurrent.stats().unwrap());
+        assert_eq!((a.file_count, a.chunk_count), (12, serial_chunks));
+        assert_eq!((b.file_count, b.chunk_count), (12, serial_chunks));
+        assert!(!b.is_indexing);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_files_are_skipped() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (indexer, dir) = indexer_with_mock_ollama(requests.clone()).await;
+        indexer.set_scan_config(ScanConfig::default().with_max_file_bytes(64));
+
+        let small = dir.join("small.txt");
+        let large = dir.join("large.log");
+        std::fs::write(&small, "short note").unwrap();
+        std::fs::write(&large, "x".repeat(65)).unwrap();
+
+        assert_eq!(indexer.index_file(&large, false).await.unwrap(), 0);
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
+        assert_eq!(indexer.index_file(&small, false).await.unwrap(), 1);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // 0 lifts the limit
+        indexer.set_scan_config(ScanConfig::default().with_max_file_bytes(0));
+        assert_eq!(indexer.index_file(&large, false).await.unwrap(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_search_reads_rows_once() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (indexer, dir) = indexer_with_mock_ollama(requests).await;
+        let first = dir.join("first.md");
+        let second = dir.join("second.md");
+        std::fs::write(&first, "First notes").unwrap();
+        std::fs::write(&second, "Second notes").unwrap();
+        indexer.index_file(&first, false).await.unwrap();
+
+        assert_eq!(indexer.search("notes", 10).await.unwrap().len(), 1);
+        assert_eq!(indexer.rows_loaded.load(Ordering::Relaxed), 1);
+        indexer.search("notes", 10).await.unwrap();
+        indexer.search("other", 10).await.unwrap();
+        assert_eq!(indexer.rows_loaded.load(Ordering::Relaxed), 1);
+
+        // Writes after the load update the cache directly
+        indexer.index_file(&second, false).await.unwrap();
+        assert_eq!(indexer.search("notes", 10).await.unwrap().len(), 2);
+        indexer.remove_file(&first).unwrap();
+        let results = indexer.search("notes", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, second.to_string_lossy());
+        assert_eq!(indexer.rows_loaded.load(Ordering::Relaxed), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_search_reports_line_range() {
+        // Texts mentioning the phrase embed close to each other, the rest far away
+        let url = spawn_mock_server(|request| {
+            if request.path != "/api/embed" {
+                return (200, r#"{"models":[]}"#.to_string());
+            }
+            let embeddings: Vec<Vec<f32>> = request.body["input"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|input| match input.as_str().unwrap_or("").contains("zebra") {
+                    true => vec![1.0, 0.0],
+                    false => vec![0.0, 1.0],
+                })
+                .collect();
+            (200, serde_json::json!({ "embeddings": embeddings }).to_string())
+        })
+        .await;
+        let embeddings = EmbeddingModel::new().with_ollama_url(&url);
+        assert!(embeddings.refresh_provider().await);
+
+        let dir = std::env::temp_dir().join(format!("superbrain_lines_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let indexer = FileIndexer::new(dir.join("files.db"), Arc::new(embeddings)).unwrap();
+
+        // ~1500 words over 150 lines, with blank lines and one distinctive phrase
+        let phrase = "zebra quantum lighthouse";
+        let mut lines: Vec<String> = Vec::new();
+        for i in 0..150 {
+            if i % 10 == 0 {
+                lines.push(String::new());
             }
-            collect_files_recursive(&path, files, max_depth - 1);
-        } else if path.is_file() {
-            files.push(path);
+            lines.push(if i == 90 {
+                format!("the meeting notes mention {} near the harbour today", phrase)
+            } else {
+                (0..10).map(|w| format!("filler{}", w)).collect::<Vec<_>>().join(" ")
+            });
         }
+        let path = dir.join("long.txt");
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        assert!(indexer.index_file(&path, false).await.unwrap() > 1);
+
+        let results = indexer.search(phrase, 10).await.unwrap();
+        let top = &results[0];
+        assert!(top.chunk.contains(phrase));
+        assert!(top.start_line >= 1 && top.start_line <= top.end_line);
+        let span = lines[top.start_line as usize - 1..top.end_line as usize].join("\n");
+        assert!(span.contains(phrase), "lines {}-{}", top.start_line, top.end_line);
+        assert!(results.iter().all(|r| r.end_line <= lines.len() as u32));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Cold (SQLite load, what every search used to cost) vs warm (cached)
+    /// search over 20k 384-dim chunks. On a release build: ~100 ms vs ~17 ms.
+    /// Run with `cargo test --release -- --ignored bench_search_20k --nocapture`.
+    #[tokio::test]
+    #[ignore]
+    async fn bench_search_20k_chunks() {
+        let dir = std::env::temp_dir().join(format!("superbrain_bench_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let embeddings = Arc::new(EmbeddingModel::new());
+        let indexer = FileIndexer::new(dir.join("files.db"), embeddings).unwrap();
+
+        let mut conn = indexer.open_connection().unwrap();
+        let tx = conn.transaction().unwrap();
+        for f in 0..1000 {
+            let path = format!("/bench/file{}.md", f);
+            tx.execute(
+                "INSERT INTO file_index (path, name, ext, modified, chunk_count) VALUES (?1, ?2, 'md', 0, 20)",
+                params![path, format!("file{}.md", f)],
+            )
+            .unwrap();
+            for c in 0..20 {
+                let text = format!("chunk {} of file {} about topic {}", c, f, (f * 20 + c) % 97);
+                let vector = indexer.embeddings.embed(&text).await.unwrap();
+                tx.execute(
+                    "INSERT INTO file_chunks (file_path, chunk_index, content, vector) VALUES (?1, ?2, ?3, ?4)",
+                    params![path, c, text, vector_to_bytes(&vector)],
+                )
+                .unwrap();
+            }
+        }
+        tx.commit().unwrap();
+
+        let start = std::time::Instant::now();
+        indexer.search("topic 42", 10).await.unwrap();
+        let cold = start.elapsed();
+        let start = std::time::Instant::now();
+        for _ in 0..10 {
+            indexer.search("topic 42", 10).await.unwrap();
+        }
+        let warm = start.elapsed() / 10;
+        println!("20k chunks: first search {:?}, cached search {:?}", cold, warm);
+        assert_eq!(indexer.rows_loaded.load(Ordering::Relaxed), 20_000);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_file_is_not_reembedded() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (indexer, dir) = indexer_with_mock_ollama(requests.clone()).await;
+        let file = dir.join("notes.md");
+        std::fs::write(&file, "Meeting notes about the indexer").unwrap();
+
+        assert_eq!(indexer.index_file(&file, false).await.unwrap(), 1);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // Same mtime: skipped without embedding
+        assert_eq!(indexer.index_file(&file, false).await.unwrap(), 0);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // Forced, or touched on disk: embedded again
+        assert_eq!(indexer.index_file(&file, true).await.unwrap(), 1);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+        std::fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(indexer.index_file(&file, false).await.unwrap(), 1);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_file_chunks_are_embedded_in_batches() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (indexer, dir) = indexer_with_mock_ollama(requests.clone()).await;
+        let file = dir.join("long.txt");
+        let text = (0..3000).map(|i| format!("word{}", i)).collect::<Vec<_>>().join(" ");
+        std::fs::write(&file, text).unwrap();
+
+        let stored = indexer.index_file(&file, false).await.unwrap() as usize;
+        assert!(stored > 1);
+        assert_eq!(requests.load(Ordering::SeqCst), stored.div_ceil(EMBED_BATCH_SIZE));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_failed_chunk_write_leaves_file_unindexed() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (indexer, dir) = indexer_with_mock_ollama(requests).await;
+        let file = dir.join("long.txt");
+        let text = (0..3000).map(|i| format!("word{}", i)).collect::<Vec<_>>().join(" ");
+        std::fs::write(&file, text).unwrap();
+
+        // Make the second chunk insert fail
+        let raw = Connection::open(dir.join("files.db")).unwrap();
+        raw.execute_batch(
+            "CREATE TRIGGER fail_chunk BEFORE INSERT ON file_chunks WHEN NEW.chunk_index = 1
+             BEGIN SELECT RAISE(ABORT, 'disk full'); END;",
+        )
+        .unwrap();
+        let err = indexer.index_file(&file, false).await.unwrap_err();
+        assert!(err.contains("disk full"), "{}", err);
+
+        // Neither the chunks nor the new modified time were kept
+        let path = file.to_string_lossy();
+        assert_eq!(indexer.indexed_modified(&path).unwrap(), None);
+        assert_eq!(indexer.stats().unwrap().chunk_count, 0);
+
+        // So the next scan picks the file up again
+        raw.execute_batch("DROP TRIGGER fail_chunk;").unwrap();
+        let stored = indexer.index_file(&file, false).await.unwrap();
+        assert!(stored > 1);
+        assert_eq!(indexer.stats().unwrap().chunk_count, stored);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_deleted_file_leaves_search() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (indexer, dir) = indexer_with_mock_ollama(requests).await;
+        let kept = dir.join("kept.md");
+        let gone = dir.join("gone.md");
+        std::fs::write(&kept, "Notes that stay").unwrap();
+        std::fs::write(&gone, "Notes that get deleted").unwrap();
+        indexer.index_file(&kept, false).await.unwrap();
+        indexer.index_file(&gone, false).await.unwrap();
+
+        let found = |results: &[FileResult], path: &Path| {
+            results.iter().any(|r| r.path == path.to_string_lossy())
+        };
+        let results = indexer.search("notes", 10).await.unwrap();
+        assert!(found(&results, &gone));
+
+        std::fs::remove_file(&gone).unwrap();
+        assert!(indexer.remove_file(&gone).unwrap());
+        assert!(!indexer.remove_file(&gone).unwrap());
+
+        let results = indexer.search("notes", 10).await.unwrap();
+        assert!(!found(&results, &gone));
+        assert!(found(&results, &kept));
+        assert_eq!(indexer.stats().unwrap().chunk_count, 1);
+
+        // A scan also drops files deleted while the app wasn't watching
+        std::fs::remove_file(&kept).unwrap();
+        assert_eq!(indexer.purge_missing().unwrap(), 1);
+        assert_eq!(indexer.stats().unwrap().file_count, 0);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_purge_outside_watched() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (indexer, dir) = indexer_with_mock_ollama(requests).await;
+        let kept = dir.join("kept");
+        let dropped = dir.join("dropped");
+        // A sibling whose name shares the dropped folder's prefix
+        let similar = dir.join("dropped-archive");
+        for folder in [&kept, &dropped, &similar] {
+            std::fs::create_dir_all(folder.join("nested")).unwrap();
+            std::fs::write(folder.join("a.md"), "Top level notes").unwrap();
+            std::fs::write(folder.join("nested").join("b.md"), "Nested notes").unwrap();
+        }
+        indexer.add_watch_dirs(vec![kept.clone(), dropped.clone(), similar.clone()]);
+        assert_eq!(indexer.scan_all(false).await.unwrap(), 6);
+
+        assert!(indexer.remove_watch_dir(&dropped));
+        assert!(!indexer.remove_watch_dir(&dropped));
+        assert_eq!(indexer.purge_outside_watched().unwrap(), 2);
+
+        let stats = indexer.stats().unwrap();
+        assert_eq!((stats.file_count, stats.chunk_count, stats.watched_dirs), (4, 4, 2));
+        let results = indexer.search("notes", 10).await.unwrap();
+        assert_eq!(results.len(), 4);
+        assert!(results.iter().all(|r| !Path::new(&r.path).starts_with(&dropped)));
+        assert_eq!(indexer.purge_outside_watched().unwrap(), 0);
+
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
@@ -2,21 +2,37 @@
 //!
 //! Extracts text content from supported file types.
 
+use std::io::Read;
 use std::path::Path;
 
-/// Supported file extensions
-const SUPPORTED_EXTENSIONS: &[&str] = &[
+/// File extensions indexed unless the settings name others
+pub const DEFAULT_EXTENSIONS: &[&str] = &[
     "md", "txt", "rs", "ts", "tsx", "js", "jsx", "py", "json", "toml", "yaml", "yml", "html",
     "css", "sh", "bash", "zsh", "fish", "swift", "go", "java", "c", "cpp", "h", "hpp", "rb",
     "lua", "sql", "xml", "csv", "log", "conf", "cfg", "ini", "env", "pdf",
+    "docx",
 ];
 
-/// Check if a file extension is supported for indexing
-pub fn is_supported(ext: &str) -> bool {
-    SUPPORTED_EXTENSIONS.contains(&ext.to_lowercase().as_str())
+/// Check if a file extension is in the `supported` list (lowercase, no dot)
+pub fn is_supported(ext: &str, supported: &[String]) -> bool {
+    let ext = ext.to_lowercase();
+    supported.contains(&ext)
 }
 
-/// Parse a file and extract its text content
+/// Lowercase extensions, strip leading dots, and drop blanks and duplicates
+pub fn normalize_extensions<S: AsRef<str>>(extensions: &[S]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(extensions.len());
+    for ext in extensions {
+        let ext = ext.as_ref().trim().trim_start_matches('.').to_lowercase();
+        if !ext.is_empty() && !normalized.contains(&ext) {
+            normalized.push(ext);
+        }
+    }
+    normalized
+}
+
+/// Parse a file and extract its text content. Formats without a dedicated
+/// parser are read as UTF-8 text; binary files fail to read.
 pub fn parse_file(path: &Path) -> Result<String, String> {
     let ext = path
         .extension()
@@ -24,27 +40,55 @@ pub fn parse_file(path: &Path) -> Result<String, String> {
         .unwrap_or("")
         .to_lowercase();
 
-    if !is_supported(&ext) {
-        return Err(format!("Unsupported file type: {}", ext));
-    }
-
     // PDF gets special binary handling
     if ext == "pdf" {
         return parse_pdf(path);
     }
+    if ext == "docx" {
+        return parse_docx(path);
+    }
+
+    if looks_binary(path)? {
+        return Err(format!("Skipping binary file {:?}", path));
+    }
 
     // Read file content as text
     let content = std::fs::read_to_string(path)
         .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
 
-    // Strip content based on file type
+    // Strip content based on file type. Text keeps its blank lines and
+    // indentation so chunk line numbers and code structure match the file.
     match ext.as_str() {
         "json" => parse_json(&content),
         "html" | "xml" => parse_markup(&content),
-        _ => Ok(clean_text(&content)),
+        _ => Ok(trim_lines(&content)),
     }
 }
 
+/// Bytes inspected by `looks_binary`
+const SNIFF_BYTES: usize = 8192;
+
+/// Check the start of a file for NUL bytes or invalid UTF-8 without reading
+/// the whole thing
+fn looks_binary(path: &Path) -> Result<bool, String> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    file.by_ref()
+        .take(SNIFF_BYTES as u64)
+        .read_to_end(&mut head)
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+
+    if head.contains(&0) {
+        return Ok(true);
+    }
+    Ok(match std::str::from_utf8(&head) {
+        Ok(_) => false,
+        // A character cut off at the end of the sample is fine
+        Err(e) => e.error_len().is_some(),
+    })
+}
+
 /// Parse a PDF file and extract text
 fn parse_pdf(path: &Path) -> Result<String, String> {
     let bytes = std::fs::read(path)
@@ -56,6 +100,56 @@ fn parse_pdf(path: &Path) -> Result<String, String> {
     Ok(clean_text(&text))
 }
 
+/// Extract paragraph text from a Word document's `word/document.xml`
+fn parse_docx(path: &Path) -> Result<String, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to read DOCX {:?}: {}", path, e))?;
+    // Legacy binary .doc files renamed to .docx are not zip archives
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| format!("Not a valid DOCX archive {:?}: {}", path, e))?;
+    let mut xml = String::new();
+    archive
+        .by_name("word/document.xml")
+        .map_err(|e| format!("DOCX has no document body: {}", e))?
+        .read_to_string(&mut xml)
+        .map_err(|e| format!("Failed to read DOCX body: {}", e))?;
+
+    Ok(docx_xml_to_text(&xml))
+}
+
+/// Turn WordprocessingML into plain text: one line per paragraph, tabs and
+/// breaks kept, everything else stripped
+fn docx_xml_to_text(xml: &str) -> String {
+    let mut text = String::with_capacity(xml.len() / 4);
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        let name = tag.split([' ', '/']).find(|s| !s.is_empty()).unwrap_or("");
+        match name {
+            "w:p" if tag.starts_with('/') || tag.ends_with('/') => text.push('\n'),
+            "w:tab" => text.push('\t'),
+            "w:br" | "w:cr" => text.push('\n'),
+            _ => {}
+        }
+        rest = &rest[start + end + 1..];
+    }
+
+    clean_text(&decode_xml_entities(&text))
+}
+
+/// Decode the predefined XML entities
+fn decode_xml_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
 /// Clean raw text content
 fn clean_text(content: &str) -> String {
     // Remove excessive whitespace and empty lines
@@ -67,11 +161,44 @@ fn clean_text(content: &str) -> String {
         .join("\n")
 }
 
-/// Parse JSON and extract meaningful text values
+/// Strip trailing whitespace, keeping blank lines and indentation
+fn trim_lines(content: &str) -> String {
+    content.lines().map(|line| line.trim_end()).collect::<Vec<_>>().join("\n")
+}
+
+/// Parse JSON and extract meaningful text values, one per line and prefixed
+/// with their key. Numbers, booleans and nulls are dropped. Content that
+/// isn't a single JSON document (e.g. JSONL) is kept as cleaned text.
 fn parse_json(content: &str) -> Result<String, String> {
-    // For JSON, we extract string values that likely contain meaningful text
-    // Simple approach: just return the raw content cleaned up
-    Ok(clean_text(content))
+    let value: serde_json::Value = match serde_json::from_str(content) {
+        Ok(value) => value,
+        Err(_) => return Ok(clean_text(content)),
+    };
+    let mut lines = Vec::new();
+    collect_json_strings(&value, None, &mut lines);
+    Ok(clean_text(&lines.join("\n")))
+}
+
+/// Append every string in `value` to `lines`, as `key: value` when it is an
+/// object field
+fn collect_json_strings(value: &serde_json::Value, key: Option<&str>, lines: &mut Vec<String>) {
+    match value {
+        serde_json::Value::String(s) if !s.trim().is_empty() => match key {
+            Some(key) => lines.push(format!("{}: {}", key, s)),
+            None => lines.push(s.clone()),
+        },
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_json_strings(item, key, lines);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for (k, v) in map {
+                collect_json_strings(v, Some(k), lines);
+            }
+        }
+        _ => {}
+    }
 }
 
 /// Parse HTML/XML and strip tags
@@ -114,13 +241,25 @@ mod tests {
 
     #[test]
     fn test_supported_extensions() {
-        assert!(is_supported("rs"));
-        assert!(is_supported("ts"));
-        assert!(is_supported("py"));
-        assert!(is_supported("md"));
-        assert!(is_supported("pdf"));
-        assert!(!is_supported("exe"));
-        assert!(!is_supported("png"));
+        let defaults = normalize_extensions(DEFAULT_EXTENSIONS);
+        assert_eq!(defaults.len(), DEFAULT_EXTENSIONS.len());
+        assert!(is_supported("rs", &defaults));
+        assert!(is_supported("ts", &defaults));
+        assert!(is_supported("py", &defaults));
+        assert!(is_supported("MD", &defaults));
+        assert!(is_supported("pdf", &defaults));
+        assert!(is_supported("docx", &defaults));
+        assert!(!is_supported("doc", &defaults));
+        assert!(!is_supported("exe", &defaults));
+        assert!(!is_supported("png", &defaults));
+    }
+
+    #[test]
+    fn test_normalize_extensions() {
+        assert_eq!(
+            normalize_extensions(&[".ORG", "md", " Md ", "", "txt", "."]),
+            vec!["org", "md", "txt"]
+        );
     }
 
     #[test]
@@ -130,6 +269,61 @@ mod tests {
         assert_eq!(result, "hello\nworld");
     }
 
+    #[test]
+    fn test_binary_files_are_skipped() {
+        let dir = std::env::temp_dir().join(format!("superbrain_bin_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut blob = b"id,name\n1,alpha\n".to_vec();
+        blob.extend_from_slice(&[0x00, 0x01, 0x02, 0xFF]);
+        blob.extend(std::iter::repeat_n(b'x', 1 << 20));
+        let csv = dir.join("data.csv");
+        std::fs::write(&csv, &blob).unwrap();
+        assert!(parse_file(&csv).unwrap_err().contains("binary file"));
+
+        let latin1 = dir.join("notes.txt");
+        std::fs::write(&latin1, b"caf\xE9 au lait").unwrap();
+        assert!(parse_file(&latin1).unwrap_err().contains("binary file"));
+
+        // A multi-byte character straddling the sniffed prefix is still text
+        let mut text = "a".repeat(SNIFF_BYTES - 1);
+        text.push_str("é and more");
+        let utf8 = dir.join("long.txt");
+        std::fs::write(&utf8, &text).unwrap();
+        assert!(parse_file(&utf8).unwrap().ends_with("é and more"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let json = r#"{
+            "title": "Quarterly plan",
+            "tags": ["roadmap", "  ", "q3"],
+            "owner": {"name": "Ada", "active": true, "age": 36},
+            "notes": null,
+            "items": [{"task": "Ship the indexer"}, 42]
+        }"#;
+        let text = parse_json(json).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        for expected in [
+            "title: Quarterly plan",
+            "tags: roadmap",
+            "tags: q3",
+            "name: Ada",
+            "task: Ship the indexer",
+        ] {
+            assert!(lines.contains(&expected), "missing {:?} in {:?}", expected, lines);
+        }
+        assert_eq!(lines.len(), 5);
+        assert!(!text.contains(['{', '}', '[', ']', '"']));
+        assert!(!text.contains("36") && !text.contains("true") && !text.contains("null"));
+
+        // JSONL isn't one document, so it's kept as text
+        let jsonl = "{\"a\": 1}\n{\"a\": 2}\n";
+        assert_eq!(parse_json(jsonl).unwrap(), "{\"a\": 1}\n{\"a\": 2}");
+    }
+
     #[test]
     fn test_parse_markup() {
         let html = "<p>Hello <b>world</b></p>";
@@ -138,4 +332,46 @@ mod tests {
         assert!(result.contains("world"));
         assert!(!result.contains("<p>"));
     }
+
+    #[test]
+    fn test_parse_docx() {
+        use std::io::Write;
+
+        let dir = std::env::temp_dir().join(format!("superbrain_docx_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let docx = dir.join("notes.docx");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&docx).unwrap());
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file("[Content_Types].xml", options).unwrap();
+        zip.write_all(br#"<?xml version="1.0"?><Types/>"#).unwrap();
+        zip.start_file("word/document.xml", options).unwrap();
+        zip.write_all(
+            br#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+<w:body>
+<w:p><w:pPr><w:pStyle w:val="Title"/></w:pPr><w:r><w:t>Quarterly plan</w:t></w:r></w:p>
+<w:p><w:r><w:t xml:space="preserve">Ship the </w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t>indexer</w:t></w:r><w:r><w:t> &amp; tests</w:t></w:r></w:p>
+<w:p/>
+<w:p><w:r><w:t>Owner:</w:t><w:tab/><w:t>Ada</w:t></w:r></w:p>
+</w:body>
+</w:document>"#,
+        )
+        .unwrap();
+        zip.finish().unwrap();
+
+        let text = parse_file(&docx).unwrap();
+        assert_eq!(text, "Quarterly plan\nShip the indexer & tests\nOwner:\tAda");
+
+        // A legacy binary document with a .docx name is rejected, not garbled
+        let fake = dir.join("legacy.docx");
+        std::fs::write(&fake, [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1]).unwrap();
+        assert!(parse_file(&fake).unwrap_err().contains("Not a valid DOCX"));
+        let doc = dir.join("legacy.doc");
+        std::fs::write(&doc, [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1]).unwrap();
+        assert!(parse_file(&doc).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -9,5 +9,7 @@ pub mod indexer;
 pub mod keychain;
 pub mod overlay;
 pub mod state;
+#[cfg(test)]
+mod test_support;
 pub mod tray;
 pub mod workflows;
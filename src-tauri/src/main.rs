@@ -11,11 +11,13 @@ mod indexer;
 mod keychain;
 mod overlay;
 mod state;
+#[cfg(test)]
+mod test_support;
 mod tray;
 mod workflows;
 
 use state::AppState;
-use tauri::Manager;
+use tauri::{Emitter, Manager};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -43,27 +45,53 @@ pub fn run() {
             // Initialize application state
             let app_state = AppState::new().expect("Failed to initialize SuperBrain");
 
-            // Try to initialize Ollama embeddings in background
-            let embeddings = app_state.embeddings.clone();
+            app.manage(app_state);
+
+            // Try to initialize Ollama embeddings in background, then size the
+            // memory store to the provider's vectors
+            let init_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
-                embeddings.try_init_ollama().await;
-            });
+                let state = init_handle.state::<AppState>();
+                state.embeddings.try_init_ollama().await;
+                state.sync_embedding_dimensions().await;
 
-            app.manage(app_state);
+                // Keep following Ollama as it is started or stopped
+                loop {
+                    tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
+                    if state.embeddings.refresh_provider().await {
+                        state.sync_embedding_dimensions().await;
+                        let provider = format!("{:?}", state.embeddings.provider());
+                        let _ = init_handle.emit("embedding-provider-changed", provider);
+                    }
+                }
+            });
 
             // Setup system tray
             tray::setup_tray(app.handle())?;
 
-            // Setup global shortcut (Cmd+Shift+Space)
-            use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
-            let shortcut: Shortcut = "CmdOrCtrl+Shift+Space"
-                .parse()
-                .expect("Failed to parse shortcut");
+            // Setup the global shortcut from settings, falling back to the default
+            let hotkey = app.state::<AppState>().settings.read().hotkey.clone();
+            let registered = overlay::parse_hotkey(&hotkey)
+                .and_then(|shortcut| overlay::register_hotkey(app.handle(), shortcut));
+            if let Err(e) = registered {
+                tracing::warn!("{}; using {} instead", e, state::DEFAULT_HOTKEY);
+                let shortcut = overlay::parse_hotkey(state::DEFAULT_HOTKEY)?;
+                overlay::register_hotkey(app.handle(), shortcut)?;
+            }
 
-            let handle = app.handle().clone();
-            app.global_shortcut().on_shortcut(shortcut, move |_app, _shortcut, _event| {
-                overlay::toggle(&handle);
-            })?;
+            // Forward indexing progress to the frontend
+            let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+            app.state::<AppState>().indexer.set_progress_sender(progress_tx);
+            let progress_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                use indexer::IndexEvent;
+                while let Some(event) = progress_rx.recv().await {
+                    let _ = match event {
+                        IndexEvent::Progress(p) => progress_handle.emit("index-progress", p),
+                        IndexEvent::Complete(c) => progress_handle.emit("index-complete", c),
+                    };
+                }
+            });
 
             // Start file watcher for indexed directories
             let indexer_ref = app.state::<AppState>().indexer.clone();
@@ -96,14 +124,16 @@ pub fn run() {
                         // Keep _watcher alive by moving it into the task
                         let _keep_alive = _watcher;
                         while let Some(change) = rx.recv().await {
-                            let path = match &change {
-                                indexer::watcher::FileChange::Created(p)
-                                | indexer::watcher::FileChange::Modified(p) => Some(p.clone()),
-                                indexer::watcher::FileChange::Deleted(_) => None,
-                            };
-                            if let Some(path) = path {
-                                tracing::debug!("File changed, re-indexing: {:?}", path);
-                                let _ = idx.index_file(&path).await;
+                            match change {
+                                indexer::watcher::FileChange::Created(path)
+                                | indexer::watcher::FileChange::Modified(path) => {
+                                    tracing::debug!("File changed, re-indexing: {:?}", path);
+                                    let _ = idx.index_file(&path, false).await;
+                                }
+                                indexer::watcher::FileChange::Deleted(path) => {
+                                    tracing::debug!("File deleted, un-indexing: {:?}", path);
+                                    let _ = idx.remove_file(&path);
+                                }
                             }
                         }
                     });
@@ -119,10 +149,6 @@ pub fn run() {
                 .state::<AppState>()
                 .engine
                 .clone();
-            let persistence = app
-                .state::<AppState>()
-                .persistence
-                .clone();
             let cycle_handle = app.handle().clone();
 
             tauri::async_runtime::spawn(async move {
@@ -142,26 +168,32 @@ pub fn run() {
 
                     // Run a cognitive cycle
                     let _ = engine.cycle();
-                    // Periodic flush
-                    let nodes = engine.memory.all_nodes();
-                    let _ = persistence.store_memories_batch(&nodes);
+                    // Periodic flush, skipped if one is already running
+                    if let Err(e) = commands::flush_off_thread(cycle_handle.clone(), false).await {
+                        tracing::warn!("Periodic flush failed: {}", e);
+                    }
                     tracing::debug!("Background cycle completed (battery={})", on_battery);
 
                     tray::set_status(&cycle_handle, tray::TrayStatus::Idle);
                 }
             });
 
-            // Start clipboard monitoring (poll every 2s)
+            // Start clipboard monitoring (poll every 2s, paused in privacy mode)
             let context_ref = app.state::<AppState>().context.clone();
+            let clipboard_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
-                let mut last_clipboard = String::new();
                 loop {
                     tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+                    let privacy_mode = clipboard_handle
+                        .state::<AppState>()
+                        .settings
+                        .read()
+                        .privacy_mode;
+                    if privacy_mode {
+                        continue;
+                    }
                     if let Some(current) = get_clipboard_text() {
-                        let trimmed = current.trim().to_string();
-                        if !trimmed.is_empty() && trimmed != last_clipboard {
-                            last_clipboard = trimmed.clone();
-                            context_ref.record_clipboard(trimmed);
+                        if context_ref.observe_clipboard(&current) {
                             tracing::debug!("Clipboard captured");
                         }
                     }
@@ -180,18 +212,28 @@ pub fn run() {
             // Hide window on blur (click outside), but ignore blur events
             // that fire immediately after show (caused by shortcut key release)
             if let tauri::WindowEvent::Focused(false) = event {
-                if overlay::should_hide_on_blur() {
-                    let _ = window.hide();
+                if overlay::should_hide_on_blur(window.app_handle()) {
+                    overlay::hide(window.app_handle());
                 }
             }
         })
         .invoke_handler(tauri::generate_handler![
             commands::think,
+            commands::cancel_think,
+            commands::validate_claude_key,
+            commands::clear_conversation,
+            commands::get_ai_usage,
             commands::remember,
             commands::recall,
             commands::get_status,
             commands::get_settings,
             commands::update_settings,
+            commands::set_overlay_pinned,
+            commands::add_goal,
+            commands::list_goals,
+            commands::update_goal,
+            commands::add_belief,
+            commands::list_beliefs,
             commands::get_thoughts,
             commands::get_stats,
             commands::evolve,
@@ -201,8 +243,27 @@ pub fn run() {
             commands::run_workflow,
             commands::check_ollama,
             commands::get_clipboard_history,
+            commands::search_clipboard,
+            commands::backup_brain,
+            commands::restore_brain,
+            commands::compact_database,
             commands::add_indexed_folder,
+            commands::remove_indexed_folder,
             commands::flush,
+            commands::flush_in_background,
+            commands::get_connected_memories,
+            commands::get_memory_graph,
+            commands::set_distance_metric,
+            commands::connect_memories,
+            commands::disconnect_memories,
+            commands::update_memory,
+            commands::get_memory,
+            commands::delete_memory,
+            commands::clear_memory_type,
+            commands::export_brain,
+            commands::import_brain,
+            commands::learn,
+            commands::reembed_memories,
         ])
         .run(tauri::generate_context!())
         .expect("Error while running SuperBrain");
@@ -1,7 +1,11 @@
 //! Overlay window management for SuperBrain
 
+use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicI64, Ordering};
-use tauri::{AppHandle, Emitter, Manager};
+use tauri::{AppHandle, Emitter, Manager, PhysicalPosition, PhysicalSize, WebviewWindow};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+use crate::state::AppState;
 
 /// Timestamp (ms) of the last show() call — used to debounce blur events
 static LAST_SHOW_MS: AtomicI64 = AtomicI64::new(0);
@@ -11,6 +15,22 @@ static LAST_SHOW_MS: AtomicI64 = AtomicI64::new(0);
 /// the overlay on macOS.
 const BLUR_DEBOUNCE_MS: i64 = 300;
 
+/// Config key holding the overlay's last position and size
+const GEOMETRY_KEY: &str = "overlay_geometry";
+
+/// Pixels of the overlay, across and down, that must land on a monitor for
+/// its saved position to be reused
+const MIN_VISIBLE_PX: i64 = 50;
+
+/// A rectangle in physical pixels: the overlay's frame or a monitor's bounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScreenRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
 /// Toggle the overlay window
 pub fn toggle(app: &AppHandle) {
     if let Some(window) = app.get_webview_window("main") {
@@ -25,7 +45,11 @@ pub fn toggle(app: &AppHandle) {
 /// Show the overlay window
 pub fn show(app: &AppHandle) {
     if let Some(window) = app.get_webview_window("main") {
-        let _ = window.center();
+        // Put the overlay back where it was, or center it the first time
+        // and whenever that spot is no longer on any monitor
+        if !restore_geometry(app, &window) {
+            let _ = window.center();
+        }
         let _ = window.show();
         let _ = window.set_focus();
         LAST_SHOW_MS.store(now_ms(), Ordering::Relaxed);
@@ -36,16 +60,144 @@ pub fn show(app: &AppHandle) {
 /// Hide the overlay window
 pub fn hide(app: &AppHandle) {
     if let Some(window) = app.get_webview_window("main") {
+        save_geometry(app, &window);
         let _ = window.hide();
         let _ = window.emit("overlay-hidden", ());
     }
 }
 
-/// Returns true if enough time has passed since the last show() that a blur
-/// event should be honoured.  Called from the `on_window_event` handler.
-pub fn should_hide_on_blur() -> bool {
-    let shown_at = LAST_SHOW_MS.load(Ordering::Relaxed);
-    now_ms() - shown_at > BLUR_DEBOUNCE_MS
+/// Save the overlay's position and size so the next show() can restore them
+fn save_geometry(app: &AppHandle, window: &WebviewWindow) {
+    let (Ok(position), Ok(size)) = (window.outer_position(), window.inner_size()) else {
+        return;
+    };
+    if size.width == 0 || size.height == 0 {
+        return;
+    }
+    let geometry = ScreenRect {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    };
+    let saved = serde_json::to_string(&geometry)
+        .map_err(|e| format!("Serialize error: {}", e))
+        .and_then(|json| app.state::<AppState>().persistence.store_config(GEOMETRY_KEY, &json));
+    if let Err(e) = saved {
+        tracing::warn!("Failed to save overlay position: {}", e);
+    }
+}
+
+/// Move and resize the overlay to its saved geometry. Returns false when
+/// nothing is saved or the saved spot is off every monitor.
+fn restore_geometry(app: &AppHandle, window: &WebviewWindow) -> bool {
+    let Some(geometry) = app
+        .state::<AppState>()
+        .persistence
+        .load_config(GEOMETRY_KEY)
+        .ok()
+        .flatten()
+        .and_then(|json| serde_json::from_str::<ScreenRect>(&json).ok())
+    else {
+        return false;
+    };
+    let monitors: Vec<ScreenRect> = window
+        .available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .map(|monitor| ScreenRect {
+            x: monitor.position().x,
+            y: monitor.position().y,
+            width: monitor.size().width,
+            height: monitor.size().height,
+        })
+        .collect();
+    if !is_on_screen(&geometry, &monitors) {
+        return false;
+    }
+    let _ = window.set_size(PhysicalSize::new(geometry.width, geometry.height));
+    let _ = window.set_position(PhysicalPosition::new(geometry.x, geometry.y));
+    true
+}
+
+/// Whether enough of `window` overlaps one of `monitors` to be seen and
+/// dragged, e.g. after a display it was on has been unplugged
+fn is_on_screen(window: &ScreenRect, monitors: &[ScreenRect]) -> bool {
+    let span = |start: i32, len: u32| (start as i64, start as i64 + len as i64);
+    let (left, right) = span(window.x, window.width);
+    let (top, bottom) = span(window.y, window.height);
+    let min_across = MIN_VISIBLE_PX.min(window.width as i64);
+    let min_down = MIN_VISIBLE_PX.min(window.height as i64);
+    monitors.iter().any(|monitor| {
+        let (m_left, m_right) = span(monitor.x, monitor.width);
+        let (m_top, m_bottom) = span(monitor.y, monitor.height);
+        let across = right.min(m_right) - left.max(m_left);
+        let down = bottom.min(m_bottom) - top.max(m_top);
+        across >= min_across && down >= min_down
+    })
+}
+
+/// Pin or unpin the overlay. While pinned, losing focus doesn't hide it.
+pub fn set_pinned(app: &AppHandle, pinned: bool) -> Result<(), String> {
+    app.state::<AppState>().set_overlay_pinned(pinned)?;
+    crate::tray::set_pin_checked(app, pinned);
+    let _ = app.emit("overlay-pinned", pinned);
+    Ok(())
+}
+
+/// Whether the overlay is currently pinned
+pub fn is_pinned(app: &AppHandle) -> bool {
+    app.try_state::<AppState>()
+        .map(|state| state.settings.read().overlay_pinned)
+        .unwrap_or(false)
+}
+
+/// Parse an accelerator string such as "CmdOrCtrl+Shift+Space"
+pub fn parse_hotkey(hotkey: &str) -> Result<Shortcut, String> {
+    hotkey
+        .trim()
+        .parse()
+        .map_err(|e| format!("Invalid hotkey \"{}\": {}", hotkey, e))
+}
+
+/// Make `hotkey` toggle the overlay. Only the key press toggles; the
+/// release is ignored so one keystroke doesn't show and hide it again.
+pub fn register_hotkey(app: &AppHandle, hotkey: Shortcut) -> Result<(), String> {
+    app.global_shortcut()
+        .on_shortcut(hotkey, |app, _shortcut, event| {
+            if event.state == ShortcutState::Pressed {
+                toggle(app);
+            }
+        })
+        .map_err(|e| format!("Failed to register hotkey: {}", e))
+}
+
+/// Swap the overlay hotkey from `old` to `new`. If `new` can't be
+/// registered, `old` is put back and the error returned.
+pub fn replace_hotkey(app: &AppHandle, old: &str, new: Shortcut) -> Result<(), String> {
+    let old = parse_hotkey(old).ok();
+    if let Some(old) = old {
+        let _ = app.global_shortcut().unregister(old);
+    }
+    if let Err(e) = register_hotkey(app, new) {
+        if let Some(old) = old {
+            let _ = register_hotkey(app, old);
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Returns true if the overlay isn't pinned and enough time has passed since
+/// the last show() that a blur event should be honoured.  Called from the
+/// `on_window_event` handler.
+pub fn should_hide_on_blur(app: &AppHandle) -> bool {
+    blur_should_hide(LAST_SHOW_MS.load(Ordering::Relaxed), now_ms(), is_pinned(app))
+}
+
+/// Whether a blur at `now` (ms) should hide a window shown at `shown_at`
+fn blur_should_hide(shown_at: i64, now: i64, pinned: bool) -> bool {
+    !pinned && now - shown_at > BLUR_DEBOUNCE_MS
 }
 
 fn now_ms() -> i64 {
@@ -54,3 +206,70 @@ fn now_ms() -> i64 {
         .map(|d| d.as_millis() as i64)
         .unwrap_or(0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blur_is_ignored_right_after_show() {
+        let shown_at = 1_000_000;
+        // The shortcut's key release lands within the debounce window
+        assert!(!blur_should_hide(shown_at, shown_at, false));
+        assert!(!blur_should_hide(shown_at, shown_at + 50, false));
+        assert!(!blur_should_hide(shown_at, shown_at + BLUR_DEBOUNCE_MS, false));
+        // A real click elsewhere, later on, hides the overlay
+        assert!(blur_should_hide(shown_at, shown_at + BLUR_DEBOUNCE_MS + 1, false));
+        assert!(blur_should_hide(shown_at, shown_at + 10_000, false));
+        // Never shown yet: blur always hides
+        assert!(blur_should_hide(0, shown_at, false));
+    }
+
+    #[test]
+    fn test_pinned_overlay_ignores_blur() {
+        let shown_at = 1_000_000;
+        assert!(!blur_should_hide(shown_at, shown_at + 10_000, true));
+        assert!(!blur_should_hide(0, shown_at, true));
+        // Unpinning restores the normal behaviour
+        assert!(blur_should_hide(shown_at, shown_at + 10_000, false));
+    }
+
+    #[test]
+    fn test_is_on_screen() {
+        let rect = |x, y, width, height| ScreenRect { x, y, width, height };
+        let laptop = rect(0, 0, 2560, 1600);
+        let external = rect(2560, -200, 3840, 2160);
+        let overlay = |x, y| rect(x, y, 680, 480);
+
+        assert!(is_on_screen(&overlay(940, 560), &[laptop]));
+        // On the second monitor, including its part above the first
+        assert!(is_on_screen(&overlay(4000, -150), &[laptop, external]));
+        // Straddling both monitors
+        assert!(is_on_screen(&overlay(2300, 100), &[laptop, external]));
+        // Mostly off the edge but a grabbable strip still shows
+        assert!(is_on_screen(&overlay(2500, 1000), &[laptop]));
+        assert!(!is_on_screen(&overlay(2530, 1000), &[laptop]));
+        // The external monitor was unplugged
+        assert!(!is_on_screen(&overlay(4000, 300), &[laptop]));
+        assert!(!is_on_screen(&overlay(-700, 0), &[laptop]));
+        assert!(!is_on_screen(&overlay(0, 0), &[]));
+    }
+
+    #[test]
+    fn test_parse_hotkey() {
+        for valid in [
+            crate::state::DEFAULT_HOTKEY,
+            "Alt+Space",
+            "Ctrl+Shift+K",
+            "Cmd+Option+F5",
+            " Shift+Digit1 ",
+            "F12",
+        ] {
+            assert!(parse_hotkey(valid).is_ok(), "{} should parse", valid);
+        }
+        for invalid in ["", "Ctrl+", "Ctrl++Space", "Ctrl+Shift", "Hyper+Space", "Ctrl+A+B"] {
+            let err = parse_hotkey(invalid).unwrap_err();
+            assert!(err.starts_with("Invalid hotkey"), "{}", err);
+        }
+    }
+}
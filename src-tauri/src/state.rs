@@ -2,31 +2,118 @@
 //!
 //! Wraps CognitiveEngine + EmbeddingModel + Persistence in Arc for Tauri managed state.
 
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
 use tokio::sync::Notify;
 
+use crate::ai::cancel::Cancellations;
+use crate::ai::claude::ClaudeProvider;
+use crate::ai::conversation::Conversations;
+use crate::ai::ollama::OllamaProvider;
+use crate::ai::usage::UsageTracker;
 use crate::ai::AiProvider;
-use crate::brain::cognitive::CognitiveEngine;
-use crate::brain::embeddings::EmbeddingModel;
-use crate::brain::persistence::BrainPersistence;
-use crate::brain::types::CognitiveConfig;
-use crate::context::ContextManager;
-use crate::indexer::FileIndexer;
+use crate::brain::cognitive::{Belief, CognitiveEngine, Goal, RecallResult, MAX_THOUGHTS};
+use crate::brain::crypto::Cipher;
+use crate::brain::embeddings::{EmbeddingModel, DEFAULT_OLLAMA_EMBED_MODEL};
+use crate::brain::memory::NativeMemory;
+use crate::brain::persistence::{BrainPersistence, CompactStats};
+use crate::brain::types::{CognitiveConfig, DistanceMetric};
+use crate::brain::utils::cosine_similarity;
+use crate::context::{
+    ClipboardEntry, ContextManager, SecretFilter, DEFAULT_MAX_ENTRY_BYTES,
+    DEFAULT_SECRET_PATTERNS,
+};
+use crate::indexer::{
+    parser, FileIndexer, ScanConfig, DEFAULT_CONCURRENCY, DEFAULT_MAX_DEPTH,
+    DEFAULT_MAX_FILE_BYTES, DEFAULT_SKIP_DIRS,
+};
+
+/// Keychain entry holding the database encryption key
+const STORAGE_KEY_NAME: &str = "brain_db_key";
+
+/// Semantic clipboard search drops entries less similar than this
+const MIN_CLIPBOARD_SIMILARITY: f32 = 0.3;
+
+/// Global shortcut that toggles the overlay unless the settings say otherwise
+pub const DEFAULT_HOTKEY: &str = "CmdOrCtrl+Shift+Space";
 
 /// Application settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppSettings {
     pub ai_provider: String,         // "ollama" | "claude" | "none"
+    /// Providers tried in order when `ai_provider` fails, e.g. ["ollama"]
+    #[serde(default)]
+    pub ai_fallbacks: Vec<String>,
     pub ollama_model: String,        // e.g. "llama3.2"
+    /// Ollama server used for chat and embeddings, e.g. "http://192.168.1.5:11434"
+    #[serde(default = "default_ollama_base_url")]
+    pub ollama_base_url: String,
+    /// Ollama model used for embeddings, e.g. "nomic-embed-text"
+    #[serde(default = "default_embedding_model")]
+    pub embedding_model: String,
     pub claude_api_key: Option<String>,
+    /// Claude model, e.g. "claude-haiku-4-5" for faster answers
+    #[serde(default = "default_claude_model")]
+    pub claude_model: String,
+    /// Longest Claude response, in tokens
+    #[serde(default = "default_claude_max_tokens")]
+    pub claude_max_tokens: u32,
+    /// Replaces the default AI instructions; memory context is still added
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Sampling temperature; unset keeps the model default
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// Nucleus sampling cutoff; unset keeps the model default
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    /// Indexed file chunks added to the AI context; 0 leaves files out
+    #[serde(default)]
+    pub file_context_chunks: usize,
+    /// Used for embeddings when neither ONNX nor Ollama is available
+    #[serde(default)]
+    pub openai_api_key: Option<String>,
     pub hotkey: String,              // e.g. "CmdOrCtrl+Shift+Space"
+    /// Keep the overlay open when another window takes focus
+    #[serde(default)]
+    pub overlay_pinned: bool,
     pub indexed_folders: Vec<String>,
+    /// Skip files excluded by .gitignore when indexing folders
+    #[serde(default = "default_true")]
+    pub respect_gitignore: bool,
+    /// Directory names skipped when indexing folders
+    #[serde(default = "default_skip_dirs")]
+    pub skip_dirs: Vec<String>,
+    /// File extensions indexed, lowercase without the dot
+    #[serde(default = "default_index_extensions")]
+    pub index_extensions: Vec<String>,
+    /// Files embedded at once while indexing folders
+    #[serde(default = "default_index_concurrency")]
+    pub index_concurrency: usize,
+    /// Files larger than this are not indexed; 0 means no limit
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
+    /// Subfolder levels indexed below each folder; 0 means only its own files
+    #[serde(default = "default_max_depth")]
+    pub max_depth: u32,
+    /// Clipboard entries longer than this are cut; 0 means no limit
+    #[serde(default = "default_clipboard_max_bytes")]
+    pub clipboard_max_bytes: usize,
+    /// Keep likely passwords, keys, and tokens out of the clipboard history
+    #[serde(default = "default_true")]
+    pub clipboard_filter_secrets: bool,
+    /// Regex patterns of clipboard content never recorded
+    #[serde(default = "default_clipboard_denylist")]
+    pub clipboard_denylist: Vec<String>,
     pub theme: String,               // "dark" | "light" | "system"
     pub auto_start: bool,
     pub privacy_mode: bool,
+    /// Encrypt memories and clipboard history in the database, keyed from the Keychain
+    #[serde(default)]
+    pub encrypt_storage: bool,
     pub onboarded: bool,
 }
 
@@ -34,18 +121,87 @@ impl Default for AppSettings {
     fn default() -> Self {
         Self {
             ai_provider: "ollama".to_string(),
+            ai_fallbacks: vec![],
             ollama_model: "llama3.2".to_string(),
+            ollama_base_url: default_ollama_base_url(),
+            embedding_model: default_embedding_model(),
             claude_api_key: None,
-            hotkey: "CmdOrCtrl+Shift+Space".to_string(),
+            claude_model: default_claude_model(),
+            claude_max_tokens: default_claude_max_tokens(),
+            system_prompt: None,
+            temperature: None,
+            top_p: None,
+            file_context_chunks: 0,
+            openai_api_key: None,
+            hotkey: DEFAULT_HOTKEY.to_string(),
+            overlay_pinned: false,
             indexed_folders: vec![],
+            respect_gitignore: true,
+            skip_dirs: default_skip_dirs(),
+            index_extensions: default_index_extensions(),
+            index_concurrency: DEFAULT_CONCURRENCY,
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+            max_depth: DEFAULT_MAX_DEPTH,
+            clipboard_max_bytes: DEFAULT_MAX_ENTRY_BYTES,
+            clipboard_filter_secrets: true,
+            clipboard_denylist: default_clipboard_denylist(),
             theme: "dark".to_string(),
             auto_start: false,
             privacy_mode: false,
+            encrypt_storage: false,
             onboarded: false,
         }
     }
 }
 
+fn default_ollama_base_url() -> String {
+    crate::ai::ollama::DEFAULT_BASE_URL.to_string()
+}
+
+fn default_embedding_model() -> String {
+    DEFAULT_OLLAMA_EMBED_MODEL.to_string()
+}
+
+fn default_claude_model() -> String {
+    crate::ai::claude::DEFAULT_MODEL.to_string()
+}
+
+fn default_claude_max_tokens() -> u32 {
+    crate::ai::claude::DEFAULT_MAX_TOKENS
+}
+
+fn default_clipboard_max_bytes() -> usize {
+    DEFAULT_MAX_ENTRY_BYTES
+}
+
+fn default_clipboard_denylist() -> Vec<String> {
+    DEFAULT_SECRET_PATTERNS.iter().map(|p| p.to_string()).collect()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_skip_dirs() -> Vec<String> {
+    DEFAULT_SKIP_DIRS.iter().map(|d| d.to_string()).collect()
+}
+
+fn default_index_extensions() -> Vec<String> {
+    parser::DEFAULT_EXTENSIONS.iter().map(|e| e.to_string()).collect()
+}
+
+fn default_index_concurrency() -> usize {
+    DEFAULT_CONCURRENCY
+}
+
+fn default_max_file_bytes() -> u64 {
+    DEFAULT_MAX_FILE_BYTES
+}
+
+fn default_max_depth() -> u32 {
+    DEFAULT_MAX_DEPTH
+}
+
 /// System status for the frontend
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemStatus {
@@ -56,11 +212,33 @@ pub struct SystemStatus {
     pub ai_provider: String,
     pub ai_available: bool,
     pub embedding_provider: String,
+    /// Memories embedded by a different provider than the active one
+    pub mismatched_embeddings: u32,
+    pub belief_count: u32,
     pub learning_trend: String,
     pub indexed_files: u32,
     pub indexed_chunks: u32,
 }
 
+/// Outcome of asking for a background flush
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlushStatus {
+    /// A flush was handed to a background thread
+    Started,
+    /// Another flush is still writing; no second one was started
+    AlreadyRunning,
+}
+
+/// Database sizes before and after `AppState::compact_database`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactReport {
+    /// brain.db: memories, learner state, and settings
+    pub brain: CompactStats,
+    /// files.db: the file index
+    pub files: CompactStats,
+}
+
 /// Main application state
 pub struct AppState {
     pub engine: Arc<CognitiveEngine>,
@@ -69,8 +247,13 @@ pub struct AppState {
     pub indexer: Arc<FileIndexer>,
     pub context: Arc<ContextManager>,
     pub ai_provider: RwLock<Option<Box<dyn AiProvider>>>,
+    pub conversations: Conversations,
+    pub cancellations: Cancellations,
+    pub ai_usage: UsageTracker,
     pub settings: RwLock<AppSettings>,
     pub shutdown: Notify,
+    /// Held while a flush is writing, so flushes never overlap
+    flush_lock: Mutex<()>,
 }
 
 impl AppState {
@@ -80,36 +263,6 @@ impl AppState {
         let engine = CognitiveEngine::new(Some(CognitiveConfig::default()));
         let embeddings = EmbeddingModel::new();
 
-        // Restore persisted memories
-        match persistence.load_memories() {
-            Ok(memories) => {
-                let count = memories.len();
-                for node in memories {
-                    engine.memory.restore_node(node);
-                }
-                if count > 0 {
-                    tracing::info!("Restored {} memories from database", count);
-                }
-            }
-            Err(e) => {
-                tracing::warn!("Failed to load memories: {}", e);
-            }
-        }
-
-        // Restore Q-table
-        match persistence.load_q_table() {
-            Ok(entries) => {
-                let count = entries.len();
-                engine.learner.import_q_table(entries);
-                if count > 0 {
-                    tracing::info!("Restored {} Q-table entries", count);
-                }
-            }
-            Err(e) => {
-                tracing::warn!("Failed to load Q-table: {}", e);
-            }
-        }
-
         // Load settings
         let mut settings: AppSettings = match persistence.load_config("app_settings") {
             Ok(Some(json)) => serde_json::from_str(&json).unwrap_or_default(),
@@ -121,6 +274,38 @@ impl AppState {
             settings.claude_api_key = Some(key);
             tracing::info!("Loaded Claude API key from Keychain");
         }
+        if let Ok(Some(key)) = crate::keychain::get_secret("openai_api_key") {
+            settings.openai_api_key = Some(key);
+            tracing::info!("Loaded OpenAI API key from Keychain");
+        }
+
+        // Encrypt sensitive columns at rest when enabled
+        if settings.encrypt_storage {
+            match Self::storage_cipher() {
+                Ok(cipher) => persistence.set_cipher(Some(cipher)),
+                Err(e) => tracing::warn!("Database encryption unavailable: {}", e),
+            }
+        }
+
+        restore_brain_state(&persistence, &engine);
+
+        // Restore AI usage totals
+        let ai_usage = match persistence.load_config("ai_usage") {
+            Ok(Some(json)) => match serde_json::from_str(&json) {
+                Ok(totals) => UsageTracker::with_totals(totals),
+                Err(e) => {
+                    tracing::warn!("Failed to parse AI usage: {}", e);
+                    UsageTracker::new()
+                }
+            },
+            _ => UsageTracker::new(),
+        };
+
+        embeddings.set_privacy_mode(settings.privacy_mode);
+        embeddings.configure_openai(settings.openai_api_key.clone(), engine.memory.dimensions());
+        embeddings.set_ollama_url(&settings.ollama_base_url);
+        embeddings.set_ollama_model(&settings.embedding_model);
+        engine.memory.set_embedding_provider(&embeddings.provider_id());
 
         engine.set_running(true);
 
@@ -132,6 +317,24 @@ impl AppState {
             .join("SuperBrain")
             .join("files.db");
         let indexer = FileIndexer::new(index_db, embeddings.clone())?;
+        indexer.set_scan_config(Self::scan_config(&settings));
+        indexer.set_concurrency(settings.index_concurrency);
+        indexer.set_privacy_mode(settings.privacy_mode);
+
+        let context = ContextManager::new();
+        context.set_max_entry_bytes(settings.clipboard_max_bytes);
+        match Self::secret_filter(&settings) {
+            Ok(filter) => context.set_secret_filter(filter),
+            Err(e) => tracing::warn!("Clipboard denylist ignored: {}", e),
+        }
+
+        // Restore clipboard history (never kept in privacy mode)
+        if !settings.privacy_mode {
+            match persistence.load_clipboard(context.max_history()) {
+                Ok(entries) => context.restore_clipboard(entries),
+                Err(e) => tracing::warn!("Failed to load clipboard history: {}", e),
+            }
+        }
 
         let ai_provider = Self::build_ai_provider(&settings);
 
@@ -140,23 +343,99 @@ impl AppState {
             embeddings,
             persistence: Arc::new(persistence),
             indexer: Arc::new(indexer),
-            context: Arc::new(ContextManager::new()),
+            context: Arc::new(context),
             ai_provider: RwLock::new(ai_provider),
+            conversations: Conversations::default(),
+            cancellations: Cancellations::new(),
+            ai_usage,
             settings: RwLock::new(settings),
             shutdown: Notify::new(),
+            flush_lock: Mutex::new(()),
         })
     }
 
+    /// The database encryption key from the Keychain, created on first use
+    fn storage_cipher() -> Result<Cipher, String> {
+        if let Some(key) = crate::keychain::get_secret(STORAGE_KEY_NAME)? {
+            return Cipher::from_hex(&key);
+        }
+        let key = Cipher::generate_key_hex()?;
+        crate::keychain::store_secret(STORAGE_KEY_NAME, &key)?;
+        Cipher::from_hex(&key)
+    }
+
+    /// Turn database encryption on or off, rewriting stored memories and
+    /// clipboard history to match. Returns the number of memories rewritten.
+    /// Flushes wait until it is done, so none can delete rows the rewrite
+    /// is about to put back.
+    pub fn set_storage_encryption(&self, enabled: bool) -> Result<u32, String> {
+        let cipher = if enabled {
+            Some(Self::storage_cipher()?)
+        } else {
+            None
+        };
+        let _guard = self.flush_lock.lock();
+        self.persistence.reencrypt(cipher)
+    }
+
+    /// Clipboard secret filter from current settings; `None` when disabled
+    pub fn secret_filter(settings: &AppSettings) -> Result<Option<SecretFilter>, String> {
+        if !settings.clipboard_filter_secrets {
+            return Ok(None);
+        }
+        SecretFilter::new(&settings.clipboard_denylist).map(Some)
+    }
+
+    /// Indexer scan rules from current settings
+    pub fn scan_config(settings: &AppSettings) -> ScanConfig {
+        ScanConfig::new(
+            settings.respect_gitignore,
+            &settings.skip_dirs,
+            &settings.index_extensions,
+        )
+        .with_max_file_bytes(settings.max_file_bytes)
+        .with_max_depth(settings.max_depth)
+    }
+
     /// Build an AI provider from current settings
     pub fn build_ai_provider(settings: &AppSettings) -> Option<Box<dyn AiProvider>> {
-        match settings.ai_provider.as_str() {
+        Self::build_named_provider(&settings.ai_provider, settings)
+    }
+
+    /// Build `ai_provider` followed by its fallbacks, skipping any that
+    /// aren't usable (no API key, privacy mode) or are listed twice
+    pub fn build_ai_chain(settings: &AppSettings) -> Vec<Box<dyn AiProvider>> {
+        let mut names: Vec<&str> = Vec::new();
+        for name in std::iter::once(&settings.ai_provider).chain(&settings.ai_fallbacks) {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+        names
+            .into_iter()
+            .filter_map(|name| Self::build_named_provider(name, settings))
+            .collect()
+    }
+
+    fn build_named_provider(name: &str, settings: &AppSettings) -> Option<Box<dyn AiProvider>> {
+        match name {
             "ollama" => Some(Box::new(
-                crate::ai::ollama::OllamaProvider::new(&settings.ollama_model),
+                OllamaProvider::with_url(&settings.ollama_base_url, &settings.ollama_model)
+                    .with_system_prompt(settings.system_prompt.as_deref())
+                    .with_sampling(settings.temperature, settings.top_p),
             )),
+            "claude" if settings.privacy_mode => {
+                tracing::info!("Privacy mode is on, not using the Claude provider");
+                None
+            }
             "claude" => {
                 if let Some(ref key) = settings.claude_api_key {
                     if !key.is_empty() {
-                        return Some(Box::new(crate::ai::claude::ClaudeProvider::new(key)));
+                        let provider = ClaudeProvider::with_model(key, &settings.claude_model)
+                            .with_max_tokens(settings.claude_max_tokens)
+                            .with_system_prompt(settings.system_prompt.as_deref())
+                            .with_sampling(settings.temperature, settings.top_p);
+                        return Some(Box::new(provider));
                     }
                 }
                 None
@@ -165,30 +444,1088 @@ impl AppState {
         }
     }
 
+    /// Delete a memory from the engine and the database. Returns false if it
+    /// didn't exist.
+    pub fn delete_memory(&self, id: &str) -> Result<bool, String> {
+        let existed = self.engine.memory.delete(id);
+        self.persistence.delete_memory(id)?;
+        Ok(existed)
+    }
+
+    /// Create and save a goal
+    pub fn add_goal(&self, description: String, priority: f64) -> Result<Goal, String> {
+        let id = self.engine.add_goal(description, priority);
+        let goal = self.engine.get_goal(&id).ok_or("Goal was not created")?;
+        self.persistence.store_goal(&goal)?;
+        Ok(goal)
+    }
+
+    /// Set a goal's progress, which also moves its status, and save it
+    pub fn update_goal(&self, id: &str, progress: f64) -> Result<Goal, String> {
+        if !self.engine.update_goal(id, progress) {
+            return Err(format!("Goal not found: {}", id));
+        }
+        let goal = self
+            .engine
+            .get_goal(id)
+            .ok_or_else(|| format!("Goal not found: {}", id))?;
+        self.persistence.store_goal(&goal)?;
+        Ok(goal)
+    }
+
+    /// Switch the distance metric search uses and save it. Unknown names
+    /// fall back to cosine. Returns the name of the metric now in use.
+    pub fn set_distance_metric(&self, metric: &str) -> Result<String, String> {
+        let applied = self.engine.memory.set_metric(metric);
+        if applied == DistanceMetric::Cosine && !metric.eq_ignore_ascii_case("cosine") {
+            tracing::warn!("Unknown distance metric '{}', using cosine", metric);
+        }
+        self.persistence.store_config("distance_metric", applied.name())?;
+        Ok(applied.name().to_string())
+    }
+
+    /// Record and save a belief. Confidence is clamped to [0, 1].
+    pub fn add_belief(
+        &self,
+        content: String,
+        confidence: f64,
+        source: String,
+    ) -> Result<Belief, String> {
+        let id = self.engine.add_belief(content, confidence, source);
+        let belief = self.engine.get_belief(&id).ok_or("Belief was not created")?;
+        self.persistence.store_belief(&belief)?;
+        Ok(belief)
+    }
+
+    /// Link two memories and save both. Returns false if either is missing.
+    pub fn connect_memories(&self, id1: &str, id2: &str) -> Result<bool, String> {
+        if !self.engine.memory.connect(id1, id2) {
+            return Ok(false);
+        }
+        self.persist_nodes(&[id1, id2])?;
+        Ok(true)
+    }
+
+    /// Unlink two memories and save both. Returns false if they weren't linked.
+    pub fn disconnect_memories(&self, id1: &str, id2: &str) -> Result<bool, String> {
+        if !self.engine.memory.disconnect(id1, id2) {
+            return Ok(false);
+        }
+        self.persist_nodes(&[id1, id2])?;
+        Ok(true)
+    }
+
+    fn persist_nodes(&self, ids: &[&str]) -> Result<(), String> {
+        let nodes: Vec<_> = ids
+            .iter()
+            .filter_map(|id| self.engine.memory.get_node(id))
+            .collect();
+        self.persistence.store_memories_batch(&nodes)
+    }
+
+    /// The indexed file chunks most relevant to `query`, as AI context entries
+    pub async fn file_context(&self, query: &str, limit: usize) -> Vec<RecallResult> {
+        if limit == 0 {
+            return Vec::new();
+        }
+        match self.indexer.search(query, limit as u32).await {
+            Ok(files) => files.into_iter().map(crate::ai::file_context_entry).collect(),
+            Err(e) => {
+                tracing::warn!("File search for AI context failed: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Search the clipboard history. Substring matches come newest first;
+    /// `semantic` instead ranks entries by embedding similarity to `query`.
+    pub async fn search_clipboard(
+        &self,
+        query: &str,
+        limit: usize,
+        semantic: bool,
+    ) -> Result<Vec<ClipboardEntry>, String> {
+        if !semantic {
+            return Ok(self.context.search_clipboard(query, limit));
+        }
+
+        let entries = self.context.recent_clipboard(self.context.max_history());
+        if entries.is_empty() {
+            return Ok(entries);
+        }
+        let query_vec = self.embeddings.embed(query).await?;
+        let texts: Vec<&str> = entries.iter().map(|e| e.content.as_str()).collect();
+        let vectors = self.embeddings.embed_batch(&texts).await?;
+
+        let mut scored: Vec<(f32, ClipboardEntry)> = vectors
+            .iter()
+            .map(|v| cosine_similarity(&query_vec, v))
+            .zip(entries)
+            .filter(|(similarity, _)| *similarity >= MIN_CLIPBOARD_SIMILARITY)
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Ok(scored.into_iter().take(limit).map(|(_, e)| e).collect())
+    }
+
+    /// Count an AI answer toward its provider's totals and save them
+    pub fn record_ai_usage(&self, provider: &str, tokens_used: Option<u32>) {
+        self.ai_usage.record(provider, tokens_used);
+        match serde_json::to_string(&self.ai_usage.totals()) {
+            Ok(json) => {
+                if let Err(e) = self.persistence.store_config("ai_usage", &json) {
+                    tracing::warn!("Failed to save AI usage: {}", e);
+                }
+            }
+            Err(e) => tracing::warn!("Failed to serialize AI usage: {}", e),
+        }
+    }
+
     /// Refresh the AI provider (call after settings change)
     pub fn refresh_ai_provider(&self) {
         let settings = self.settings.read().clone();
         *self.ai_provider.write() = Self::build_ai_provider(&settings);
     }
 
-    /// Persist current state to disk
+    /// Save current settings to SQLite (API keys stripped — they're in Keychain)
+    pub fn persist_settings(&self) -> Result<(), String> {
+        let json = self.stored_settings_json()?;
+        self.persistence.store_config("app_settings", &json)
+    }
+
+    /// Current settings as saved in the `app_settings` row. API keys are
+    /// left out; they live in the Keychain.
+    fn stored_settings_json(&self) -> Result<String, String> {
+        let mut settings = self.settings.read().clone();
+        settings.claude_api_key = None;
+        settings.openai_api_key = None;
+        serde_json::to_string(&settings).map_err(|e| format!("Serialize error: {}", e))
+    }
+
+    /// Pin or unpin the overlay and save the choice
+    pub fn set_overlay_pinned(&self, pinned: bool) -> Result<(), String> {
+        self.settings.write().overlay_pinned = pinned;
+        self.persist_settings()
+    }
+
+    /// Stop watching `folders` and drop their files from the index. The
+    /// default folders are always watched and are left alone. Returns how
+    /// many files were removed.
+    pub fn unwatch_folders(&self, folders: &[PathBuf]) -> Result<u32, String> {
+        let default_dirs = crate::indexer::watcher::default_watch_dirs();
+        let unwatched = folders
+            .iter()
+            .filter(|dir| !default_dirs.contains(dir))
+            .filter(|dir| self.indexer.remove_watch_dir(dir))
+            .count();
+        if unwatched == 0 {
+            return Ok(0);
+        }
+        self.indexer.purge_outside_watched()
+    }
+
+    /// Stop indexing a folder: drop it from the settings and the watch list,
+    /// purge its files and save. Returns how many files were removed.
+    pub fn remove_indexed_folder(&self, path: &str) -> Result<u32, String> {
+        let listed = {
+            let mut settings = self.settings.write();
+            let before = settings.indexed_folders.len();
+            settings.indexed_folders.retain(|f| f != path);
+            settings.indexed_folders.len() != before
+        };
+        let purged = self.unwatch_folders(&[PathBuf::from(path)])?;
+        if !listed && purged == 0 {
+            return Err(format!("Folder is not indexed: {}", path));
+        }
+        self.persist_settings()?;
+        tracing::info!("Stopped indexing {} ({} files removed)", path, purged);
+        Ok(purged)
+    }
+
+    /// Record the active embedding provider on new memories and warn when
+    /// existing ones were embedded by another
+    pub fn sync_embedding_provider(&self) {
+        let memory = &self.engine.memory;
+        let provider = self.embeddings.provider_id();
+        if memory.embedding_provider() == provider {
+            return;
+        }
+        memory.set_embedding_provider(&provider);
+
+        let mismatched = memory.mismatched_provider_ids().len();
+        if mismatched > 0 {
+            tracing::warn!(
+                "{} memories were embedded by another provider than {}; \
+                 recall ignores their meaning until they are re-embedded",
+                mismatched,
+                provider
+            );
+        }
+    }
+
+    /// Match the memory store's vector width to the active embedding provider.
+    /// A non-empty store keeps its width; new memories from a provider of a
+    /// different width are then rejected rather than mixed in.
+    pub async fn sync_embedding_dimensions(&self) {
+        self.sync_embedding_provider();
+        let detected = match self.embeddings.detect_dimensions().await {
+            Ok(dim) => dim,
+            Err(e) => {
+                tracing::warn!("Failed to detect embedding dimensions: {}", e);
+                return;
+            }
+        };
+
+        let current = self.engine.memory.dimensions();
+        if detected == current {
+            return;
+        }
+
+        match self.engine.memory.set_dimensions(detected) {
+            Ok(()) => {
+                tracing::info!("Memory store now uses {}-dim vectors", detected);
+                if let Err(e) = self
+                    .persistence
+                    .store_config("embedding_dimensions", &detected.to_string())
+                {
+                    tracing::warn!("Failed to save embedding dimensions: {}", e);
+                }
+            }
+            Err(e) => tracing::warn!(
+                "Embedding provider produces {}-dim vectors but the store is {}-dim: {}",
+                detected,
+                current,
+                e
+            ),
+        }
+    }
+
+    /// Checkpoint and vacuum both databases. Flushes wait until it is done,
+    /// and it refuses to run during a file scan.
+    pub fn compact_database(&self) -> Result<CompactReport, String> {
+        let _guard = self.flush_lock.lock();
+        let files = self.indexer.compact()?;
+        let brain = self.persistence.compact()?;
+        tracing::info!(
+            "Compacted databases: brain {} -> {} bytes, files {} -> {} bytes",
+            brain.before_bytes,
+            brain.after_bytes,
+            files.before_bytes,
+            files.after_bytes
+        );
+        Ok(CompactReport { brain, files })
+    }
+
+    /// Save current state, then copy the whole database to `dest`.
+    /// Returns the size of the backup in bytes.
+    pub fn backup_brain(&self, dest: &std::path::Path) -> Result<u64, String> {
+        self.flush()?;
+        self.persistence.backup_to(dest)
+    }
+
+    /// Replace the database with the backup at `src` and reload memories,
+    /// learner state, goals, and beliefs from it. Settings stay as they are.
+    /// Returns the number of memories restored.
+    pub fn restore_brain(&self, src: &std::path::Path) -> Result<u32, String> {
+        BrainPersistence::validate_backup(src)?;
+
+        // Flushes wait until the reload is done, so none can save half-cleared
+        // or stale state over the restored database
+        let _guard = self.flush_lock.lock();
+        self.engine.memory.clear();
+        self.engine.learner.clear_learned();
+        let restored = self.persistence.restore_from(src);
+        // Reload from the restored file, or from the untouched one on failure
+        restore_brain_state(&self.persistence, &self.engine);
+        restored?;
+
+        tracing::info!("Restored brain from {}", src.display());
+        Ok(self.engine.memory.len())
+    }
+
+    /// Persist current state to disk, waiting for any flush already running
     pub fn flush(&self) -> Result<(), String> {
-        // Save memories
-        let nodes = self.engine.memory.all_nodes();
-        self.persistence.store_memories_batch(&nodes)?;
+        let _guard = self.flush_lock.lock();
+        self.write_state()
+    }
+
+    /// Persist current state unless another flush is already running.
+    /// Returns false if it skipped.
+    pub fn try_flush(&self) -> Result<bool, String> {
+        match self.flush_lock.try_lock() {
+            Some(_guard) => self.write_state().map(|_| true),
+            None => Ok(false),
+        }
+    }
 
-        // Save Q-table
+    /// Whether a flush is writing right now
+    pub fn is_flushing(&self) -> bool {
+        self.flush_lock.is_locked()
+    }
+
+    /// Write everything in one transaction, so a failure part-way leaves
+    /// the last flush intact. Callers hold `flush_lock`.
+    fn write_state(&self) -> Result<(), String> {
         let q_entries = self.engine.learner.export_q_table();
-        self.persistence.store_q_table(&q_entries)?;
+        let q_entries_b = self.engine.learner.export_q_table_b();
+        let experiences = self.engine.learner.export_experiences();
+        let thoughts = self.engine.export_thoughts();
+        let rewards = self.engine.learner.export_recent_rewards();
+        let schedule_json = serde_json::to_string(&self.engine.learner.epsilon_schedule())
+            .map_err(|e| format!("Serialize error: {}", e))?;
 
-        // Save settings
-        let settings = self.settings.read().clone();
-        let settings_json =
-            serde_json::to_string(&settings).map_err(|e| format!("Serialize error: {}", e))?;
-        self.persistence
-            .store_config("app_settings", &settings_json)?;
+        let settings_json = self.stored_settings_json()?;
+
+        // Clipboard history is left as it was while in privacy mode
+        let privacy_mode = self.settings.read().privacy_mode;
+        let clipboard = (!privacy_mode)
+            .then(|| self.context.recent_clipboard(self.context.max_history()));
+
+        let memory = &self.engine.memory;
+        let discarded = memory.take_discarded();
+        let written = self.persistence.transaction(|batch| {
+            // Drop rows of memories merged away, pruned, or evicted since the last flush
+            batch.delete_memories(&discarded)?;
+
+            // Save memories, streamed straight from the store
+            let saved = batch.store_memories_with(|sink| memory.for_each_node(sink))?;
+
+            // Save Q-tables
+            batch.store_q_table(&q_entries)?;
+            batch.store_q_table_b(&q_entries_b)?;
+
+            // Save experience replay buffer
+            batch.store_experiences(&experiences)?;
+
+            // Save the thought stream
+            batch.store_thoughts(&thoughts)?;
+
+            // Save the vector width the memory store was built with
+            batch.store_config("embedding_dimensions", &memory.dimensions().to_string())?;
+
+            // Save the reward window behind the learning trend
+            batch.store_recent_rewards(&rewards)?;
+
+            // Save exploration schedule
+            batch.store_config("learner_epsilon", &schedule_json)?;
+
+            if let Some(clipboard) = &clipboard {
+                batch.store_clipboard(clipboard)?;
+            }
+
+            // Save settings
+            batch.store_config("app_settings", &settings_json)?;
+
+            Ok(saved)
+        });
+        let saved = match written {
+            Ok(saved) => saved,
+            Err(e) => {
+                // Nothing was written, so try those deletions again next time
+                memory.requeue_discarded(discarded);
+                return Err(e);
+            }
+        };
 
-        tracing::info!("State flushed to disk ({} memories)", nodes.len());
+        tracing::info!("State flushed to disk ({} memories)", saved);
         Ok(())
     }
 }
+
+/// Load everything the engine keeps in the database: memories, learner
+/// state, goals, beliefs, thoughts, and search settings. Failures are logged and skipped.
+fn restore_brain_state(persistence: &BrainPersistence, engine: &CognitiveEngine) {
+    // Keep the store at the vector width it was built with
+    if let Ok(Some(dim)) = persistence.load_config("embedding_dimensions") {
+        match dim.parse::<usize>() {
+            Ok(dim) => {
+                if let Err(e) = engine.memory.set_dimensions(dim) {
+                    tracing::warn!("Failed to restore embedding dimensions: {}", e);
+                }
+            }
+            Err(e) => tracing::warn!("Invalid stored embedding dimensions: {}", e),
+        }
+    }
+
+    restore_distance_metric(persistence, &engine.memory);
+
+    // Restore persisted memories
+    match persistence.load_memories() {
+        Ok(memories) => {
+            let count = memories.len();
+            for node in memories {
+                engine.memory.restore_node(node);
+            }
+            if count > 0 {
+                tracing::info!("Restored {} memories from database", count);
+            }
+        }
+        Err(e) => {
+            tracing::warn!("Failed to load memories: {}", e);
+        }
+    }
+
+    // Restore Q-table
+    match persistence.load_q_table() {
+        Ok(entries) => {
+            let count = entries.len();
+            engine.learner.import_q_table(entries);
+            if count > 0 {
+                tracing::info!("Restored {} Q-table entries", count);
+            }
+        }
+        Err(e) => {
+            tracing::warn!("Failed to load Q-table: {}", e);
+        }
+    }
+
+    // Restore the secondary Double Q-learning table
+    match persistence.load_q_table_b() {
+        Ok(entries) => engine.learner.import_q_table_b(entries),
+        Err(e) => tracing::warn!("Failed to load secondary Q-table: {}", e),
+    }
+
+    // Restore experience replay buffer
+    match persistence.load_experiences(engine.learner.buffer_size()) {
+        Ok(experiences) => {
+            let count = experiences.len();
+            engine.learner.import_experiences(experiences);
+            if count > 0 {
+                tracing::info!("Restored {} experiences", count);
+            }
+        }
+        Err(e) => {
+            tracing::warn!("Failed to load experiences: {}", e);
+        }
+    }
+
+    // Restore the reward window behind the learning trend
+    match persistence.load_recent_rewards() {
+        Ok(rewards) => engine.learner.import_recent_rewards(rewards),
+        Err(e) => tracing::warn!("Failed to load recent rewards: {}", e),
+    }
+
+    // Restore goals
+    match persistence.load_goals() {
+        Ok(goals) => engine.restore_goals(goals),
+        Err(e) => tracing::warn!("Failed to load goals: {}", e),
+    }
+
+    // Restore beliefs
+    match persistence.load_beliefs() {
+        Ok(beliefs) => engine.restore_beliefs(beliefs),
+        Err(e) => tracing::warn!("Failed to load beliefs: {}", e),
+    }
+
+    // Restore the most recent thoughts
+    match persistence.load_thoughts(MAX_THOUGHTS) {
+        Ok(thoughts) => engine.restore_thoughts(thoughts),
+        Err(e) => tracing::warn!("Failed to load thoughts: {}", e),
+    }
+
+    // Restore exploration schedule
+    if let Ok(Some(json)) = persistence.load_config("learner_epsilon") {
+        match serde_json::from_str(&json) {
+            Ok(schedule) => engine.learner.set_epsilon_schedule(schedule),
+            Err(e) => tracing::warn!("Failed to parse epsilon schedule: {}", e),
+        }
+    }
+}
+
+/// Apply the distance metric saved by `AppState::set_distance_metric`
+fn restore_distance_metric(persistence: &BrainPersistence, memory: &NativeMemory) {
+    match persistence.load_config("distance_metric") {
+        Ok(Some(name)) => {
+            memory.set_metric(&name);
+        }
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Failed to load distance metric: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// State backed by databases in `dir`, with hash embeddings and no AI
+    fn state_in(dir: &std::path::Path) -> AppState {
+        let embeddings = Arc::new(EmbeddingModel::new());
+        let indexer = FileIndexer::new(dir.join("files.db"), embeddings.clone()).unwrap();
+        AppState {
+            engine: Arc::new(CognitiveEngine::new(Some(CognitiveConfig::default()))),
+            embeddings,
+            persistence: Arc::new(BrainPersistence::with_path(dir.join("brain.db")).unwrap()),
+            indexer: Arc::new(indexer),
+            context: Arc::new(ContextManager::new()),
+            ai_provider: RwLock::new(None),
+            conversations: Conversations::default(),
+            cancellations: Cancellations::new(),
+            ai_usage: UsageTracker::new(),
+            settings: RwLock::new(AppSettings::default()),
+            shutdown: Notify::new(),
+            flush_lock: Mutex::new(()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remove_indexed_folder() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        let kept = dir.join("kept");
+        let removed = dir.join("removed");
+        for folder in [&kept, &removed] {
+            std::fs::create_dir_all(folder).unwrap();
+            std::fs::write(folder.join("a.md"), "Project notes").unwrap();
+            std::fs::write(folder.join("b.txt"), "More project notes").unwrap();
+        }
+        let state = state_in(&dir);
+        let folders: Vec<String> = [&kept, &removed]
+            .iter()
+            .map(|f| f.to_string_lossy().to_string())
+            .collect();
+        state.settings.write().indexed_folders = folders.clone();
+        state.indexer.add_watch_dirs(vec![kept.clone(), removed.clone()]);
+        assert_eq!(state.indexer.scan_all(false).await.unwrap(), 4);
+
+        assert_eq!(state.remove_indexed_folder(&folders[1]).unwrap(), 2);
+        assert_eq!(state.settings.read().indexed_folders, vec![folders[0].clone()]);
+        let stats = state.indexer.stats().unwrap();
+        assert_eq!((stats.file_count, stats.watched_dirs), (2, 1));
+        let results = state.indexer.search("project notes", 10).await.unwrap();
+        assert!(results.iter().all(|r| r.path.starts_with(&folders[0])));
+
+        // The change is saved
+        let saved: AppSettings = serde_json::from_str(
+            &state.persistence.load_config("app_settings").unwrap().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(saved.indexed_folders, vec![folders[0].clone()]);
+
+        assert!(state.remove_indexed_folder(&folders[1]).unwrap_err().contains("not indexed"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_saved_settings_leave_out_api_keys() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = state_in(&dir);
+        {
+            let mut settings = state.settings.write();
+            settings.claude_api_key = Some("sk-ant-key-1234".to_string());
+            settings.openai_api_key = Some("sk-openai-key-5678".to_string());
+        }
+
+        let assert_keys_left_out = || {
+            let json = state.persistence.load_config("app_settings").unwrap().unwrap();
+            assert!(!json.contains("key-1234") && !json.contains("key-5678"), "{}", json);
+            let saved: AppSettings = serde_json::from_str(&json).unwrap();
+            assert_eq!((saved.claude_api_key, saved.openai_api_key), (None, None));
+        };
+        state.persist_settings().unwrap();
+        assert_keys_left_out();
+        // The periodic flush saves settings too
+        state.flush().unwrap();
+        assert_keys_left_out();
+
+        // The live settings keep the keys
+        assert!(state.settings.read().claude_api_key.is_some());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_ai_usage_is_saved() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = state_in(&dir);
+
+        state.record_ai_usage("claude", Some(100));
+        state.record_ai_usage("claude", Some(50));
+        state.record_ai_usage("ollama", None);
+
+        let json = state.persistence.load_config("ai_usage").unwrap().unwrap();
+        let saved: std::collections::BTreeMap<String, crate::ai::usage::ProviderUsage> =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(saved, state.ai_usage.totals());
+        assert_eq!((saved["claude"].requests, saved["claude"].tokens), (2, 150));
+        assert_eq!((saved["ollama"].requests, saved["ollama"].tokens), (1, 0));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_build_ai_provider_respects_privacy_mode() {
+        let mut settings = AppSettings {
+            ai_provider: "claude".to_string(),
+            claude_api_key: Some("sk-ant-test".to_string()),
+            ..AppSettings::default()
+        };
+        assert_eq!(
+            AppState::build_ai_provider(&settings).map(|p| p.name().to_string()),
+            Some("claude".to_string())
+        );
+
+        settings.privacy_mode = true;
+        assert!(AppState::build_ai_provider(&settings).is_none());
+
+        // Local providers are still allowed
+        settings.ai_provider = "ollama".to_string();
+        assert!(AppState::build_ai_provider(&settings).is_some());
+    }
+
+    /// Store and persist a memory the way the `remember` command does
+    async fn remember_in(state: &AppState, content: &str) -> String {
+        let embedding = state.embeddings.embed(content).await.unwrap();
+        let id = state
+            .engine
+            .remember_with_embedding(
+                content.to_string(),
+                embedding,
+                "semantic".to_string(),
+                Some(0.8),
+            )
+            .unwrap();
+        state
+            .persistence
+            .store_memory(&state.engine.memory.get_node(&id).unwrap())
+            .unwrap();
+        id
+    }
+
+    #[tokio::test]
+    async fn test_get_and_delete_memory() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = state_in(&dir);
+        let kept = remember_in(&state, "Coffee with Ana on Friday").await;
+        let deleted = remember_in(&state, "Wrong phone number for the plumber").await;
+
+        let entry = state.engine.memory.get(&kept).unwrap();
+        assert_eq!(entry.id, kept);
+        assert_eq!(entry.content, "Coffee with Ana on Friday");
+        assert_eq!(entry.memory_type, "Semantic");
+        assert_eq!(entry.importance, 0.8);
+
+        assert!(state.delete_memory(&deleted).unwrap());
+        assert!(state.engine.memory.get(&deleted).is_none());
+        let stored: Vec<String> =
+            state.persistence.load_memories().unwrap().into_iter().map(|n| n.id).collect();
+        assert_eq!(stored, vec![kept.clone()]);
+
+        assert!(!state.delete_memory(&deleted).unwrap());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore_into_fresh_state() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        for sub in ["source", "target"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        let source = state_in(&dir.join("source"));
+        remember_in(&source, "The spare key is under the blue pot").await;
+        remember_in(&source, "Dentist appointment on Friday").await;
+        source.add_goal("Learn Portuguese".to_string(), 0.7).unwrap();
+        let backup = dir.join("brain-backup.db");
+        assert!(source.backup_brain(&backup).unwrap() > 0);
+
+        // Not a SuperBrain database: rejected, live data untouched
+        let target = state_in(&dir.join("target"));
+        remember_in(&target, "Memory that will be replaced").await;
+        let bogus = dir.join("notes.txt");
+        std::fs::write(&bogus, "just some text").unwrap();
+        assert!(target.restore_brain(&bogus).is_err());
+        assert_eq!(target.engine.memory.len(), 1);
+
+        assert_eq!(target.restore_brain(&backup).unwrap(), 2);
+        let contents: Vec<String> = target
+            .engine
+            .memory
+            .all_nodes()
+            .into_iter()
+            .map(|n| n.content)
+            .collect();
+        assert!(contents.contains(&"The spare key is under the blue pot".to_string()));
+        assert!(!contents.contains(&"Memory that will be replaced".to_string()));
+        assert_eq!(target.engine.goals()[0].description, "Learn Portuguese");
+
+        // The restored database is the live one from now on
+        assert_eq!(target.persistence.memory_count().unwrap(), 2);
+        target.flush().unwrap();
+        assert_eq!(target.persistence.load_memories().unwrap().len(), 2);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_experiences_survive_flush() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = state_in(&dir);
+
+        let dims = CognitiveConfig::default().dimensions as usize;
+        for (action, reward) in [(0, 1.0), (1, -0.5), (2, 0.25)] {
+            state
+                .engine
+                .learn(vec![0.1; dims], action, reward, vec![0.2; dims], false)
+                .unwrap();
+        }
+        state.flush().unwrap();
+
+        // Reload the replay buffer the way `AppState::new` does
+        let reloaded = CognitiveEngine::new(Some(CognitiveConfig::default()));
+        let saved = state
+            .persistence
+            .load_experiences(reloaded.learner.buffer_size())
+            .unwrap();
+        reloaded.learner.import_experiences(saved);
+        let actions: Vec<u32> = reloaded
+            .learner
+            .export_experiences()
+            .iter()
+            .map(|e| e.action)
+            .collect();
+        assert_eq!(actions, vec![0, 1, 2]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_consolidated_memories_stay_gone_after_reload() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = state_in(&dir);
+        let dims = state.engine.memory.dimensions();
+        let vector = |lean: f32| {
+            let mut v = vec![0.0f32; dims];
+            v[0] = 1.0;
+            v[1] = lean;
+            v
+        };
+        for (content, lean) in [("Rust is fast", 0.0), ("Rust is quick", 0.01)] {
+            state
+                .engine
+                .remember_with_embedding(content.into(), vector(lean), "semantic".into(), None)
+                .unwrap();
+        }
+        state.flush().unwrap();
+        assert_eq!(state.persistence.memory_count().unwrap(), 2);
+
+        assert_eq!(state.engine.memory.consolidate().merged, 1);
+        state.flush().unwrap();
+        assert_eq!(state.persistence.memory_count().unwrap(), 1);
+
+        // A restart loads only the surviving memory
+        let reloaded = state_in(&dir);
+        restore_brain_state(&reloaded.persistence, &reloaded.engine);
+        assert_eq!(reloaded.engine.memory.len(), 1);
+        assert_eq!(reloaded.engine.memory.consolidate().merged, 0);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_failed_flush_leaves_database_unchanged() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = state_in(&dir);
+        let first = remember_in(&state, "Ana works at the observatory").await;
+        state.flush().unwrap();
+
+        // Changes only a flush would save
+        let dims = CognitiveConfig::default().dimensions as usize;
+        let content = "It opens at night".to_string();
+        let embedding = state.embeddings.embed(&content).await.unwrap();
+        state
+            .engine
+            .remember_with_embedding(content, embedding, "semantic".to_string(), None)
+            .unwrap();
+        state
+            .engine
+            .learn(vec![0.1; dims], 3, 1.0, vec![0.2; dims], false)
+            .unwrap();
+
+        // Break a table written late in the flush, after memories and Q-tables
+        let db_path = state.persistence.db_path().to_path_buf();
+        rusqlite::Connection::open(&db_path)
+            .unwrap()
+            .execute_batch("DROP TABLE thoughts;")
+            .unwrap();
+        assert!(state.flush().is_err());
+
+        let saved: Vec<String> = state
+            .persistence
+            .load_memories()
+            .unwrap()
+            .into_iter()
+            .map(|n| n.id)
+            .collect();
+        assert_eq!(saved, vec![first]);
+        assert!(state.persistence.load_experiences(10).unwrap().is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_flushes_do_not_overlap() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = state_in(&dir);
+
+        // Pretend a first flush is still writing
+        let first = state.flush_lock.lock();
+        assert!(state.is_flushing());
+        assert!(!state.try_flush().unwrap());
+
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                state.flush().unwrap();
+                done_tx.send(()).unwrap();
+            });
+
+            // The second flush waits until the first one finishes
+            let wait = std::time::Duration::from_millis(100);
+            assert!(done_rx.recv_timeout(wait).is_err());
+            drop(first);
+            done_rx.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+        });
+
+        assert!(!state.is_flushing());
+        assert!(state.try_flush().unwrap());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_thoughts_survive_flush() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = state_in(&dir);
+
+        let dims = CognitiveConfig::default().dimensions as usize;
+        for input in ["first question", "second question"] {
+            state.engine.think(input.to_string(), vec![0.1; dims]).unwrap();
+        }
+        let before = state.engine.get_thoughts(Some(10));
+        state.flush().unwrap();
+
+        // Reload the thought stream the way `AppState::new` does
+        let reloaded = CognitiveEngine::new(Some(CognitiveConfig::default()));
+        restore_brain_state(&state.persistence, &reloaded);
+        let after = reloaded.get_thoughts(Some(10));
+        let ids = |thoughts: &[crate::brain::types::Thought]| -> Vec<String> {
+            thoughts.iter().map(|t| t.id.clone()).collect()
+        };
+        assert_eq!(ids(&after), ids(&before));
+        assert_eq!(after[0].content, "Processing: second question");
+
+        // Restoring more than the cap keeps only the newest thoughts
+        let mut many = reloaded.export_thoughts();
+        while many.len() <= MAX_THOUGHTS {
+            many.extend(before.iter().cloned());
+        }
+        let newest = many.last().unwrap().id.clone();
+        reloaded.restore_thoughts(many);
+        assert_eq!(reloaded.export_thoughts().len(), MAX_THOUGHTS);
+        assert_eq!(reloaded.get_thoughts(Some(1))[0].id, newest);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_goals_are_saved() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = state_in(&dir);
+
+        let goal = state.add_goal("Run a marathon".to_string(), 0.8).unwrap();
+        assert_eq!(goal.status, crate::brain::cognitive::GoalStatus::Pending);
+        assert_eq!(state.engine.goals().len(), 1);
+
+        let updated = state.update_goal(&goal.id, 0.25).unwrap();
+        assert_eq!(updated.status, crate::brain::cognitive::GoalStatus::Active);
+        assert!(state.update_goal("missing", 0.5).unwrap_err().contains("not found"));
+
+        let saved = state.persistence.load_goals().unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].description, "Run a marathon");
+        assert_eq!(saved[0].progress, 0.25);
+        assert_eq!(saved[0].status, crate::brain::cognitive::GoalStatus::Active);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_semantic_clipboard_search() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = state_in(&dir);
+        for text in [
+            "ACME invoice for March consulting",
+            "Grocery list: eggs, milk, bread",
+            "Flight confirmation to Lisbon",
+        ] {
+            state.context.record_clipboard(text.to_string());
+        }
+
+        // Word order differs, so only the semantic search finds it
+        let query = "march invoice acme";
+        assert!(state.search_clipboard(query, 5, false).await.unwrap().is_empty());
+        let found = state.search_clipboard(query, 5, true).await.unwrap();
+        assert_eq!(found[0].content, "ACME invoice for March consulting");
+        assert!(found.iter().all(|e| !e.content.starts_with("Grocery")));
+
+        let substring = state.search_clipboard("lisbon", 5, false).await.unwrap();
+        assert_eq!(substring.len(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_distance_metric_is_saved() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = state_in(&dir);
+
+        assert_eq!(state.set_distance_metric("chebyshev").unwrap(), "cosine");
+        assert_eq!(state.engine.memory.metric(), DistanceMetric::Cosine);
+        assert_eq!(state.set_distance_metric("Euclidean").unwrap(), "euclidean");
+
+        // A reload picks the saved metric back up
+        let reloaded = state_in(&dir);
+        assert_eq!(reloaded.engine.memory.metric(), DistanceMetric::Cosine);
+        restore_distance_metric(&reloaded.persistence, &reloaded.engine.memory);
+        assert_eq!(reloaded.engine.memory.metric(), DistanceMetric::Euclidean);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_beliefs_are_saved_and_restored() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = state_in(&dir);
+
+        let doubtful = state
+            .add_belief("It will rain tomorrow".to_string(), -0.5, "forecast".to_string())
+            .unwrap();
+        let certain = state
+            .add_belief("Water boils at 100C".to_string(), 1.7, "physics".to_string())
+            .unwrap();
+        assert_eq!((doubtful.confidence, certain.confidence), (0.0, 1.0));
+
+        let listed: Vec<String> = state.engine.beliefs().into_iter().map(|b| b.id).collect();
+        assert_eq!(listed, vec![certain.id.clone(), doubtful.id.clone()]);
+        assert_eq!(state.engine.introspect().total_beliefs, 2);
+
+        // A fresh engine picks the beliefs back up from disk
+        let restored = CognitiveEngine::new(Some(CognitiveConfig::default()));
+        restored.restore_beliefs(state.persistence.load_beliefs().unwrap());
+        let belief = restored.get_belief(&certain.id).unwrap();
+        assert_eq!(belief.content, "Water boils at 100C");
+        assert_eq!(belief.source, "physics");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_memory_connections_are_saved() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = state_in(&dir);
+        let a = remember_in(&state, "Ana works at the observatory").await;
+        let b = remember_in(&state, "The observatory opens at night").await;
+        let saved_connections = |id: &str| -> Vec<String> {
+            let nodes = state.persistence.load_memories().unwrap();
+            nodes.into_iter().find(|n| n.id == id).unwrap().connections.to_vec()
+        };
+
+        assert!(state.connect_memories(&a, &b).unwrap());
+        assert!(state.connect_memories(&a, &b).unwrap());
+        assert_eq!(saved_connections(&a), vec![b.clone()]);
+        assert_eq!(saved_connections(&b), vec![a.clone()]);
+        assert!(!state.connect_memories(&a, "missing").unwrap());
+
+        assert!(state.disconnect_memories(&a, &b).unwrap());
+        assert!(saved_connections(&a).is_empty());
+        assert!(saved_connections(&b).is_empty());
+        assert!(!state.disconnect_memories(&a, &b).unwrap());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_indexed_files_reach_ai_context() {
+        let dir = std::env::temp_dir().join(format!("superbrain_state_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = state_in(&dir);
+        let notes = dir.join("notes.txt");
+        std::fs::write(&notes, "The launch deadline for project aurora is March 3rd").unwrap();
+        state.indexer.index_file(&notes, false).await.unwrap();
+
+        let prompts = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen = prompts.clone();
+        let url = crate::test_support::spawn_mock_server(move |request| {
+            seen.lock().push(request.body["prompt"].as_str().unwrap_or_default().to_string());
+            (200, r#"{"response":"March 3rd"}"#.to_string())
+        })
+        .await;
+
+        let context = state.file_context("project aurora launch deadline", 3).await;
+        assert_eq!(context[0].id, notes.to_string_lossy());
+        let provider = OllamaProvider::with_url(&url, "llama3.2");
+        provider
+            .generate("When is the aurora launch?", &context, &[])
+            .await
+            .unwrap();
+
+        let prompt = prompts.lock()[0].clone();
+        assert!(prompt.contains("Relevant Files"), "{}", prompt);
+        assert!(prompt.contains(&format!("Source: {}", notes.display())), "{}", prompt);
+        assert!(prompt.contains("deadline for project aurora is March 3rd"), "{}", prompt);
+        assert!(state.file_context("project aurora", 0).await.is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_ollama_base_url_setting_is_used() {
+        let paths = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen = paths.clone();
+        let url = crate::test_support::spawn_mock_server(move |request| {
+            seen.lock().push(request.path);
+            (200, r#"{"models":[{"name":"llama3.2:latest"}]}"#.to_string())
+        })
+        .await;
+        let settings = AppSettings {
+            ollama_base_url: format!("{}/", url),
+            ..AppSettings::default()
+        };
+
+        let provider = AppState::build_ai_provider(&settings).unwrap();
+        assert!(provider.is_available().await);
+        let models = crate::ai::ollama::list_models(&settings.ollama_base_url).await.unwrap();
+
+        assert_eq!(models, vec!["llama3.2:latest"]);
+        assert_eq!(*paths.lock(), vec!["/api/tags", "/api/tags"]);
+    }
+
+    #[test]
+    fn test_build_ai_chain_follows_fallback_order() {
+        let mut settings = AppSettings {
+            ai_provider: "claude".to_string(),
+            ai_fallbacks: vec!["ollama".to_string(), "claude".to_string(), "none".to_string()],
+            claude_api_key: Some("sk-ant-test".to_string()),
+            ..AppSettings::default()
+        };
+        let names = |settings: &AppSettings| -> Vec<String> {
+            AppState::build_ai_chain(settings).iter().map(|p| p.name().to_string()).collect()
+        };
+        assert_eq!(names(&settings), vec!["claude", "ollama"]);
+
+        settings.claude_api_key = None;
+        assert_eq!(names(&settings), vec!["ollama"]);
+    }
+}
@@ -0,0 +1,116 @@
+//! Test helpers shared across modules
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// A request received by [`spawn_mock_server`]
+pub struct MockRequest {
+    pub path: String,
+    /// Raw header block, lowercased
+    pub headers: String,
+    pub body: serde_json::Value,
+}
+
+/// Start a one-request-per-connection HTTP server on a free local port and
+/// return its base URL. `respond` maps each request to `(status, json body)`.
+pub async fn spawn_mock_server<F>(respond: F) -> String
+where
+    F: Fn(MockRequest) -> (u16, String) + Send + Sync + 'static,
+{
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let Some(request) = read_request(&mut socket).await else {
+                continue;
+            };
+            let (status, body) = respond(request);
+            let response = format!(
+                "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+
+    format!("http://{}", addr)
+}
+
+/// Start a server that answers one request with a body sent in `parts`,
+/// writing each part only after a message arrives on `gate`, and return its
+/// base URL and the request it received
+pub async fn spawn_streaming_server(
+    parts: Vec<String>,
+    mut gate: tokio::sync::mpsc::UnboundedReceiver<()>,
+) -> (String, tokio::sync::oneshot::Receiver<MockRequest>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (request_tx, request_rx) = tokio::sync::oneshot::channel();
+
+    tokio::spawn(async move {
+        let Ok((mut socket, _)) = listener.accept().await else {
+            return;
+        };
+        let Some(request) = read_request(&mut socket).await else {
+            return;
+        };
+        let _ = request_tx.send(request);
+        // No content length: the body runs until the connection closes
+        let head = "HTTP/1.1 200 Mock\r\nContent-Type: application/x-ndjson\r\n\
+                    Connection: close\r\n\r\n";
+        if socket.write_all(head.as_bytes()).await.is_err() {
+            return;
+        }
+        for part in parts {
+            if gate.recv().await.is_none() || socket.write_all(part.as_bytes()).await.is_err() {
+                return;
+            }
+            let _ = socket.flush().await;
+        }
+    });
+
+    (format!("http://{}", addr), request_rx)
+}
+
+/// Read one HTTP request (headers and a content-length body) from `socket`
+async fn read_request(socket: &mut tokio::net::TcpStream) -> Option<MockRequest> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let body_start = loop {
+        let n = socket.read(&mut chunk).await.unwrap();
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let headers = String::from_utf8_lossy(&buf[..body_start]).to_lowercase();
+    let content_length: usize = headers
+        .lines()
+        .find_map(|l| l.strip_prefix("content-length:"))
+        .map(|v| v.trim().parse().unwrap())
+        .unwrap_or(0);
+    while buf.len() < body_start + content_length {
+        let n = socket.read(&mut chunk).await.unwrap();
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let path = headers
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .to_string();
+    let body = serde_json::from_slice(&buf[body_start..]).unwrap_or_default();
+
+    Some(MockRequest {
+        path,
+        headers,
+        body,
+    })
+}
@@ -2,11 +2,13 @@
 
 use tauri::{
     image::Image,
-    menu::{Menu, MenuItem},
+    menu::{CheckMenuItem, Menu, MenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
-    AppHandle, Emitter, Manager,
+    AppHandle, Emitter, Manager, Wry,
 };
 
+use crate::state::AppState;
+
 /// Tray icon status variants
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TrayStatus {
@@ -15,15 +17,23 @@ pub enum TrayStatus {
     Learning, // blue
 }
 
+/// Menu items whose text changes while the app runs
+struct TrayMenu {
+    status: MenuItem<Wry>,
+    pin: CheckMenuItem<Wry>,
+}
+
 /// Set up the system tray icon and menu
 pub fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
     let show = MenuItem::with_id(app, "show", "Show SuperBrain", true, None::<&str>)?;
-    let status = MenuItem::with_id(app, "status", "Status: Running", false, None::<&str>)?;
+    let status = MenuItem::with_id(app, "status", "Status: Starting...", false, None::<&str>)?;
     let separator = MenuItem::with_id(app, "sep1", "---", false, None::<&str>)?;
+    let pinned = crate::overlay::is_pinned(app);
+    let pin = CheckMenuItem::with_id(app, "pin", "Pin Overlay", true, pinned, None::<&str>)?;
     let settings = MenuItem::with_id(app, "settings", "Settings...", true, None::<&str>)?;
     let quit = MenuItem::with_id(app, "quit", "Quit SuperBrain", true, None::<&str>)?;
 
-    let menu = Menu::with_items(app, &[&show, &status, &separator, &settings, &quit])?;
+    let menu = Menu::with_items(app, &[&show, &status, &separator, &pin, &settings, &quit])?;
 
     let _tray = TrayIconBuilder::with_id("main-tray")
         .menu(&menu)
@@ -32,22 +42,31 @@ pub fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
         .on_menu_event(move |app, event| {
             match event.id.as_ref() {
                 "show" => {
-                    toggle_overlay(app);
+                    crate::overlay::toggle(app);
+                }
+                "pin" => {
+                    let pinned = !crate::overlay::is_pinned(app);
+                    if let Err(e) = crate::overlay::set_pinned(app, pinned) {
+                        tracing::warn!("Failed to pin overlay: {}", e);
+                    }
                 }
                 "settings" => {
+                    crate::overlay::show(app);
+                    // Emit event to navigate to settings
                     if let Some(window) = app.get_webview_window("main") {
-                        let _ = window.show();
-                        let _ = window.set_focus();
-                        // Emit event to navigate to settings
                         let _ = window.emit("navigate", "settings");
                     }
                 }
                 "quit" => {
-                    // Flush state before quitting
-                    if let Some(state) = app.try_state::<crate::state::AppState>() {
-                        let _ = state.flush();
-                    }
-                    app.exit(0);
+                    // Flush state off the main thread, then quit
+                    let app = app.clone();
+                    tauri::async_runtime::spawn(async move {
+                        let flushed = crate::commands::flush_off_thread(app.clone(), true).await;
+                        if let Err(e) = flushed {
+                            tracing::warn!("Failed to save state before quitting: {}", e);
+                        }
+                        app.exit(0);
+                    });
                 }
                 _ => {}
             }
@@ -59,14 +78,41 @@ pub fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
                 ..
             } = event
             {
-                toggle_overlay(tray.app_handle());
+                crate::overlay::toggle(tray.app_handle());
             }
         })
         .build(app)?;
 
+    app.manage(TrayMenu { status, pin });
+    refresh_status_text(app);
+
     Ok(())
 }
 
+/// Rewrite the status menu item from the engine's current introspection
+pub fn refresh_status_text(app: &AppHandle) {
+    let (Some(menu), Some(state)) = (app.try_state::<TrayMenu>(), app.try_state::<AppState>())
+    else {
+        return;
+    };
+    let introspection = state.engine.introspect();
+    let text = status_text(introspection.total_memories, &introspection.learning_trend);
+    let _ = menu.status.set_text(text);
+}
+
+/// Sync the "Pin Overlay" check mark with the pinned state
+pub fn set_pin_checked(app: &AppHandle, pinned: bool) {
+    if let Some(menu) = app.try_state::<TrayMenu>() {
+        let _ = menu.pin.set_checked(pinned);
+    }
+}
+
+/// Format the tray status line, e.g. "42 memories · improving"
+pub fn status_text(memory_count: u32, learning_trend: &str) -> String {
+    let noun = if memory_count == 1 { "memory" } else { "memories" };
+    format!("{} {} · {}", memory_count, noun, learning_trend)
+}
+
 /// Update the tray icon to reflect current status
 pub fn set_status(app: &AppHandle, status: TrayStatus) {
     if let Some(tray) = app.tray_by_id("main-tray") {
@@ -78,6 +124,10 @@ pub fn set_status(app: &AppHandle, status: TrayStatus) {
         };
         let _ = tray.set_tooltip(Some(tooltip));
     }
+    // Work just finished, so the counts may have moved
+    if status == TrayStatus::Idle {
+        refresh_status_text(app);
+    }
 }
 
 /// Generate a 22x22 RGBA tray icon with a colored brain-dot indicator
@@ -123,15 +173,14 @@ fn make_status_icon(status: TrayStatus) -> Image<'static> {
     Image::new_owned(rgba, SIZE, SIZE)
 }
 
-/// Toggle the overlay window visibility
-fn toggle_overlay(app: &AppHandle) {
-    if let Some(window) = app.get_webview_window("main") {
-        if window.is_visible().unwrap_or(false) {
-            let _ = window.hide();
-        } else {
-            let _ = window.show();
-            let _ = window.set_focus();
-            let _ = window.center();
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_text() {
+        assert_eq!(status_text(42, "improving"), "42 memories · improving");
+        assert_eq!(status_text(1, "stable"), "1 memory · stable");
+        assert_eq!(status_text(0, "declining"), "0 memories · declining");
     }
 }
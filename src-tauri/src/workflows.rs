@@ -2,13 +2,42 @@
 //!
 //! Built-in actions that combine multiple cognitive operations.
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
+use crate::ai::{file_context_entry, generate_with_fallback, AiProvider};
 use crate::brain::cognitive::CognitiveEngine;
 use crate::brain::embeddings::EmbeddingModel;
+use crate::brain::memory::{MemoryNode, SearchOptions};
+use crate::brain::utils::{cosine_similarity, normalize_vector, truncate_str};
 use crate::context::ContextManager;
+use crate::indexer::FileIndexer;
+
+/// File chunks handed to the AI when answering from indexed files
+const ANSWER_CONTEXT_CHUNKS: u32 = 5;
+
+/// Memories sent to the AI in one auto-tagging request
+const AUTO_TAG_BATCH: usize = 10;
+
+/// Most tags kept per memory from one auto-tagging answer
+const MAX_AUTO_TAGS: usize = 3;
+
+/// Bytes of each memory included in an auto-tagging prompt
+const AUTO_TAG_CONTENT_BYTES: usize = 500;
+
+/// Memories `AutoTag` looks at when the caller doesn't say
+pub const DEFAULT_AUTO_TAG_LIMIT: usize = 20;
+
+/// Topics `ClusterMemories` looks for when the caller doesn't say
+pub const DEFAULT_CLUSTER_COUNT: usize = 5;
+
+/// Rounds of k-means before giving up on convergence
+const MAX_CLUSTER_ITERATIONS: usize = 25;
+
+/// Bytes of content shown for each cluster's representative memory
+const CLUSTER_SNIPPET_BYTES: usize = 120;
 
 /// Available workflow actions
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +46,10 @@ pub enum WorkflowAction {
     SummarizeRecent,
     LearningDigest,
     SearchAndRemember { query: String },
+    AnswerFromFiles { query: String },
+    ExportMarkdown { dest_path: String },
+    ClusterMemories { k: usize },
+    AutoTag { limit: usize },
 }
 
 /// Workflow execution result
@@ -34,6 +67,8 @@ pub async fn execute_workflow(
     engine: &Arc<CognitiveEngine>,
     embeddings: &Arc<EmbeddingModel>,
     context: &ContextManager,
+    indexer: &FileIndexer,
+    providers: &[Box<dyn AiProvider>],
 ) -> Result<WorkflowResult, String> {
     match action {
         WorkflowAction::RememberClipboard => {
@@ -48,6 +83,18 @@ pub async fn execute_workflow(
         WorkflowAction::SearchAndRemember { query } => {
             search_and_remember(&query, engine, embeddings).await
         }
+        WorkflowAction::AnswerFromFiles { query } => {
+            answer_from_files(&query, indexer, providers).await
+        }
+        WorkflowAction::ExportMarkdown { dest_path } => {
+            export_markdown(&dest_path, engine).await
+        }
+        WorkflowAction::ClusterMemories { k } => {
+            cluster_memories(k, engine).await
+        }
+        WorkflowAction::AutoTag { limit } => {
+            auto_tag(limit, engine, providers).await
+        }
     }
 }
 
@@ -72,8 +119,11 @@ async fn remember_clipboard(
     Ok(WorkflowResult {
         action: "RememberClipboard".to_string(),
         success: true,
-        message: format!("Stored clipboard content as memory {}", &id[..8]),
-        data: Some(serde_json::json!({ "id": id, "content_preview": &content[..content.len().min(100)] })),
+        message: format!("Stored clipboard content as memory {}", truncate_str(&id, 8)),
+        data: Some(serde_json::json!({
+            "id": id,
+            "content_preview": truncate_str(&content, 100),
+        })),
     })
 }
 
@@ -96,7 +146,7 @@ async fn summarize_recent(engine: &Arc<CognitiveEngine>) -> Result<WorkflowResul
         thoughts
             .iter()
             .take(5)
-            .map(|t| format!("  - [{}] {}", t.thought_type, &t.content[..t.content.len().min(80)]))
+            .map(|t| format!("  - [{}] {}", t.thought_type, truncate_str(&t.content, 80)))
             .collect::<Vec<_>>()
             .join("\n")
     );
@@ -155,7 +205,7 @@ async fn search_and_remember(
     embeddings: &Arc<EmbeddingModel>,
 ) -> Result<WorkflowResult, String> {
     let vector = embeddings.embed(query).await?;
-    let results = engine.recall_f32(&vector, Some(5), None)?;
+    let results = engine.recall_f32(&vector, Some(5), SearchOptions::default())?;
 
     if results.is_empty() {
         return Ok(WorkflowResult {
@@ -180,3 +230,530 @@ async fn search_and_remember(
         })),
     })
 }
+
+/// Answer a question from the most relevant indexed file chunks, citing
+/// the files they came from
+async fn answer_from_files(
+    query: &str,
+    indexer: &FileIndexer,
+    providers: &[Box<dyn AiProvider>],
+) -> Result<WorkflowResult, String> {
+    let files = indexer.search(query, ANSWER_CONTEXT_CHUNKS).await?;
+    if files.is_empty() {
+        return Ok(WorkflowResult {
+            action: "AnswerFromFiles".to_string(),
+            success: true,
+            message: format!("No indexed files match '{}'", query),
+            data: None,
+        });
+    }
+
+    let mut sources: Vec<String> = Vec::new();
+    for file in &files {
+        if !sources.contains(&file.path) {
+            sources.push(file.path.clone());
+        }
+    }
+    let context: Vec<_> = files.into_iter().map(file_context_entry).collect();
+
+    match generate_with_fallback(providers, query, &context, &[], None).await {
+        Ok((provider, response)) => Ok(WorkflowResult {
+            action: "AnswerFromFiles".to_string(),
+            success: true,
+            message: response.content.clone(),
+            data: Some(serde_json::json!({
+                "answer": response.content,
+                "provider": provider,
+                "sources": sources,
+            })),
+        }),
+        Err(e) => Ok(WorkflowResult {
+            action: "AnswerFromFiles".to_string(),
+            success: false,
+            message: format!(
+                "Found {} matching files but no AI provider answered: {}",
+                sources.len(),
+                e
+            ),
+            data: Some(serde_json::json!({ "sources": sources })),
+        }),
+    }
+}
+
+/// Write every memory to a Markdown file at `dest_path`, one section per
+/// memory type
+async fn export_markdown(
+    dest_path: &str,
+    engine: &Arc<CognitiveEngine>,
+) -> Result<WorkflowResult, String> {
+    let nodes = engine.memory.all_nodes();
+    std::fs::write(dest_path, render_markdown(&nodes))
+        .map_err(|e| format!("Failed to write {}: {}", dest_path, e))?;
+
+    Ok(WorkflowResult {
+        action: "ExportMarkdown".to_string(),
+        success: true,
+        message: format!("Exported {} memories to {}", nodes.len(), dest_path),
+        data: Some(serde_json::json!({ "path": dest_path, "count": nodes.len() })),
+    })
+}
+
+/// Memories as Markdown: a heading per memory type, then one bullet per
+/// memory, newest first
+fn render_markdown(nodes: &[MemoryNode]) -> String {
+    let mut by_type: BTreeMap<String, Vec<&MemoryNode>> = BTreeMap::new();
+    for node in nodes {
+        by_type
+            .entry(format!("{:?}", node.memory_type))
+            .or_default()
+            .push(node);
+    }
+
+    let mut markdown = format!("# SuperBrain Memories\n\n{} memories\n", nodes.len());
+    for (memory_type, mut group) in by_type {
+        group.sort_by_key(|node| std::cmp::Reverse(node.timestamp));
+        markdown.push_str(&format!("\n## {}\n\n", memory_type));
+        for node in group {
+            let when = chrono::DateTime::from_timestamp_millis(node.timestamp)
+                .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_default();
+            // Indent continuation lines so multi-line memories stay in their bullet
+            let content = node.content.trim().replace('\n', "\n  ");
+            markdown.push_str(&format!(
+                "- {}\n  *importance {:.2} · {}*\n",
+                content, node.importance, when
+            ));
+        }
+    }
+    markdown
+}
+
+/// Group memories into at most `k` topics by k-means over their vectors.
+/// Each cluster reports its size, member ids, and the memory closest to
+/// its centre as a representative.
+async fn cluster_memories(
+    k: usize,
+    engine: &Arc<CognitiveEngine>,
+) -> Result<WorkflowResult, String> {
+    if k == 0 {
+        return Err("Number of clusters must be at least 1".to_string());
+    }
+
+    let dims = engine.memory.dimensions();
+    let mut nodes: Vec<MemoryNode> = engine
+        .memory
+        .all_nodes()
+        .into_iter()
+        .filter(|node| node.vector.len() == dims)
+        .collect();
+    if nodes.is_empty() {
+        return Ok(WorkflowResult {
+            action: "ClusterMemories".to_string(),
+            success: true,
+            message: "No memories to cluster".to_string(),
+            data: None,
+        });
+    }
+    // Stable order so the same memories always give the same clusters
+    nodes.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.id.cmp(&b.id)));
+
+    let vectors: Vec<Vec<f32>> = nodes
+        .iter()
+        .map(|node| {
+            let mut vector = node.vector.to_f32();
+            normalize_vector(&mut vector);
+            vector
+        })
+        .collect();
+    let (assignments, centroids) = kmeans(&vectors, k);
+
+    let mut clusters = Vec::new();
+    for (cluster, centroid) in centroids.iter().enumerate() {
+        let members: Vec<usize> =
+            (0..nodes.len()).filter(|&i| assignments[i] == cluster).collect();
+        let Some(&closest) = members.iter().max_by(|&&a, &&b| {
+            cosine_similarity(&vectors[a], centroid)
+                .total_cmp(&cosine_similarity(&vectors[b], centroid))
+        }) else {
+            continue;
+        };
+        clusters.push(serde_json::json!({
+            "size": members.len(),
+            "representative": truncate_str(&nodes[closest].content, CLUSTER_SNIPPET_BYTES),
+            "memory_ids": members.iter().map(|&i| &nodes[i].id).collect::<Vec<_>>(),
+        }));
+    }
+
+    Ok(WorkflowResult {
+        action: "ClusterMemories".to_string(),
+        success: true,
+        message: format!("Grouped {} memories into {} clusters", nodes.len(), clusters.len()),
+        data: Some(serde_json::json!({ "clusters": clusters })),
+    })
+}
+
+/// Ask the AI for 1-3 tags for each of the `limit` most recent untagged
+/// memories, several memories per request. Memories the AI gives no usable
+/// answer for, or whose request fails, are skipped and stay untagged.
+async fn auto_tag(
+    limit: usize,
+    engine: &Arc<CognitiveEngine>,
+    providers: &[Box<dyn AiProvider>],
+) -> Result<WorkflowResult, String> {
+    if providers.is_empty() {
+        return Err("No AI provider configured".to_string());
+    }
+
+    let mut untagged: Vec<MemoryNode> = engine
+        .memory
+        .all_nodes()
+        .into_iter()
+        .filter(|node| node.tags.is_empty())
+        .collect();
+    untagged.sort_by_key(|node| std::cmp::Reverse(node.timestamp));
+    untagged.truncate(limit);
+
+    let mut tagged = 0usize;
+    for batch in untagged.chunks(AUTO_TAG_BATCH) {
+        let mut prompt = format!(
+            "Suggest 1 to {} short topic tags for each numbered note below. \
+             Reply with one line per note in the form `<number>: tag, tag` and nothing else.\n\n",
+            MAX_AUTO_TAGS
+        );
+        for (i, node) in batch.iter().enumerate() {
+            let content = truncate_str(&node.content, AUTO_TAG_CONTENT_BYTES).replace('\n', " ");
+            prompt.push_str(&format!("{}. {}\n", i + 1, content));
+        }
+
+        let answer = match generate_with_fallback(providers, &prompt, &[], &[], None).await {
+            Ok((_, response)) => response.content,
+            Err(e) => {
+                tracing::warn!("Auto-tagging skipped {} memories: {}", batch.len(), e);
+                continue;
+            }
+        };
+        for (number, tags) in parse_numbered_tags(&answer) {
+            let Some(node) = number.checked_sub(1).and_then(|i| batch.get(i)) else {
+                continue;
+            };
+            if engine.memory.add_tags(&node.id, &tags) {
+                tagged += 1;
+            }
+        }
+    }
+
+    Ok(WorkflowResult {
+        action: "AutoTag".to_string(),
+        success: true,
+        message: format!("Tagged {} of {} untagged memories", tagged, untagged.len()),
+        data: Some(serde_json::json!({ "tagged": tagged, "candidates": untagged.len() })),
+    })
+}
+
+/// Read `<number>: tag, tag` lines from an auto-tagging answer. Stray
+/// markdown around numbers and tags is ignored, as are lines without tags.
+fn parse_numbered_tags(answer: &str) -> Vec<(usize, Vec<String>)> {
+    let junk: &[char] = &['#', '*', '`', '"', '\'', '.', '-'];
+    answer
+        .lines()
+        .filter_map(|line| {
+            let (number, tags) = line.split_once(':')?;
+            let number: usize = number.trim().trim_matches(junk).trim().parse().ok()?;
+            let tags: Vec<String> = tags
+                .split(',')
+                .map(|tag| tag.trim().trim_matches(junk).trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .take(MAX_AUTO_TAGS)
+                .collect();
+            (!tags.is_empty()).then_some((number, tags))
+        })
+        .collect()
+}
+
+/// Spherical k-means over unit vectors. Starts from mutually distant points
+/// and stops once assignments settle. `k` is capped at the number of
+/// vectors. Returns each vector's cluster index and the cluster centres.
+fn kmeans(vectors: &[Vec<f32>], k: usize) -> (Vec<usize>, Vec<Vec<f32>>) {
+    let k = k.min(vectors.len());
+    let mut centroids: Vec<Vec<f32>> = Vec::with_capacity(k);
+    if k == 0 {
+        return (Vec::new(), centroids);
+    }
+
+    // Farthest-point seeding: each new centre is the vector least like any
+    // centre chosen so far
+    centroids.push(vectors[0].clone());
+    while centroids.len() < k {
+        let farthest = (0..vectors.len())
+            .min_by(|&a, &b| {
+                let closest = |i: usize| {
+                    centroids
+                        .iter()
+                        .map(|c| cosine_similarity(&vectors[i], c))
+                        .fold(f32::MIN, f32::max)
+                };
+                closest(a).total_cmp(&closest(b))
+            })
+            .unwrap_or(0);
+        centroids.push(vectors[farthest].clone());
+    }
+
+    let nearest = |vector: &[f32], centroids: &[Vec<f32>]| -> usize {
+        (0..centroids.len())
+            .max_by(|&a, &b| {
+                cosine_similarity(vector, &centroids[a])
+                    .total_cmp(&cosine_similarity(vector, &centroids[b]))
+            })
+            .unwrap_or(0)
+    };
+
+    let mut assignments: Vec<usize> = vectors.iter().map(|v| nearest(v, &centroids)).collect();
+    for _ in 0..MAX_CLUSTER_ITERATIONS {
+        // Move each centre to the normalized mean of its members; empty
+        // clusters keep their old centre
+        for (cluster, centroid) in centroids.iter_mut().enumerate() {
+            let mut sum = vec![0.0f32; centroid.len()];
+            let mut count = 0;
+            for (vector, _) in vectors.iter().zip(&assignments).filter(|(_, &a)| a == cluster) {
+                for (s, x) in sum.iter_mut().zip(vector) {
+                    *s += x;
+                }
+                count += 1;
+            }
+            if count > 0 {
+                normalize_vector(&mut sum);
+                *centroid = sum;
+            }
+        }
+
+        let next: Vec<usize> = vectors.iter().map(|v| nearest(v, &centroids)).collect();
+        if next == assignments {
+            break;
+        }
+        assignments = next;
+    }
+
+    (assignments, centroids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::ollama::OllamaProvider;
+
+    #[tokio::test]
+    async fn test_answer_from_files_uses_matching_chunks() {
+        let dir = std::env::temp_dir().join(format!("superbrain_wf_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let embeddings = Arc::new(EmbeddingModel::new());
+        let indexer = FileIndexer::new(dir.join("files.db"), embeddings).unwrap();
+
+        let prompts = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen = prompts.clone();
+        let url = crate::test_support::spawn_mock_server(move |request| {
+            seen.lock().push(request.body["prompt"].as_str().unwrap_or_default().to_string());
+            (200, r#"{"response":"The boiler is serviced every October."}"#.to_string())
+        })
+        .await;
+        let providers: Vec<Box<dyn AiProvider>> =
+            vec![Box::new(OllamaProvider::with_url(&url, "llama3.2"))];
+
+        // Nothing indexed yet
+        let query = "When is the boiler serviced?";
+        let empty = answer_from_files(query, &indexer, &providers).await.unwrap();
+        assert!(empty.message.starts_with("No indexed files match"));
+        assert!(prompts.lock().is_empty());
+
+        let manual = dir.join("house.txt");
+        std::fs::write(&manual, "The boiler is serviced every October by Heatwise.").unwrap();
+        indexer.index_file(&manual, false).await.unwrap();
+
+        let result = answer_from_files(query, &indexer, &providers).await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.message, "The boiler is serviced every October.");
+        let data = result.data.unwrap();
+        assert_eq!(data["sources"][0], manual.to_string_lossy().as_ref());
+
+        let prompt = prompts.lock()[0].clone();
+        assert!(prompt.contains("serviced every October by Heatwise"), "{}", prompt);
+        assert!(prompt.contains(&format!("Source: {}", manual.display())), "{}", prompt);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_previews_keep_whole_characters() {
+        let engine = Arc::new(CognitiveEngine::new(None));
+        let embeddings = Arc::new(EmbeddingModel::new());
+        let context = ContextManager::new();
+        // "é" straddles the 100-byte preview cut
+        let copied = format!("{}é — copied from the café menu 🍰", "a".repeat(99));
+        assert!(context.observe_clipboard(&copied));
+
+        let result = remember_clipboard(&engine, &embeddings, &context).await.unwrap();
+        assert!(result.success);
+        let data = result.data.unwrap();
+        assert_eq!(data["content_preview"], "a".repeat(99));
+        let id = data["id"].as_str().unwrap();
+        assert_eq!(engine.memory.get(id).unwrap().content, copied);
+
+        // A thought whose emoji straddles the 80-byte summary cut
+        engine.think_with_embedding(&format!("x{}", "🧠".repeat(30)), &[0.1; 384]).unwrap();
+        let summary = summarize_recent(&engine).await.unwrap();
+        let line = format!("] Processing: x{}", "🧠".repeat(16));
+        assert!(summary.message.lines().any(|l| l.ends_with(&line)), "{}", summary.message);
+    }
+
+    #[tokio::test]
+    async fn test_export_markdown_groups_by_type() {
+        let dir = std::env::temp_dir().join(format!("superbrain_wf_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let engine = Arc::new(CognitiveEngine::new(None));
+        let dims = engine.memory.dimensions();
+        for (content, memory_type) in [
+            ("Paris is the capital of France", "semantic"),
+            ("Met Ana at the station", "episodic"),
+            ("Water boils at 100C", "semantic"),
+        ] {
+            engine
+                .remember_with_embedding(content.into(), vec![0.1; dims], memory_type.into(), None)
+                .unwrap();
+        }
+
+        let dest = dir.join("memories.md");
+        let result = export_markdown(dest.to_str().unwrap(), &engine).await.unwrap();
+        assert_eq!(result.data.unwrap()["count"], 3);
+
+        let markdown = std::fs::read_to_string(&dest).unwrap();
+        let episodic = markdown.find("## Episodic").unwrap();
+        let semantic = markdown.find("## Semantic").unwrap();
+        let paris = markdown.find("- Paris is the capital of France").unwrap();
+        let ana = markdown.find("- Met Ana at the station").unwrap();
+        assert!(episodic < ana && ana < semantic && semantic < paris, "{}", markdown);
+        assert!(markdown.contains("*importance 0.50 · "), "{}", markdown);
+
+        // A destination that can't be written is reported, not ignored
+        let missing = dir.join("no_such_dir").join("memories.md");
+        let err = export_markdown(missing.to_str().unwrap(), &engine).await.unwrap_err();
+        assert!(err.starts_with("Failed to write"), "{}", err);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_cluster_memories_separates_groups() {
+        let engine = Arc::new(CognitiveEngine::new(None));
+        let dims = engine.memory.dimensions();
+        // Two groups pointing along different axes, with a little spread
+        let vector = |axis: usize, wobble: f32| {
+            let mut v = vec![0.0f32; dims];
+            v[axis] = 1.0;
+            v[axis + 2] = wobble;
+            v
+        };
+        let remember = |content: &str, v: Vec<f32>| {
+            engine
+                .remember_with_embedding(content.into(), v, "semantic".into(), None)
+                .unwrap()
+        };
+        let cats = [
+            remember("Cats purr when content", vector(0, 0.1)),
+            remember("Cats sleep most of the day", vector(0, 0.2)),
+            remember("Cats groom themselves", vector(0, 0.15)),
+        ];
+        let rust = [
+            remember("Rust has no garbage collector", vector(1, 0.1)),
+            remember("Rust enums carry data", vector(1, 0.2)),
+        ];
+
+        let result = cluster_memories(2, &engine).await.unwrap();
+        let clusters = result.data.unwrap()["clusters"].as_array().unwrap().clone();
+        assert_eq!(clusters.len(), 2);
+        let cluster_of = |id: &str| {
+            clusters
+                .iter()
+                .position(|c| c["memory_ids"].as_array().unwrap().iter().any(|m| m == id))
+                .unwrap()
+        };
+        assert!(cats.iter().all(|id| cluster_of(id) == cluster_of(&cats[0])));
+        assert!(rust.iter().all(|id| cluster_of(id) == cluster_of(&rust[0])));
+        assert_ne!(cluster_of(&cats[0]), cluster_of(&rust[0]));
+        let cat_cluster = &clusters[cluster_of(&cats[0])];
+        assert_eq!(cat_cluster["size"], 3);
+        assert!(cat_cluster["representative"].as_str().unwrap().starts_with("Cats"));
+
+        // Asking for more clusters than memories gives one per memory
+        let result = cluster_memories(10, &engine).await.unwrap();
+        assert_eq!(result.data.unwrap()["clusters"].as_array().unwrap().len(), 5);
+        assert!(cluster_memories(0, &engine).await.is_err());
+    }
+
+    /// Provider that answers every prompt with `respond(prompt)`
+    struct StubProvider {
+        respond: fn(&str) -> Result<String, String>,
+    }
+
+    #[async_trait::async_trait]
+    impl AiProvider for StubProvider {
+        async fn generate(
+            &self,
+            prompt: &str,
+            _context_memories: &[crate::brain::cognitive::RecallResult],
+            _history: &[crate::ai::conversation::ChatTurn],
+        ) -> Result<crate::ai::AiResponse, String> {
+            (self.respond)(prompt).map(|content| crate::ai::AiResponse {
+                content,
+                model: "stub".to_string(),
+                tokens_used: None,
+            })
+        }
+
+        async fn is_available(&self) -> bool {
+            true
+        }
+
+        fn name(&self) -> &str {
+            "stub"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auto_tag_attaches_suggested_tags() {
+        let engine = Arc::new(CognitiveEngine::new(None));
+        let dims = engine.memory.dimensions();
+        let remember = |content: &str| {
+            engine
+                .remember_with_embedding(content.into(), vec![0.1; dims], "semantic".into(), None)
+                .unwrap()
+        };
+        let kyoto = remember("Trip to Kyoto in April");
+        let ramen = remember("Best ramen is at Ichiran");
+        let tagged = remember("Already sorted");
+        engine.memory.add_tags(&tagged, &["done".to_string()]);
+
+        // Tags for the Kyoto note only, wherever it lands in the batch
+        let providers: Vec<Box<dyn AiProvider>> = vec![Box::new(StubProvider {
+            respond: |prompt| {
+                let line = prompt.lines().find(|l| l.contains("Kyoto")).unwrap();
+                let number = line.split('.').next().unwrap();
+                Ok(format!("**{}**: #Travel, Japan, spring, extra\nnonsense", number))
+            },
+        })];
+        let result = auto_tag(10, &engine, &providers).await.unwrap();
+        assert_eq!(result.data.unwrap()["tagged"], 1);
+
+        let tags = |id: &str| engine.memory.get_node(id).unwrap().tags;
+        assert_eq!(tags(&kyoto), vec!["travel", "japan", "spring"]);
+        assert!(tags(&ramen).is_empty());
+        assert_eq!(tags(&tagged), vec!["done"]);
+
+        // A failing provider skips the memories instead of failing the workflow
+        let failing: Vec<Box<dyn AiProvider>> = vec![Box::new(StubProvider {
+            respond: |_| Err("offline".to_string()),
+        })];
+        let result = auto_tag(10, &engine, &failing).await.unwrap();
+        assert_eq!(result.data.unwrap()["tagged"], 0);
+        assert!(tags(&ramen).is_empty());
+        assert!(auto_tag(10, &engine, &[]).await.is_err());
+    }
+}
@@ -13,7 +13,16 @@ type View = "search" | "settings";
 function App() {
   const [view, setView] = useState<View>("search");
   const [expanded, setExpanded] = useState(false);
-  const { query, results, isSearching, loadStatus, loadSettings, settings } = useAppStore();
+  const {
+    query,
+    results,
+    isSearching,
+    loadStatus,
+    loadSettings,
+    settings,
+    appendStreamingToken,
+    resetStreamingResponse,
+  } = useAppStore();
 
   useEffect(() => {
     loadStatus();
@@ -34,11 +43,29 @@ function App() {
       input?.focus();
     });
 
+    // Refresh status when embeddings switch between Ollama and the fallback
+    const unlistenProvider = listen("embedding-provider-changed", () => {
+      loadStatus();
+    });
+
+    // Show the AI response as it is generated
+    const unlistenToken = listen<string>("think-token", (event) => {
+      appendStreamingToken(event.payload);
+    });
+
+    // A provider failed mid-answer and the next one starts over
+    const unlistenReset = listen("think-reset", () => {
+      resetStreamingResponse();
+    });
+
     return () => {
       unlisten.then((fn) => fn());
       unlistenShow.then((fn) => fn());
+      unlistenProvider.then((fn) => fn());
+      unlistenToken.then((fn) => fn());
+      unlistenReset.then((fn) => fn());
     };
-  }, [loadStatus, loadSettings]);
+  }, [loadStatus, loadSettings, appendStreamingToken, resetStreamingResponse]);
 
   // Expand when we have results or query
   useEffect(() => {
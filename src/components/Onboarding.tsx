@@ -1,7 +1,14 @@
 import { useCallback, useEffect, useState } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { useAppStore } from "../store/appStore";
 
+interface IndexProgress {
+  processed: number;
+  total: number;
+  current_path: string;
+}
+
 interface OllamaStatus {
   available: boolean;
   models: string[];
@@ -38,6 +45,16 @@ export default function Onboarding() {
 
   const [indexing, setIndexing] = useState(false);
   const [indexCount, setIndexCount] = useState<number | null>(null);
+  const [progress, setProgress] = useState<IndexProgress | null>(null);
+
+  useEffect(() => {
+    const unlisten = listen<IndexProgress>("index-progress", (event) => {
+      setProgress(event.payload);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
 
   const finish = useCallback(async () => {
     if (settings) {
@@ -158,7 +175,9 @@ export default function Onboarding() {
           </p>
           <p className="text-brain-text/40 text-[10px] mb-8 max-w-[250px]">
             {indexing
-              ? "Indexing your files..."
+              ? progress
+                ? `Indexing your files... ${progress.processed}/${progress.total}`
+                : "Indexing your files..."
               : indexCount !== null
               ? `Indexed ${indexCount} files from your Documents, Desktop, and Downloads.`
               : "Click below to index your files and start using SuperBrain."}
@@ -5,7 +5,7 @@ type Tab = "response" | "memories" | "files";
 
 export default function ResultsList() {
   const [activeTab, setActiveTab] = useState<Tab>("response");
-  const { results, isSearching } = useAppStore();
+  const { results, isSearching, streamingResponse, deleteMemory } = useAppStore();
   const { memories, files, thinkResult } = results;
 
   return (
@@ -34,12 +34,20 @@ export default function ResultsList() {
 
       {/* Content */}
       <div className="flex-1 overflow-y-auto p-4">
-        {isSearching && (
+        {isSearching && (!streamingResponse || activeTab !== "response") && (
           <div className="text-brain-text/50 text-sm text-center py-8">
             Searching...
           </div>
         )}
 
+        {isSearching && streamingResponse && activeTab === "response" && (
+          <div className="bg-brain-surface rounded-xl p-4 border border-brain-border">
+            <p className="text-white text-sm leading-relaxed whitespace-pre-wrap">
+              {streamingResponse}
+            </p>
+          </div>
+        )}
+
         {!isSearching && activeTab === "response" && thinkResult && (
           <div className="space-y-3 animate-fade-in">
             <div className="bg-brain-surface rounded-xl p-4 border border-brain-border">
@@ -50,9 +58,18 @@ export default function ResultsList() {
                 <span>Confidence: {(thinkResult.confidence * 100).toFixed(0)}%</span>
                 <span>{thinkResult.memory_count} memories used</span>
                 {thinkResult.ai_enhanced && (
-                  <span className="text-brain-accent">AI Enhanced</span>
+                  <span className="text-brain-accent">AI Enhanced ({thinkResult.provider})</span>
                 )}
               </div>
+              {thinkResult.sources.length > 0 && (
+                <div className="mt-2 text-xs text-brain-text/50">
+                  Sources:{" "}
+                  {thinkResult.sources.map((path) => path.split("/").pop()).join(", ")}
+                </div>
+              )}
+              {thinkResult.ai_error && (
+                <p className="mt-2 text-xs text-red-400/80">AI unavailable: {thinkResult.ai_error}</p>
+              )}
             </div>
           </div>
         )}
@@ -75,6 +92,12 @@ export default function ResultsList() {
                   <div className="flex items-center gap-3 mt-2 text-xs text-brain-text/50">
                     <TypeBadge type={memory.memory_type} />
                     <span>{(memory.similarity * 100).toFixed(0)}% match</span>
+                    <button
+                      onClick={() => deleteMemory(memory.id)}
+                      className="ml-auto hover:text-red-400 transition-colors"
+                    >
+                      Delete
+                    </button>
                   </div>
                 </div>
               ))
@@ -108,6 +131,13 @@ export default function ResultsList() {
                   </p>
                   <div className="flex items-center gap-3 mt-2 text-xs text-brain-text/50 ml-5.5">
                     <span className="truncate max-w-[300px]">{file.path}</span>
+                    {file.start_line > 0 && (
+                      <span>
+                        {file.start_line === file.end_line
+                          ? `Line ${file.start_line}`
+                          : `Lines ${file.start_line}–${file.end_line}`}
+                      </span>
+                    )}
                     <span>{(file.similarity * 100).toFixed(0)}% match</span>
                   </div>
                 </div>
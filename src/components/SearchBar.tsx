@@ -6,8 +6,18 @@ let debounceTimer: ReturnType<typeof setTimeout>;
 
 export default function SearchBar() {
   const inputRef = useRef<HTMLInputElement>(null);
-  const { query, setQuery, search, isSearching, clearResults, mode, setMode, remember } =
-    useAppStore();
+  const {
+    query,
+    setQuery,
+    search,
+    isSearching,
+    clearResults,
+    clearConversation,
+    cancelThink,
+    mode,
+    setMode,
+    remember,
+  } = useAppStore();
 
   const handleChange = useCallback(
     (e: React.ChangeEvent<HTMLInputElement>) => {
@@ -32,8 +42,12 @@ export default function SearchBar() {
   const handleKeyDown = useCallback(
     async (e: React.KeyboardEvent) => {
       if (e.key === "Escape") {
-        if (query) {
+        if (isSearching) {
+          // Stop the AI answer; memory results still come back
+          cancelThink();
+        } else if (query) {
           clearResults();
+          clearConversation();
           setQuery("");
         } else {
           getCurrentWindow().hide();
@@ -43,14 +57,25 @@ export default function SearchBar() {
           await remember(query.trim(), "semantic", 0.7);
           setQuery("");
         } else {
-          search(query.trim());
+          search(query.trim(), true);
         }
       } else if (e.key === "Tab") {
         e.preventDefault();
         setMode(mode === "search" ? "remember" : "search");
       }
     },
-    [query, search, clearResults, setQuery, mode, setMode, remember],
+    [
+      query,
+      isSearching,
+      search,
+      clearResults,
+      clearConversation,
+      cancelThink,
+      setQuery,
+      mode,
+      setMode,
+      remember,
+    ],
   );
 
   const isRemember = mode === "remember";
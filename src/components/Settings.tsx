@@ -1,14 +1,30 @@
 import { useEffect, useState, useCallback } from "react";
+import { invoke } from "@tauri-apps/api/core";
 import { useAppStore } from "../store/appStore";
 
 interface SettingsProps {
   onBack: () => void;
 }
 
+interface KeyValidation {
+  valid: boolean;
+  error: string | null;
+}
+
 export default function Settings({ onBack }: SettingsProps) {
-  const { settings, loadSettings, updateSettings, status, addIndexedFolder } = useAppStore();
+  const {
+    settings,
+    loadSettings,
+    updateSettings,
+    status,
+    loadStatus,
+    addIndexedFolder,
+    removeIndexedFolder,
+  } = useAppStore();
   const [localSettings, setLocalSettings] = useState(settings);
   const [newFolder, setNewFolder] = useState("");
+  const [reembedding, setReembedding] = useState(false);
+  const [keyCheck, setKeyCheck] = useState<KeyValidation | "checking" | null>(null);
 
   useEffect(() => {
     loadSettings();
@@ -27,6 +43,30 @@ export default function Settings({ onBack }: SettingsProps) {
     }
   }, [localSettings, updateSettings, onBack]);
 
+  const handleReembed = useCallback(async () => {
+    setReembedding(true);
+    try {
+      await invoke<number>("reembed_memories");
+    } catch (error) {
+      console.error("Failed to re-embed memories:", error);
+    } finally {
+      setReembedding(false);
+      loadStatus();
+    }
+  }, [loadStatus]);
+
+  const handleValidateKey = useCallback(async () => {
+    if (!localSettings?.claude_api_key) return;
+    setKeyCheck("checking");
+    try {
+      setKeyCheck(
+        await invoke<KeyValidation>("validate_claude_key", { key: localSettings.claude_api_key }),
+      );
+    } catch (error) {
+      setKeyCheck({ valid: false, error: String(error) });
+    }
+  }, [localSettings]);
+
   if (!localSettings) {
     return (
       <div className="p-4 text-brain-text/50 text-sm">Loading settings...</div>
@@ -79,6 +119,16 @@ export default function Settings({ onBack }: SettingsProps) {
                 }
                 className="w-full bg-brain-bg text-white text-sm px-3 py-2 rounded-lg border border-brain-border outline-none focus:border-brain-accent/50"
               />
+              <label className="block text-brain-text/50 text-xs mb-1 mt-3">Server URL</label>
+              <input
+                type="text"
+                value={localSettings.ollama_base_url}
+                onChange={(e) =>
+                  setLocalSettings({ ...localSettings, ollama_base_url: e.target.value })
+                }
+                placeholder="http://localhost:11434"
+                className="w-full bg-brain-bg text-white text-sm px-3 py-2 rounded-lg border border-brain-border outline-none focus:border-brain-accent/50"
+              />
             </div>
           )}
 
@@ -88,14 +138,156 @@ export default function Settings({ onBack }: SettingsProps) {
               <input
                 type="password"
                 value={localSettings.claude_api_key || ""}
+                onChange={(e) => {
+                  setLocalSettings({ ...localSettings, claude_api_key: e.target.value || null });
+                  setKeyCheck(null);
+                }}
+                placeholder="sk-ant-..."
+                className="w-full bg-brain-bg text-white text-sm px-3 py-2 rounded-lg border border-brain-border outline-none focus:border-brain-accent/50"
+              />
+              <div className="flex items-center gap-2 mt-1.5 text-xs">
+                <button
+                  onClick={handleValidateKey}
+                  disabled={!localSettings.claude_api_key || keyCheck === "checking"}
+                  className="text-brain-accent hover:underline disabled:opacity-50"
+                >
+                  {keyCheck === "checking" ? "Checking..." : "Verify key"}
+                </button>
+                {keyCheck && keyCheck !== "checking" && (
+                  <span className={keyCheck.valid ? "text-green-400/80" : "text-red-400/80"}>
+                    {keyCheck.valid ? "Key works" : keyCheck.error}
+                  </span>
+                )}
+              </div>
+              <label className="block text-brain-text/50 text-xs mb-1 mt-3">Model</label>
+              <input
+                type="text"
+                value={localSettings.claude_model}
                 onChange={(e) =>
-                  setLocalSettings({ ...localSettings, claude_api_key: e.target.value || null })
+                  setLocalSettings({ ...localSettings, claude_model: e.target.value })
+                }
+                placeholder="claude-sonnet-4-5-20250929"
+                className="w-full bg-brain-bg text-white text-sm px-3 py-2 rounded-lg border border-brain-border outline-none focus:border-brain-accent/50"
+              />
+              <label className="block text-brain-text/50 text-xs mb-1 mt-3">Max Response Tokens</label>
+              <input
+                type="number"
+                min={1}
+                value={localSettings.claude_max_tokens}
+                onChange={(e) =>
+                  setLocalSettings({
+                    ...localSettings,
+                    claude_max_tokens: Math.max(1, Number(e.target.value) || 1),
+                  })
                 }
-                placeholder="sk-ant-..."
                 className="w-full bg-brain-bg text-white text-sm px-3 py-2 rounded-lg border border-brain-border outline-none focus:border-brain-accent/50"
               />
             </div>
           )}
+
+          <label className="block text-brain-text/50 text-xs mt-3 mb-1">
+            Fallback providers, in order (comma-separated)
+          </label>
+          <input
+            type="text"
+            value={localSettings.ai_fallbacks.join(", ")}
+            onChange={(e) =>
+              setLocalSettings({ ...localSettings, ai_fallbacks: splitList(e.target.value) })
+            }
+            placeholder="ollama"
+            className="w-full bg-brain-bg text-white text-xs px-3 py-1.5 rounded-lg border border-brain-border outline-none focus:border-brain-accent/50"
+          />
+
+          <div className="flex gap-3 mt-3">
+            <div className="flex-1">
+              <label className="block text-brain-text/50 text-xs mb-1">Temperature</label>
+              <input
+                type="number"
+                min={0}
+                max={2}
+                step={0.1}
+                value={localSettings.temperature ?? ""}
+                onChange={(e) =>
+                  setLocalSettings({ ...localSettings, temperature: parseOptional(e.target.value) })
+                }
+                placeholder="Default"
+                className="w-full bg-brain-bg text-white text-xs px-3 py-1.5 rounded-lg border border-brain-border outline-none focus:border-brain-accent/50"
+              />
+            </div>
+            <div className="flex-1">
+              <label className="block text-brain-text/50 text-xs mb-1">Top P</label>
+              <input
+                type="number"
+                min={0}
+                max={1}
+                step={0.05}
+                value={localSettings.top_p ?? ""}
+                onChange={(e) =>
+                  setLocalSettings({ ...localSettings, top_p: parseOptional(e.target.value) })
+                }
+                placeholder="Default"
+                className="w-full bg-brain-bg text-white text-xs px-3 py-1.5 rounded-lg border border-brain-border outline-none focus:border-brain-accent/50"
+              />
+            </div>
+          </div>
+
+          <label className="block text-brain-text/50 text-xs mt-3 mb-1">
+            File excerpts sent to the AI (0 = off)
+          </label>
+          <input
+            type="number"
+            min={0}
+            max={20}
+            value={localSettings.file_context_chunks}
+            onChange={(e) =>
+              setLocalSettings({
+                ...localSettings,
+                file_context_chunks: Math.max(0, Number(e.target.value) || 0),
+              })
+            }
+            className="w-full bg-brain-bg text-white text-xs px-3 py-1.5 rounded-lg border border-brain-border outline-none focus:border-brain-accent/50"
+          />
+
+          {localSettings.ai_provider !== "none" && (
+            <div className="mt-3">
+              <label className="block text-brain-text/50 text-xs mb-1">System Prompt</label>
+              <textarea
+                rows={3}
+                value={localSettings.system_prompt || ""}
+                onChange={(e) =>
+                  setLocalSettings({ ...localSettings, system_prompt: e.target.value || null })
+                }
+                placeholder="Default: You are SuperBrain, an intelligent cognitive assistant..."
+                className="w-full bg-brain-bg text-white text-sm px-3 py-2 rounded-lg border border-brain-border outline-none focus:border-brain-accent/50 resize-none"
+              />
+            </div>
+          )}
+        </Section>
+
+        {/* Embeddings */}
+        <Section title="Embeddings">
+          <label className="block text-brain-text/50 text-xs mb-1">Ollama Embedding Model</label>
+          <input
+            type="text"
+            value={localSettings.embedding_model}
+            onChange={(e) =>
+              setLocalSettings({ ...localSettings, embedding_model: e.target.value })
+            }
+            placeholder="nomic-embed-text"
+            className="w-full bg-brain-bg text-white text-sm px-3 py-2 rounded-lg border border-brain-border outline-none focus:border-brain-accent/50 mb-3"
+          />
+          <label className="block text-brain-text/50 text-xs mb-1">
+            OpenAI API Key (used when no local model is available)
+          </label>
+          <input
+            type="password"
+            value={localSettings.openai_api_key || ""}
+            onChange={(e) =>
+              setLocalSettings({ ...localSettings, openai_api_key: e.target.value || null })
+            }
+            placeholder="sk-..."
+            className="w-full bg-brain-bg text-white text-sm px-3 py-2 rounded-lg border border-brain-border outline-none focus:border-brain-accent/50"
+          />
         </Section>
 
         {/* Hotkey */}
@@ -133,6 +325,7 @@ export default function Settings({ onBack }: SettingsProps) {
                   </span>
                   <button
                     onClick={() => {
+                      removeIndexedFolder(folder);
                       const updated = localSettings!.indexed_folders.filter((_, idx) => idx !== i);
                       setLocalSettings({ ...localSettings!, indexed_folders: updated });
                     }}
@@ -178,6 +371,35 @@ export default function Settings({ onBack }: SettingsProps) {
               Add
             </button>
           </div>
+          <div className="mt-3">
+            <Toggle
+              label="Skip files ignored by .gitignore"
+              checked={localSettings.respect_gitignore}
+              onChange={(v) => setLocalSettings({ ...localSettings, respect_gitignore: v })}
+            />
+          </div>
+          <label className="block text-brain-text/50 text-xs mt-3 mb-1">
+            File types (comma-separated)
+          </label>
+          <input
+            type="text"
+            value={localSettings.index_extensions.join(", ")}
+            onChange={(e) =>
+              setLocalSettings({ ...localSettings, index_extensions: splitList(e.target.value) })
+            }
+            className="w-full bg-brain-bg text-white text-xs px-3 py-1.5 rounded-lg border border-brain-border outline-none focus:border-brain-accent/50"
+          />
+          <label className="block text-brain-text/50 text-xs mt-3 mb-1">
+            Skipped folders (comma-separated)
+          </label>
+          <input
+            type="text"
+            value={localSettings.skip_dirs.join(", ")}
+            onChange={(e) =>
+              setLocalSettings({ ...localSettings, skip_dirs: splitList(e.target.value) })
+            }
+            className="w-full bg-brain-bg text-white text-xs px-3 py-1.5 rounded-lg border border-brain-border outline-none focus:border-brain-accent/50"
+          />
         </Section>
 
         {/* Auto-Start */}
@@ -192,16 +414,33 @@ export default function Settings({ onBack }: SettingsProps) {
         {/* Privacy */}
         <Section title="Privacy">
           <Toggle
-            label="Privacy Mode (disable cloud AI)"
+            label="Privacy Mode (no cloud AI or file indexing)"
             checked={localSettings.privacy_mode}
             onChange={(v) => setLocalSettings({ ...localSettings, privacy_mode: v })}
           />
+          <Toggle
+            label="Encrypt memories and clipboard on disk"
+            checked={localSettings.encrypt_storage}
+            onChange={(v) => setLocalSettings({ ...localSettings, encrypt_storage: v })}
+          />
         </Section>
 
         {/* Info */}
         <Section title="System Info">
           <div className="text-brain-text/50 text-xs space-y-1">
             <p>Embedding: {status?.embedding_provider || "..."}</p>
+            {(status?.mismatched_embeddings ?? 0) > 0 && (
+              <p className="text-yellow-400/80">
+                {status?.mismatched_embeddings} memories were embedded by another provider.{" "}
+                <button
+                  onClick={handleReembed}
+                  disabled={reembedding}
+                  className="underline hover:text-yellow-300 disabled:opacity-50"
+                >
+                  {reembedding ? "Re-embedding..." : "Re-embed now"}
+                </button>
+              </p>
+            )}
             <p>AI: {status?.ai_provider || "..."}</p>
             <p>Memories: {status?.memory_count ?? 0}</p>
             <p>Indexed Files: {status?.indexed_files ?? 0} ({status?.indexed_chunks ?? 0} chunks)</p>
@@ -213,6 +452,15 @@ export default function Settings({ onBack }: SettingsProps) {
   );
 }
 
+function splitList(value: string): string[] {
+  return value.split(",").map((item) => item.trim());
+}
+
+function parseOptional(value: string): number | null {
+  const parsed = parseFloat(value);
+  return Number.isNaN(parsed) ? null : parsed;
+}
+
 function Section({ title, children }: { title: string; children: React.ReactNode }) {
   return (
     <div>
@@ -49,5 +49,10 @@ export function useBrainActions() {
     return invoke("flush");
   }, []);
 
-  return { evolve, cycle, flush };
+  /** Start a flush without waiting for it; resolves to "started" or "already_running" */
+  const flushInBackground = useCallback(async () => {
+    return invoke<"started" | "already_running">("flush_in_background");
+  }, []);
+
+  return { evolve, cycle, flush, flushInBackground };
 }
@@ -14,6 +14,10 @@ interface ThinkResult {
   thought_id: string;
   memory_count: number;
   ai_enhanced: boolean;
+  provider: string;
+  ai_error: string | null;
+  cancelled: boolean;
+  sources: string[];
 }
 
 interface FileResult {
@@ -22,6 +26,8 @@ interface FileResult {
   chunk: string;
   similarity: number;
   file_type: string;
+  start_line: number;
+  end_line: number;
 }
 
 interface WorkflowResult {
@@ -39,6 +45,8 @@ interface SystemStatus {
   ai_provider: string;
   ai_available: boolean;
   embedding_provider: string;
+  mismatched_embeddings: number;
+  belief_count: number;
   learning_trend: string;
   indexed_files: number;
   indexed_chunks: number;
@@ -46,13 +54,34 @@ interface SystemStatus {
 
 interface Settings {
   ai_provider: string;
+  ai_fallbacks: string[];
   ollama_model: string;
+  ollama_base_url: string;
+  embedding_model: string;
   claude_api_key: string | null;
+  claude_model: string;
+  claude_max_tokens: number;
+  system_prompt: string | null;
+  temperature: number | null;
+  top_p: number | null;
+  file_context_chunks: number;
+  openai_api_key: string | null;
   hotkey: string;
+  overlay_pinned: boolean;
   indexed_folders: string[];
+  respect_gitignore: boolean;
+  skip_dirs: string[];
+  index_extensions: string[];
+  index_concurrency: number;
+  max_file_bytes: number;
+  max_depth: number;
+  clipboard_max_bytes: number;
+  clipboard_filter_secrets: boolean;
+  clipboard_denylist: string[];
   theme: string;
   auto_start: boolean;
   privacy_mode: boolean;
+  encrypt_storage: boolean;
   onboarded: boolean;
 }
 
@@ -71,6 +100,12 @@ interface AppState {
   query: string;
   results: SearchResults;
   isSearching: boolean;
+  /** AI response text received so far while a search is running */
+  streamingResponse: string;
+  /** Groups submitted searches into one multi-turn conversation */
+  conversationId: string;
+  /** Id of the think request still running, for cancelThink */
+  thinkRequestId: string | null;
   recentMemories: Memory[];
   status: SystemStatus | null;
   settings: Settings | null;
@@ -79,24 +114,33 @@ interface AppState {
 
   setQuery: (query: string) => void;
   setMode: (mode: "search" | "remember") => void;
-  search: (query: string) => Promise<void>;
+  appendStreamingToken: (token: string) => void;
+  resetStreamingResponse: () => void;
+  search: (query: string, submitted?: boolean) => Promise<void>;
   searchFiles: (query: string) => Promise<FileResult[]>;
   runWorkflow: (action: string, query?: string) => Promise<WorkflowResult>;
   remember: (content: string, type: string, importance?: number) => Promise<void>;
   think: (input: string) => Promise<ThinkResult>;
+  deleteMemory: (id: string) => Promise<void>;
   loadStatus: () => Promise<void>;
   loadSettings: () => Promise<void>;
   updateSettings: (settings: Settings) => Promise<void>;
   loadClipboardHistory: () => Promise<void>;
   addIndexedFolder: (path: string) => Promise<void>;
+  removeIndexedFolder: (path: string) => Promise<void>;
   indexFiles: () => Promise<void>;
   clearResults: () => void;
+  clearConversation: () => Promise<void>;
+  cancelThink: () => Promise<void>;
 }
 
 export const useAppStore = create<AppState>((set, get) => ({
   query: "",
   results: { memories: [], files: [], thinkResult: null },
   isSearching: false,
+  streamingResponse: "",
+  conversationId: crypto.randomUUID(),
+  thinkRequestId: null,
   recentMemories: [],
   status: null,
   settings: null,
@@ -105,25 +149,39 @@ export const useAppStore = create<AppState>((set, get) => ({
 
   setQuery: (query: string) => set({ query }),
   setMode: (mode: "search" | "remember") => set({ mode }),
+  appendStreamingToken: (token: string) =>
+    set((state) => ({ streamingResponse: state.streamingResponse + token })),
+  resetStreamingResponse: () => set({ streamingResponse: "" }),
 
-  search: async (query: string) => {
-    set({ query, isSearching: true });
+  search: async (query: string, submitted = false) => {
+    // A newer search replaces any answer still being generated
+    get().cancelThink();
+    const requestId = crypto.randomUUID();
+    set({ query, isSearching: true, streamingResponse: "", thinkRequestId: requestId });
+
+    // Only submitted searches join the conversation, not search-as-you-type
+    const conversationId = submitted ? get().conversationId : null;
 
     try {
       // Run recall, think, and file search in parallel
       const [memories, thinkResult, files] = await Promise.all([
         invoke<Memory[]>("recall", { query, limit: 10 }),
-        invoke<ThinkResult>("think", { input: query }),
+        invoke<ThinkResult>("think", { input: query, stream: true, conversationId, requestId }),
         invoke<FileResult[]>("search_files", { query, limit: 10 }).catch(() => [] as FileResult[]),
       ]);
 
-      set({
-        results: { memories, files, thinkResult },
-        isSearching: false,
-      });
+      if (get().thinkRequestId === requestId) {
+        set({
+          results: { memories, files, thinkResult },
+          isSearching: false,
+          thinkRequestId: null,
+        });
+      }
     } catch (error) {
       console.error("Search failed:", error);
-      set({ isSearching: false });
+      if (get().thinkRequestId === requestId) {
+        set({ isSearching: false, thinkRequestId: null });
+      }
     }
   },
 
@@ -154,6 +212,21 @@ export const useAppStore = create<AppState>((set, get) => ({
     return result;
   },
 
+  deleteMemory: async (id: string) => {
+    try {
+      await invoke<boolean>("delete_memory", { id });
+      set((state) => ({
+        results: {
+          ...state.results,
+          memories: state.results.memories.filter((memory) => memory.id !== id),
+        },
+      }));
+      get().loadStatus();
+    } catch (error) {
+      console.error("Failed to delete memory:", error);
+    }
+  },
+
   loadStatus: async () => {
     try {
       const status = await invoke<SystemStatus>("get_status");
@@ -200,6 +273,16 @@ export const useAppStore = create<AppState>((set, get) => ({
     }
   },
 
+  removeIndexedFolder: async (path: string) => {
+    try {
+      await invoke<number>("remove_indexed_folder", { path });
+      get().loadSettings();
+      get().loadStatus();
+    } catch (error) {
+      console.error("Failed to remove folder:", error);
+    }
+  },
+
   indexFiles: async () => {
     try {
       await invoke("index_files");
@@ -212,4 +295,24 @@ export const useAppStore = create<AppState>((set, get) => ({
   clearResults: () => {
     set({ query: "", results: { memories: [], files: [], thinkResult: null } });
   },
+
+  cancelThink: async () => {
+    const requestId = get().thinkRequestId;
+    if (!requestId) return;
+    try {
+      await invoke<boolean>("cancel_think", { requestId });
+    } catch (error) {
+      console.error("Failed to cancel think:", error);
+    }
+  },
+
+  clearConversation: async () => {
+    const conversationId = get().conversationId;
+    set({ conversationId: crypto.randomUUID() });
+    try {
+      await invoke<boolean>("clear_conversation", { conversationId });
+    } catch (error) {
+      console.error("Failed to clear conversation:", error);
+    }
+  },
 }));